@@ -1,7 +1,21 @@
-use std::{
-    path::{Path, PathBuf},
-    process::Stdio,
-};
+use shader_tools::{SPIRV_MAGIC_NUMBER, spirv_entry_points};
+use std::{fmt::Write, path::Path, process::Stdio};
+
+/// Extra versions of a shader compiled with preprocessor defines, as the shader's file stem, the variant name and its defines,
+/// each one is exposed as `shaders::<shader>::<variant>` and listed in `shaders::<shader>::VARIANTS`
+const VARIANTS: &[(&str, &str, &[&str])] = &[
+    ("full_screen_quad", "debug_colored", &["DEBUG_COLORED"]),
+    ("full_screen_quad", "bounds_checked", &["BOUNDS_CHECKED"]),
+    ("full_screen_quad", "visit_counting", &["VISIT_COUNTING"]),
+    ("full_screen_quad", "cached_commands", &["CACHED_COMMANDS"]),
+];
+
+struct Compilation {
+    shader: String,
+    variant: Option<&'static str>,
+    defines: &'static [&'static str],
+    spirv_file_name: String,
+}
 
 fn main() {
     println!("cargo::rerun-if-changed=./shaders");
@@ -11,6 +25,9 @@ fn main() {
     _ = std::fs::remove_dir_all(&out_dir);
     std::fs::create_dir_all(&out_dir).unwrap();
 
+    // keeps debug info in debug builds so validation errors and shader printf point at source lines
+    let debug_info = std::env::var("PROFILE").unwrap() == "debug";
+
     let mut compilations = vec![];
     for entry in std::fs::read_dir("./shaders").unwrap() {
         let entry = entry.unwrap();
@@ -19,33 +36,166 @@ fn main() {
         }
 
         let file_path = entry.path();
-        let name = PathBuf::from(file_path.file_name().unwrap());
-        let out_filepath = out_dir.join(name.with_extension("spv"));
-
-        let process = std::process::Command::new("slangc")
-            .arg(&file_path)
-            .arg("-o")
-            .arg(out_filepath)
-            .args([
-                "-warnings-as-errors",
-                "all",
-                "-fvk-use-scalar-layout",
-                "-fvk-use-entrypoint-name",
-            ])
+        let shader = file_path.file_stem().unwrap().to_str().unwrap().to_owned();
+
+        let variants = VARIANTS
+            .iter()
+            .filter(|&&(variant_shader, _, _)| variant_shader == shader)
+            .map(|&(_, variant, defines)| (Some(variant), defines));
+        for (variant, defines) in std::iter::once((None, &[] as &[_])).chain(variants) {
+            let spirv_file_name = match variant {
+                Some(variant) => format!("{shader}.{variant}.spv"),
+                None => format!("{shader}.spv"),
+            };
+
+            let process = shader_tools::slangc(
+                &file_path,
+                &out_dir.join(&spirv_file_name),
+                defines,
+                debug_info,
+            )
             .stderr(Stdio::piped())
             .spawn()
             .unwrap();
-        compilations.push((name, process));
+            let compilation = Compilation {
+                shader: shader.clone(),
+                variant,
+                defines,
+                spirv_file_name,
+            };
+            compilations.push((compilation, process));
+        }
     }
 
-    for (file, process) in compilations {
+    for &(shader, variant, _) in VARIANTS {
+        assert!(
+            compilations
+                .iter()
+                .any(|(compilation, _)| compilation.variant == Some(variant)
+                    && compilation.shader == shader),
+            "variant {variant} is for {shader}, which doesn't exist",
+        );
+    }
+
+    let mut shaders = vec![];
+    for (compilation, process) in compilations {
         let output = process.wait_with_output().unwrap();
         if !output.status.success() {
             panic!(
                 "{}\n{}",
-                file.to_string_lossy(),
+                compilation.spirv_file_name,
                 String::from_utf8_lossy(&output.stderr),
             );
         }
+
+        let spirv_path = out_dir.join(&compilation.spirv_file_name);
+        validate(&spirv_path);
+
+        let spirv_code = std::fs::read(&spirv_path)
+            .unwrap()
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spirv_code.first(),
+            Some(&SPIRV_MAGIC_NUMBER),
+            "{} is not SPIR-V",
+            spirv_path.display(),
+        );
+
+        shaders.push((compilation, spirv_entry_points(&spirv_code)));
+    }
+
+    let mut shaders_module = String::new();
+    for (shader, entry_points) in shaders
+        .iter()
+        .filter(|(shader, _)| shader.variant.is_none())
+    {
+        writeln!(shaders_module, "pub mod {} {{", shader.shader).unwrap();
+        write_shader_constants(&mut shaders_module, "    ", shader, entry_points);
+
+        let variants = shaders
+            .iter()
+            .filter(|(variant, _)| variant.variant.is_some() && variant.shader == shader.shader)
+            .collect::<Vec<_>>();
+        writeln!(
+            shaders_module,
+            "    pub const VARIANTS: &[(&str, &[&str], &[u32])] = &["
+        )
+        .unwrap();
+        for (variant, _) in &variants {
+            let variant = variant.variant.unwrap();
+            writeln!(
+                shaders_module,
+                "        (\"{variant}\", {variant}::DEFINES, {variant}::SPIRV),"
+            )
+            .unwrap();
+        }
+        writeln!(shaders_module, "    ];").unwrap();
+
+        for (variant, entry_points) in variants {
+            writeln!(
+                shaders_module,
+                "    pub mod {} {{",
+                variant.variant.unwrap()
+            )
+            .unwrap();
+            write_shader_constants(&mut shaders_module, "        ", variant, entry_points);
+            writeln!(shaders_module, "    }}").unwrap();
+        }
+        writeln!(shaders_module, "}}").unwrap();
+    }
+
+    std::fs::write(
+        Path::new(&std::env::var("OUT_DIR").unwrap()).join("shaders.rs"),
+        shaders_module,
+    )
+    .unwrap();
+}
+
+fn write_shader_constants(
+    shaders_module: &mut String,
+    indent: &str,
+    compilation: &Compilation,
+    entry_points: &[String],
+) {
+    writeln!(
+        shaders_module,
+        "{indent}pub const SPIRV: &[u32] = rendering::include_spirv!(concat!(env!(\"OUT_DIR\"), \"/shaders/{}\"));",
+        compilation.spirv_file_name,
+    )
+    .unwrap();
+    writeln!(
+        shaders_module,
+        "{indent}pub const DEFINES: &[&str] = &{:?};",
+        compilation.defines,
+    )
+    .unwrap();
+    for entry_point in entry_points {
+        writeln!(
+            shaders_module,
+            "{indent}pub const {}: &core::ffi::CStr = c\"{entry_point}\";",
+            entry_point.to_uppercase(),
+        )
+        .unwrap();
+    }
+}
+
+/// Runs `spirv-val` when it is installed, it comes with the vulkan SDK
+fn validate(spirv_path: &Path) {
+    let Ok(output) = std::process::Command::new("spirv-val")
+        .args(["--target-env", "vulkan1.2", "--scalar-block-layout"])
+        .arg(spirv_path)
+        .output()
+    else {
+        return;
+    };
+    if !output.status.success() {
+        panic!(
+            "{} failed validation\n{}{}",
+            spirv_path.display(),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+        );
     }
 }
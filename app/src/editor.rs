@@ -0,0 +1,475 @@
+use crate::map::{Edge, Map, MapTriangle};
+use ash::vk;
+use rendering::{DebugDraw, Device};
+use std::sync::Arc;
+
+/// How close the cursor has to be to a vertex or an edge to pick it, in pixels
+const PICK_RADIUS_PIXELS: f32 = 8.0;
+/// Older edits are forgotten after this many
+const UNDO_LIMIT: usize = 100;
+/// How much one notch of the scroll wheel zooms by
+const ZOOM_PER_LINE: f32 = 1.1;
+
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.8];
+const OPEN_EDGE_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const MIRROR_EDGE_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+const GLUED_EDGE_COLOR: [f32; 4] = [0.2, 1.0, 0.2, 1.0];
+const SELECTED_EDGE_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+/// For the edge the selected edge is glued to
+const PARTNER_EDGE_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 1.0];
+const VERTEX_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const NEW_VERTEX_COLOR: [f32; 4] = [1.0, 0.5, 0.0, 1.0];
+const SPAWN_COLOR: [f32; 4] = [0.5, 0.5, 1.0, 1.0];
+
+/// Draws the whole map from above in the coordinates it is stored in, and edits it with the mouse
+///
+/// - left clicking empty space places a vertex, every third one makes a triangle with open edges,
+///   clicking near a vertex snaps to it
+/// - dragging with the left button moves a vertex, along with any other vertices at the same place
+/// - right clicking an edge selects it, right clicking a second edge of the same length glues the two,
+///   from the start of one to the start of the other as the arrows show
+/// - dragging with the middle button pans and the scroll wheel zooms
+pub struct Editor<'allocator> {
+    map: Map,
+    undo_stack: Vec<Map>,
+    debug_draw: DebugDraw<'allocator>,
+    center: [f32; 2],
+    half_height: f32,
+    /// The size of the swapchain's viewport the map is drawn in, which leaves out the letterbox bars, in pixels
+    view_size: [f32; 2],
+    /// In pixels from the top left of the viewport
+    cursor: [f32; 2],
+    /// The vertices placed for the next triangle
+    new_vertices: Vec<[f32; 2]>,
+    /// `(triangle, vertex)` of every vertex being dragged
+    dragging: Vec<(usize, usize)>,
+    /// The map from before the drag, which only goes on the undo stack if the drag moved anything
+    drag_start: Option<Map>,
+    panning: bool,
+    /// `(triangle, edge)`
+    selected_edge: Option<(usize, usize)>,
+}
+
+impl<'allocator> Editor<'allocator> {
+    pub fn new(
+        map: Map,
+        device: Arc<Device<'allocator>>,
+        color_format: vk::Format,
+        view_size: [u32; 2],
+    ) -> Self {
+        // fit the whole map in the view
+        let (min, max) = map
+            .triangles
+            .iter()
+            .flat_map(|triangle| triangle.vertices)
+            .fold(
+                ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+                |(min, max), [x, y]| {
+                    (
+                        [min[0].min(x), min[1].min(y)],
+                        [max[0].max(x), max[1].max(y)],
+                    )
+                },
+            );
+        let (center, half_height) = if min[0] <= max[0] {
+            (
+                [(min[0] + max[0]) * 0.5, (min[1] + max[1]) * 0.5],
+                (max[0] - min[0]).max(max[1] - min[1]) * 0.6,
+            )
+        } else {
+            ([0.0, 0.0], 5.0)
+        };
+
+        Self {
+            map,
+            undo_stack: vec![],
+            debug_draw: DebugDraw::new(device, color_format),
+            center,
+            half_height: half_height.max(1.0),
+            view_size: view_size.map(|size| size.max(1) as f32),
+            cursor: [0.0, 0.0],
+            new_vertices: vec![],
+            dragging: vec![],
+            drag_start: None,
+            panning: false,
+            selected_edge: None,
+        }
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    pub fn debug_draw_mut(&mut self) -> &mut DebugDraw<'allocator> {
+        &mut self.debug_draw
+    }
+
+    /// Whether a vertex is being dragged, the map is usually invalid until it is let go
+    pub fn is_dragging(&self) -> bool {
+        !self.dragging.is_empty()
+    }
+
+    /// The size of [`Swapchain::viewport`](rendering::Swapchain::viewport), so the map keeps the letterbox's aspect ratio
+    pub fn set_view_size(&mut self, view_size: [u32; 2]) {
+        self.view_size = view_size.map(|size| size.max(1) as f32);
+    }
+
+    /// `cursor` is in pixels from the top left of the viewport, returns whether the map changed
+    pub fn cursor_moved(&mut self, cursor: [f32; 2]) -> bool {
+        if self.panning {
+            let units_per_pixel = self.units_per_pixel();
+            self.center[0] -= (cursor[0] - self.cursor[0]) * units_per_pixel;
+            self.center[1] += (cursor[1] - self.cursor[1]) * units_per_pixel;
+        }
+        self.cursor = cursor;
+
+        let position = self.cursor_position();
+        for &(triangle, vertex) in &self.dragging {
+            self.map.triangles[triangle].vertices[vertex] = position;
+        }
+        !self.dragging.is_empty()
+    }
+
+    /// `lines` is positive for zooming in
+    pub fn scrolled(&mut self, lines: f32) {
+        self.half_height /= ZOOM_PER_LINE.powf(lines);
+    }
+
+    pub fn middle_button(&mut self, pressed: bool) {
+        self.panning = pressed;
+    }
+
+    /// Returns whether the map changed
+    pub fn left_button(&mut self, pressed: bool) -> bool {
+        if !pressed {
+            let dragging = std::mem::take(&mut self.dragging);
+            let Some(drag_start) = self.drag_start.take() else {
+                return false;
+            };
+            // clicking a vertex without moving it isn't an edit
+            let moved = dragging.iter().any(|&(triangle, vertex)| {
+                self.map.triangles[triangle].vertices[vertex]
+                    != drag_start.triangles[triangle].vertices[vertex]
+            });
+            if moved {
+                self.push_undo_map(drag_start);
+            }
+            // the map is uploaded again once the drag ends, in case it wasn't valid while dragging
+            return moved;
+        }
+
+        let position = self.cursor_position();
+        if let Some(vertex) = self.vertex_near(position) {
+            if self.new_vertices.is_empty() {
+                self.drag_start = Some(self.map.clone());
+                self.dragging = self.vertices_at(vertex);
+                return false;
+            }
+            self.new_vertices.push(vertex);
+        } else {
+            self.new_vertices.push(position);
+        }
+
+        if self.new_vertices.len() < 3 {
+            return false;
+        }
+        let vertices = std::mem::take(&mut self.new_vertices);
+        self.push_undo();
+        self.map.triangles.push(MapTriangle {
+            vertices: [vertices[0], vertices[1], vertices[2]],
+            edges: [Edge::Open; 3],
+            color: new_triangle_color(self.map.triangles.len()),
+            texture: None,
+            uvs: None,
+        });
+        true
+    }
+
+    /// Returns whether the map changed
+    pub fn right_button(&mut self, pressed: bool) -> bool {
+        if !pressed {
+            return false;
+        }
+        let Some(edge) = self.edge_near(self.cursor_position()) else {
+            self.selected_edge = None;
+            return false;
+        };
+        match self.selected_edge.take() {
+            None => {
+                self.selected_edge = Some(edge);
+                false
+            }
+            Some(selected) if selected == edge => false,
+            Some(selected) => {
+                let length = self.edge_length(selected);
+                let other_length = self.edge_length(edge);
+                if (length - other_length).abs() > length.max(other_length) * 1e-4 {
+                    println!(
+                        "Unable to glue an edge of length {length} to one of length {other_length}"
+                    );
+                    return false;
+                }
+                self.push_undo();
+                self.unglue(selected);
+                self.unglue(edge);
+                self.set_edge(
+                    selected,
+                    Edge::Glued {
+                        triangle: edge.0 as u32,
+                        edge: edge.1 as u8,
+                        bend: 0.0,
+                    },
+                );
+                self.set_edge(
+                    edge,
+                    Edge::Glued {
+                        triangle: selected.0 as u32,
+                        edge: selected.1 as u8,
+                        bend: 0.0,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// Makes the selected edge, and whatever it was glued to, open again, returns whether the map changed
+    pub fn unglue_selected(&mut self) -> bool {
+        let Some(selected) = self.selected_edge.take() else {
+            return false;
+        };
+        self.push_undo();
+        self.unglue(selected);
+        true
+    }
+
+    /// Returns whether the map changed
+    pub fn undo(&mut self) -> bool {
+        let Some(map) = self.undo_stack.pop() else {
+            println!("Nothing to undo");
+            return false;
+        };
+        self.map = map;
+        self.new_vertices.clear();
+        self.dragging.clear();
+        self.drag_start = None;
+        self.selected_edge = None;
+        true
+    }
+
+    /// Draws the map into [`Editor::debug_draw_mut`], over a darkened scene
+    pub fn draw(&mut self) {
+        let aspect = self.view_size[0] / self.view_size[1];
+        let half_extent = [self.half_height * aspect, self.half_height];
+        self.debug_draw.set_view(self.center, half_extent);
+
+        let [x, y] = self.center;
+        let [half_width, half_height] = half_extent;
+        self.debug_draw.polygon(
+            &[
+                [x - half_width, y - half_height],
+                [x + half_width, y - half_height],
+                [x + half_width, y + half_height],
+                [x - half_width, y + half_height],
+            ],
+            BACKGROUND_COLOR,
+        );
+
+        let vertex_radius = PICK_RADIUS_PIXELS * 0.5 * self.units_per_pixel();
+        let partner = self
+            .selected_edge
+            .and_then(|selected| match self.edge(selected) {
+                Edge::Glued { triangle, edge, .. } => Some((triangle as usize, edge as usize)),
+                _ => None,
+            });
+        for (index, triangle) in self.map.triangles.iter().enumerate() {
+            let [r, g, b, a] = triangle.color;
+            let fill = if a > 0.0 {
+                [r, g, b, 0.3 * a]
+            } else {
+                [1.0, 1.0, 1.0, 0.1]
+            };
+            self.debug_draw.polygon(&triangle.vertices, fill);
+            for (edge, map_edge) in triangle.edges.iter().enumerate() {
+                let (from, to) = edge_vertices(triangle, edge);
+                let color = if self.selected_edge == Some((index, edge)) {
+                    SELECTED_EDGE_COLOR
+                } else if partner == Some((index, edge)) {
+                    PARTNER_EDGE_COLOR
+                } else {
+                    match map_edge {
+                        Edge::Open => OPEN_EDGE_COLOR,
+                        Edge::Mirror => MIRROR_EDGE_COLOR,
+                        Edge::Glued { .. } => GLUED_EDGE_COLOR,
+                    }
+                };
+                // the arrows show which way around edges are glued
+                self.debug_draw.arrow(from, to, color);
+            }
+            for vertex in triangle.vertices {
+                self.debug_draw.circle(vertex, vertex_radius, VERTEX_COLOR);
+            }
+        }
+
+        for &vertex in &self.new_vertices {
+            self.debug_draw
+                .circle(vertex, vertex_radius, NEW_VERTEX_COLOR);
+        }
+        self.debug_draw
+            .polyline(&self.new_vertices, NEW_VERTEX_COLOR);
+
+        let spawn = self.map.spawn.position;
+        let (sin, cos) = self.map.spawn.rotation.sin_cos();
+        self.debug_draw
+            .circle(spawn, vertex_radius * 2.0, SPAWN_COLOR);
+        self.debug_draw.arrow(
+            spawn,
+            [
+                spawn[0] + cos * vertex_radius * 6.0,
+                spawn[1] + sin * vertex_radius * 6.0,
+            ],
+            SPAWN_COLOR,
+        );
+    }
+
+    fn units_per_pixel(&self) -> f32 {
+        2.0 * self.half_height / self.view_size[1]
+    }
+
+    fn cursor_position(&self) -> [f32; 2] {
+        let units_per_pixel = self.units_per_pixel();
+        [
+            self.center[0] + (self.cursor[0] - self.view_size[0] * 0.5) * units_per_pixel,
+            self.center[1] - (self.cursor[1] - self.view_size[1] * 0.5) * units_per_pixel,
+        ]
+    }
+
+    fn push_undo(&mut self) {
+        self.push_undo_map(self.map.clone());
+    }
+
+    fn push_undo_map(&mut self, map: Map) {
+        if self.undo_stack.len() == UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(map);
+    }
+
+    /// The closest vertex within the pick radius of `position`
+    fn vertex_near(&self, position: [f32; 2]) -> Option<[f32; 2]> {
+        let pick_radius = PICK_RADIUS_PIXELS * self.units_per_pixel();
+        self.map
+            .triangles
+            .iter()
+            .flat_map(|triangle| triangle.vertices)
+            .map(|vertex| (vertex, distance(vertex, position)))
+            .filter(|&(_, distance)| distance <= pick_radius)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(vertex, _)| vertex)
+    }
+
+    /// Every vertex of every triangle that is at `position`
+    fn vertices_at(&self, position: [f32; 2]) -> Vec<(usize, usize)> {
+        self.map
+            .triangles
+            .iter()
+            .enumerate()
+            .flat_map(|(index, triangle)| {
+                (0..3)
+                    .filter(move |&vertex| triangle.vertices[vertex] == position)
+                    .map(move |vertex| (index, vertex))
+            })
+            .collect()
+    }
+
+    /// The closest edge within the pick radius of `position`
+    fn edge_near(&self, position: [f32; 2]) -> Option<(usize, usize)> {
+        let pick_radius = PICK_RADIUS_PIXELS * self.units_per_pixel();
+        self.map
+            .triangles
+            .iter()
+            .enumerate()
+            .flat_map(|(index, triangle)| {
+                (0..3).map(move |edge| {
+                    let (from, to) = edge_vertices(triangle, edge);
+                    ((index, edge), distance_to_segment(position, from, to))
+                })
+            })
+            .filter(|&(_, distance)| distance <= pick_radius)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(edge, _)| edge)
+    }
+
+    fn edge(&self, (triangle, edge): (usize, usize)) -> Edge {
+        self.map.triangles[triangle].edges[edge]
+    }
+
+    fn set_edge(&mut self, (triangle, edge): (usize, usize), value: Edge) {
+        self.map.triangles[triangle].edges[edge] = value;
+    }
+
+    fn edge_length(&self, (triangle, edge): (usize, usize)) -> f32 {
+        let (from, to) = edge_vertices(&self.map.triangles[triangle], edge);
+        distance(from, to)
+    }
+
+    /// Opens `edge` and the edge it is glued to
+    fn unglue(&mut self, edge: (usize, usize)) {
+        if let Edge::Glued {
+            triangle,
+            edge: other_edge,
+            ..
+        } = self.edge(edge)
+        {
+            let other = (triangle as usize, other_edge as usize);
+            // only when it is glued back, so an edge glued to itself isn't opened twice
+            if let Edge::Glued {
+                triangle,
+                edge: back,
+                ..
+            } = self.edge(other)
+                && (triangle as usize, back as usize) == edge
+            {
+                self.set_edge(other, Edge::Open);
+            }
+        }
+        self.set_edge(edge, Edge::Open);
+    }
+}
+
+/// A different color for each triangle, like the `DEBUG_COLORED` shader variant
+fn new_triangle_color(index: usize) -> [f32; 4] {
+    let hash = (index as u32).wrapping_mul(2654435761);
+    let [r, g, b] = [0, 8, 16].map(|shift| ((hash >> shift) & 0xFF) as f32 / 255.0);
+    [r, g, b, 0.5]
+}
+
+/// The start and end of `edge`, in the same order as the edge indices of [`Triangle`](crate::Triangle)
+fn edge_vertices(triangle: &MapTriangle, edge: usize) -> ([f32; 2], [f32; 2]) {
+    let [a, b, c] = triangle.vertices;
+    match edge {
+        0 => (a, b),
+        1 => (a, c),
+        _ => (b, c),
+    }
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+fn distance_to_segment(point: [f32; 2], from: [f32; 2], to: [f32; 2]) -> f32 {
+    let direction = [to[0] - from[0], to[1] - from[1]];
+    let length_squared = direction[0] * direction[0] + direction[1] * direction[1];
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point[0] - from[0]) * direction[0] + (point[1] - from[1]) * direction[1])
+            / length_squared)
+            .clamp(0.0, 1.0)
+    };
+    distance(
+        point,
+        [from[0] + direction[0] * t, from[1] + direction[1] * t],
+    )
+}
@@ -0,0 +1,59 @@
+use rendering::FrameStats;
+use std::time::Duration;
+
+/// Sums up [`FrameStats`] between reports, so it can be drained from the swapchain every frame
+/// without keeping every frame around
+#[derive(Default)]
+pub struct FrameTimings {
+    presented: u32,
+    skipped: u32,
+    fence_wait_time: Duration,
+    acquire_time: Duration,
+    record_time: Duration,
+    submit_time: Duration,
+    present_time: Duration,
+    gpu_time: Duration,
+    gpu_frames: u32,
+}
+
+impl FrameTimings {
+    pub fn add(&mut self, stats: FrameStats) {
+        // polling for a free frame is counted separately, as it happens many times for each presented frame
+        if stats.skipped {
+            self.skipped += 1;
+            return;
+        }
+        self.presented += 1;
+        self.fence_wait_time += stats.fence_wait_time;
+        self.acquire_time += stats.acquire_time;
+        self.record_time += stats.record_time;
+        self.submit_time += stats.submit_time;
+        self.present_time += stats.present_time;
+        if let Some(gpu_time) = stats.gpu_time {
+            self.gpu_time += gpu_time;
+            self.gpu_frames += 1;
+        }
+    }
+
+    /// The average of every presented frame since the last report
+    pub fn report(&self) -> String {
+        if self.presented == 0 {
+            return "No frames were presented".to_owned();
+        }
+        let average = |time: Duration| time / self.presented;
+        let mut report = format!(
+            "{} frames, {} skipped, on average: fences {:.2?}, acquire {:.2?}, record {:.2?}, submit {:.2?}, present {:.2?}",
+            self.presented,
+            self.skipped,
+            average(self.fence_wait_time),
+            average(self.acquire_time),
+            average(self.record_time),
+            average(self.submit_time),
+            average(self.present_time),
+        );
+        if self.gpu_frames > 0 {
+            report += &format!(", gpu {:.2?}", self.gpu_time / self.gpu_frames);
+        }
+        report
+    }
+}
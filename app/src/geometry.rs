@@ -0,0 +1,420 @@
+//! The math of moving through the triangles, the same as `walk` in `full_screen_quad.slang` does it,
+//! any change here has to be made to the shader too
+
+use crate::{Position, Triangle, traversal::MAX_STEPS};
+
+pub type Vec2 = [f32; 2];
+
+pub fn add(a: Vec2, b: Vec2) -> Vec2 {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+pub fn sub(a: Vec2, b: Vec2) -> Vec2 {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+pub fn scale(a: Vec2, s: f32) -> Vec2 {
+    [a[0] * s, a[1] * s]
+}
+
+pub fn dot(a: Vec2, b: Vec2) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+pub fn length(a: Vec2) -> f32 {
+    dot(a, a).sqrt()
+}
+
+pub fn normalize(a: Vec2) -> Vec2 {
+    scale(a, 1.0 / length(a))
+}
+
+/// Counterclockwise by `angle` radians
+pub fn rotate(a: Vec2, angle: f32) -> Vec2 {
+    let (s, c) = angle.sin_cos();
+    [a[0] * c - a[1] * s, a[0] * s + a[1] * c]
+}
+
+/// Mirrors `direction` off a line with the unit `normal`
+pub fn reflect(direction: Vec2, normal: Vec2) -> Vec2 {
+    sub(direction, scale(normal, 2.0 * dot(direction, normal)))
+}
+
+/// The start, unit direction and unit normal pointing into the triangle of each edge, in the same order as the edge indices
+pub struct Edges {
+    pub starts: [Vec2; 3],
+    pub directions: [Vec2; 3],
+    pub normals: [Vec2; 3],
+}
+
+impl Edges {
+    pub fn new(triangle: &Triangle) -> Self {
+        let a = [0.0, 0.0];
+        let b = [triangle.bx, 0.0];
+        let c = [triangle.cx, triangle.cy];
+
+        let starts = [a, a, b];
+        let directions = [
+            normalize(sub(b, a)),
+            normalize(sub(c, a)),
+            normalize(sub(c, b)),
+        ];
+        let opposite = [c, b, a];
+        let normals = std::array::from_fn(|edge| {
+            let [x, y] = directions[edge];
+            let perp = [-y, x];
+            // sign, so a degenerate triangle gives the same nonsense as the shader
+            let side = dot(perp, sub(opposite[edge], starts[edge]));
+            scale(perp, if side == 0.0 { 0.0 } else { side.signum() })
+        });
+        Self {
+            starts,
+            directions,
+            normals,
+        }
+    }
+
+    /// How far `point` is from each edge, negative on the far side of it
+    pub fn distances(&self, point: Vec2) -> [f32; 3] {
+        std::array::from_fn(|edge| dot(sub(point, self.starts[edge]), self.normals[edge]))
+    }
+
+    /// Whether `point` is inside the triangle or on one of its edges
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.distances(point)
+            .iter()
+            .all(|&distance| distance >= 0.0)
+    }
+
+    /// The edge a ray from `point` along `direction` leaves the triangle through, and how far along the ray that is,
+    /// `incoming_edge` is skipped as the ray has just come in through it
+    ///
+    /// Only `None` when `point` is outside of the triangle or the triangle is degenerate
+    pub fn exit(
+        &self,
+        point: Vec2,
+        direction: Vec2,
+        incoming_edge: Option<usize>,
+    ) -> Option<(usize, f32)> {
+        let mut closest = None;
+        for edge in 0..3 {
+            let edge_distance = dot(sub(self.starts[edge], point), self.normals[edge])
+                / dot(direction, self.normals[edge]);
+            if edge_distance >= 0.0
+                && incoming_edge != Some(edge)
+                && closest.is_none_or(|(_, closest_distance)| closest_distance > edge_distance)
+            {
+                closest = Some((edge, edge_distance));
+            }
+        }
+        closest
+    }
+}
+
+/// How coordinates change when crossing an edge into the triangle glued to it,
+/// points on the edge stay where they are along it and points inside the triangle end up on the far side of the other edge
+pub struct Transition {
+    /// The triangle on the other side
+    pub triangle_index: u32,
+    /// The edge of that triangle that was crossed
+    pub edge: usize,
+    start: Vec2,
+    direction: Vec2,
+    normal: Vec2,
+    other_start: Vec2,
+    other_direction: Vec2,
+    other_normal: Vec2,
+    bend: f32,
+}
+
+impl Transition {
+    /// `None` for mirrors and edges that aren't glued to anything
+    pub fn new(triangles: &[Triangle], triangle_index: u32, edge: usize) -> Option<Self> {
+        let triangle = &triangles[triangle_index as usize];
+        let other_index = triangle.edge_triangles[edge];
+        if triangle.is_mirror(edge) || other_index == u32::MAX {
+            return None;
+        }
+        let other_edge = triangle.edge_indices[edge] as usize;
+        let edges = Edges::new(triangle);
+        let other_edges = Edges::new(&triangles[other_index as usize]);
+        Some(Self {
+            triangle_index: other_index,
+            edge: other_edge,
+            start: edges.starts[edge],
+            direction: edges.directions[edge],
+            normal: edges.normals[edge],
+            other_start: other_edges.starts[other_edge],
+            other_direction: other_edges.directions[other_edge],
+            other_normal: other_edges.normals[other_edge],
+            bend: triangle.edge_bends[edge],
+        })
+    }
+
+    /// `point` in the coordinates of the triangle on the other side
+    pub fn point(&self, point: Vec2) -> Vec2 {
+        let offset = sub(point, self.start);
+        add(self.other_start, self.linear(offset))
+    }
+
+    /// `direction` in the coordinates of the triangle on the other side, turned by the edge's bend
+    pub fn direction(&self, direction: Vec2) -> Vec2 {
+        let direction = self.linear(direction);
+        if self.bend != 0.0 {
+            rotate(direction, self.bend)
+        } else {
+            direction
+        }
+    }
+
+    /// Along the edge stays along the edge, and into this triangle becomes out of the other one
+    fn linear(&self, v: Vec2) -> Vec2 {
+        add(
+            scale(self.other_direction, dot(self.direction, v)),
+            scale(self.other_normal, -dot(self.normal, v)),
+        )
+    }
+}
+
+/// Moves `position` along `movement` in a straight line, crossing into the triangles glued to the edges it reaches,
+/// `forward` is carried along with it, so the view can be turned to match the coordinates of the triangle it ends up in
+///
+/// Returns the new position and `forward` in its triangle's coordinates,
+/// or `None` when the way is blocked by a mirror or an edge that isn't glued to anything
+pub fn move_position(
+    triangles: &[Triangle],
+    mut position: Position,
+    movement: Vec2,
+    mut forward: Vec2,
+) -> Option<(Position, Vec2)> {
+    let mut distance = length(movement);
+    if position.triangle_index == u32::MAX || distance == 0.0 {
+        return Some((position, forward));
+    }
+    let mut direction = scale(movement, 1.0 / distance);
+    let mut point = [position.offset_x, position.offset_y];
+
+    // the player can end up exactly on an edge, or just past it from rounding, which they must not cross back through
+    let edges = Edges::new(triangles.get(position.triangle_index as usize)?);
+    let distances = edges.distances(point);
+    let mut incoming_edge =
+        (0..3).find(|&edge| distances[edge] <= 0.0 && dot(direction, edges.normals[edge]) > 0.0);
+
+    for _ in 0..MAX_STEPS {
+        let edges = Edges::new(&triangles[position.triangle_index as usize]);
+        let (edge, distance_to_edge) = edges.exit(point, direction, incoming_edge)?;
+        if distance_to_edge > distance {
+            point = add(point, scale(direction, distance));
+            break;
+        }
+        distance -= distance_to_edge;
+
+        let transition = Transition::new(triangles, position.triangle_index, edge)?;
+        point = transition.point(add(point, scale(direction, distance_to_edge)));
+        direction = transition.direction(direction);
+        forward = transition.direction(forward);
+        position.triangle_index = transition.triangle_index;
+        incoming_edge = Some(transition.edge);
+    }
+
+    [position.offset_x, position.offset_y] = point;
+    Some((position, forward))
+}
+
+/// `position` moved into the triangle with the same index in `new`, at the same place relative to its vertices,
+/// so it stays inside when an edit moves them, `None` when either doesn't have the triangle
+pub fn carry_position(old: &[Triangle], new: &[Triangle], position: Position) -> Option<Position> {
+    let old_triangle = old.get(position.triangle_index as usize)?;
+    let new_triangle = new.get(position.triangle_index as usize)?;
+    // `a` is at the origin, so the point is only made of `b` and `c`
+    let c_weight = position.offset_y / old_triangle.cy;
+    let b_weight = (position.offset_x - c_weight * old_triangle.cx) / old_triangle.bx;
+    Some(Position {
+        offset_x: b_weight * new_triangle.bx + c_weight * new_triangle.cx,
+        offset_y: c_weight * new_triangle.cy,
+        triangle_index: position.triangle_index,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::Map, tilings};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(length(sub(a, b)) < EPSILON, "{a:?} is not close to {b:?}");
+    }
+
+    /// Two of the same triangle glued edge to edge, with every point on an edge of one at the same place on the other
+    fn pair() -> Vec<Triangle> {
+        Map::default().build().unwrap().triangles
+    }
+
+    fn position(triangle_index: u32, [offset_x, offset_y]: Vec2) -> Position {
+        Position {
+            offset_x,
+            offset_y,
+            triangle_index,
+        }
+    }
+
+    #[test]
+    fn contains_the_inside_and_edges() {
+        let edges = Edges::new(&pair()[0]);
+        assert!(edges.contains([1.0, 0.5]));
+        assert!(edges.contains([0.0, 0.0]));
+        assert!(edges.contains([1.0, 0.0]));
+        assert!(!edges.contains([1.0, -0.1]));
+        assert!(!edges.contains([0.0, 1.0]));
+        assert!(!edges.contains([2.0, 1.0]));
+        assert_eq!(edges.distances([1.0, 0.5])[0], 0.5);
+    }
+
+    #[test]
+    fn exits_through_the_nearest_edge() {
+        let edges = Edges::new(&pair()[0]);
+        let (edge, distance) = edges.exit([1.0, 0.5], [0.0, -1.0], None).unwrap();
+        assert_eq!(edge, 0);
+        assert!((distance - 0.5).abs() < EPSILON);
+        // coming in through the bottom edge, the ray goes on to the right
+        let (edge, _) = edges.exit([1.2, 0.0], [0.0, 1.0], Some(0)).unwrap();
+        assert_eq!(edge, 2);
+        assert!(edges.exit([1.0, -1.0], [0.0, -1.0], None).is_none());
+    }
+
+    #[test]
+    fn transitions_keep_points_on_the_edge() {
+        let triangles = pair();
+        for edge in 0..3 {
+            let transition = Transition::new(&triangles, 0, edge).unwrap();
+            assert_eq!((transition.triangle_index, transition.edge), (1, edge));
+            let edges = Edges::new(&triangles[0]);
+            let on_edge = add(edges.starts[edge], scale(edges.directions[edge], 0.3));
+            assert_close(transition.point(on_edge), on_edge);
+            // the inside of one triangle is the outside of the other
+            assert!(!Edges::new(&triangles[1]).contains(transition.point([1.0, 0.5])));
+        }
+    }
+
+    #[test]
+    fn transitions_back_undo_transitions() {
+        let triangles = tilings::klein_bottle(3, 2, 2.0);
+        for triangle_index in 0..triangles.len() as u32 {
+            for edge in 0..3 {
+                let there = Transition::new(&triangles, triangle_index, edge).unwrap();
+                let back = Transition::new(&triangles, there.triangle_index, there.edge).unwrap();
+                assert_eq!((back.triangle_index, back.edge), (triangle_index, edge));
+                let point = [0.7, 0.4];
+                assert_close(back.point(there.point(point)), point);
+                let direction = normalize([0.3, -0.8]);
+                assert_close(back.direction(there.direction(direction)), direction);
+            }
+        }
+    }
+
+    #[test]
+    fn bends_turn_directions_but_not_points() {
+        let mut triangles = pair();
+        triangles[0].edge_bends[0] = 0.5;
+        let transition = Transition::new(&triangles, 0, 0).unwrap();
+        let straight = Transition::new(&pair(), 0, 0).unwrap();
+        assert_close(transition.point([1.0, 0.5]), straight.point([1.0, 0.5]));
+        assert_close(
+            transition.direction([0.0, -1.0]),
+            rotate(straight.direction([0.0, -1.0]), 0.5),
+        );
+    }
+
+    #[test]
+    fn mirrors_and_open_edges_have_no_transition() {
+        let mut triangles = pair();
+        triangles[0].mirror_edges = 0b001;
+        triangles[0].edge_triangles[1] = u32::MAX;
+        assert!(Transition::new(&triangles, 0, 0).is_none());
+        assert!(Transition::new(&triangles, 0, 1).is_none());
+        assert!(Transition::new(&triangles, 0, 2).is_some());
+        assert!(
+            move_position(&triangles, position(0, [1.0, 0.5]), [0.0, -1.0], [1.0, 0.0]).is_none()
+        );
+        assert!(
+            move_position(&triangles, position(0, [1.0, 0.5]), [0.0, 0.2], [1.0, 0.0]).is_some()
+        );
+    }
+
+    #[test]
+    fn moving_around_a_torus_comes_back() {
+        let triangles = tilings::flat_torus(3, 2, 2.0);
+        let start = position(0, [1.2, 0.4]);
+        for movement in [[6.0, 0.0], [0.0, 4.0], [6.0, 4.0]] {
+            let (end, forward) = move_position(&triangles, start, movement, [1.0, 0.0]).unwrap();
+            assert_eq!(end.triangle_index, start.triangle_index);
+            assert_close(
+                [end.offset_x, end.offset_y],
+                [start.offset_x, start.offset_y],
+            );
+            assert_close(forward, [1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn moving_across_a_klein_bottle_mirrors() {
+        let triangles = tilings::klein_bottle(3, 2, 2.0);
+        let start = position(0, [1.2, 0.4]);
+        let (once, forward) = move_position(&triangles, start, [6.0, 0.0], [0.0, 1.0]).unwrap();
+        // a point in the bottom row comes back in the top row with up turned into down,
+        // which is along the first edge of the triangles at the seam, as they go down the left side
+        assert_eq!(once.triangle_index, 2 * 3 + 1);
+        assert_close([once.offset_x, once.offset_y], [0.4, 1.2]);
+        assert_close(forward, [1.0, 0.0]);
+        // right is along the second axis of those triangles
+        let (twice, forward) = move_position(&triangles, once, [0.0, 6.0], forward).unwrap();
+        assert_eq!(twice.triangle_index, start.triangle_index);
+        assert_close(
+            [twice.offset_x, twice.offset_y],
+            [start.offset_x, start.offset_y],
+        );
+        assert_close(forward, [0.0, 1.0]);
+    }
+
+    #[test]
+    fn moving_from_an_edge_does_not_cross_it_again() {
+        let triangles = tilings::flat_torus(1, 1, 2.0);
+        let transition = Transition::new(&triangles, 0, 1).unwrap();
+        let on_edge = transition.point([1.0, 1.0]);
+        let edges = Edges::new(&triangles[transition.triangle_index as usize]);
+        let inwards = edges.normals[transition.edge];
+        let (moved, _) = move_position(
+            &triangles,
+            position(transition.triangle_index, on_edge),
+            scale(inwards, 0.1),
+            [1.0, 0.0],
+        )
+        .unwrap();
+        assert_eq!(moved.triangle_index, transition.triangle_index);
+        assert!(edges.contains([moved.offset_x, moved.offset_y]));
+    }
+
+    #[test]
+    fn carried_positions_keep_their_place_between_the_vertices() {
+        let old = pair();
+        let mut new = old.clone();
+        new[0].bx = 3.0;
+        new[0].cx = -0.5;
+        new[0].cy = 4.0;
+
+        // the middle of edge 2 stays the middle of it
+        let middle = position(0, scale(add([old[0].bx, 0.0], [old[0].cx, old[0].cy]), 0.5));
+        let carried = carry_position(&old, &new, middle).unwrap();
+        assert_eq!(carried.triangle_index, 0);
+        assert_close([carried.offset_x, carried.offset_y], [1.25, 2.0]);
+        assert!(Edges::new(&new[0]).contains([carried.offset_x, carried.offset_y]));
+
+        // triangles that weren't edited don't move the position
+        let unchanged = carry_position(&old, &new, position(1, [1.0, 0.5])).unwrap();
+        assert_close([unchanged.offset_x, unchanged.offset_y], [1.0, 0.5]);
+
+        assert!(carry_position(&old, &new[..1], position(1, [1.0, 0.5])).is_none());
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{
+    CACHED_COMMANDS_VARIANT, NO_GHOST, NO_TEXTURE, Position, PushConstants, Triangle,
+    create_full_screen_quad_pipeline, create_full_screen_quad_pipeline_layout, push_constants,
+    record_traversal_pass, render, shader_variant_spirv, shaders, validate_triangles,
+};
+use ash::vk;
+use gpu_allocator::MemoryLocation;
+use rendering::{
+    BindlessTextures, Buffer, Device, DeviceConfig, DeviceFeature, GOLDEN_IMAGE_FORMAT,
+    GoldenImage, GpuPtr, Instance, InstanceConfig, Shader, assert_golden_image, render_offscreen,
+};
+use std::{path::Path, sync::Arc};
+
+const GOLDEN_IMAGE_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden");
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 144;
+/// Small differences are expected between drivers, especially around edges
+const TOLERANCE: u8 = 4;
+const MAX_DIFFERING_PIXELS: usize = 64;
+
+/// Two triangles glued along every edge, with a mirror on one edge of the first and a bend on one edge of the second,
+/// kept separate from the map in `main` so changing the map doesn't change the reference images
+fn scene() -> [Triangle; 2] {
+    [
+        Triangle {
+            bx: 2.0,
+            cx: 1.0,
+            cy: 2.0,
+
+            edge_triangles: [1, 1, 1],
+            edge_indices: [0, 1, 2],
+
+            _padding1: 0,
+            mirror_edges: 0b100,
+
+            edge_bends: [0.0; 3],
+
+            _padding2: 0,
+
+            color: [0.0; 4],
+
+            texture: NO_TEXTURE,
+            _padding3: 0,
+            uvs: [[0.0; 2]; 3],
+        },
+        Triangle {
+            bx: 2.0,
+            cx: 1.0,
+            cy: 2.0,
+
+            edge_triangles: [0, 0, 0],
+            edge_indices: [0, 1, 2],
+
+            _padding1: 0,
+            mirror_edges: 0,
+
+            edge_bends: [0.0, 0.3, 0.0],
+
+            _padding2: 0,
+
+            color: [0.0; 4],
+
+            texture: NO_TEXTURE,
+            _padding3: 0,
+            uvs: [[0.0; 2]; 3],
+        },
+    ]
+}
+
+/// Renders the traversal shader headlessly from `position` with `ghost_position`,
+/// with `cached_commands` the info is read from a buffer through the cached commands variant like `--cached-commands` does
+fn render_scene(
+    position: Position,
+    ghost_position: Position,
+    cached_commands: bool,
+) -> GoldenImage {
+    let entry = unsafe { ash::Entry::load() }.unwrap();
+    let instance = Arc::new(unsafe {
+        Instance::new(
+            entry,
+            None,
+            InstanceConfig::default().application_name(c"NonEuclidean Golden Images"),
+        )
+    });
+    let device = Arc::new(Device::new(
+        instance,
+        DeviceConfig::default().require_feature(DeviceFeature::BindlessTextures),
+    ));
+
+    let triangles = scene();
+    validate_triangles(&triangles).unwrap();
+
+    let mut triangles_buffer = Buffer::new(
+        device.clone(),
+        "Triangles Buffer",
+        MemoryLocation::CpuToGpu,
+        size_of_val::<[_]>(&triangles) as _,
+        vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        false,
+        None,
+    );
+    unsafe { triangles_buffer.get_mapped_mut() }
+        .unwrap()
+        .copy_from_slice(bytemuck::cast_slice(&triangles));
+
+    let mut visit_counts_buffer = Buffer::new(
+        device.clone(),
+        "Visit Counts Buffer",
+        MemoryLocation::GpuToCpu,
+        (triangles.len() * size_of::<u32>()) as _,
+        vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        false,
+        None,
+    );
+    unsafe { visit_counts_buffer.get_mapped_mut() }
+        .unwrap()
+        .fill(0);
+
+    let spirv = if cached_commands {
+        let variant = shaders::full_screen_quad::VARIANTS
+            .iter()
+            .position(|&(name, _, _)| name == CACHED_COMMANDS_VARIANT)
+            .unwrap();
+        shader_variant_spirv(variant + 1).2
+    } else {
+        shaders::full_screen_quad::SPIRV
+    };
+    let shader = unsafe { Shader::new(device.clone(), "Full Screen Quad Shader", spirv) };
+    // the scene is untextured, but the shader still declares the texture array
+    let textures = BindlessTextures::new(device.clone(), "Golden Test Textures", 1);
+    let pipeline_layout = create_full_screen_quad_pipeline_layout(&device, &shader, &textures);
+    let pipeline = create_full_screen_quad_pipeline(
+        &device,
+        &pipeline_layout,
+        GOLDEN_IMAGE_FORMAT,
+        &shader,
+        None,
+    );
+
+    let render_area = vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent: vk::Extent2D {
+            width: WIDTH,
+            height: HEIGHT,
+        },
+    };
+    // where the swapchain would keep the frame's info with cached commands
+    let mut frame_data = Buffer::new(
+        device.clone(),
+        "Frame Data",
+        MemoryLocation::CpuToGpu,
+        size_of::<PushConstants>() as _,
+        vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        false,
+        None,
+    );
+    let frame_info = unsafe {
+        push_constants(
+            &triangles_buffer,
+            triangles.len() as u32,
+            &visit_counts_buffer,
+            render_area,
+            position,
+            0.0,
+            ghost_position,
+            0.0,
+            (GpuPtr::null(), [0, 0]),
+            [0.0, 0.0],
+        )
+    };
+    unsafe { frame_data.get_mapped_mut() }.unwrap()[..size_of::<PushConstants>()]
+        .copy_from_slice(bytemuck::bytes_of(&frame_info));
+
+    let image = unsafe {
+        render_offscreen(&device, WIDTH, HEIGHT, |command_buffer, image| {
+            let mut image_layout = image.layout();
+            if cached_commands {
+                record_traversal_pass(
+                    &device,
+                    &pipeline_layout,
+                    &textures,
+                    &pipeline,
+                    command_buffer,
+                    &mut image_layout,
+                    render_area,
+                    image.handle(),
+                    image.view(),
+                    &frame_data.device_address(),
+                );
+            } else {
+                render(
+                    &device,
+                    &pipeline_layout,
+                    &textures,
+                    &pipeline,
+                    &triangles_buffer,
+                    triangles.len() as u32,
+                    &visit_counts_buffer,
+                    command_buffer,
+                    &mut image_layout,
+                    render_area,
+                    image.handle(),
+                    image.view(),
+                    position,
+                    0.0,
+                    ghost_position,
+                    0.0,
+                    (GpuPtr::null(), [0, 0]),
+                    [0.0, 0.0],
+                );
+            }
+            image.set_layout(image_layout);
+        })
+    };
+
+    device.destroy_resources();
+    image
+}
+
+fn assert_scene(name: &str, position: Position, ghost_position: Position, cached_commands: bool) {
+    let image = render_scene(position, ghost_position, cached_commands);
+    assert_golden_image(
+        &image,
+        &Path::new(GOLDEN_IMAGE_DIRECTORY).join(format!("{name}.pam")),
+        TOLERANCE,
+        MAX_DIFFERING_PIXELS,
+    );
+}
+
+#[test]
+#[ignore = "needs a vulkan device, CI runs it on lavapipe with --ignored"]
+fn center_of_first_triangle() {
+    assert_scene(
+        "center_of_first_triangle",
+        Position {
+            offset_x: 1.0,
+            offset_y: 0.7,
+            triangle_index: 0,
+        },
+        NO_GHOST,
+        false,
+    );
+}
+
+#[test]
+#[ignore = "needs a vulkan device, CI runs it on lavapipe with --ignored"]
+fn near_bent_edge_with_ghost() {
+    assert_scene(
+        "near_bent_edge_with_ghost",
+        Position {
+            offset_x: 0.6,
+            offset_y: 1.0,
+            triangle_index: 1,
+        },
+        Position {
+            offset_x: 1.2,
+            offset_y: 0.5,
+            triangle_index: 0,
+        },
+        false,
+    );
+}
+
+/// The cached commands variant only pushes the address of the info, like every frame with `--cached-commands` does,
+/// and has to draw exactly what pushing the info does
+#[test]
+#[ignore = "needs a vulkan device, CI runs it on lavapipe with --ignored"]
+fn center_of_first_triangle_with_cached_commands() {
+    assert_scene(
+        "center_of_first_triangle",
+        Position {
+            offset_x: 1.0,
+            offset_y: 0.7,
+            triangle_index: 0,
+        },
+        NO_GHOST,
+        true,
+    );
+}
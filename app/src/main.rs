@@ -1,17 +1,54 @@
+mod editor;
+mod frame_timings;
+mod geometry;
+#[cfg(test)]
+mod golden_tests;
+mod map;
+mod map_textures;
+mod permalink;
+mod session;
+mod tilings;
+mod traversal;
+mod visit_heatmap;
+
+/// Generated by `build.rs`, a module per shader with its `SPIRV`, a constant per entry point, and a module per variant
+// the variants are only used through `VARIANTS`, so their entry point constants go unused
+#[expect(dead_code)]
+mod shaders {
+    include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+}
+
 use ash::vk;
-use bytemuck::NoUninit;
+use bytemuck::{AnyBitPattern, NoUninit};
+use editor::Editor;
+use frame_timings::FrameTimings;
 use gpu_allocator::MemoryLocation;
+use map::{BuiltMap, Map};
+use map_textures::MapTextures;
+use permalink::{Permalink, ViewSettings};
 use rendering::{
-    Buffer, Device, Instance, RenderResult, RenderSync, ResourceToDestroy, Shader, Surface,
-    Swapchain, include_spirv, transition_image,
+    AntiAliasing, BarrierBuilder, BindlessTextures, Buffer, DebugDraw, Device, DeviceConfig,
+    DeviceFeature, FrameLimiter, GpuPtr, GraphicsPipelineBuilder, GraphicsPipelineLibrary,
+    HIGH_MEMORY_PRIORITY, ImageUsage, Instance, InstanceConfig, LatencyMode, Pipeline,
+    PipelineLayout, PresentScaling, RenderResult, RenderSync, Shader, ShaderWatcher, Surface,
+    Swapchain, TonemapOperator, ValidationFeatures, read_spirv,
 };
-use scope_guard::scope_guard;
-use std::{sync::Arc, time::Instant};
+use session::{SessionRecorder, SessionReplay};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use traversal::{TraversalCheck, VIEW_DISTANCE};
 use winit::{
-    event::{Event, KeyEvent, WindowEvent},
+    event::{
+        DeviceEvent, ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+    },
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     keyboard::{KeyCode, PhysicalKey},
-    window::WindowAttributes,
+    window::{CursorGrabMode, Window, WindowAttributes},
 };
 
 #[derive(Clone, Copy, NoUninit)]
@@ -29,10 +66,36 @@ struct Triangle {
     edge_triangles: [u32; 3],
     edge_indices: [u8; 3],
 
-    _padding2: u8,
+    /// Bit `n` is set when edge `n` reflects instead of leading to `edge_triangles[n]`
+    mirror_edges: u8,
+
+    /// Radians to rotate the direction of rays by after crossing each edge, counterclockwise, for lens like regions
+    edge_bends: [f32; 3],
+
+    _padding2: u32,
+
+    /// Linear RGBA, blended over the shading by its alpha so cells can be told apart
+    color: [f32; 4],
+
+    /// Index into the map's [`BindlessTextures`], drawn instead of the shading, or [`NO_TEXTURE`]
+    texture: u32,
+
+    _padding3: u32,
+
+    /// Where `a`, `b` and `c` are in the texture
+    uvs: [[f32; 2]; 3],
 }
 
-#[derive(Clone, Copy, NoUninit)]
+/// The [`Triangle::texture`] of triangles that are shaded instead of textured
+const NO_TEXTURE: u32 = u32::MAX;
+
+impl Triangle {
+    fn is_mirror(&self, edge: usize) -> bool {
+        (self.mirror_edges >> edge) & 1 != 0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, NoUninit, AnyBitPattern)]
 #[repr(C)]
 struct Position {
     offset_x: f32,
@@ -40,15 +103,105 @@ struct Position {
     triangle_index: u32,
 }
 
+/// The text that is copied to and pasted from the clipboard, `triangle_index offset_x offset_y`
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.triangle_index, self.offset_x, self.offset_y
+        )
+    }
+}
+
+impl std::str::FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let mut next = |name: &str| {
+            parts
+                .next()
+                .ok_or_else(|| format!("Missing {name} in position '{s}'"))
+        };
+        let triangle_index = next("triangle index")?;
+        let offset_x = next("x offset")?;
+        let offset_y = next("y offset")?;
+        if parts.next().is_some() {
+            return Err(format!("Unexpected text after position '{s}'"));
+        }
+
+        Ok(Self {
+            offset_x: offset_x
+                .parse()
+                .map_err(|error| format!("Invalid x offset '{offset_x}': {error}"))?,
+            offset_y: offset_y
+                .parse()
+                .map_err(|error| format!("Invalid y offset '{offset_y}': {error}"))?,
+            triangle_index: triangle_index
+                .parse()
+                .map_err(|error| format!("Invalid triangle index '{triangle_index}': {error}"))?,
+        })
+    }
+}
+
 #[derive(Clone, Copy, NoUninit)]
 #[repr(C)]
 struct PushConstants {
-    triangles: vk::DeviceAddress,
+    triangles: GpuPtr<Triangle>,
     start_position: Position,
     aspect: f32,
+    triangle_count: u32,
+
+    /// Radians the view is turned counterclockwise
+    rotation: f32,
+
+    visit_counts: GpuPtr<u32>,
+    ghost_position: Position,
+    crossing_effect: f32,
+
+    traversal_probe: GpuPtr<Position>,
+    probe_pixel: [u32; 2],
+
+    jitter: [f32; 2],
 }
 
+/// Hides the ghost, the shader treats this triangle index as outside of the map
+const NO_GHOST: Position = Position {
+    offset_x: 0.0,
+    offset_y: 0.0,
+    triangle_index: u32::MAX,
+};
+
+/// How fast Q/E and the left/right arrow keys turn the view, in radians per second
+const ROTATION_SPEED: f32 = 2.0;
+
+/// Radians the view turns per pixel the mouse moves while the cursor is captured, `--mouse-sensitivity <radians>` overrides it
+const DEFAULT_MOUSE_SENSITIVITY: f32 = 0.003;
+
+/// How long the crossing effects take to fade after the player crosses an edge, in seconds
+const CROSSING_EFFECT_DURATION: f32 = 0.3;
+
+/// The render scales F7 cycles through
+const RENDER_SCALES: [f32; 5] = [1.0, 0.75, 0.5, 1.5, 2.0];
+
+/// The frame rate cap when the monitor's refresh rate is unknown
+const DEFAULT_REFRESH_RATE: f64 = 60.0;
+
+/// The full screen quad shader variant `--cached-commands` renders with, which reads its info through a pointer
+const CACHED_COMMANDS_VARIANT: &str = "cached_commands";
+
+/// The aspect ratio F8 letterboxes to
+const LETTERBOX_SIZE: vk::Extent2D = vk::Extent2D {
+    width: 16,
+    height: 9,
+};
+
+const SHADER_SOURCE_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders");
+
 fn main() {
+    tracing_subscriber::fmt::init();
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
@@ -60,148 +213,262 @@ fn main() {
 
     let entry = unsafe { ash::Entry::load() }.unwrap();
 
-    let instance = Arc::new(unsafe { Instance::new(entry, None) });
+    let instance = Arc::new(unsafe {
+        Instance::new(
+            entry,
+            None,
+            InstanceConfig::default()
+                .application_name(c"NonEuclidean Renderer")
+                .validation_features(
+                    ValidationFeatures::default().debug_printf(cfg!(debug_assertions)),
+                ),
+        )
+    });
     let surface = Arc::new(Surface::new(instance.clone(), &window));
 
-    let device = Arc::new(Device::new(instance.clone()));
-    let mut swapchain = Swapchain::new(device.clone(), surface);
-
-    let triangles = [
-        Triangle {
-            bx: 2.0,
-            cx: 1.0,
-            cy: 2.0,
-
-            edge_triangles: [1, 1, 1],
-            edge_indices: [0, 1, 2],
-
-            _padding1: 0,
-            _padding2: 0,
-        },
-        Triangle {
-            bx: 2.0,
-            cx: 1.0,
-            cy: 2.0,
-
-            edge_triangles: [0, 0, 0],
-            edge_indices: [0, 1, 2],
+    // `--adapter <index>` picks which of the listed gpus to render with
+    let adapters = instance.enumerate_adapters();
+    for (index, adapter) in adapters.iter().enumerate() {
+        println!(
+            "Adapter {index}: {} ({:?}, {} MiB), {} {}",
+            adapter.name,
+            adapter.device_type,
+            adapter.device_local_memory / (1024 * 1024),
+            adapter.driver_name,
+            adapter.driver_info,
+        );
+    }
+    let adapter = std::env::args()
+        .skip_while(|arg| arg != "--adapter")
+        .nth(1)
+        .and_then(|index| index.parse::<usize>().ok())
+        .and_then(|index| adapters.get(index));
 
-            _padding1: 0,
-            _padding2: 0,
-        },
-    ];
+    let mut device_config = DeviceConfig::default();
+    if let Some(adapter) = adapter {
+        device_config = device_config.physical_device(adapter.physical_device);
+    }
+    let device = Arc::new(Device::new(
+        instance.clone(),
+        device_config
+            // the full screen quad shader samples map textures from a bindless array, see MapTextures
+            .require_feature(DeviceFeature::BindlessTextures)
+            .request_feature(DeviceFeature::GraphicsPipelineLibrary)
+            .request_feature(DeviceFeature::PresentWait)
+            .request_feature(DeviceFeature::DiagnosticCheckpoints)
+            .request_feature(DeviceFeature::BufferMarker)
+            .pipeline_cache_directory(&std::env::temp_dir().join("NonEuclidean")),
+    ));
+    let mut swapchain = Swapchain::new(device.clone(), surface);
+    swapchain.set_scale_factor(window.scale_factor());
+    // while resizing, show the previous frame undistorted instead of stretched until the swapchain catches up
+    if !swapchain.set_present_scaling(PresentScaling::AspectRatioStretch) {
+        println!("Present scaling is unsupported, frames may stretch while resizing");
+    }
 
-    let mut triangles_buffer = Buffer::new(
-        device.clone(),
-        "Triangles Buffer",
-        MemoryLocation::CpuToGpu,
-        size_of_val::<[_]>(&triangles) as _,
-        vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
-        false,
+    // `--map <map.ron>` loads a map file and `--tiling <torus|klein-bottle>[:<columns>x<rows>]` generates a flat grid
+    // glued into that surface, otherwise the default map is used
+    let arg_value = |name: &str| std::env::args().skip_while(|arg| arg != name).nth(1);
+    let map_path = arg_value("--map").map(PathBuf::from);
+    let map = if let Some(path) = &map_path {
+        Map::load(path)
+    } else if let Some(tiling) = arg_value("--tiling") {
+        tilings::parse(&tiling)
+    } else {
+        Ok(Map::default())
+    };
+    let max_textures = BindlessTextures::max_capacity(&device) as usize;
+    // kept for the editor, which edits the map in the coordinates it was made in
+    let (mut map, built) = match map.and_then(|map| {
+        if map.textures.len() > max_textures {
+            return Err(format!(
+                "the map has {} textures but this device supports at most {max_textures}",
+                map.textures.len()
+            ));
+        }
+        Ok((map.build()?, map))
+    }) {
+        Ok((built, map)) => (map, built),
+        Err(error) => {
+            println!("Unable to load the map, using the default map instead: {error}");
+            let map = Map::default();
+            (map.clone(), map.build().unwrap())
+        }
+    };
+    let BuiltMap {
+        mut triangles,
+        spawn,
+        spawn_rotation,
+    } = built;
+    // texture paths are relative to the map file
+    let map_textures = MapTextures::load(
+        &device,
+        &map,
+        map_path
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("")),
     );
 
-    {
-        let triangles_buffer = unsafe { triangles_buffer.get_mapped_mut() }.unwrap();
-        triangles_buffer.copy_from_slice(bytemuck::cast_slice(&triangles));
+    // `--cached-commands` records each frame's commands once and replays them, with the frame's info written to memory instead of pushed
+    let cached_commands = std::env::args().any(|arg| arg == "--cached-commands");
+    if cached_commands {
+        swapchain.set_cached_commands(Some(size_of::<PushConstants>() as u64));
     }
+    // 0 is the shader without any defines, the rest index into its variants,
+    // cached commands always use their own variant as it reads the info through a pointer
+    let mut shader_variant = if cached_commands {
+        shaders::full_screen_quad::VARIANTS
+            .iter()
+            .position(|&(name, _, _)| name == CACHED_COMMANDS_VARIANT)
+            .unwrap()
+            + 1
+    } else {
+        0
+    };
+
+    let mut triangles_buffer = create_triangles_buffer(&device, &triangles);
+    // written by the visit_counting shader variant, the counts are printed and reset with F2
+    let mut visit_counts_buffer = create_visit_counts_buffer(&device, triangles.len());
 
     let shader = unsafe {
         Shader::new(
             device.clone(),
-            include_spirv!(concat!(env!("OUT_DIR"), "/shaders/full_screen_quad.spv")),
+            "Full Screen Quad Shader",
+            shader_variant_spirv(shader_variant).2,
         )
     };
 
-    let push_constant_range = vk::PushConstantRange::default()
-        .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
-        .offset(0)
-        .size(size_of::<PushConstants>() as _);
+    let pipeline_layout =
+        create_full_screen_quad_pipeline_layout(&device, &shader, map_textures.textures());
 
-    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default()
-        .push_constant_ranges(core::slice::from_ref(&push_constant_range));
+    // the parts of the pipeline that don't depend on the shader, so switching shaders only compiles the shader stages
+    let mut interface_libraries =
+        create_interface_libraries(&device, &pipeline_layout, swapchain.render_format());
 
-    let pipeline_layout = scope_guard!(
-        |pipeline_layout| unsafe {
-            device.schedule_destroy_resource(
-                device.current_timeline_counter(),
-                ResourceToDestroy::PipelineLayout(pipeline_layout),
-            );
-        },
-        unsafe { device.create_pipeline_layout(&pipeline_layout_create_info, device.allocator()) }
-            .unwrap()
-    );
-
-    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default();
-    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::default()
-        .topology(vk::PrimitiveTopology::TRIANGLE_STRIP);
-    let shader_stages = [
-        vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::VERTEX)
-            .module(shader.handle())
-            .name(c"vertex"),
-        vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::FRAGMENT)
-            .module(shader.handle())
-            .name(c"fragment"),
-    ];
-    let viewport_state = vk::PipelineViewportStateCreateInfo::default()
-        .viewport_count(1)
-        .scissor_count(1);
-    let dynamic_state = vk::PipelineDynamicStateCreateInfo::default()
-        .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
-    let mut rendering_create_info = vk::PipelineRenderingCreateInfo::default()
-        .color_attachment_formats(&[vk::Format::B8G8R8A8_UNORM]);
-    let blend_attachment = vk::PipelineColorBlendAttachmentState::default()
-        .color_write_mask(vk::ColorComponentFlags::RGBA);
-    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default()
-        .attachments(core::slice::from_ref(&blend_attachment));
-    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::default().line_width(1.0);
-    let multisample_state = vk::PipelineMultisampleStateCreateInfo::default()
-        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
-        .push_next(&mut rendering_create_info)
-        .stages(&shader_stages)
-        .vertex_input_state(&vertex_input_state)
-        .input_assembly_state(&input_assembly_state)
-        .viewport_state(&viewport_state)
-        .rasterization_state(&rasterization_state)
-        .multisample_state(&multisample_state)
-        .color_blend_state(&color_blend_state)
-        .dynamic_state(&dynamic_state)
-        .layout(*pipeline_layout);
-
-    let pipeline = scope_guard!(
-        |pipeline| unsafe {
-            device.schedule_destroy_resource(
-                device.current_timeline_counter(),
-                ResourceToDestroy::Pipeline(pipeline),
-            );
-        },
-        unsafe {
-            device.create_graphics_pipelines(
-                vk::PipelineCache::null(),
-                &[pipeline_create_info],
-                device.allocator(),
-            )
-        }
-        .unwrap()[0]
+    let mut pipeline = create_full_screen_quad_pipeline(
+        &device,
+        &pipeline_layout,
+        swapchain.render_format(),
+        &shader,
+        interface_libraries.as_ref(),
     );
 
     drop(shader);
 
-    let mut position = Position {
-        offset_x: 0.5,
-        offset_y: 0.5,
-        triangle_index: 0,
+    // release builds aren't expected to run next to the shader sources
+    let shader_watcher = if cfg!(debug_assertions) {
+        match ShaderWatcher::new().and_then(|mut shader_watcher| {
+            shader_watcher.watch(Path::new(SHADER_SOURCE_DIRECTORY))?;
+            Ok(shader_watcher)
+        }) {
+            Ok(shader_watcher) => Some(shader_watcher),
+            Err(error) => {
+                println!("Shader hot reload is disabled: {error}");
+                None
+            }
+        }
+    } else {
+        None
     };
 
+    let mut position = spawn;
+    let mut rotation = spawn_rotation;
+    let mut crossing_effects_enabled = false;
+    let mut anti_aliasing = AntiAliasing::None;
+    let mut render_scale = 1.0;
+    // set when the anti aliasing, render scale or shader variant change,
+    // the tonemap pass and the pipeline are updated before the next frame
+    let mut view_settings_changed = false;
+    // `--replay <session.csv>` plays back a recorded session as a ghost,
+    // any other argument besides `--adapter`, `--mouse-sensitivity`, `--map`, `--tiling` and `--cached-commands` is a permalink to start from a shared view
+    let mut session_replay = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--replay" {
+            let Some(path) = args.next() else {
+                println!("Missing the session file after --replay");
+                continue;
+            };
+            match SessionReplay::load(Path::new(&path), &triangles) {
+                Ok(replay) => session_replay = Some((replay, Instant::now())),
+                Err(error) => println!("Unable to load session replay: {error}"),
+            }
+        } else if ["--adapter", "--mouse-sensitivity", "--map", "--tiling"].contains(&arg.as_str())
+        {
+            args.next();
+        } else if arg != "--cached-commands" {
+            match Permalink::decode(&arg).and_then(|permalink| permalink.restore(&triangles)) {
+                Ok(permalink) => {
+                    position = permalink.position;
+                    rotation = permalink.rotation;
+                    restore_view_settings(
+                        permalink.settings,
+                        cached_commands,
+                        &mut swapchain,
+                        &mut shader_variant,
+                        &mut anti_aliasing,
+                        &mut render_scale,
+                        &mut crossing_effects_enabled,
+                    );
+                    view_settings_changed = true;
+                }
+                Err(error) => println!("Unable to restore permalink: {error}"),
+            }
+        }
+    }
+    let mut ghost_position = NO_GHOST;
+    let mut session_recorder: Option<SessionRecorder> = None;
+    let mut crossing_effect = 0.0;
+    let mut last_triangle_index = position.triangle_index;
+    let mut traversal_check: Option<TraversalCheck> = None;
+    // where the player was in the last rendered frame, for reprojecting the temporal anti aliasing history
+    let mut taa_position = position;
+    let mut taa_rotation = 0.0;
+    let mut debug_draw: Option<DebugDraw> = None;
+    let mut editor: Option<Editor> = None;
+    // set by edits, the map is built and uploaded again before the next frame
+    let mut map_edited = false;
+
+    // mailbox presentation would otherwise render as many frames as the gpu can, only to throw most of them away
+    let refresh_rate = window
+        .current_monitor()
+        .and_then(|monitor| monitor.refresh_rate_millihertz())
+        .map_or(DEFAULT_REFRESH_RATE, |millihertz| {
+            millihertz as f64 / 1000.0
+        });
+    let mut frame_limiter = FrameLimiter::new(device.clone(), Some(refresh_rate));
+    let mut frame_timings = FrameTimings::default();
+
     let mut last_time = Instant::now();
     let mut dt = 0.0;
     let mut w_pressed = false;
     let mut s_pressed = false;
     let mut a_pressed = false;
     let mut d_pressed = false;
+    let mut turn_left_pressed = false;
+    let mut turn_right_pressed = false;
+    let mouse_sensitivity = std::env::args()
+        .skip_while(|arg| arg != "--mouse-sensitivity")
+        .nth(1)
+        .map(|sensitivity| {
+            sensitivity.parse::<f32>().unwrap_or_else(|error| {
+                println!("Invalid mouse sensitivity '{sensitivity}': {error}");
+                DEFAULT_MOUSE_SENSITIVITY
+            })
+        })
+        .unwrap_or(DEFAULT_MOUSE_SENSITIVITY);
+    let mut cursor_captured = false;
+    // summed up between frames, as there can be many motion events per frame
+    let mut mouse_delta_x = 0.0;
+    let mut control_pressed = false;
+    // slangc runs on its own thread so the window keeps responding, see compile_shader
+    let mut shader_compilation: Option<std::thread::JoinHandle<Option<Vec<u32>>>> = None;
+    // set when the sources change while a compilation is already running
+    let mut shader_sources_changed = false;
+    let mut clipboard = arboard::Clipboard::new()
+        .inspect_err(|error| println!("Clipboard is unavailable: {error}"))
+        .ok();
     let run = |event: Event<()>, event_loop: &ActiveEventLoop| match event {
         Event::NewEvents(_) => {
             let time = Instant::now();
@@ -216,40 +483,66 @@ fn main() {
                 device.destroy_resources();
 
                 swapchain.resize(size.width, size.height);
-                swapchain.try_next_frame(
-                    |command_buffer: vk::CommandBuffer,
-                     image_layout: &mut vk::ImageLayout,
-                     width: u32,
-                     height: u32,
-                     image: vk::Image,
-                     image_view: vk::ImageView,
-                     frame_index: usize| {
-                        unsafe {
-                            render(
-                                &device,
-                                *pipeline_layout,
-                                *pipeline,
-                                &triangles_buffer,
-                                command_buffer,
-                                image_layout,
-                                width,
-                                height,
-                                image,
-                                image_view,
-                                frame_index,
-                                position,
-                            )
-                        }
-                    },
+                if let Some(editor) = &mut editor {
+                    editor.set_view_size(viewport_size(&swapchain));
+                }
+                // resizing throws the temporal anti aliasing history away, so only the jitter matters
+                let jitter = swapchain
+                    .tonemapper()
+                    .and_then(|tonemapper| tonemapper.taa())
+                    .map_or([0.0, 0.0], |taa| taa.jitter());
+                // redrawn straight away, as some platforms don't run the event loop while the window is being resized
+                draw_frame(
+                    &mut swapchain,
+                    cached_commands,
+                    &device,
+                    &pipeline_layout,
+                    map_textures.textures(),
+                    &pipeline,
+                    &triangles_buffer,
+                    &triangles,
+                    &visit_counts_buffer,
+                    traversal_check.as_mut(),
+                    position,
+                    rotation,
+                    ghost_position,
+                    crossing_effect,
+                    jitter,
+                    None,
                 );
             }
 
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                mut inner_size_writer,
+            } => {
+                // the window keeps its logical size, which is requested through the writer
+                // so the swapchain gets the size the window ends up with rather than the one from before the change
+                let size = window
+                    .inner_size()
+                    .to_logical::<f64>(swapchain.scale_factor())
+                    .to_physical::<u32>(scale_factor);
+                if let Err(error) = inner_size_writer.request_inner_size(size) {
+                    println!(
+                        "Unable to resize the window for scale factor {scale_factor}: {error}"
+                    );
+                }
+                swapchain.set_scale_factor(scale_factor);
+
+                // not every platform sends a `Resized` after the scale factor changes
+                swapchain.resize(size.width, size.height);
+                if let Some(editor) = &mut editor {
+                    editor.set_view_size(viewport_size(&swapchain));
+                }
+            }
+
             WindowEvent::KeyboardInput {
                 device_id: _,
                 event:
                     KeyEvent {
                         physical_key: PhysicalKey::Code(code),
                         state,
+                        repeat,
                         ..
                     },
                 is_synthetic: _,
@@ -258,61 +551,629 @@ fn main() {
                 KeyCode::KeyS => s_pressed = state.is_pressed(),
                 KeyCode::KeyA => a_pressed = state.is_pressed(),
                 KeyCode::KeyD => d_pressed = state.is_pressed(),
+                KeyCode::KeyQ | KeyCode::ArrowLeft => turn_left_pressed = state.is_pressed(),
+                KeyCode::KeyE | KeyCode::ArrowRight => turn_right_pressed = state.is_pressed(),
+                KeyCode::Escape if cursor_captured && state.is_pressed() => {
+                    cursor_captured = capture_cursor(&window, false);
+                }
+                KeyCode::Tab if state.is_pressed() && !repeat => {
+                    if editor.take().is_some() {
+                        println!("Closed the map editor");
+                    } else {
+                        if cursor_captured {
+                            cursor_captured = capture_cursor(&window, false);
+                        }
+                        editor = Some(Editor::new(
+                            map.clone(),
+                            device.clone(),
+                            swapchain.render_format(),
+                            viewport_size(&swapchain),
+                        ));
+                        println!(
+                            "Opened the map editor, left click places vertices and drags them, right click glues edges, \
+                             Delete unglues the selected edge and Ctrl+Z undoes"
+                        );
+                    }
+                }
+                KeyCode::KeyZ if control_pressed && state.is_pressed() => {
+                    if let Some(editor) = &mut editor {
+                        map_edited |= editor.undo();
+                    }
+                }
+                KeyCode::Delete if state.is_pressed() && !repeat => {
+                    if let Some(editor) = &mut editor {
+                        map_edited |= editor.unglue_selected();
+                    }
+                }
+
+                KeyCode::KeyC if control_pressed && state.is_pressed() => {
+                    if let Some(clipboard) = &mut clipboard
+                        && let Err(error) = clipboard.set_text(position.to_string())
+                    {
+                        println!("Unable to copy position: {error}");
+                    }
+                }
+                KeyCode::KeyV if control_pressed && state.is_pressed() => {
+                    let Some(clipboard) = &mut clipboard else {
+                        return;
+                    };
+                    match clipboard
+                        .get_text()
+                        .map_err(|error| error.to_string())
+                        .and_then(|text| {
+                            match Permalink::decode(&text)
+                                .and_then(|permalink| permalink.restore(&triangles))
+                            {
+                                Ok(permalink) => Ok((permalink.position, Some(permalink))),
+                                Err(_) => text.parse::<Position>().map(|position| (position, None)),
+                            }
+                        }) {
+                        Ok((pasted, permalink)) => {
+                            match triangles.get(pasted.triangle_index as usize) {
+                                // movement never leaves a triangle, so it has to start inside one
+                                Some(triangle)
+                                    if geometry::Edges::new(triangle)
+                                        .contains([pasted.offset_x, pasted.offset_y]) =>
+                                {
+                                    position = pasted;
+                                    if let Some(permalink) = permalink {
+                                        rotation = permalink.rotation;
+                                        restore_view_settings(
+                                            permalink.settings,
+                                            cached_commands,
+                                            &mut swapchain,
+                                            &mut shader_variant,
+                                            &mut anti_aliasing,
+                                            &mut render_scale,
+                                            &mut crossing_effects_enabled,
+                                        );
+                                        view_settings_changed = true;
+                                        if let Some(editor) = &mut editor {
+                                            editor.set_view_size(viewport_size(&swapchain));
+                                        }
+                                    }
+                                }
+                                Some(_) => println!(
+                                    "Unable to paste position: {pasted} is outside of its triangle"
+                                ),
+                                None => println!(
+                                    "Unable to paste position: triangle {} doesn't exist",
+                                    pasted.triangle_index
+                                ),
+                            }
+                        }
+                        Err(error) => println!("Unable to paste position: {error}"),
+                    }
+                }
+                KeyCode::F1 if state.is_pressed() && !repeat && cached_commands => {
+                    println!("Shader variants can't be switched with --cached-commands");
+                }
+                KeyCode::F1 if state.is_pressed() && !repeat => {
+                    // the cached commands variant takes different push constants
+                    loop {
+                        shader_variant =
+                            (shader_variant + 1) % (shaders::full_screen_quad::VARIANTS.len() + 1);
+                        if shader_variant_spirv(shader_variant).0 != CACHED_COMMANDS_VARIANT {
+                            break;
+                        }
+                    }
+                    let (variant_name, _, spirv_code) = shader_variant_spirv(shader_variant);
+                    let shader = unsafe {
+                        Shader::new(device.clone(), "Full Screen Quad Shader", spirv_code)
+                    };
+                    pipeline = create_full_screen_quad_pipeline(
+                        &device,
+                        &pipeline_layout,
+                        swapchain.render_format(),
+                        &shader,
+                        interface_libraries.as_ref(),
+                    );
+                    println!("Switched to the {variant_name} full screen quad shader");
+                }
+                KeyCode::F2 if state.is_pressed() && !repeat => {
+                    // the counters are written by every frame in flight
+                    unsafe { device.device_wait_idle() }.unwrap();
+                    let visit_counts = unsafe { visit_counts_buffer.get_mapped_mut() }.unwrap();
+                    println!(
+                        "{}",
+                        visit_heatmap::report(bytemuck::cast_slice(visit_counts))
+                    );
+                    visit_counts.fill(0);
+                }
+                KeyCode::F3 if state.is_pressed() && !repeat => match session_recorder.take() {
+                    Some(recorder) => match recorder.finish() {
+                        Ok(()) => println!("Stopped recording the session"),
+                        Err(error) => println!("Unable to save the session: {error}"),
+                    },
+                    None => {
+                        let path = PathBuf::from(format!(
+                            "session-{}.csv",
+                            SystemTime::now()
+                                .duration_since(UNIX_EPOCH)
+                                .unwrap()
+                                .as_secs()
+                        ));
+                        match SessionRecorder::create(&path, &triangles) {
+                            Ok(recorder) => {
+                                println!("Recording the session to '{}'", path.display());
+                                session_recorder = Some(recorder);
+                            }
+                            Err(error) => println!(
+                                "Unable to record the session to '{}': {error}",
+                                path.display()
+                            ),
+                        }
+                    }
+                },
+                KeyCode::F4 if state.is_pressed() && !repeat => {
+                    crossing_effects_enabled = !crossing_effects_enabled;
+                    crossing_effect = 0.0;
+                    println!(
+                        "Crossing effects {}",
+                        if crossing_effects_enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    );
+                }
+                KeyCode::F5 if state.is_pressed() && !repeat => {
+                    if cached_commands {
+                        // the probe is picked for the frame index, which isn't known when the frame's info is written
+                        println!("The gpu traversal can't be checked with --cached-commands");
+                    } else if traversal_check.take().is_some() {
+                        println!("Stopped checking the gpu traversal");
+                    } else {
+                        traversal_check = Some(TraversalCheck::new(device.clone()));
+                        println!("Checking the gpu traversal against the cpu every frame");
+                    }
+                }
+                KeyCode::F6 | KeyCode::F7 if state.is_pressed() && !repeat => {
+                    if code == KeyCode::F6 {
+                        anti_aliasing = match anti_aliasing {
+                            AntiAliasing::None => AntiAliasing::Fxaa,
+                            AntiAliasing::Fxaa => AntiAliasing::Taa,
+                            AntiAliasing::Taa => AntiAliasing::None,
+                        };
+                        println!("Anti aliasing with {anti_aliasing:?}");
+                    } else {
+                        let index = RENDER_SCALES
+                            .iter()
+                            .position(|&scale| scale == render_scale)
+                            .unwrap_or(0);
+                        render_scale = RENDER_SCALES[(index + 1) % RENDER_SCALES.len()];
+                        println!("Rendering at {}% resolution", render_scale * 100.0);
+                    }
+                    view_settings_changed = true;
+                }
+                KeyCode::F9 if state.is_pressed() && !repeat => {
+                    if debug_draw.take().is_some() {
+                        println!("Debug drawing disabled");
+                    } else {
+                        debug_draw =
+                            Some(DebugDraw::new(device.clone(), swapchain.render_format()));
+                        println!("Drawing the current triangle and the player over the scene");
+                    }
+                }
+                KeyCode::F11 if state.is_pressed() && !repeat => {
+                    if !swapchain.present_wait() {
+                        println!("Low latency presentation needs VK_KHR_present_wait");
+                    } else if swapchain.latency_mode() == LatencyMode::LowLatency {
+                        swapchain.set_latency_mode(LatencyMode::Throughput);
+                        println!("Queueing up frames for throughput");
+                    } else {
+                        swapchain.set_latency_mode(LatencyMode::LowLatency);
+                        println!("Waiting for each frame to be shown before rendering the next");
+                    }
+                }
+                KeyCode::F10 if state.is_pressed() && !repeat => {
+                    if let Some(pacing) = frame_limiter.pacing() {
+                        println!(
+                            "{:.1} fps, frame times {:.2?} to {:.2?} with {:.2?} of jitter",
+                            pacing.fps(),
+                            pacing.min_frame_time,
+                            pacing.max_frame_time,
+                            pacing.jitter
+                        );
+                    }
+                    if frame_limiter.target_fps().is_some() {
+                        frame_limiter.set_target_fps(None);
+                        println!("Frame rate uncapped");
+                    } else {
+                        frame_limiter.set_target_fps(Some(refresh_rate));
+                        println!("Frame rate capped to {refresh_rate} fps");
+                    }
+                }
+                KeyCode::F8 if state.is_pressed() && !repeat => {
+                    if swapchain.letterbox().is_some() {
+                        swapchain.set_letterbox(None);
+                        println!("Letterboxing disabled");
+                    } else {
+                        swapchain.set_letterbox(Some(LETTERBOX_SIZE));
+                        println!(
+                            "Letterboxing to {}:{}",
+                            LETTERBOX_SIZE.width, LETTERBOX_SIZE.height
+                        );
+                    }
+                    if let Some(editor) = &mut editor {
+                        editor.set_view_size(viewport_size(&swapchain));
+                    }
+                }
+                KeyCode::F12 if state.is_pressed() && !repeat => {
+                    print!("{}", device.dump_allocator_report());
+                }
+                KeyCode::KeyT if control_pressed && state.is_pressed() => {
+                    println!("{}", frame_timings.report());
+                    frame_timings = FrameTimings::default();
+                }
+                // saves the map with the current view as its spawn, including generated tilings,
+                // or the map being edited
+                KeyCode::KeyM if control_pressed && state.is_pressed() && !repeat => {
+                    let path = PathBuf::from(format!(
+                        "map-{}.ron",
+                        SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs()
+                    ));
+                    // the editor's map keeps the coordinates it was made in, even while it is invalid
+                    let mut map = match &editor {
+                        Some(editor) => editor.map().clone(),
+                        None => Map::from_triangles(&triangles, position, rotation),
+                    };
+                    // saved into the working directory instead of next to the map it was loaded from
+                    map.textures = map_textures.paths().to_vec();
+                    match map.save(&path) {
+                        Ok(()) => println!("Saved the map to '{}'", path.display()),
+                        Err(error) => println!("Unable to save the map: {error}"),
+                    }
+                }
+                KeyCode::KeyL if control_pressed && state.is_pressed() => {
+                    let settings = ViewSettings {
+                        // the cached commands variant draws the same as the shader without any defines
+                        shader_variant: if cached_commands {
+                            0
+                        } else {
+                            shader_variant as u8
+                        },
+                        anti_aliasing,
+                        render_scale,
+                        letterbox: swapchain.letterbox().is_some(),
+                        crossing_effects: crossing_effects_enabled,
+                    };
+                    let permalink =
+                        Permalink::new(&triangles, position, rotation, settings).encode();
+                    println!("Permalink: {permalink}");
+                    if let Some(clipboard) = &mut clipboard
+                        && let Err(error) = clipboard.set_text(permalink)
+                    {
+                        println!("Unable to copy permalink: {error}");
+                    }
+                }
+
                 _ => {}
             },
 
+            WindowEvent::MouseInput { state, button, .. } if editor.is_some() => {
+                let editor = editor.as_mut().unwrap();
+                match button {
+                    MouseButton::Left => map_edited |= editor.left_button(state.is_pressed()),
+                    MouseButton::Right => map_edited |= editor.right_button(state.is_pressed()),
+                    MouseButton::Middle => editor.middle_button(state.is_pressed()),
+                    _ => {}
+                }
+            }
+            WindowEvent::CursorMoved {
+                position: cursor, ..
+            } => {
+                // the editor is drawn in the viewport, and doesn't see the cursor over the letterbox bars
+                if let Some(editor) = &mut editor
+                    && let Some(cursor) = cursor_in_viewport(&swapchain, [cursor.x, cursor.y])
+                {
+                    map_edited |= editor.cursor_moved(cursor);
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                if let Some(editor) = &mut editor {
+                    editor.scrolled(match delta {
+                        MouseScrollDelta::LineDelta(_, lines) => lines,
+                        MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / 50.0,
+                    });
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if !cursor_captured => {
+                cursor_captured = capture_cursor(&window, true);
+            }
+            WindowEvent::Focused(false) if cursor_captured => {
+                cursor_captured = capture_cursor(&window, false);
+            }
+
+            WindowEvent::ModifiersChanged(modifiers) => {
+                control_pressed = modifiers.state().control_key();
+            }
+
             _ => {}
         },
 
+        Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { delta: (x, _) },
+            ..
+        } if cursor_captured => {
+            mouse_delta_x += x as f32;
+        }
+
         Event::AboutToWait => {
-            device.destroy_resources();
+            if let Some(shader_watcher) = &shader_watcher
+                && shader_watcher.poll().iter().any(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "slang")
+                })
+            {
+                shader_sources_changed = true;
+            }
+            // only one compilation runs at a time, as they all write to the same file
+            if shader_sources_changed && shader_compilation.is_none() {
+                shader_sources_changed = false;
+                let (_, defines, _) = shader_variant_spirv(shader_variant);
+                shader_compilation = Some(std::thread::spawn(move || {
+                    compile_shader(Path::new("full_screen_quad.slang"), defines)
+                }));
+            }
+            if shader_compilation
+                .as_ref()
+                .is_some_and(|compilation| compilation.is_finished())
+                && let Some(spirv_code) = shader_compilation.take().unwrap().join().unwrap()
+            {
+                let shader =
+                    unsafe { Shader::new(device.clone(), "Full Screen Quad Shader", &spirv_code) };
+                // the old pipeline is destroyed once the frames using it have finished
+                pipeline = create_full_screen_quad_pipeline(
+                    &device,
+                    &pipeline_layout,
+                    swapchain.render_format(),
+                    &shader,
+                    interface_libraries.as_ref(),
+                );
+                swapchain.invalidate_cached_commands();
+                println!("Reloaded full_screen_quad.slang");
+            }
+
+            if std::mem::take(&mut view_settings_changed) {
+                let render_format = swapchain.render_format();
+                if anti_aliasing == AntiAliasing::None && render_scale == 1.0 {
+                    swapchain.set_tonemap(None);
+                } else {
+                    // the shader's colors are already meant to be shown as they are,
+                    // the pass is only for anti aliasing and scaling
+                    swapchain.set_tonemap(Some(TonemapOperator::Clamp));
+                    let tonemapper = swapchain.tonemapper_mut().unwrap();
+                    tonemapper.set_encode_srgb(false);
+                    tonemapper.set_anti_aliasing(anti_aliasing);
+                    tonemapper.set_render_scale(render_scale);
+                }
+
+                if swapchain.render_format() != render_format {
+                    if let Some(debug_draw) = &mut debug_draw {
+                        debug_draw.set_color_format(swapchain.render_format());
+                    }
+                    if let Some(editor) = &mut editor {
+                        editor
+                            .debug_draw_mut()
+                            .set_color_format(swapchain.render_format());
+                    }
+                    interface_libraries = create_interface_libraries(
+                        &device,
+                        &pipeline_layout,
+                        swapchain.render_format(),
+                    );
+                }
+                // a restored permalink can switch the shader variant too
+                let (_, _, spirv_code) = shader_variant_spirv(shader_variant);
+                let shader =
+                    unsafe { Shader::new(device.clone(), "Full Screen Quad Shader", spirv_code) };
+                pipeline = create_full_screen_quad_pipeline(
+                    &device,
+                    &pipeline_layout,
+                    swapchain.render_format(),
+                    &shader,
+                    interface_libraries.as_ref(),
+                );
+                swapchain.invalidate_cached_commands();
+            }
+
+            if std::mem::take(&mut map_edited)
+                && let Some(editor) = &editor
+            {
+                match editor.map().build() {
+                    Ok(built) => {
+                        // the old buffers are destroyed once the frames using them have finished
+                        triangles_buffer = create_triangles_buffer(&device, &built.triangles);
+                        visit_counts_buffer =
+                            create_visit_counts_buffer(&device, built.triangles.len());
+                        swapchain.invalidate_cached_commands();
+                        // the player stays at the same place between their triangle's vertices as they move,
+                        // facing the same way in the map, unless undoing removed the triangle
+                        match geometry::carry_position(&triangles, &built.triangles, position) {
+                            Some(carried) => {
+                                let triangle_index = position.triangle_index as usize;
+                                rotation = (rotation + map.triangles[triangle_index].angle()
+                                    - editor.map().triangles[triangle_index].angle())
+                                .rem_euclid(std::f32::consts::TAU);
+                                position = carried;
+                            }
+                            None => {
+                                position = built.spawn;
+                                rotation = built.spawn_rotation;
+                            }
+                        }
+                        triangles = built.triangles;
+                        map = editor.map().clone();
+                    }
+                    // most maps are invalid part way through a drag
+                    Err(_) if editor.is_dragging() => {}
+                    Err(error) => println!("The edited map is uploaded once it is valid: {error}"),
+                }
+            }
+
+            if turn_left_pressed {
+                rotation += ROTATION_SPEED * dt;
+            }
+            if turn_right_pressed {
+                rotation -= ROTATION_SPEED * dt;
+            }
+            // moving the mouse right turns the view right, which is clockwise
+            rotation -= core::mem::take(&mut mouse_delta_x) * mouse_sensitivity;
+            rotation = rotation.rem_euclid(std::f32::consts::TAU);
 
             let speed = 1.0;
+            // relative to the view, so W always moves towards the top of the screen
+            let mut movement = [0.0, 0.0];
             if w_pressed {
-                position.offset_y += speed * dt;
+                movement[1] += speed * dt;
             }
             if s_pressed {
-                position.offset_y -= speed * dt;
+                movement[1] -= speed * dt;
             }
             if a_pressed {
-                position.offset_x -= speed * dt;
+                movement[0] -= speed * dt;
             }
             if d_pressed {
-                position.offset_x += speed * dt;
-            }
-
-            match swapchain.try_next_frame(
-                |command_buffer: vk::CommandBuffer,
-                 image_layout: &mut vk::ImageLayout,
-                 width: u32,
-                 height: u32,
-                 image: vk::Image,
-                 image_view: vk::ImageView,
-                 frame_index: usize| {
-                    unsafe {
-                        render(
-                            &device,
-                            *pipeline_layout,
-                            *pipeline,
-                            &triangles_buffer,
-                            command_buffer,
-                            image_layout,
-                            width,
-                            height,
-                            image,
-                            image_view,
-                            frame_index,
-                            position,
-                        )
-                    }
-                },
+                movement[0] += speed * dt;
+            }
+            // crossing an edge turns the view with the coordinates of the triangle on the other side
+            let forward = geometry::rotate([1.0, 0.0], rotation);
+            if let Some((moved, moved_forward)) = geometry::move_position(
+                &triangles,
+                position,
+                geometry::rotate(movement, rotation),
+                forward,
             ) {
+                position = moved;
+                if moved_forward != forward {
+                    rotation = moved_forward[1]
+                        .atan2(moved_forward[0])
+                        .rem_euclid(std::f32::consts::TAU);
+                }
+            }
+
+            if let Some(recorder) = &mut session_recorder
+                && let Err(error) = recorder.record(position)
+            {
+                println!("Unable to record the session, stopping: {error}");
+                session_recorder = None;
+            }
+            ghost_position = session_replay
+                .as_ref()
+                .and_then(|(replay, start)| replay.position_at(start.elapsed().as_secs_f64()))
+                .unwrap_or(NO_GHOST);
+
+            crossing_effect = f32::max(crossing_effect - dt / CROSSING_EFFECT_DURATION, 0.0);
+            if position.triangle_index != last_triangle_index {
+                last_triangle_index = position.triangle_index;
+                if crossing_effects_enabled {
+                    crossing_effect = 1.0;
+                }
+            }
+
+            // the event loop waits for the next frame instead of blocking in it, so input still gets handled
+            frame_limiter.update();
+            if !frame_limiter.is_frame_due() {
+                event_loop.set_control_flow(
+                    frame_limiter
+                        .next_wakeup()
+                        .map_or(ControlFlow::Poll, ControlFlow::WaitUntil),
+                );
+                return;
+            }
+            event_loop.set_control_flow(ControlFlow::Poll);
+
+            let viewport = swapchain.viewport();
+            let aspect = viewport.extent.width as f32 / viewport.extent.height as f32;
+            let jitter = match swapchain
+                .tonemapper_mut()
+                .and_then(|tonemapper| tonemapper.taa_mut())
+            {
+                Some(taa) => {
+                    // the whole view moves with the player, unless they crossed into another triangle's coordinates,
+                    // turning moves every pixel differently, which a single offset can't reproject
+                    if position.triangle_index == taa_position.triangle_index
+                        && rotation == taa_rotation
+                    {
+                        let [screen_x, screen_y] = geometry::rotate(
+                            [
+                                position.offset_x - taa_position.offset_x,
+                                position.offset_y - taa_position.offset_y,
+                            ],
+                            -rotation,
+                        );
+                        taa.set_history_offset([
+                            screen_x / (2.0 * VIEW_DISTANCE * aspect),
+                            -screen_y / (2.0 * VIEW_DISTANCE),
+                        ]);
+                    } else {
+                        taa.reset_history();
+                    }
+                    taa.jitter()
+                }
+                None => [0.0, 0.0],
+            };
+
+            if let Some(editor) = &mut editor {
+                editor.draw();
+            } else if let Some(debug_draw) = &mut debug_draw {
+                draw_debug_shapes(
+                    debug_draw,
+                    &triangles,
+                    position,
+                    rotation,
+                    ghost_position,
+                    aspect,
+                );
+            }
+
+            // the editor is drawn instead of the debug shapes
+            let overlay = match &mut editor {
+                Some(editor) => Some(editor.debug_draw_mut()),
+                None => debug_draw.as_mut(),
+            };
+            let result = draw_frame(
+                &mut swapchain,
+                cached_commands,
+                &device,
+                &pipeline_layout,
+                map_textures.textures(),
+                &pipeline,
+                &triangles_buffer,
+                &triangles,
+                &visit_counts_buffer,
+                traversal_check.as_mut(),
+                position,
+                rotation,
+                ghost_position,
+                crossing_effect,
+                jitter,
+                overlay,
+            );
+            match result {
                 RenderResult::NotReady => {}
                 RenderResult::OutOfDate | RenderResult::Suboptimal => {
+                    taa_position = position;
+                    taa_rotation = rotation;
                     let size = window.inner_size();
                     swapchain.resize(size.width, size.height);
                 }
-                RenderResult::Success => {}
+                RenderResult::Success => {
+                    taa_position = position;
+                    taa_rotation = rotation;
+                    frame_limiter.presented();
+                }
+            }
+            for stats in swapchain.drain_frame_stats() {
+                frame_timings.add(stats);
             }
         }
 
@@ -322,30 +1183,644 @@ fn main() {
     event_loop.run(run).unwrap();
 }
 
+/// Takes on the settings of a restored [`Permalink`], the tonemap pass and the pipeline have to be updated afterwards
+fn restore_view_settings(
+    settings: ViewSettings,
+    cached_commands: bool,
+    swapchain: &mut Swapchain<'_, '_>,
+    shader_variant: &mut usize,
+    anti_aliasing: &mut AntiAliasing,
+    render_scale: &mut f32,
+    crossing_effects_enabled: &mut bool,
+) {
+    let variant = settings.shader_variant as usize;
+    if variant > shaders::full_screen_quad::VARIANTS.len()
+        || shader_variant_spirv(variant).0 == CACHED_COMMANDS_VARIANT
+    {
+        println!("The permalink's shader variant {variant} doesn't exist, keeping the current one");
+    } else if cached_commands {
+        if variant != 0 {
+            println!("Shader variants can't be switched with --cached-commands");
+        }
+    } else {
+        *shader_variant = variant;
+    }
+    *anti_aliasing = settings.anti_aliasing;
+    *render_scale = settings.render_scale;
+    *crossing_effects_enabled = settings.crossing_effects;
+    swapchain.set_letterbox(settings.letterbox.then_some(LETTERBOX_SIZE));
+}
+
+/// The size of the part of the swapchain frames are shown in, which is what the editor draws the map into
+fn viewport_size(swapchain: &Swapchain<'_, '_>) -> [u32; 2] {
+    let extent = swapchain.viewport().extent;
+    [extent.width, extent.height]
+}
+
+/// `cursor` in physical pixels of the window moved to pixels from the top left of [`Swapchain::viewport`],
+/// `None` when it is over the letterbox bars
+fn cursor_in_viewport(swapchain: &Swapchain<'_, '_>, cursor: [f64; 2]) -> Option<[f32; 2]> {
+    let [x, y] = swapchain.cursor_to_logical(cursor)?;
+    let viewport = swapchain.viewport().extent;
+    let logical_size = swapchain.letterbox().unwrap_or(viewport);
+    Some([
+        (x * viewport.width as f64 / logical_size.width as f64) as f32,
+        (y * viewport.height as f64 / logical_size.height as f64) as f32,
+    ])
+}
+
+/// Hides the cursor and keeps it in the window for mouse-look, or releases it again, returns whether it is captured
+fn capture_cursor(window: &Window, capture: bool) -> bool {
+    if !capture {
+        if let Err(error) = window.set_cursor_grab(CursorGrabMode::None) {
+            println!("Unable to release the cursor: {error}");
+        }
+        window.set_cursor_visible(true);
+        return false;
+    }
+
+    // not every platform can lock the cursor in place, confining it still gives relative motion
+    match window
+        .set_cursor_grab(CursorGrabMode::Locked)
+        .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+    {
+        Ok(()) => {
+            window.set_cursor_visible(false);
+            println!("Mouse-look enabled, press Escape to release the cursor");
+            true
+        }
+        Err(error) => {
+            println!("Unable to capture the cursor: {error}");
+            false
+        }
+    }
+}
+
+/// Draws the edges of the triangle the player is in, green where they lead to another triangle and red for mirrors,
+/// and the player and ghost, in the player's triangle's coordinates turned with the view so they line up with the traversal
+fn draw_debug_shapes(
+    debug_draw: &mut DebugDraw<'_>,
+    triangles: &[Triangle],
+    position: Position,
+    rotation: f32,
+    ghost_position: Position,
+    aspect: f32,
+) {
+    let player = [position.offset_x, position.offset_y];
+    debug_draw.set_view(player, [VIEW_DISTANCE * aspect, VIEW_DISTANCE]);
+    // the view turns around the player
+    let to_view = |[x, y]: [f32; 2]| {
+        let [x, y] = geometry::rotate([x - player[0], y - player[1]], -rotation);
+        [x + player[0], y + player[1]]
+    };
+
+    let Some(triangle) = triangles.get(position.triangle_index as usize) else {
+        return;
+    };
+    let a = to_view([0.0, 0.0]);
+    let b = to_view([triangle.bx, 0.0]);
+    let c = to_view([triangle.cx, triangle.cy]);
+    debug_draw.polygon(&[a, b, c], [1.0, 1.0, 1.0, 0.1]);
+    for (edge, (from, to)) in [(a, b), (a, c), (b, c)].into_iter().enumerate() {
+        let color = if triangle.is_mirror(edge) {
+            [1.0, 0.2, 0.2, 1.0]
+        } else {
+            [0.2, 1.0, 0.2, 1.0]
+        };
+        debug_draw.line(from, to, color);
+    }
+
+    debug_draw.circle(player, 0.1, [1.0, 1.0, 1.0, 1.0]);
+    // rays through the right of the screen go along +x once turned with the view
+    debug_draw.arrow(player, [player[0] + 0.5, player[1]], [1.0, 1.0, 0.0, 1.0]);
+
+    if ghost_position.triangle_index == position.triangle_index {
+        debug_draw.circle(
+            to_view([ghost_position.offset_x, ghost_position.offset_y]),
+            0.1,
+            [0.5, 0.5, 1.0, 1.0],
+        );
+    }
+}
+
+fn create_triangles_buffer<'allocator>(
+    device: &Arc<Device<'allocator>>,
+    triangles: &[Triangle],
+) -> Buffer<'allocator> {
+    let mut triangles_buffer = Buffer::new(
+        device.clone(),
+        "Triangles Buffer",
+        MemoryLocation::CpuToGpu,
+        size_of_val(triangles) as _,
+        vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        // every pixel walks the triangles
+        true,
+        Some(HIGH_MEMORY_PRIORITY),
+    );
+    unsafe { triangles_buffer.get_mapped_mut() }
+        .unwrap()
+        .copy_from_slice(bytemuck::cast_slice(triangles));
+    triangles_buffer
+}
+
+fn create_visit_counts_buffer<'allocator>(
+    device: &Arc<Device<'allocator>>,
+    triangle_count: usize,
+) -> Buffer<'allocator> {
+    let mut visit_counts_buffer = Buffer::new(
+        device.clone(),
+        "Visit Counts Buffer",
+        MemoryLocation::GpuToCpu,
+        (triangle_count * size_of::<u32>()) as _,
+        vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        false,
+        None,
+    );
+    unsafe { visit_counts_buffer.get_mapped_mut() }
+        .unwrap()
+        .fill(0);
+    visit_counts_buffer
+}
+
+/// Checks that every edge is glued to an edge of the same length that is glued back,
+/// an out of range index would otherwise be read out of bounds on the GPU
+fn validate_triangles(triangles: &[Triangle]) -> Result<(), String> {
+    let edge_length = |triangle: &Triangle, edge: usize| match edge {
+        0 => triangle.bx.abs(),
+        1 => triangle.cx.hypot(triangle.cy),
+        _ => (triangle.cx - triangle.bx).hypot(triangle.cy),
+    };
+
+    for (index, triangle) in triangles.iter().enumerate() {
+        if triangle.mirror_edges >> 3 != 0 {
+            return Err(format!(
+                "triangle {index} has mirror edges {:#b}, but only has 3 edges",
+                triangle.mirror_edges
+            ));
+        }
+        if let Some(edge) = triangle
+            .edge_bends
+            .iter()
+            .position(|bend| !bend.is_finite())
+        {
+            return Err(format!(
+                "triangle {index} edge {edge} bends rays by {}",
+                triangle.edge_bends[edge]
+            ));
+        }
+        for edge in 0..3 {
+            let other_index = triangle.edge_triangles[edge];
+            if other_index == u32::MAX || triangle.is_mirror(edge) {
+                continue;
+            }
+            let Some(other) = triangles.get(other_index as usize) else {
+                return Err(format!(
+                    "triangle {index} edge {edge} is glued to triangle {other_index}, which doesn't exist"
+                ));
+            };
+
+            let other_edge = triangle.edge_indices[edge] as usize;
+            if other_edge >= 3 {
+                return Err(format!(
+                    "triangle {index} edge {edge} is glued to edge {other_edge}, which doesn't exist"
+                ));
+            }
+            if other.edge_triangles[other_edge] as usize != index
+                || other.edge_indices[other_edge] as usize != edge
+            {
+                return Err(format!(
+                    "triangle {index} edge {edge} is glued to triangle {other_index} edge {other_edge}, which isn't glued back"
+                ));
+            }
+
+            let length = edge_length(triangle, edge);
+            let other_length = edge_length(other, other_edge);
+            if (length - other_length).abs() > length.max(other_length) * 1e-4 {
+                return Err(format!(
+                    "triangle {index} edge {edge} has length {length}, but triangle {other_index} edge {other_edge} has length {other_length}"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The push constants come from the shader's reflection, but the texture array has no fixed size to reflect,
+/// so its set layout comes from `textures`
+fn create_full_screen_quad_pipeline_layout<'allocator>(
+    device: &Arc<Device<'allocator>>,
+    shader: &Shader<'allocator>,
+    textures: &BindlessTextures<'allocator>,
+) -> PipelineLayout<'allocator> {
+    let push_constant_range = shader
+        .reflection()
+        .push_constant_range
+        .expect("the full screen quad shader has push constants");
+    PipelineLayout::new(
+        device.clone(),
+        "Full Screen Quad Pipeline Layout",
+        &[textures.set_layout()],
+        &[push_constant_range],
+    )
+}
+
+fn full_screen_quad_pipeline_builder(
+    pipeline_layout: &PipelineLayout<'_>,
+    color_format: vk::Format,
+) -> GraphicsPipelineBuilder<'static> {
+    GraphicsPipelineBuilder::new(pipeline_layout, color_format)
+        .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+}
+
+/// The name, defines and SPIR-V of a full screen quad shader variant, 0 is the shader without any defines
+fn shader_variant_spirv(
+    shader_variant: usize,
+) -> (&'static str, &'static [&'static str], &'static [u32]) {
+    match shader_variant {
+        0 => (
+            "default",
+            shaders::full_screen_quad::DEFINES,
+            shaders::full_screen_quad::SPIRV,
+        ),
+        index => shaders::full_screen_quad::VARIANTS[index - 1],
+    }
+}
+
+/// `None` when the device doesn't support graphics pipeline libraries
+fn create_interface_libraries<'allocator>(
+    device: &Arc<Device<'allocator>>,
+    pipeline_layout: &PipelineLayout<'allocator>,
+    color_format: vk::Format,
+) -> Option<[GraphicsPipelineLibrary<'allocator>; 2]> {
+    device
+        .capabilities()
+        .has_feature(DeviceFeature::GraphicsPipelineLibrary)
+        .then(|| {
+            let builder = full_screen_quad_pipeline_builder(pipeline_layout, color_format);
+            [
+                builder.build_library(
+                    device.clone(),
+                    "Full Screen Quad Vertex Input Library",
+                    vk::GraphicsPipelineLibraryFlagsEXT::VERTEX_INPUT_INTERFACE,
+                ),
+                builder.build_library(
+                    device.clone(),
+                    "Full Screen Quad Fragment Output Library",
+                    vk::GraphicsPipelineLibraryFlagsEXT::FRAGMENT_OUTPUT_INTERFACE,
+                ),
+            ]
+        })
+}
+
+/// Links against `interface_libraries` when the device supports graphics pipeline libraries
+fn create_full_screen_quad_pipeline<'allocator>(
+    device: &Arc<Device<'allocator>>,
+    pipeline_layout: &PipelineLayout<'allocator>,
+    color_format: vk::Format,
+    shader: &Shader<'allocator>,
+    interface_libraries: Option<&[GraphicsPipelineLibrary<'allocator>; 2]>,
+) -> Pipeline<'allocator> {
+    let builder = full_screen_quad_pipeline_builder(pipeline_layout, color_format)
+        .vertex(shader, shaders::full_screen_quad::VERTEX)
+        .fragment(shader, shaders::full_screen_quad::FRAGMENT);
+    let Some([vertex_input, fragment_output]) = interface_libraries else {
+        return builder.build(device.clone(), "Full Screen Quad Pipeline");
+    };
+
+    let shaders = builder.build_library(
+        device.clone(),
+        "Full Screen Quad Shaders Library",
+        vk::GraphicsPipelineLibraryFlagsEXT::PRE_RASTERIZATION_SHADERS
+            | vk::GraphicsPipelineLibraryFlagsEXT::FRAGMENT_SHADER,
+    );
+    GraphicsPipelineLibrary::link(
+        device.clone(),
+        "Full Screen Quad Pipeline",
+        pipeline_layout,
+        &[vertex_input, &shaders, fragment_output],
+        false,
+    )
+}
+
+/// Compiles a shader from [`SHADER_SOURCE_DIRECTORY`] at runtime for hot reloading, the same way `build.rs` does
+fn compile_shader(name: &Path, defines: &[&str]) -> Option<Vec<u32>> {
+    let out_dir = std::env::temp_dir().join("NonEuclidean/shaders");
+    if let Err(error) = std::fs::create_dir_all(&out_dir) {
+        println!("Unable to create '{}': {error}", out_dir.display());
+        return None;
+    }
+    let out_filepath = out_dir.join(name.with_extension("spv"));
+
+    let output = shader_tools::slangc(
+        &PathBuf::from(SHADER_SOURCE_DIRECTORY).join(name),
+        &out_filepath,
+        defines,
+        cfg!(debug_assertions),
+    )
+    .stderr(Stdio::piped())
+    .output();
+    match output {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            println!(
+                "{}\n{}",
+                name.display(),
+                String::from_utf8_lossy(&output.stderr),
+            );
+            return None;
+        }
+        Err(error) => {
+            println!("Unable to run slangc: {error}");
+            return None;
+        }
+    }
+
+    match read_spirv(&out_filepath) {
+        Ok(spirv_code) => Some(spirv_code),
+        Err(error) => {
+            println!("Unable to read '{}': {error}", out_filepath.display());
+            None
+        }
+    }
+}
+
+/// Renders the traversal shader, with `overlay` over it, into the next frame and presents it,
+/// through the swapchain's cached commands when `cached_commands` is set
+///
+/// The traversal is only checked against `traversal_check` without cached commands,
+/// as the probe is picked for the frame index, which isn't known when the frame's info is written
+#[expect(clippy::too_many_arguments)]
+fn draw_frame(
+    swapchain: &mut Swapchain<'_, '_>,
+    cached_commands: bool,
+    device: &Device<'_>,
+    pipeline_layout: &PipelineLayout<'_>,
+    textures: &BindlessTextures<'_>,
+    pipeline: &Pipeline<'_>,
+    triangles_buffer: &Buffer,
+    triangles: &[Triangle],
+    visit_counts_buffer: &Buffer,
+    mut traversal_check: Option<&mut TraversalCheck>,
+    position: Position,
+    rotation: f32,
+    ghost_position: Position,
+    crossing_effect: f32,
+    jitter: [f32; 2],
+    mut overlay: Option<&mut DebugDraw<'_>>,
+) -> RenderResult {
+    if !cached_commands {
+        return swapchain.try_next_frame(
+            |command_buffer: vk::CommandBuffer,
+             image_layout: &mut vk::ImageLayout,
+             render_area: vk::Rect2D,
+             image: vk::Image,
+             image_view: vk::ImageView,
+             frame_index: usize| {
+                unsafe {
+                    let traversal_probe = match &mut traversal_check {
+                        Some(traversal_check) => traversal_check.check_and_probe(
+                            frame_index,
+                            triangles,
+                            position,
+                            rotation,
+                            render_area.extent.width,
+                            render_area.extent.height,
+                        ),
+                        None => (GpuPtr::null(), [0, 0]),
+                    };
+                    let render_sync = render(
+                        device,
+                        pipeline_layout,
+                        textures,
+                        pipeline,
+                        triangles_buffer,
+                        triangles.len() as u32,
+                        visit_counts_buffer,
+                        command_buffer,
+                        image_layout,
+                        render_area,
+                        image,
+                        image_view,
+                        position,
+                        rotation,
+                        ghost_position,
+                        crossing_effect,
+                        traversal_probe,
+                        jitter,
+                    );
+                    if let Some(debug_draw) = &mut overlay {
+                        debug_draw.cmd_draw(
+                            command_buffer,
+                            frame_index,
+                            image,
+                            image_view,
+                            image_layout,
+                            render_area,
+                        );
+                    }
+                    render_sync
+                }
+            },
+        );
+    }
+
+    // the overlays write their vertices while recording, so frames with one are recorded every time
+    if overlay.is_some() {
+        swapchain.invalidate_cached_commands();
+    }
+    let render_area = match swapchain.tonemapper() {
+        Some(tonemapper) => vk::Rect2D {
+            offset: vk::Offset2D::default(),
+            extent: tonemapper.target().extent(),
+        },
+        None => swapchain.viewport(),
+    };
+    let frame_info = unsafe {
+        push_constants(
+            triangles_buffer,
+            triangles.len() as u32,
+            visit_counts_buffer,
+            render_area,
+            position,
+            rotation,
+            ghost_position,
+            crossing_effect,
+            (GpuPtr::null(), [0, 0]),
+            jitter,
+        )
+    };
+    swapchain.try_next_cached_frame(
+        |frame_data| {
+            frame_data[..size_of::<PushConstants>()]
+                .copy_from_slice(bytemuck::bytes_of(&frame_info));
+        },
+        |command_buffer,
+         image_layout,
+         render_area,
+         image,
+         image_view,
+         frame_index,
+         frame_data_address| unsafe {
+            record_traversal_pass(
+                device,
+                pipeline_layout,
+                textures,
+                pipeline,
+                command_buffer,
+                image_layout,
+                render_area,
+                image,
+                image_view,
+                &frame_data_address,
+            );
+            if let Some(debug_draw) = &mut overlay {
+                debug_draw.cmd_draw(
+                    command_buffer,
+                    frame_index,
+                    image,
+                    image_view,
+                    image_layout,
+                    render_area,
+                );
+            }
+        },
+    )
+}
+
 #[expect(clippy::too_many_arguments)]
 unsafe fn render<'a>(
     device: &Device<'_>,
-    pipeline_layout: vk::PipelineLayout,
-    pipeline: vk::Pipeline,
+    pipeline_layout: &PipelineLayout<'_>,
+    textures: &BindlessTextures<'_>,
+    pipeline: &Pipeline<'_>,
     triangles_buffer: &Buffer,
+    triangle_count: u32,
+    visit_counts_buffer: &Buffer,
     command_buffer: vk::CommandBuffer,
     image_layout: &mut vk::ImageLayout,
-    width: u32,
-    height: u32,
+    render_area: vk::Rect2D,
     image: vk::Image,
     image_view: vk::ImageView,
-    #[expect(unused)] frame_index: usize,
     position: Position,
+    rotation: f32,
+    ghost_position: Position,
+    crossing_effect: f32,
+    traversal_probe: (GpuPtr<Position>, [u32; 2]),
+    jitter: [f32; 2],
 ) -> RenderSync<'a> {
+    let push_constants = unsafe {
+        push_constants(
+            triangles_buffer,
+            triangle_count,
+            visit_counts_buffer,
+            render_area,
+            position,
+            rotation,
+            ghost_position,
+            crossing_effect,
+            traversal_probe,
+            jitter,
+        )
+    };
     unsafe {
-        transition_image(
+        record_traversal_pass(
             device,
+            pipeline_layout,
+            textures,
+            pipeline,
             command_buffer,
-            image,
             image_layout,
-            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            render_area,
+            image,
+            image_view,
+            &push_constants,
         );
     }
+    RenderSync::default()
+}
+
+/// What the full screen quad shader reads as its info, pushed by [`render`] or written to the frame data with `--cached-commands`
+///
+/// # Safety
+/// `triangles_buffer` and `visit_counts_buffer` must come from [`create_triangles_buffer`] and [`create_visit_counts_buffer`]
+#[expect(clippy::too_many_arguments)]
+unsafe fn push_constants(
+    triangles_buffer: &Buffer,
+    triangle_count: u32,
+    visit_counts_buffer: &Buffer,
+    render_area: vk::Rect2D,
+    position: Position,
+    rotation: f32,
+    ghost_position: Position,
+    crossing_effect: f32,
+    (traversal_probe, probe_pixel): (GpuPtr<Position>, [u32; 2]),
+    jitter: [f32; 2],
+) -> PushConstants {
+    let vk::Rect2D {
+        offset,
+        extent: vk::Extent2D { width, height },
+    } = render_area;
+    PushConstants {
+        triangles: unsafe { triangles_buffer.device_ptr() },
+        start_position: position,
+        aspect: width as f32 / height as f32,
+        triangle_count,
+
+        rotation,
+
+        visit_counts: unsafe { visit_counts_buffer.device_ptr() },
+        ghost_position,
+        crossing_effect,
+
+        traversal_probe,
+        // the shader compares against pixels of the whole image
+        probe_pixel: [
+            probe_pixel[0] + offset.x as u32,
+            probe_pixel[1] + offset.y as u32,
+        ],
+
+        jitter,
+    }
+}
+
+/// Draws the full screen quad over `render_area` with `push_constants`, which are [`PushConstants`]
+/// or the address of them with the cached commands shader variant
+///
+/// # Safety
+/// Everything the push constants point to must stay alive until the command buffer has finished
+#[expect(clippy::too_many_arguments)]
+unsafe fn record_traversal_pass(
+    device: &Device<'_>,
+    pipeline_layout: &PipelineLayout<'_>,
+    textures: &BindlessTextures<'_>,
+    pipeline: &Pipeline<'_>,
+    command_buffer: vk::CommandBuffer,
+    image_layout: &mut vk::ImageLayout,
+    render_area: vk::Rect2D,
+    image: vk::Image,
+    image_view: vk::ImageView,
+    push_constants: &impl NoUninit,
+) {
+    let _label =
+        unsafe { device.cmd_label(command_buffer, c"Traversal Pass", [0.2, 0.4, 1.0, 1.0]) };
+
+    // the image was just acquired, or had its letterbox bars cleared, and the render area is cleared when rendering begins
+    let from = match ImageUsage::from_layout(*image_layout) {
+        Some(ImageUsage::Undefined) => ImageUsage::Acquired,
+        Some(usage) => usage,
+        None => ImageUsage::General,
+    };
+    unsafe {
+        BarrierBuilder::new()
+            .image(
+                image,
+                vk::ImageAspectFlags::COLOR,
+                from,
+                ImageUsage::ColorAttachment,
+            )
+            .record(device, command_buffer);
+    }
+    *image_layout = ImageUsage::ColorAttachment.layout();
 
     let color_attachment_info = vk::RenderingAttachmentInfo::default()
         .image_view(image_view)
@@ -358,47 +1833,40 @@ unsafe fn render<'a>(
             },
         });
     let rendering_info = vk::RenderingInfo::default()
-        .render_area(vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: vk::Extent2D { width, height },
-        })
+        .render_area(render_area)
         .layer_count(1)
         .color_attachments(core::slice::from_ref(&color_attachment_info));
     unsafe { device.cmd_begin_rendering(command_buffer, &rendering_info) };
 
+    let vk::Rect2D {
+        offset,
+        extent: vk::Extent2D { width, height },
+    } = render_area;
     let viewport = vk::Viewport::default()
-        .x(0.0)
-        .y(height as f32)
+        .x(offset.x as _)
+        .y((offset.y as u32 + height) as f32)
         .width(width as _)
         .height(-(height as f32));
     unsafe { device.cmd_set_viewport(command_buffer, 0, &[viewport]) };
 
-    let scissor = vk::Rect2D {
-        offset: vk::Offset2D { x: 0, y: 0 },
-        extent: vk::Extent2D { width, height },
-    };
-    unsafe { device.cmd_set_scissor(command_buffer, 0, &[scissor]) };
+    unsafe { device.cmd_set_scissor(command_buffer, 0, &[render_area]) };
 
     unsafe {
-        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
-        device.cmd_push_constants(
+        device.cmd_bind_pipeline(command_buffer, pipeline.bind_point(), pipeline.handle());
+        textures.cmd_bind(
             command_buffer,
-            pipeline_layout,
-            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            pipeline.bind_point(),
+            pipeline_layout.handle(),
             0,
-            bytemuck::bytes_of(&PushConstants {
-                triangles: triangles_buffer.device_address(),
-                start_position: position,
-                aspect: width as f32 / height as f32,
-            }),
+        );
+        pipeline_layout.cmd_push_constants(
+            command_buffer,
+            pipeline_layout.push_constant_stages(),
+            0,
+            push_constants,
         );
         device.cmd_draw(command_buffer, 4, 1, 0, 0);
     }
 
     unsafe { device.cmd_end_rendering(command_buffer) };
-
-    RenderSync {
-        wait_sempahore_info: None,
-        signal_sempahore_info: None,
-    }
 }
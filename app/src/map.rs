@@ -0,0 +1,233 @@
+use crate::{NO_TEXTURE, Position, Triangle, geometry, validate_triangles};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The map used when no `--map` or `--tiling` is given
+const DEFAULT_MAP: &str = include_str!("../maps/default.ron");
+
+/// A map as it is stored in `.ron` files, see `app/maps/default.ron` for an example
+///
+/// Unlike [`Triangle`] the vertices can be anywhere, [`Map::build`] moves each triangle so `a` is at the origin and `b` is on the x axis
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Map {
+    /// PNG or JPEG files the triangles can be textured with, relative to the map file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub textures: Vec<String>,
+    pub triangles: Vec<MapTriangle>,
+    pub spawn: Spawn,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapTriangle {
+    /// `a`, `b` and `c`, edge 0 goes from `a` to `b`, edge 1 from `a` to `c` and edge 2 from `b` to `c`
+    pub vertices: [[f32; 2]; 3],
+    pub edges: [Edge; 3],
+    /// Linear RGBA, blended over the shading by its alpha, transparent by default
+    #[serde(default)]
+    pub color: [f32; 4],
+    /// An index into [`Map::textures`], drawn instead of the shading, under the color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<u32>,
+    /// Where `a`, `b` and `c` are in the texture, which repeats outside of 0 to 1,
+    /// the vertices themselves when not given so triangles that are next to each other in the file line up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uvs: Option<[[f32; 2]; 3]>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Edge {
+    /// Rays and movement stop at this edge
+    Open,
+    /// Rays are reflected back into the triangle, and movement can't pass through
+    Mirror,
+    /// Leads into `edge` of `triangle`, with the start of each edge glued to the start of the other,
+    /// rays are rotated counterclockwise by `bend` radians when crossing it
+    Glued {
+        triangle: u32,
+        edge: u8,
+        #[serde(default)]
+        bend: f32,
+    },
+}
+
+/// Where the player starts
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Spawn {
+    pub triangle: u32,
+    /// In the same coordinates as the vertices of the triangle
+    pub position: [f32; 2],
+    /// Radians counterclockwise from looking along the x axis
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+/// A [`Map`] ready to be uploaded to the triangles buffer
+pub struct BuiltMap {
+    pub triangles: Vec<Triangle>,
+    pub spawn: Position,
+    pub spawn_rotation: f32,
+}
+
+impl Default for Map {
+    fn default() -> Self {
+        Self::parse(DEFAULT_MAP).expect("the default map should be valid")
+    }
+}
+
+impl MapTriangle {
+    /// How far [`Map::build`] rotates the triangle to put `b` on the x axis
+    pub fn angle(&self) -> f32 {
+        let [a, b, _] = self.vertices;
+        (b[1] - a[1]).atan2(b[0] - a[0])
+    }
+}
+
+impl Map {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        ron::from_str(text).map_err(|error| format!("Invalid map: {error}"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read '{}': {error}", path.display()))?;
+        Self::parse(&text).map_err(|error| format!("'{}': {error}", path.display()))
+    }
+
+    /// The text of a `.ron` file that [`Map::parse`] gives this map back from
+    pub fn to_ron(&self) -> String {
+        // one line for each of the vertices, edges and color of a triangle
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new().depth_limit(3))
+            .expect("maps should always be serializable")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_ron())
+            .map_err(|error| format!("Unable to write '{}': {error}", path.display()))
+    }
+
+    /// A map with exactly the same triangles, with each triangle's vertices in its own coordinates,
+    /// [`Map::textures`] is left empty as the triangles only know the index of their texture
+    pub fn from_triangles(triangles: &[Triangle], spawn: Position, spawn_rotation: f32) -> Self {
+        let triangles = triangles
+            .iter()
+            .map(|triangle| MapTriangle {
+                vertices: [[0.0, 0.0], [triangle.bx, 0.0], [triangle.cx, triangle.cy]],
+                edges: std::array::from_fn(|edge| {
+                    if triangle.is_mirror(edge) {
+                        Edge::Mirror
+                    } else if triangle.edge_triangles[edge] == u32::MAX {
+                        Edge::Open
+                    } else {
+                        Edge::Glued {
+                            triangle: triangle.edge_triangles[edge],
+                            edge: triangle.edge_indices[edge],
+                            bend: triangle.edge_bends[edge],
+                        }
+                    }
+                }),
+                color: triangle.color,
+                texture: (triangle.texture != NO_TEXTURE).then_some(triangle.texture),
+                uvs: (triangle.texture != NO_TEXTURE).then_some(triangle.uvs),
+            })
+            .collect();
+        Self {
+            textures: vec![],
+            triangles,
+            spawn: Spawn {
+                triangle: spawn.triangle_index,
+                position: [spawn.offset_x, spawn.offset_y],
+                rotation: spawn_rotation,
+            },
+        }
+    }
+
+    /// Converts every triangle to the layout the shader uses and checks that the gluings are consistent
+    pub fn build(&self) -> Result<BuiltMap, String> {
+        let mut triangles = Vec::with_capacity(self.triangles.len());
+        // how far each triangle was rotated to put `b` on the x axis
+        let mut angles = Vec::with_capacity(self.triangles.len());
+        for (index, map_triangle) in self.triangles.iter().enumerate() {
+            let [a, b, c] = map_triangle.vertices;
+            let ab = [b[0] - a[0], b[1] - a[1]];
+            let angle = map_triangle.angle();
+            let [cx, cy] = geometry::rotate([c[0] - a[0], c[1] - a[1]], -angle);
+            let bx = ab[0].hypot(ab[1]);
+            if !(bx > 0.0 && cy != 0.0 && cx.is_finite() && cy.is_finite()) {
+                return Err(format!(
+                    "triangle {index} has no area, its vertices are {:?}",
+                    map_triangle.vertices
+                ));
+            }
+            if let Some(texture) = map_triangle.texture
+                && texture as usize >= self.textures.len()
+            {
+                return Err(format!(
+                    "triangle {index} has texture {texture}, but there are only {} textures",
+                    self.textures.len()
+                ));
+            }
+
+            let mut triangle = Triangle {
+                bx,
+                cx,
+                cy,
+
+                edge_triangles: [u32::MAX; 3],
+                edge_indices: [0; 3],
+
+                _padding1: 0,
+                mirror_edges: 0,
+
+                edge_bends: [0.0; 3],
+
+                _padding2: 0,
+
+                color: map_triangle.color,
+
+                texture: map_triangle.texture.unwrap_or(NO_TEXTURE),
+                _padding3: 0,
+                uvs: map_triangle.uvs.unwrap_or(map_triangle.vertices),
+            };
+            for (edge, &map_edge) in map_triangle.edges.iter().enumerate() {
+                match map_edge {
+                    Edge::Open => {}
+                    Edge::Mirror => triangle.mirror_edges |= 1 << edge,
+                    Edge::Glued {
+                        triangle: other,
+                        edge: other_edge,
+                        bend,
+                    } => {
+                        triangle.edge_triangles[edge] = other;
+                        triangle.edge_indices[edge] = other_edge;
+                        triangle.edge_bends[edge] = bend;
+                    }
+                }
+            }
+            triangles.push(triangle);
+            angles.push(angle);
+        }
+        validate_triangles(&triangles)?;
+
+        let Some(map_triangle) = self.triangles.get(self.spawn.triangle as usize) else {
+            return Err(format!(
+                "the spawn is in triangle {}, which doesn't exist",
+                self.spawn.triangle
+            ));
+        };
+        let angle = angles[self.spawn.triangle as usize];
+        let a = map_triangle.vertices[0];
+        let [offset_x, offset_y] = geometry::rotate(
+            [self.spawn.position[0] - a[0], self.spawn.position[1] - a[1]],
+            -angle,
+        );
+        Ok(BuiltMap {
+            triangles,
+            spawn: Position {
+                offset_x,
+                offset_y,
+                triangle_index: self.spawn.triangle,
+            },
+            spawn_rotation: self.spawn.rotation - angle,
+        })
+    }
+}
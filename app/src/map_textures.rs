@@ -0,0 +1,71 @@
+use crate::map::Map;
+use ash::vk;
+use rendering::{BindlessTextures, Device, Image, Sampler};
+use std::{path::Path, sync::Arc};
+
+/// The textures of a [`Map`], registered in the order of [`Map::textures`],
+/// so the index a triangle refers to its texture by is also the texture's bindless handle
+pub struct MapTextures<'allocator> {
+    textures: BindlessTextures<'allocator>,
+    /// Where each texture was loaded from, relative to the working directory
+    paths: Vec<String>,
+    // registered in `textures`, so they have to be kept alive with it
+    _images: Vec<Image<'allocator>>,
+    _sampler: Sampler<'allocator>,
+}
+
+impl<'allocator> MapTextures<'allocator> {
+    /// Textures that fail to load are replaced with a magenta pixel, so the map still has one texture per index
+    ///
+    /// The map can't have more textures than [`BindlessTextures::max_capacity`], which needs [`DeviceFeature::BindlessTextures`](rendering::DeviceFeature::BindlessTextures)
+    pub fn load(device: &Arc<Device<'allocator>>, map: &Map, map_directory: &Path) -> Self {
+        // there has to be room for at least one texture for the descriptor set to exist
+        let capacity = map.textures.len().max(1) as u32;
+        let mut textures = BindlessTextures::new(device.clone(), "Map Textures", capacity);
+        let sampler = Sampler::linear_repeat(device.clone(), "Map Texture Sampler");
+
+        let mut paths = Vec::with_capacity(map.textures.len());
+        let mut images = Vec::with_capacity(map.textures.len());
+        for (index, path) in map.textures.iter().enumerate() {
+            let path = map_directory.join(path);
+            let name = format!("Map Texture {index}");
+            let image = match Image::from_path(device.clone(), &name, &path) {
+                Ok(image) => image,
+                Err(error) => {
+                    println!("Unable to load texture '{}': {error}", path.display());
+                    Image::from_rgba8(device.clone(), &name, 1, 1, &[255, 0, 255, 255])
+                }
+            };
+            let handle = unsafe {
+                textures.register(
+                    image.view(),
+                    sampler.handle(),
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                )
+            };
+            assert_eq!(
+                handle,
+                Some(index as u32),
+                "map textures are registered into consecutive slots"
+            );
+            paths.push(path.to_string_lossy().into_owned());
+            images.push(image);
+        }
+
+        Self {
+            textures,
+            paths,
+            _images: images,
+            _sampler: sampler,
+        }
+    }
+
+    pub fn textures(&self) -> &BindlessTextures<'allocator> {
+        &self.textures
+    }
+
+    /// What [`Map::textures`] should be for a map saved into the working directory
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{Position, Triangle, geometry};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rendering::{AntiAliasing, RENDER_SCALE_RANGE};
+
+const VERSION: u8 = 2;
+const ENCODED_SIZE: usize = 1 + 8 + 4 + 4 + 4 + 4 + 1 + 1 + 4 + 1;
+
+const LETTERBOX_FLAG: u8 = 1 << 0;
+const CROSSING_EFFECTS_FLAG: u8 = 1 << 1;
+
+/// A compact, url safe, string for an exact view of a map, to be shared in bug reports
+///
+/// The map is only identified by a hash of its triangles, so a permalink can't be restored on a different map
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Permalink {
+    pub map_hash: u64,
+    pub position: Position,
+    pub rotation: f32,
+    pub settings: ViewSettings,
+}
+
+/// How the view was rendered, everything the function keys switch that changes what is shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewSettings {
+    /// 0 is the shader without any defines, the rest index into its variants
+    pub shader_variant: u8,
+    pub anti_aliasing: AntiAliasing,
+    pub render_scale: f32,
+    pub letterbox: bool,
+    pub crossing_effects: bool,
+}
+
+impl Permalink {
+    pub fn new(
+        triangles: &[Triangle],
+        position: Position,
+        rotation: f32,
+        settings: ViewSettings,
+    ) -> Self {
+        Self {
+            map_hash: map_hash(triangles),
+            position,
+            rotation,
+            settings,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let ViewSettings {
+            shader_variant,
+            anti_aliasing,
+            render_scale,
+            letterbox,
+            crossing_effects,
+        } = self.settings;
+
+        let mut bytes = Vec::with_capacity(ENCODED_SIZE);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.map_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.position.triangle_index.to_le_bytes());
+        bytes.extend_from_slice(&self.position.offset_x.to_le_bytes());
+        bytes.extend_from_slice(&self.position.offset_y.to_le_bytes());
+        bytes.extend_from_slice(&self.rotation.to_le_bytes());
+        bytes.push(shader_variant);
+        bytes.push(match anti_aliasing {
+            AntiAliasing::None => 0,
+            AntiAliasing::Fxaa => 1,
+            AntiAliasing::Taa => 2,
+        });
+        bytes.extend_from_slice(&render_scale.to_le_bytes());
+        let mut flags = 0;
+        if letterbox {
+            flags |= LETTERBOX_FLAG;
+        }
+        if crossing_effects {
+            flags |= CROSSING_EFFECTS_FLAG;
+        }
+        bytes.push(flags);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    pub fn decode(text: &str) -> Result<Self, String> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(text.trim())
+            .map_err(|error| format!("Invalid permalink '{text}': {error}"))?;
+        if bytes.first() != Some(&VERSION) {
+            return Err(format!("Unsupported permalink version in '{text}'"));
+        }
+        if bytes.len() != ENCODED_SIZE {
+            return Err(format!("Invalid permalink length in '{text}'"));
+        }
+
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let anti_aliasing = match bytes[26] {
+            0 => AntiAliasing::None,
+            1 => AntiAliasing::Fxaa,
+            2 => AntiAliasing::Taa,
+            anti_aliasing => {
+                return Err(format!(
+                    "Unknown anti aliasing {anti_aliasing} in permalink '{text}'"
+                ));
+            }
+        };
+        let flags = bytes[31];
+        Ok(Self {
+            map_hash: u64::from_le_bytes(bytes[1..9].try_into().unwrap()),
+            position: Position {
+                triangle_index: u32_at(9),
+                offset_x: f32::from_bits(u32_at(13)),
+                offset_y: f32::from_bits(u32_at(17)),
+            },
+            rotation: f32::from_bits(u32_at(21)),
+            settings: ViewSettings {
+                shader_variant: bytes[25],
+                anti_aliasing,
+                render_scale: f32::from_bits(u32_at(27)),
+                letterbox: flags & LETTERBOX_FLAG != 0,
+                crossing_effects: flags & CROSSING_EFFECTS_FLAG != 0,
+            },
+        })
+    }
+
+    /// This permalink if it was made for `triangles`, and its view can be shown
+    pub fn restore(&self, triangles: &[Triangle]) -> Result<Self, String> {
+        if self.map_hash != map_hash(triangles) {
+            return Err("The permalink was made for a different map".into());
+        }
+        let Some(triangle) = triangles.get(self.position.triangle_index as usize) else {
+            return Err(format!(
+                "Triangle {} doesn't exist",
+                self.position.triangle_index
+            ));
+        };
+        // NaN is never inside a triangle
+        if !geometry::Edges::new(triangle)
+            .contains([self.position.offset_x, self.position.offset_y])
+        {
+            return Err(format!("{} is outside of its triangle", self.position));
+        }
+        if !self.rotation.is_finite() {
+            return Err(format!("Invalid rotation {}", self.rotation));
+        }
+        if !RENDER_SCALE_RANGE.contains(&self.settings.render_scale) {
+            return Err(format!(
+                "Invalid render scale {}",
+                self.settings.render_scale
+            ));
+        }
+        Ok(*self)
+    }
+}
+
+/// FNV-1a, which is stable across builds unlike [`std::hash::DefaultHasher`]
+pub fn map_hash(triangles: &[Triangle]) -> u64 {
+    bytemuck::cast_slice::<_, u8>(triangles)
+        .iter()
+        .fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilings;
+
+    /// From the middle of the second triangle, as the corners are on several edges at once
+    fn permalink(triangles: &[Triangle]) -> Permalink {
+        let triangle = &triangles[1];
+        Permalink::new(
+            triangles,
+            Position {
+                offset_x: (triangle.bx + triangle.cx) / 3.0,
+                offset_y: triangle.cy / 3.0,
+                triangle_index: 1,
+            },
+            1.5,
+            ViewSettings {
+                shader_variant: 2,
+                anti_aliasing: AntiAliasing::Taa,
+                render_scale: 0.75,
+                letterbox: true,
+                crossing_effects: false,
+            },
+        )
+    }
+
+    /// The encoded bytes of `permalink`, to make permalinks that [`Permalink::encode`] never would
+    fn bytes(permalink: &Permalink) -> Vec<u8> {
+        URL_SAFE_NO_PAD.decode(permalink.encode()).unwrap()
+    }
+
+    #[test]
+    fn round_trips_the_whole_view() {
+        let triangles = tilings::flat_torus(2, 2, 1.0);
+        let permalink = permalink(&triangles);
+
+        let decoded = Permalink::decode(&permalink.encode()).unwrap();
+        assert_eq!(decoded, permalink);
+        assert_eq!(decoded.restore(&triangles).unwrap(), permalink);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = bytes(&permalink(&tilings::flat_torus(2, 2, 1.0)));
+        bytes[0] = 1;
+        assert!(Permalink::decode(&URL_SAFE_NO_PAD.encode(bytes)).is_err());
+    }
+
+    #[test]
+    fn other_lengths_are_rejected() {
+        let mut bytes = bytes(&permalink(&tilings::flat_torus(2, 2, 1.0)));
+        bytes.push(0);
+        assert!(Permalink::decode(&URL_SAFE_NO_PAD.encode(&bytes)).is_err());
+        bytes.truncate(ENCODED_SIZE - 1);
+        assert!(Permalink::decode(&URL_SAFE_NO_PAD.encode(&bytes)).is_err());
+    }
+
+    #[test]
+    fn other_maps_are_rejected() {
+        let permalink = permalink(&tilings::flat_torus(2, 2, 1.0));
+        assert!(permalink.restore(&tilings::flat_torus(2, 2, 2.0)).is_err());
+    }
+
+    #[test]
+    fn views_that_cant_be_shown_are_rejected() {
+        let triangles = tilings::flat_torus(2, 2, 1.0);
+
+        let mut not_a_number = permalink(&triangles);
+        not_a_number.position.offset_x = f32::NAN;
+        assert!(not_a_number.restore(&triangles).is_err());
+
+        let mut infinite = permalink(&triangles);
+        infinite.rotation = f32::INFINITY;
+        assert!(infinite.restore(&triangles).is_err());
+
+        let mut missing_triangle = permalink(&triangles);
+        missing_triangle.position.triangle_index = triangles.len() as u32;
+        assert!(missing_triangle.restore(&triangles).is_err());
+
+        let mut render_scale = permalink(&triangles);
+        render_scale.settings.render_scale = 0.0;
+        assert!(render_scale.restore(&triangles).is_err());
+    }
+}
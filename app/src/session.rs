@@ -0,0 +1,192 @@
+use crate::{Position, Triangle, permalink::map_hash};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+const HEADER: &str = "time,triangle_index,offset_x,offset_y";
+/// Starts the line before [`HEADER`], followed by the [`map_hash`] of the triangles the session was recorded on
+const MAP_HASH_PREFIX: &str = "# map ";
+
+/// Writes the player's trajectory to a CSV file, one row per change of position, with the seconds since recording started
+///
+/// The file starts with a hash of the map, so it is only replayed on the map it was recorded on
+pub struct SessionRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+    last_position: Option<Position>,
+}
+
+impl SessionRecorder {
+    pub fn create(path: &Path, triangles: &[Triangle]) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{MAP_HASH_PREFIX}{:016x}", map_hash(triangles))?;
+        writeln!(writer, "{HEADER}")?;
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+            last_position: None,
+        })
+    }
+
+    pub fn record(&mut self, position: Position) -> io::Result<()> {
+        if self.last_position == Some(position) {
+            return Ok(());
+        }
+        self.last_position = Some(position);
+
+        writeln!(
+            self.writer,
+            "{},{},{},{}",
+            self.start.elapsed().as_secs_f64(),
+            position.triangle_index,
+            position.offset_x,
+            position.offset_y,
+        )
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// A trajectory written by [`SessionRecorder`], played back as a ghost
+pub struct SessionReplay {
+    /// Sorted by time
+    samples: Vec<(f64, Position)>,
+}
+
+impl SessionReplay {
+    /// Fails for sessions recorded on a different map than `triangles`
+    pub fn load(path: &Path, triangles: &[Triangle]) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read '{}': {error}", path.display()))?;
+        Self::parse(&text, triangles)
+    }
+
+    fn parse(text: &str, triangles: &[Triangle]) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        let Some(recorded_map_hash) = lines
+            .next()
+            .and_then(|(_, line)| line.trim().strip_prefix(MAP_HASH_PREFIX))
+        else {
+            return Err(format!(
+                "Expected the map the session was recorded on after '{MAP_HASH_PREFIX}'"
+            ));
+        };
+        let recorded_map_hash = u64::from_str_radix(recorded_map_hash, 16)
+            .map_err(|error| format!("Invalid map hash '{recorded_map_hash}': {error}"))?;
+        if recorded_map_hash != map_hash(triangles) {
+            return Err("The session was recorded on a different map".to_owned());
+        }
+        if lines.next().map(|(_, header)| header.trim()) != Some(HEADER) {
+            return Err(format!("Expected the header '{HEADER}'"));
+        }
+
+        let mut samples = vec![];
+        for (line_index, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line_number = line_index + 1;
+            let (time, position) = line
+                .split_once(',')
+                .ok_or_else(|| format!("Line {line_number} is missing the time"))?;
+            let time = time
+                .parse::<f64>()
+                .map_err(|error| format!("Invalid time on line {line_number}: {error}"))?;
+            if samples
+                .last()
+                .is_some_and(|&(last_time, _)| last_time > time)
+            {
+                return Err(format!("Line {line_number} goes back in time"));
+            }
+            let position = position
+                .replace(',', " ")
+                .parse::<Position>()
+                .map_err(|error| format!("Line {line_number}: {error}"))?;
+            samples.push((time, position));
+        }
+
+        Ok(Self { samples })
+    }
+
+    /// How long the recording lasts in seconds
+    pub fn duration(&self) -> f64 {
+        self.samples.last().map_or(0.0, |&(time, _)| time)
+    }
+
+    /// Where the player was `time` seconds into the recording, `None` before the first sample and after the last
+    pub fn position_at(&self, time: f64) -> Option<Position> {
+        if time > self.duration() {
+            return None;
+        }
+        let index = self
+            .samples
+            .partition_point(|&(sample_time, _)| sample_time <= time);
+        index.checked_sub(1).map(|index| self.samples[index].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilings;
+
+    /// A session recorded on `triangles` with `rows` after the header
+    fn session(triangles: &[Triangle], rows: &[&str]) -> String {
+        let mut text = format!("{MAP_HASH_PREFIX}{:016x}\n{HEADER}\n", map_hash(triangles));
+        for row in rows {
+            text.push_str(row);
+            text.push('\n');
+        }
+        text
+    }
+
+    fn position(triangle_index: u32, offset_x: f32, offset_y: f32) -> Position {
+        Position {
+            offset_x,
+            offset_y,
+            triangle_index,
+        }
+    }
+
+    #[test]
+    fn only_loads_on_the_map_it_was_recorded_on() {
+        let triangles = tilings::flat_torus(2, 2, 1.0);
+        let text = session(&triangles, &["0,0,0.5,0.25"]);
+        assert!(SessionReplay::parse(&text, &triangles).is_ok());
+        assert!(SessionReplay::parse(&text, &tilings::klein_bottle(2, 2, 1.0)).is_err());
+
+        // sessions from before the map was written down can't be checked
+        let without_map = text.lines().skip(1).collect::<Vec<_>>().join("\n");
+        assert!(SessionReplay::parse(&without_map, &triangles).is_err());
+    }
+
+    #[test]
+    fn time_has_to_go_forwards() {
+        let triangles = tilings::flat_torus(2, 2, 1.0);
+        let text = session(&triangles, &["1,0,0.5,0.25", "0.5,0,0.6,0.25"]);
+        assert!(SessionReplay::parse(&text, &triangles).is_err());
+
+        // standing still for a moment can leave samples at the same time
+        let text = session(&triangles, &["1,0,0.5,0.25", "1,0,0.6,0.25"]);
+        assert!(SessionReplay::parse(&text, &triangles).is_ok());
+    }
+
+    #[test]
+    fn positions_hold_until_the_next_sample() {
+        let triangles = tilings::flat_torus(2, 2, 1.0);
+        let text = session(&triangles, &["0.5,0,0.5,0.25", "1.5,3,0.25,0.5"]);
+        let replay = SessionReplay::parse(&text, &triangles).unwrap();
+
+        assert_eq!(replay.duration(), 1.5);
+        assert_eq!(replay.position_at(0.0), None);
+        assert_eq!(replay.position_at(0.5), Some(position(0, 0.5, 0.25)));
+        assert_eq!(replay.position_at(1.0), Some(position(0, 0.5, 0.25)));
+        assert_eq!(replay.position_at(1.5), Some(position(3, 0.25, 0.5)));
+        assert_eq!(replay.position_at(2.0), None);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{NO_TEXTURE, Position, Triangle, map::Map};
+use std::f32::consts::TAU;
+
+/// The default size of a `--tiling`, in cells
+const DEFAULT_GRID_SIZE: (u32, u32) = (4, 4);
+/// The side length of each square cell
+const CELL_SIZE: f32 = 2.0;
+
+/// Parses the value of `--tiling`, which is `torus` or `klein-bottle`, optionally followed by `:<columns>x<rows>`
+pub fn parse(text: &str) -> Result<Map, String> {
+    let (name, size) = match text.split_once(':') {
+        Some((name, size)) => (name, Some(size)),
+        None => (text, None),
+    };
+    let (columns, rows) = match size {
+        Some(size) => {
+            let parsed = size.split_once('x').and_then(|(columns, rows)| {
+                Some((columns.parse::<u32>().ok()?, rows.parse::<u32>().ok()?))
+            });
+            match parsed {
+                Some((columns, rows)) if columns > 0 && rows > 0 => (columns, rows),
+                _ => {
+                    return Err(format!(
+                        "Invalid tiling size '{size}', expected <columns>x<rows>"
+                    ));
+                }
+            }
+        }
+        None => DEFAULT_GRID_SIZE,
+    };
+    let triangles = match name {
+        "torus" => flat_torus(columns, rows, CELL_SIZE),
+        "klein-bottle" => klein_bottle(columns, rows, CELL_SIZE),
+        _ => {
+            return Err(format!(
+                "Unknown tiling '{name}', expected 'torus' or 'klein-bottle'"
+            ));
+        }
+    };
+    // the middle of the first triangle, as the corners of the cells are on several edges at once
+    let first = &triangles[0];
+    let spawn = Position {
+        offset_x: (first.bx + first.cx) / 3.0,
+        offset_y: first.cy / 3.0,
+        triangle_index: 0,
+    };
+    Ok(Map::from_triangles(&triangles, spawn, 0.0))
+}
+
+/// A grid of `columns` by `rows` square cells, with the right side glued to the left and the top to the bottom,
+/// so walking off any side comes back on the opposite one
+pub fn flat_torus(columns: u32, rows: u32, cell_size: f32) -> Vec<Triangle> {
+    grid(columns, rows, cell_size, false)
+}
+
+/// Like [`flat_torus`], but the right side is glued to the left upside down,
+/// so walking across it once comes back mirrored, and twice comes back the same
+pub fn klein_bottle(columns: u32, rows: u32, cell_size: f32) -> Vec<Triangle> {
+    grid(columns, rows, cell_size, true)
+}
+
+/// Each cell is split along its diagonal from the bottom left to the top right into a lower and an upper triangle,
+/// triangle `2 * (row * columns + column)` is the lower one and the next is the upper one
+///
+/// Edges are glued from their start to their start, so the lower triangle has its `a` at the bottom left,
+/// with edges 0 along the bottom, 1 along the diagonal and 2 up the right side,
+/// and the upper triangle has its `a` at the bottom left too, with edges 0 up the left side, 1 along the diagonal
+/// and 2 along the top
+///
+/// When `flip_seam` is set, the left side of the first column is glued to the right side of the last column upside down,
+/// so those upper triangles have their `a` at the top left instead, with edges 0 down the left side, 1 along the top
+/// and 2 along the diagonal
+fn grid(columns: u32, rows: u32, cell_size: f32, flip_seam: bool) -> Vec<Triangle> {
+    assert!(columns > 0 && rows > 0, "a tiling needs at least one cell");
+
+    let lower = |column: u32, row: u32| 2 * (row * columns + column);
+    let upper = |column: u32, row: u32| lower(column, row) + 1;
+    let is_seam = |column: u32| flip_seam && column == 0;
+    // the edge indices of the upper triangle in `column`
+    let upper_diagonal = |column: u32| if is_seam(column) { 2 } else { 1 };
+    let upper_top = |column: u32| if is_seam(column) { 1 } else { 2 };
+
+    let mut triangles = Vec::with_capacity((2 * columns * rows) as usize);
+    for row in 0..rows {
+        let below = (row + rows - 1) % rows;
+        let above = (row + 1) % rows;
+        for column in 0..columns {
+            let left = (column + columns - 1) % columns;
+            let right = (column + 1) % columns;
+
+            let color = cell_color(column, row, rows);
+            let right_row = if flip_seam && column == columns - 1 {
+                rows - 1 - row
+            } else {
+                row
+            };
+            triangles.push(triangle(
+                [cell_size, cell_size, cell_size],
+                [
+                    upper(column, below),
+                    upper(column, row),
+                    upper(right, right_row),
+                ],
+                [upper_top(column), upper_diagonal(column), 0],
+                color,
+            ));
+
+            triangles.push(if is_seam(column) {
+                triangle(
+                    [cell_size, 0.0, cell_size],
+                    [
+                        lower(left, rows - 1 - row),
+                        lower(column, above),
+                        lower(column, row),
+                    ],
+                    [2, 0, 1],
+                    color,
+                )
+            } else {
+                triangle(
+                    [cell_size, cell_size, cell_size],
+                    [lower(left, row), lower(column, row), lower(column, above)],
+                    [2, 1, 0],
+                    color,
+                )
+            });
+        }
+    }
+    triangles
+}
+
+/// A hue for each row, darker in every other column, so walking across the seam of a Klein bottle
+/// visibly comes back with the rows in the opposite order
+fn cell_color(column: u32, row: u32, rows: u32) -> [f32; 4] {
+    let hue = row as f32 / rows as f32;
+    let brightness = if column.is_multiple_of(2) { 1.0 } else { 0.6 };
+    let [r, g, b] = [0.0, 1.0, 2.0]
+        .map(|offset: f32| (0.5 + 0.5 * (TAU * (hue + offset / 3.0)).cos()) * brightness);
+    [r, g, b, 0.5]
+}
+
+fn triangle(
+    [bx, cx, cy]: [f32; 3],
+    edge_triangles: [u32; 3],
+    edge_indices: [u8; 3],
+    color: [f32; 4],
+) -> Triangle {
+    Triangle {
+        bx,
+        cx,
+        cy,
+
+        edge_triangles,
+        edge_indices,
+
+        _padding1: 0,
+        mirror_edges: 0,
+
+        edge_bends: [0.0; 3],
+
+        _padding2: 0,
+
+        color,
+
+        texture: NO_TEXTURE,
+        _padding3: 0,
+        uvs: [[0.0; 2]; 3],
+    }
+}
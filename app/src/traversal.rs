@@ -0,0 +1,177 @@
+use crate::{
+    Position, Triangle,
+    geometry::{Edges, Transition, Vec2, add, length, reflect, rotate, scale},
+};
+use ash::vk;
+use gpu_allocator::MemoryLocation;
+use rendering::{Buffer, Device, GpuPtr, PerFrame};
+use std::sync::Arc;
+
+/// How far the rays from the edges of the screen travel, `full_screen_quad.slang` scales every ray by the same amount
+pub const VIEW_DISTANCE: f32 = 5.0;
+/// Same as the step limit in `full_screen_quad.slang`, rays that cross more edges than this stop where they are
+pub const MAX_STEPS: usize = 1000;
+
+/// A cpu copy of `walk` in `full_screen_quad.slang`, any change to one has to be made to the other
+pub fn walk(triangles: &[Triangle], mut position: Position, move_offset: Vec2) -> Position {
+    if position.triangle_index == u32::MAX {
+        return position;
+    }
+
+    let mut offset = [position.offset_x, position.offset_y];
+    let mut distance = length(move_offset);
+    let mut direction = scale(move_offset, 1.0 / distance);
+
+    let mut incoming_edge = None;
+    for _ in 0..MAX_STEPS {
+        let triangle = &triangles[position.triangle_index as usize];
+        let edges = Edges::new(triangle);
+
+        let Some((edge, distance_to_edge)) = edges.exit(offset, direction, incoming_edge) else {
+            position.triangle_index = u32::MAX;
+            break;
+        };
+        if distance_to_edge > distance {
+            offset = add(offset, scale(direction, distance));
+            break;
+        }
+
+        distance -= distance_to_edge;
+
+        let edge_position = add(offset, scale(direction, distance_to_edge));
+        if triangle.is_mirror(edge) {
+            // stays in this triangle, leaving the mirror the way it came in
+            offset = edge_position;
+            direction = reflect(direction, edges.normals[edge]);
+            incoming_edge = Some(edge);
+            continue;
+        }
+
+        let Some(transition) = Transition::new(triangles, position.triangle_index, edge) else {
+            position.triangle_index = u32::MAX;
+            break;
+        };
+        position.triangle_index = transition.triangle_index;
+        incoming_edge = Some(transition.edge);
+        offset = transition.point(edge_position);
+        direction = transition.direction(direction);
+    }
+
+    [position.offset_x, position.offset_y] = offset;
+    position
+}
+
+/// Where the ray through the center of a pixel ends up, matching the vertex and fragment shaders of `full_screen_quad.slang`
+pub fn trace_pixel(
+    triangles: &[Triangle],
+    start_position: Position,
+    rotation: f32,
+    width: u32,
+    height: u32,
+    [x, y]: [u32; 2],
+) -> Position {
+    // the viewport is flipped, so the first row is at the top of the screen
+    let u = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+    let v = 1.0 - (y as f32 + 0.5) / height as f32 * 2.0;
+    let aspect = width as f32 / height as f32;
+    walk(
+        triangles,
+        start_position,
+        scale(rotate([u * aspect, v], rotation), VIEW_DISTANCE),
+    )
+}
+
+#[derive(Clone, Copy)]
+struct Probe {
+    start_position: Position,
+    rotation: f32,
+    width: u32,
+    height: u32,
+    pixel: [u32; 2],
+}
+
+/// Reads back where the gpu traversal ended up for one pixel each frame and compares it against [`trace_pixel`],
+/// so the cpu and gpu traversal drifting apart is noticed immediately
+pub struct TraversalCheck<'allocator> {
+    buffers: PerFrame<Buffer<'allocator>>,
+    probes: PerFrame<Option<Probe>>,
+}
+
+impl<'allocator> TraversalCheck<'allocator> {
+    pub fn new(device: Arc<Device<'allocator>>) -> Self {
+        let buffers = PerFrame::new(|_| {
+            Buffer::new(
+                device.clone(),
+                "Traversal Probe Buffer",
+                MemoryLocation::GpuToCpu,
+                size_of::<Position>() as _,
+                vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                false,
+                None,
+            )
+        });
+        Self {
+            buffers,
+            probes: PerFrame::default(),
+        }
+    }
+
+    /// Compares the result of the last frame rendered with `frame_index` and then probes again,
+    /// returns the address the shader writes the probed position to and the probed pixel
+    ///
+    /// # Safety
+    /// The last frame rendered with `frame_index` must have finished on the gpu
+    pub unsafe fn check_and_probe(
+        &mut self,
+        frame_index: usize,
+        triangles: &[Triangle],
+        start_position: Position,
+        rotation: f32,
+        width: u32,
+        height: u32,
+    ) -> (GpuPtr<Position>, [u32; 2]) {
+        let buffer = &mut self.buffers[frame_index];
+        if let Some(probe) = self.probes[frame_index].take() {
+            let gpu_position =
+                bytemuck::pod_read_unaligned::<Position>(unsafe { buffer.get_mapped() }.unwrap());
+            let cpu_position = trace_pixel(
+                triangles,
+                probe.start_position,
+                probe.rotation,
+                probe.width,
+                probe.height,
+                probe.pixel,
+            );
+            if gpu_position.triangle_index != cpu_position.triangle_index {
+                println!(
+                    "Traversal diverged at pixel {:?} of {}x{} starting from {} turned {} radians: the cpu ended at {} and the gpu ended at {}",
+                    probe.pixel,
+                    probe.width,
+                    probe.height,
+                    probe.start_position,
+                    probe.rotation,
+                    cpu_position,
+                    gpu_position,
+                );
+            }
+        }
+
+        // the center pixel's ray barely moves, halfway to the right edge of the screen crosses edges while still being on screen
+        let pixel = [width * 3 / 4, height / 2];
+        unsafe { buffer.get_mapped_mut() }
+            .unwrap()
+            .copy_from_slice(bytemuck::bytes_of(&Position {
+                offset_x: 0.0,
+                offset_y: 0.0,
+                triangle_index: u32::MAX,
+            }));
+        self.probes[frame_index] = Some(Probe {
+            start_position,
+            rotation,
+            width,
+            height,
+            pixel,
+        });
+        (unsafe { buffer.device_ptr() }, pixel)
+    }
+}
@@ -0,0 +1,39 @@
+const BAR_WIDTH: usize = 40;
+const MAX_ROWS: usize = 16;
+
+/// Formats per triangle visit counts from the `visit_counting` shader variant as a text heatmap,
+/// the most visited triangles first, so map authors can see which parts of a map are actually reached
+pub fn report(visit_counts: &[u32]) -> String {
+    let total = visit_counts
+        .iter()
+        .map(|&count| u64::from(count))
+        .sum::<u64>();
+    if total == 0 {
+        return "No triangles were visited".to_owned();
+    }
+
+    let mut ranked = visit_counts.iter().copied().enumerate().collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| b.cmp(a));
+    let most_visits = ranked[0].1;
+
+    let mut report = format!("{total} visits over {} triangles\n", visit_counts.len());
+    for &(index, count) in ranked.iter().take(MAX_ROWS) {
+        let bar_length = (u64::from(count) * BAR_WIDTH as u64).div_ceil(u64::from(most_visits));
+        report += &format!(
+            "{index:>6} |{:<BAR_WIDTH$}| {:>6.2}%\n",
+            "#".repeat(bar_length as usize),
+            count as f64 / total as f64 * 100.0,
+        );
+    }
+
+    let unvisited = visit_counts.iter().filter(|&&count| count == 0).count();
+    if ranked.len() > MAX_ROWS {
+        report += &format!("... {} more", ranked.len() - MAX_ROWS);
+        if unvisited > 0 {
+            report += &format!(", {unvisited} never visited");
+        }
+    } else if unvisited > 0 {
+        report += &format!("{unvisited} never visited");
+    }
+    report
+}
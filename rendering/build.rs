@@ -0,0 +1,42 @@
+use std::{path::Path, process::Stdio};
+
+/// Compiles the shaders the crate runs itself, like the tonemap pass, to `OUT_DIR/shaders/<shader>.spv`
+fn main() {
+    println!("cargo::rerun-if-changed=./shaders");
+
+    let out_dir = Path::new(&std::env::var("OUT_DIR").unwrap()).join("shaders/");
+
+    _ = std::fs::remove_dir_all(&out_dir);
+    std::fs::create_dir_all(&out_dir).unwrap();
+
+    let debug_info = std::env::var("PROFILE").unwrap() == "debug";
+
+    let mut compilations = vec![];
+    for entry in std::fs::read_dir("./shaders").unwrap() {
+        let entry = entry.unwrap();
+        if !entry.file_type().unwrap().is_file() {
+            continue;
+        }
+
+        let file_path = entry.path();
+        let shader = file_path.file_stem().unwrap().to_str().unwrap().to_owned();
+        let spirv_file_name = format!("{shader}.spv");
+
+        let process =
+            shader_tools::slangc(&file_path, &out_dir.join(&spirv_file_name), &[], debug_info)
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap();
+        compilations.push((spirv_file_name, process));
+    }
+
+    for (spirv_file_name, process) in compilations {
+        let output = process.wait_with_output().unwrap();
+        if !output.status.success() {
+            panic!(
+                "{spirv_file_name}\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Churns swapchains and buffers for a long time to catch resource lifetime regressions
+//!
+//! `cargo run --example soak -- [frame count]`, panics if deferred destruction keeps growing or resources leak
+
+use ash::vk;
+use gpu_allocator::MemoryLocation;
+use rendering::{
+    Buffer, Device, DeviceConfig, FRAMES_IN_FLIGHT_COUNT, Instance, InstanceConfig, RenderResult,
+    RenderSync, Surface, Swapchain, make_subresource_range, transition_image,
+};
+use std::sync::Arc;
+use winit::{
+    dpi::PhysicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowAttributes,
+};
+
+const RESIZE_INTERVAL: u64 = 50;
+const MAP_RELOAD_INTERVAL: u64 = 200;
+const MAP_BUFFER_COUNT: usize = 16;
+/// Everything dropped in a frame is destroyed a few frames later, so this is never reached unless something is stuck
+const MAX_PENDING_DESTROYS: usize = (MAP_BUFFER_COUNT + 1) * (FRAMES_IN_FLIGHT_COUNT + 2) * 4;
+
+fn main() {
+    let frame_count = std::env::args()
+        .nth(1)
+        .map_or(10_000, |frame_count| frame_count.parse::<u64>().unwrap());
+
+    let event_loop = EventLoop::new().unwrap();
+    event_loop.set_control_flow(ControlFlow::Poll);
+
+    let window = {
+        let attributes = WindowAttributes::default().with_title("Soak Test");
+        #[expect(deprecated)]
+        event_loop.create_window(attributes).unwrap()
+    };
+
+    let entry = unsafe { ash::Entry::load() }.unwrap();
+    let instance = Arc::new(unsafe {
+        Instance::new(
+            entry,
+            None,
+            InstanceConfig::default().application_name(c"Soak Test"),
+        )
+    });
+    let surface = Arc::new(Surface::new(instance.clone(), &window));
+    let device = Arc::new(Device::new(instance.clone(), DeviceConfig::default()));
+    let mut swapchain = Swapchain::new(device.clone(), surface);
+
+    let baseline_tracked_resources = device.tracked_resource_count();
+
+    let mut frame = 0;
+    let mut max_pending_destroys = 0;
+    let mut map_buffers = load_map(&device, 0);
+    let mut scratch_buffer = None;
+    let run = |event: Event<()>, event_loop: &ActiveEventLoop| match event {
+        Event::WindowEvent { window_id, event } if window_id == window.id() => match event {
+            WindowEvent::CloseRequested | WindowEvent::Destroyed => event_loop.exit(),
+
+            WindowEvent::Resized(size) => {
+                device.destroy_resources();
+                swapchain.resize(size.width, size.height);
+            }
+
+            _ => {}
+        },
+
+        Event::AboutToWait => {
+            device.destroy_resources();
+
+            let pending_destroys = device.pending_destroy_count();
+            max_pending_destroys = max_pending_destroys.max(pending_destroys);
+            assert!(
+                pending_destroys <= MAX_PENDING_DESTROYS,
+                "{pending_destroys} resources are waiting to be destroyed on frame {frame}"
+            );
+
+            if frame == frame_count {
+                event_loop.exit();
+                return;
+            }
+
+            if frame.is_multiple_of(RESIZE_INTERVAL) {
+                let size = if (frame / RESIZE_INTERVAL).is_multiple_of(2) {
+                    PhysicalSize::new(640, 480)
+                } else {
+                    PhysicalSize::new(800, 600)
+                };
+                _ = window.request_inner_size(size);
+            }
+            if frame.is_multiple_of(MAP_RELOAD_INTERVAL) {
+                map_buffers = load_map(&device, frame);
+            }
+            scratch_buffer = Some(Buffer::new(
+                device.clone(),
+                "Soak Scratch Buffer",
+                MemoryLocation::CpuToGpu,
+                1024 + frame % 7 * 4096,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                false,
+                None,
+            ));
+
+            match swapchain.try_next_frame(
+                |command_buffer, image_layout, _render_area, image, _image_view, _| unsafe {
+                    clear(&device, command_buffer, image, image_layout, frame)
+                },
+            ) {
+                RenderResult::NotReady => return,
+                RenderResult::OutOfDate | RenderResult::Suboptimal => {
+                    let size = window.inner_size();
+                    swapchain.resize(size.width, size.height);
+                }
+                RenderResult::Success => {}
+            }
+            frame += 1;
+        }
+
+        _ => {}
+    };
+    #[expect(deprecated)]
+    event_loop.run(run).unwrap();
+
+    drop(map_buffers);
+    drop(scratch_buffer);
+    unsafe { device.device_wait_idle() }.unwrap();
+    device.destroy_resources();
+
+    assert_eq!(device.pending_destroy_count(), 0);
+    assert_eq!(
+        device.tracked_resource_count(),
+        baseline_tracked_resources,
+        "resources were leaked"
+    );
+    println!(
+        "Soaked {frame} frames, at most {max_pending_destroys} resources were waiting to be destroyed"
+    );
+}
+
+/// Stands in for loading a map, which replaces every buffer at once
+fn load_map<'allocator>(device: &Arc<Device<'allocator>>, seed: u64) -> Vec<Buffer<'allocator>> {
+    (0..MAP_BUFFER_COUNT as u64)
+        .map(|index| {
+            Buffer::new(
+                device.clone(),
+                "Soak Map Buffer",
+                MemoryLocation::GpuOnly,
+                (seed + index) % 13 * 65536 + 256,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                index == 0,
+                None,
+            )
+        })
+        .collect()
+}
+
+unsafe fn clear<'a>(
+    device: &Device<'_>,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    image_layout: &mut vk::ImageLayout,
+    frame: u64,
+) -> RenderSync<'a> {
+    unsafe {
+        transition_image(
+            device,
+            command_buffer,
+            image,
+            image_layout,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        );
+    }
+
+    let shade = (frame % 256) as f32 / 255.0;
+    unsafe {
+        device.cmd_clear_color_image(
+            command_buffer,
+            image,
+            *image_layout,
+            &vk::ClearColorValue {
+                float32: [shade, 0.0, 1.0 - shade, 1.0],
+            },
+            &[make_subresource_range(vk::ImageAspectFlags::COLOR)],
+        );
+    }
+
+    RenderSync::default()
+}
@@ -0,0 +1,107 @@
+use crate::{DeviceFeature, Instance};
+use ash::vk;
+
+/// What a physical device is and can do, for showing a gpu picker before creating a [`Device`](crate::Device),
+/// see [`Instance::enumerate_adapters`]
+#[derive(Debug, Clone)]
+pub struct AdapterInfo {
+    /// Pass to [`DeviceConfig::physical_device`](crate::DeviceConfig::physical_device) to create the device on this adapter
+    pub physical_device: vk::PhysicalDevice,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    /// Encoded in a vendor specific way, [`AdapterInfo::driver_info`] is usually more readable
+    pub driver_version: u32,
+    /// Empty on vulkan 1.1 and older devices
+    pub driver_name: String,
+    /// Empty on vulkan 1.1 and older devices
+    pub driver_info: String,
+    /// See [`vk::make_api_version`]
+    pub api_version: u32,
+    /// The total size of every [`vk::MemoryHeapFlags::DEVICE_LOCAL`] heap, in bytes
+    pub device_local_memory: u64,
+    /// The total size of every other heap, which is usually system memory the gpu can access, in bytes
+    pub shared_memory: u64,
+    /// Every [`DeviceFeature`] that could be enabled on this adapter
+    pub supported_features: Vec<DeviceFeature>,
+}
+
+impl Instance<'_> {
+    /// Every physical device, including ones [`Device::new`](crate::Device::new) would skip for not supporting what this crate needs
+    pub fn enumerate_adapters(&self) -> Vec<AdapterInfo> {
+        let physical_devices = unsafe { self.enumerate_physical_devices() }.unwrap();
+        physical_devices
+            .into_iter()
+            .map(|physical_device| self.adapter_info(physical_device))
+            .collect()
+    }
+
+    fn adapter_info(&self, physical_device: vk::PhysicalDevice) -> AdapterInfo {
+        let properties = unsafe { self.get_physical_device_properties(physical_device) };
+
+        let (driver_name, driver_info) = if properties.api_version >= vk::API_VERSION_1_2 {
+            let mut driver_properties = vk::PhysicalDeviceDriverProperties::default();
+            let mut properties2 =
+                vk::PhysicalDeviceProperties2::default().push_next(&mut driver_properties);
+            unsafe { self.get_physical_device_properties2(physical_device, &mut properties2) };
+            (
+                driver_properties.driver_name_as_c_str().map_or_else(
+                    |_| String::new(),
+                    |name| name.to_string_lossy().into_owned(),
+                ),
+                driver_properties.driver_info_as_c_str().map_or_else(
+                    |_| String::new(),
+                    |info| info.to_string_lossy().into_owned(),
+                ),
+            )
+        } else {
+            (String::new(), String::new())
+        };
+
+        let memory_properties =
+            unsafe { self.get_physical_device_memory_properties(physical_device) };
+        let heap_size = |device_local: bool| -> u64 {
+            memory_properties
+                .memory_heaps_as_slice()
+                .iter()
+                .filter(|heap| {
+                    heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) == device_local
+                })
+                .map(|heap| heap.size)
+                .sum()
+        };
+
+        let extensions =
+            unsafe { self.enumerate_device_extension_properties(physical_device) }.unwrap();
+        let supported_features = DeviceFeature::ALL
+            .into_iter()
+            .filter(|feature| {
+                feature.extensions().iter().all(|&name| {
+                    extensions
+                        .iter()
+                        .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+                }) && feature.is_supported(self, physical_device)
+            })
+            .collect();
+
+        AdapterInfo {
+            physical_device,
+            name: properties
+                .device_name_as_c_str()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+            device_type: properties.device_type,
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            driver_version: properties.driver_version,
+            driver_name,
+            driver_info,
+            api_version: properties.api_version,
+            device_local_memory: heap_size(true),
+            shared_memory: heap_size(false),
+            supported_features,
+        }
+    }
+}
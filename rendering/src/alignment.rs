@@ -0,0 +1,104 @@
+use crate::Device;
+use bytemuck::NoUninit;
+
+/// Rounds `value` up to a multiple of `alignment`, which has to be a power of two like every vulkan alignment
+pub const fn align_up(value: u64, alignment: u64) -> u64 {
+    assert!(alignment.is_power_of_two());
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+/// The least common multiple of two alignments, for offsets that have to satisfy both, unlike [`align_up`] they don't have to be powers of two
+pub const fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+impl Device<'_> {
+    /// Offsets of uniform buffer descriptors, including dynamic offsets, have to be a multiple of this
+    pub fn uniform_buffer_offset_alignment(&self) -> u64 {
+        self.limits().min_uniform_buffer_offset_alignment
+    }
+
+    /// Offsets of storage buffer descriptors, including dynamic offsets, have to be a multiple of this
+    pub fn storage_buffer_offset_alignment(&self) -> u64 {
+        self.limits().min_storage_buffer_offset_alignment
+    }
+}
+
+/// Packs values one after another into `data`, starting each at a multiple of the alignment,
+/// so many draws can each bind their own part of one mapped buffer
+///
+/// The offsets given back are from the start of `data`, which should itself be at an aligned offset in the buffer
+pub struct AlignedWriter<'a> {
+    data: &'a mut [u8],
+    alignment: u64,
+    offset: u64,
+}
+
+impl<'a> AlignedWriter<'a> {
+    pub fn new(data: &'a mut [u8], alignment: u64) -> Self {
+        assert!(alignment.is_power_of_two());
+        Self {
+            data,
+            alignment,
+            offset: 0,
+        }
+    }
+
+    /// For offsets of uniform buffer descriptors, see [`Device::uniform_buffer_offset_alignment`]
+    pub fn for_uniforms(device: &Device<'_>, data: &'a mut [u8]) -> Self {
+        Self::new(data, device.uniform_buffer_offset_alignment())
+    }
+
+    /// For offsets of storage buffer descriptors, see [`Device::storage_buffer_offset_alignment`]
+    pub fn for_storage(device: &Device<'_>, data: &'a mut [u8]) -> Self {
+        Self::new(data, device.storage_buffer_offset_alignment())
+    }
+
+    pub fn alignment(&self) -> u64 {
+        self.alignment
+    }
+
+    /// Where the next write could start, before aligning it
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Bytes left after the last write, not counting the padding the next write needs
+    pub fn remaining(&self) -> u64 {
+        self.data.len() as u64 - self.offset
+    }
+
+    /// Starts writing from the beginning again, for reusing the same memory next frame
+    pub fn reset(&mut self) {
+        self.offset = 0;
+    }
+
+    /// Returns the offset `value` was written at, or `None` when it doesn't fit
+    pub fn write<T: NoUninit>(&mut self, value: &T) -> Option<u64> {
+        self.write_aligned(bytemuck::bytes_of(value), align_of::<T>() as u64)
+    }
+
+    /// Like [`AlignedWriter::write`], with the values right after each other
+    pub fn write_slice<T: NoUninit>(&mut self, values: &[T]) -> Option<u64> {
+        self.write_aligned(bytemuck::cast_slice(values), align_of::<T>() as u64)
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Option<u64> {
+        self.write_aligned(bytes, 1)
+    }
+
+    fn write_aligned(&mut self, bytes: &[u8], value_alignment: u64) -> Option<u64> {
+        let offset = align_up(self.offset, self.alignment.max(value_alignment));
+        let end = offset.checked_add(bytes.len() as u64)?;
+        if end > self.data.len() as u64 {
+            return None;
+        }
+        self.data[offset as usize..end as usize].copy_from_slice(bytes);
+        self.offset = end;
+        Some(offset)
+    }
+}
@@ -0,0 +1,175 @@
+use crate::Device;
+use std::fmt;
+
+/// A snapshot of every memory block the allocator owns and what is allocated in them, see [`Device::dump_allocator_report`]
+#[derive(Debug, Clone)]
+pub struct AllocatorReport {
+    pub blocks: Vec<MemoryBlockReport>,
+    /// The sum of every allocation's size, in bytes
+    pub allocated_bytes: u64,
+    /// The sum of every block's size, including the free space between allocations, in bytes
+    pub capacity_bytes: u64,
+    /// Resources waiting on the gpu before they are freed, see [`Device::pending_destroy_count`]
+    pub pending_destroy_count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryBlockReport {
+    pub size: u64,
+    /// Sorted by offset
+    pub allocations: Vec<AllocationReport>,
+    /// The size of every gap between allocations, including at the start and end of the block, in bytes
+    pub free_bytes: u64,
+    /// The size of the biggest gap, which is the biggest allocation that could still fit in this block, in bytes
+    pub largest_free_range: u64,
+    /// How many separate gaps there are
+    pub free_ranges: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct AllocationReport {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl MemoryBlockReport {
+    /// 0 when all the free space is in one range, approaching 1 the more it is split up into small ranges
+    pub fn fragmentation(&self) -> f64 {
+        if self.free_bytes == 0 {
+            0.0
+        } else {
+            1.0 - self.largest_free_range as f64 / self.free_bytes as f64
+        }
+    }
+}
+
+impl AllocatorReport {
+    /// The free space of every block weighted by how fragmented it is, as a fraction of all the free space
+    pub fn fragmentation(&self) -> f64 {
+        let free_bytes = self
+            .blocks
+            .iter()
+            .map(|block| block.free_bytes)
+            .sum::<u64>();
+        if free_bytes == 0 {
+            return 0.0;
+        }
+        self.blocks
+            .iter()
+            .map(|block| block.fragmentation() * block.free_bytes as f64)
+            .sum::<f64>()
+            / free_bytes as f64
+    }
+}
+
+impl Device<'_> {
+    /// Collects everything the allocator currently has allocated, for tracking down fragmentation
+    /// and memory that is kept alive longer than expected, [`fmt::Display`] pretty prints it
+    pub fn dump_allocator_report(&self) -> AllocatorReport {
+        let report = self.with_allocator(|allocator| allocator.generate_report());
+
+        let blocks = report
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut allocations = report.allocations[block.allocations.clone()]
+                    .iter()
+                    .map(|allocation| AllocationReport {
+                        name: allocation.name.clone(),
+                        offset: allocation.offset,
+                        size: allocation.size,
+                    })
+                    .collect::<Vec<_>>();
+                allocations.sort_by_key(|allocation| allocation.offset);
+
+                let mut free_bytes = 0;
+                let mut largest_free_range = 0;
+                let mut free_ranges = 0;
+                let mut end_of_previous = 0;
+                for (start, end) in allocations
+                    .iter()
+                    .map(|allocation| (allocation.offset, allocation.offset + allocation.size))
+                    .chain([(block.size, block.size)])
+                {
+                    let gap = start.saturating_sub(end_of_previous);
+                    if gap > 0 {
+                        free_bytes += gap;
+                        largest_free_range = largest_free_range.max(gap);
+                        free_ranges += 1;
+                    }
+                    end_of_previous = end_of_previous.max(end);
+                }
+
+                MemoryBlockReport {
+                    size: block.size,
+                    allocations,
+                    free_bytes,
+                    largest_free_range,
+                    free_ranges,
+                }
+            })
+            .collect();
+
+        AllocatorReport {
+            blocks,
+            allocated_bytes: report.total_allocated_bytes,
+            capacity_bytes: report.total_capacity_bytes,
+            pending_destroy_count: self.pending_destroy_count(),
+        }
+    }
+}
+
+struct Bytes(u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+        let mut size = self.0 as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            write!(f, "{} {}", self.0, UNITS[0])
+        } else {
+            write!(f, "{size:.2} {}", UNITS[unit])
+        }
+    }
+}
+
+impl fmt::Display for AllocatorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} allocated out of {} in {} blocks, {:.1}% fragmented, {} resources waiting to be destroyed",
+            Bytes(self.allocated_bytes),
+            Bytes(self.capacity_bytes),
+            self.blocks.len(),
+            self.fragmentation() * 100.0,
+            self.pending_destroy_count,
+        )?;
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(
+                f,
+                "  Block {index}: {}, {} free in {} ranges, largest {}, {:.1}% fragmented",
+                Bytes(block.size),
+                Bytes(block.free_bytes),
+                block.free_ranges,
+                Bytes(block.largest_free_range),
+                block.fragmentation() * 100.0,
+            )?;
+            for allocation in &block.allocations {
+                writeln!(
+                    f,
+                    "    {:>10} at {:>10}: '{}'",
+                    Bytes(allocation.size).to_string(),
+                    allocation.offset,
+                    allocation.name,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
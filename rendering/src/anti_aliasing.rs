@@ -0,0 +1,285 @@
+use crate::{
+    Device, FRAMES_IN_FLIGHT_COUNT, GraphicsPipelineBuilder, HDR_FORMAT, HIGH_MEMORY_PRIORITY,
+    Image, Instance, PerFrame, Pipeline, PipelineLayout, ResourceToDestroy, Sampler, Shader,
+    cmd_begin_full_screen_pass,
+};
+use ash::vk;
+use bytemuck::NoUninit;
+use scope_guard::scope_guard;
+use std::sync::Arc;
+
+const TEMPORAL_RESOLVE_SPIRV: &[u32] =
+    crate::include_spirv!(concat!(env!("OUT_DIR"), "/shaders/temporal_resolve.spv"));
+
+/// How many frames the jitter pattern takes to repeat
+const JITTER_SEQUENCE_LENGTH: u64 = 8;
+
+/// The anti aliasing [`Tonemapper`](crate::Tonemapper) applies, see [`Tonemapper::set_anti_aliasing`](crate::Tonemapper::set_anti_aliasing)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AntiAliasing {
+    None,
+    /// Blurs along high contrast edges while tonemapping, cheap and needs nothing from the renderer
+    Fxaa,
+    /// Blends every frame with a history of previous ones, see [`TemporalAntiAliasing`]
+    Taa,
+}
+
+#[derive(Clone, Copy, NoUninit)]
+#[repr(C)]
+struct PushConstants {
+    history_offset: [f32; 2],
+    history_weight: f32,
+    has_history: u32,
+}
+
+/// Keeps a history of resolved [`HDR_FORMAT`] frames and blends each new frame into it
+///
+/// Each frame has to be rendered with the subpixel offset from [`TemporalAntiAliasing::jitter`] so the history
+/// accumulates different samples of every pixel, and when the camera moves [`TemporalAntiAliasing::set_history_offset`]
+/// has to say where the previous frame's pixels went, the history is clamped to the new frame's colors so mistakes only smear a little
+pub struct TemporalAntiAliasing<'allocator> {
+    device: Arc<Device<'allocator>>,
+    /// Written and read alternately, the one at `write_index` is written by the next resolve
+    history: [Image<'allocator>; 2],
+    write_index: usize,
+    has_history: bool,
+    sampler: Sampler<'allocator>,
+    pipeline_layout: PipelineLayout<'allocator>,
+    pipeline: Pipeline<'allocator>,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    frame_number: u64,
+    history_offset: [f32; 2],
+    history_weight: f32,
+}
+
+impl<'allocator> TemporalAntiAliasing<'allocator> {
+    pub fn new(device: Arc<Device<'allocator>>, width: u32, height: u32) -> Self {
+        let shader = unsafe {
+            Shader::new(
+                device.clone(),
+                "Temporal Resolve Shader",
+                TEMPORAL_RESOLVE_SPIRV,
+            )
+        };
+        let pipeline_layout = PipelineLayout::from_shaders(
+            device.clone(),
+            "Temporal Resolve Pipeline Layout",
+            &[&shader],
+        );
+        let pipeline = GraphicsPipelineBuilder::new(&pipeline_layout, HDR_FORMAT)
+            .vertex(&shader, c"vertex")
+            .fragment(&shader, c"fragment")
+            .build(device.clone(), "Temporal Resolve Pipeline");
+
+        let pool_sizes = [vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(2 * FRAMES_IN_FLIGHT_COUNT as u32)];
+        let pool_create_info = vk::DescriptorPoolCreateInfo::default()
+            .max_sets(FRAMES_IN_FLIGHT_COUNT as _)
+            .pool_sizes(&pool_sizes);
+        let descriptor_pool = scope_guard!(
+            |descriptor_pool| unsafe {
+                device.destroy_descriptor_pool(descriptor_pool, device.allocator())
+            },
+            unsafe { device.create_descriptor_pool(&pool_create_info, device.allocator()) }
+                .unwrap()
+        );
+
+        let set_layouts = PerFrame::splat(pipeline_layout.set_layouts()[0]);
+        let allocate_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(*descriptor_pool)
+            .set_layouts(&set_layouts);
+        let descriptor_sets = unsafe { device.allocate_descriptor_sets(&allocate_info) }
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        device.track_resource(*descriptor_pool, "Temporal Resolve Descriptor Pool");
+
+        Self {
+            history: create_history(&device, width, height),
+            write_index: 0,
+            has_history: false,
+            sampler: Sampler::linear_clamp(device.clone(), "Temporal Resolve Sampler"),
+            pipeline_layout,
+            pipeline,
+            descriptor_pool: descriptor_pool.into_inner(),
+            descriptor_sets,
+            frame_number: 0,
+            history_offset: [0.0, 0.0],
+            history_weight: 0.9,
+            device,
+        }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    /// The subpixel offset the next frame has to be rendered with, in clip space units,
+    /// it should be added to the `xy` of every clip position
+    pub fn jitter(&self) -> [f32; 2] {
+        let index = (self.frame_number % JITTER_SEQUENCE_LENGTH + 1) as u32;
+        let extent = self.history[0].extent();
+        [
+            (halton(index, 2) - 0.5) * 2.0 / extent.width as f32,
+            (halton(index, 3) - 0.5) * 2.0 / extent.height as f32,
+        ]
+    }
+
+    /// Where the content of a pixel was in the previous frame relative to where it is in the next one, in uv units,
+    /// it stays the same until it is set again so it should be set before every frame when the camera can move
+    pub fn set_history_offset(&mut self, history_offset: [f32; 2]) {
+        self.history_offset = history_offset;
+    }
+
+    /// How much of the history is kept each frame, higher is smoother but slower to react, 0.9 by default
+    pub fn history_weight(&self) -> f32 {
+        self.history_weight
+    }
+
+    pub fn set_history_weight(&mut self, history_weight: f32) {
+        self.history_weight = history_weight.clamp(0.0, 1.0);
+    }
+
+    /// Throws the history away, for when the view jumps and the history has nothing in common with the next frame
+    pub fn reset_history(&mut self) {
+        self.has_history = false;
+    }
+
+    /// Replaces the history with images of the new size, which resets it
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let extent = self.history[0].extent();
+        if (extent.width, extent.height) != (width, height) {
+            self.history = create_history(&self.device, width, height);
+            self.has_history = false;
+        }
+    }
+
+    /// Blends `current` into the history and returns the result, which is left in [`vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL`]
+    /// and stays valid until the next resolve
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state, the frame that last used `frame_index` must have finished,
+    /// and `current` must be as big as the history and have been created with [`vk::ImageUsageFlags::SAMPLED`]
+    pub unsafe fn cmd_resolve(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        current: &Image<'_>,
+    ) -> &Image<'allocator> {
+        let _label = unsafe {
+            self.device
+                .cmd_label(command_buffer, c"Temporal Resolve", [0.3, 0.7, 0.9, 1.0])
+        };
+
+        let write_index = self.write_index;
+        let [history_a, history_b] = &self.history;
+        let (written, read) = match write_index {
+            0 => (history_a, history_b),
+            _ => (history_b, history_a),
+        };
+
+        let descriptor_set = self.descriptor_sets[frame_index];
+        let image_infos = [current, read].map(|image| {
+            vk::DescriptorImageInfo::default()
+                .image_view(image.view())
+                .sampler(self.sampler.handle())
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        });
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_infos);
+        unsafe { self.device.update_descriptor_sets(&[write], &[]) };
+
+        unsafe {
+            current.cmd_transition(command_buffer, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+            read.cmd_transition(command_buffer, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+            written.cmd_transition(command_buffer, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+            cmd_begin_full_screen_pass(
+                &self.device,
+                command_buffer,
+                written.view(),
+                vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: written.extent(),
+                },
+                &self.pipeline,
+                &self.pipeline_layout,
+                descriptor_set,
+            );
+            self.pipeline_layout.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout.push_constant_stages(),
+                0,
+                &PushConstants {
+                    history_offset: self.history_offset,
+                    history_weight: self.history_weight,
+                    has_history: self.has_history as u32,
+                },
+            );
+            self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            self.device.cmd_end_rendering(command_buffer);
+        }
+
+        self.write_index = 1 - write_index;
+        self.has_history = true;
+        self.frame_number += 1;
+        &self.history[write_index]
+    }
+}
+
+impl Drop for TemporalAntiAliasing<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::DescriptorPool(self.descriptor_pool),
+            );
+        }
+    }
+}
+
+fn create_history<'allocator>(
+    device: &Arc<Device<'allocator>>,
+    width: u32,
+    height: u32,
+) -> [Image<'allocator>; 2] {
+    [0, 1].map(|index| {
+        Image::new(
+            device.clone(),
+            &format!("TAA History {index}"),
+            HDR_FORMAT,
+            width,
+            height,
+            1,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            true,
+            Some(HIGH_MEMORY_PRIORITY),
+        )
+    })
+}
+
+/// The `index`th number of the Halton sequence in `base`, which spreads out evenly in `0..1` however many are taken
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
@@ -0,0 +1,261 @@
+use crate::{Device, make_subresource_range};
+use ash::vk;
+
+/// How an image is used on one side of a barrier, each one has the layout, stages and accesses it needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageUsage {
+    /// The contents don't matter and are discarded
+    Undefined,
+    /// A swapchain image that was just acquired, this waits for the acquire semaphore,
+    /// which the swapchain waits on at [`vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT`]
+    Acquired,
+    ColorAttachment,
+    DepthAttachment,
+    TransferSrc,
+    TransferDst,
+    FragmentShaderRead,
+    ComputeShaderRead,
+    /// Read by a shader in any stage
+    ShaderRead,
+    /// Read and written by a compute shader as a storage image
+    ComputeStorage,
+    Present,
+    /// Anything at all, only use this when the usage really isn't known
+    General,
+}
+
+impl ImageUsage {
+    pub fn layout(self) -> vk::ImageLayout {
+        match self {
+            ImageUsage::Undefined | ImageUsage::Acquired => vk::ImageLayout::UNDEFINED,
+            ImageUsage::ColorAttachment => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageUsage::DepthAttachment => vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+            ImageUsage::TransferSrc => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageUsage::TransferDst => vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageUsage::FragmentShaderRead
+            | ImageUsage::ComputeShaderRead
+            | ImageUsage::ShaderRead => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ImageUsage::ComputeStorage | ImageUsage::General => vk::ImageLayout::GENERAL,
+            ImageUsage::Present => vk::ImageLayout::PRESENT_SRC_KHR,
+        }
+    }
+
+    pub fn stages(self) -> vk::PipelineStageFlags2 {
+        match self {
+            ImageUsage::Undefined | ImageUsage::Present => vk::PipelineStageFlags2::NONE,
+            ImageUsage::Acquired | ImageUsage::ColorAttachment => {
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+            }
+            ImageUsage::DepthAttachment => {
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS
+            }
+            ImageUsage::TransferSrc | ImageUsage::TransferDst => {
+                vk::PipelineStageFlags2::ALL_TRANSFER
+            }
+            ImageUsage::FragmentShaderRead => vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            ImageUsage::ComputeShaderRead | ImageUsage::ComputeStorage => {
+                vk::PipelineStageFlags2::COMPUTE_SHADER
+            }
+            ImageUsage::ShaderRead => {
+                vk::PipelineStageFlags2::VERTEX_SHADER
+                    | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags2::COMPUTE_SHADER
+            }
+            ImageUsage::General => vk::PipelineStageFlags2::ALL_COMMANDS,
+        }
+    }
+
+    pub fn access(self) -> vk::AccessFlags2 {
+        match self {
+            ImageUsage::Undefined | ImageUsage::Acquired | ImageUsage::Present => {
+                vk::AccessFlags2::NONE
+            }
+            ImageUsage::ColorAttachment => {
+                vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+            }
+            ImageUsage::DepthAttachment => {
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+            }
+            ImageUsage::TransferSrc => vk::AccessFlags2::TRANSFER_READ,
+            ImageUsage::TransferDst => vk::AccessFlags2::TRANSFER_WRITE,
+            ImageUsage::FragmentShaderRead
+            | ImageUsage::ComputeShaderRead
+            | ImageUsage::ShaderRead => vk::AccessFlags2::SHADER_SAMPLED_READ,
+            ImageUsage::ComputeStorage => {
+                vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE
+            }
+            ImageUsage::General => vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+        }
+    }
+
+    /// Only writes have to be made available, earlier reads just need the execution dependency
+    pub fn write_access(self) -> vk::AccessFlags2 {
+        write_access(self.access())
+    }
+
+    /// The usage an image in `layout` most likely had, `None` for layouts that are used in too many ways to tell
+    pub fn from_layout(layout: vk::ImageLayout) -> Option<Self> {
+        Some(match layout {
+            vk::ImageLayout::UNDEFINED => ImageUsage::Undefined,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => ImageUsage::ColorAttachment,
+            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL => ImageUsage::DepthAttachment,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL => ImageUsage::TransferSrc,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL => ImageUsage::TransferDst,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => ImageUsage::ShaderRead,
+            vk::ImageLayout::PRESENT_SRC_KHR => ImageUsage::Present,
+            _ => return None,
+        })
+    }
+}
+
+/// How a buffer is used on one side of a barrier, like [`ImageUsage`] but without layouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferUsage {
+    TransferSrc,
+    TransferDst,
+    VertexRead,
+    IndexRead,
+    IndirectRead,
+    /// Read as a uniform buffer by a shader in any stage
+    UniformRead,
+    /// Read as a storage buffer or through its device address by a shader in any stage
+    ShaderRead,
+    /// Read and written by a compute shader as a storage buffer or through its device address
+    ComputeStorage,
+    HostRead,
+    HostWrite,
+    /// Anything at all, only use this when the usage really isn't known
+    General,
+}
+
+impl BufferUsage {
+    pub fn stages(self) -> vk::PipelineStageFlags2 {
+        let shader_stages = vk::PipelineStageFlags2::VERTEX_SHADER
+            | vk::PipelineStageFlags2::FRAGMENT_SHADER
+            | vk::PipelineStageFlags2::COMPUTE_SHADER;
+        match self {
+            BufferUsage::TransferSrc | BufferUsage::TransferDst => {
+                vk::PipelineStageFlags2::ALL_TRANSFER
+            }
+            BufferUsage::VertexRead => vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT,
+            BufferUsage::IndexRead => vk::PipelineStageFlags2::INDEX_INPUT,
+            BufferUsage::IndirectRead => vk::PipelineStageFlags2::DRAW_INDIRECT,
+            BufferUsage::UniformRead | BufferUsage::ShaderRead => shader_stages,
+            BufferUsage::ComputeStorage => vk::PipelineStageFlags2::COMPUTE_SHADER,
+            BufferUsage::HostRead | BufferUsage::HostWrite => vk::PipelineStageFlags2::HOST,
+            BufferUsage::General => vk::PipelineStageFlags2::ALL_COMMANDS,
+        }
+    }
+
+    pub fn access(self) -> vk::AccessFlags2 {
+        match self {
+            BufferUsage::TransferSrc => vk::AccessFlags2::TRANSFER_READ,
+            BufferUsage::TransferDst => vk::AccessFlags2::TRANSFER_WRITE,
+            BufferUsage::VertexRead => vk::AccessFlags2::VERTEX_ATTRIBUTE_READ,
+            BufferUsage::IndexRead => vk::AccessFlags2::INDEX_READ,
+            BufferUsage::IndirectRead => vk::AccessFlags2::INDIRECT_COMMAND_READ,
+            BufferUsage::UniformRead => vk::AccessFlags2::UNIFORM_READ,
+            BufferUsage::ShaderRead => vk::AccessFlags2::SHADER_STORAGE_READ,
+            BufferUsage::ComputeStorage => {
+                vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE
+            }
+            BufferUsage::HostRead => vk::AccessFlags2::HOST_READ,
+            BufferUsage::HostWrite => vk::AccessFlags2::HOST_WRITE,
+            BufferUsage::General => vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+        }
+    }
+
+    /// See [`ImageUsage::write_access`]
+    pub fn write_access(self) -> vk::AccessFlags2 {
+        write_access(self.access())
+    }
+}
+
+fn write_access(access: vk::AccessFlags2) -> vk::AccessFlags2 {
+    access
+        & (vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+            | vk::AccessFlags2::TRANSFER_WRITE
+            | vk::AccessFlags2::SHADER_STORAGE_WRITE
+            | vk::AccessFlags2::HOST_WRITE
+            | vk::AccessFlags2::MEMORY_WRITE)
+}
+
+/// Collects image and buffer barriers with stage and access masks limited to what each side actually uses,
+/// so unrelated work isn't serialized like it is with [`crate::transition_image`], and records them all at once
+#[derive(Default)]
+pub struct BarrierBuilder<'a> {
+    image_barriers: Vec<vk::ImageMemoryBarrier2<'a>>,
+    buffer_barriers: Vec<vk::BufferMemoryBarrier2<'a>>,
+}
+
+impl BarrierBuilder<'_> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for `from` on every mip level and layer of `image` to finish before `to`, moving between their layouts
+    pub fn image(
+        self,
+        image: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+        from: ImageUsage,
+        to: ImageUsage,
+    ) -> Self {
+        self.image_range(image, make_subresource_range(aspect_mask), from, to)
+    }
+
+    pub fn image_range(
+        mut self,
+        image: vk::Image,
+        subresource_range: vk::ImageSubresourceRange,
+        from: ImageUsage,
+        to: ImageUsage,
+    ) -> Self {
+        self.image_barriers.push(
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(from.stages())
+                .src_access_mask(from.write_access())
+                .dst_stage_mask(to.stages())
+                .dst_access_mask(to.access())
+                .old_layout(from.layout())
+                .new_layout(to.layout())
+                .subresource_range(subresource_range)
+                .image(image),
+        );
+        self
+    }
+
+    /// Waits for `from` on all of `buffer` to finish before `to`
+    pub fn buffer(mut self, buffer: vk::Buffer, from: BufferUsage, to: BufferUsage) -> Self {
+        self.buffer_barriers.push(
+            vk::BufferMemoryBarrier2::default()
+                .src_stage_mask(from.stages())
+                .src_access_mask(from.write_access())
+                .dst_stage_mask(to.stages())
+                .dst_access_mask(to.access())
+                .buffer(buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE),
+        );
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.image_barriers.is_empty() && self.buffer_barriers.is_empty()
+    }
+
+    /// # Safety
+    /// See [`Device::cmd_pipeline_barrier2`], every image must be in the layout of its `from` usage
+    pub unsafe fn record(&self, device: &Device<'_>, command_buffer: vk::CommandBuffer) {
+        if self.is_empty() {
+            return;
+        }
+        let dependency_info = vk::DependencyInfo::default()
+            .image_memory_barriers(&self.image_barriers)
+            .buffer_memory_barriers(&self.buffer_barriers);
+        unsafe { device.cmd_pipeline_barrier2(command_buffer, &dependency_info) };
+    }
+}
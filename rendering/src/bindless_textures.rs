@@ -0,0 +1,250 @@
+use crate::{Device, DeviceFeature, Instance, ResourceToDestroy};
+use ash::vk;
+use scope_guard::scope_guard;
+use std::{collections::VecDeque, sync::Arc};
+
+/// One big, variable count, combined image sampler array at binding 0 of a single descriptor set,
+/// textures are referenced by the `u32` handle [`BindlessTextures::register`] returns
+///
+/// In slang the set is declared as `[[vk::binding(0, SET)]] Sampler2D textures[];`
+/// and [`BindlessTextures::set_layout`] must be at `SET` in the [`PipelineLayout`](crate::PipelineLayout)
+///
+/// Requires [`DeviceFeature::BindlessTextures`]
+pub struct BindlessTextures<'allocator> {
+    device: Arc<Device<'allocator>>,
+    set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    capacity: u32,
+    next_slot: u32,
+    free_slots: Vec<u32>,
+    /// Unregistered slots and the timeline counter the frames that could use them finish at, in submission order
+    retired_slots: VecDeque<(u64, u32)>,
+}
+
+impl<'allocator> BindlessTextures<'allocator> {
+    /// `capacity` can be at most [`BindlessTextures::max_capacity`]
+    pub fn new(device: Arc<Device<'allocator>>, name: &str, capacity: u32) -> Self {
+        assert!(
+            device
+                .capabilities()
+                .has_feature(DeviceFeature::BindlessTextures),
+            "'{name}' requires {:?}",
+            DeviceFeature::BindlessTextures,
+        );
+        let max_capacity = Self::max_capacity(&device);
+        assert!(
+            capacity <= max_capacity,
+            "'{name}' can't hold {capacity} textures, this device supports at most {max_capacity}",
+        );
+
+        let binding_flags = [vk::DescriptorBindingFlags::PARTIALLY_BOUND
+            | vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT
+            | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING];
+        let mut binding_flags_create_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags);
+        let bindings = [vk::DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(capacity)
+            .stage_flags(vk::ShaderStageFlags::ALL)];
+        let set_layout_create_info = vk::DescriptorSetLayoutCreateInfo::default()
+            .flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+            .bindings(&bindings)
+            .push_next(&mut binding_flags_create_info);
+        let set_layout = scope_guard!(
+            |set_layout| unsafe {
+                device.destroy_descriptor_set_layout(set_layout, device.allocator())
+            },
+            unsafe {
+                device.create_descriptor_set_layout(&set_layout_create_info, device.allocator())
+            }
+            .unwrap()
+        );
+
+        let pool_sizes = [vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(capacity)];
+        let pool_create_info = vk::DescriptorPoolCreateInfo::default()
+            .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+            .max_sets(1)
+            .pool_sizes(&pool_sizes);
+        let descriptor_pool = scope_guard!(
+            |descriptor_pool| unsafe {
+                device.destroy_descriptor_pool(descriptor_pool, device.allocator())
+            },
+            unsafe { device.create_descriptor_pool(&pool_create_info, device.allocator()) }
+                .unwrap()
+        );
+
+        let descriptor_counts = [capacity];
+        let mut variable_count_allocate_info =
+            vk::DescriptorSetVariableDescriptorCountAllocateInfo::default()
+                .descriptor_counts(&descriptor_counts);
+        let allocate_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(*descriptor_pool)
+            .set_layouts(core::slice::from_ref(&*set_layout))
+            .push_next(&mut variable_count_allocate_info);
+        let descriptor_set = unsafe { device.allocate_descriptor_sets(&allocate_info) }.unwrap()[0];
+
+        device.track_resource(*set_layout, &format!("{name} Set Layout"));
+        device.track_resource(*descriptor_pool, &format!("{name} Descriptor Pool"));
+
+        Self {
+            set_layout: set_layout.into_inner(),
+            descriptor_pool: descriptor_pool.into_inner(),
+            descriptor_set,
+            capacity,
+            next_slot: 0,
+            free_slots: vec![],
+            retired_slots: VecDeque::new(),
+            device,
+        }
+    }
+
+    /// The most textures the device can have in an update after bind descriptor set,
+    /// which every combined image sampler counts against as both a sampler and a sampled image
+    pub fn max_capacity(device: &Device<'_>) -> u32 {
+        let mut descriptor_indexing_properties =
+            vk::PhysicalDeviceDescriptorIndexingProperties::default();
+        let mut properties2 =
+            vk::PhysicalDeviceProperties2::default().push_next(&mut descriptor_indexing_properties);
+        unsafe {
+            device
+                .instance()
+                .get_physical_device_properties2(device.physical_device(), &mut properties2);
+        }
+        let properties = descriptor_indexing_properties;
+        [
+            properties.max_descriptor_set_update_after_bind_sampled_images,
+            properties.max_descriptor_set_update_after_bind_samplers,
+            properties.max_per_stage_descriptor_update_after_bind_sampled_images,
+            properties.max_per_stage_descriptor_update_after_bind_samplers,
+        ]
+        .into_iter()
+        .min()
+        .unwrap()
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn set_layout(&self) -> vk::DescriptorSetLayout {
+        self.set_layout
+    }
+
+    pub fn descriptor_set(&self) -> vk::DescriptorSet {
+        self.descriptor_set
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Writes the texture into a free slot and returns its index into the array, `None` when every slot is in use
+    ///
+    /// Slots are only reused once the frames submitted before they were unregistered have finished
+    ///
+    /// # Safety
+    /// `image_view` and `sampler` must stay valid until the handle is passed to [`BindlessTextures::unregister`]
+    /// and the frames using it have finished, `image_view` must be in `image_layout` whenever a shader samples it
+    pub unsafe fn register(
+        &mut self,
+        image_view: vk::ImageView,
+        sampler: vk::Sampler,
+        image_layout: vk::ImageLayout,
+    ) -> Option<u32> {
+        let completed_counter = self.device.completed_timeline_counter();
+        while let Some((_, slot)) = self
+            .retired_slots
+            .pop_front_if(|&mut (counter, _)| counter <= completed_counter)
+        {
+            self.free_slots.push(slot);
+        }
+
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None if self.next_slot < self.capacity => {
+                self.next_slot += 1;
+                self.next_slot - 1
+            }
+            None => return None,
+        };
+
+        let image_info = vk::DescriptorImageInfo::default()
+            .image_view(image_view)
+            .sampler(sampler)
+            .image_layout(image_layout);
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(slot)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(core::slice::from_ref(&image_info));
+        unsafe { self.device.update_descriptor_sets(&[write], &[]) };
+
+        Some(slot)
+    }
+
+    /// Frees the slot once the frames that have already been submitted finish,
+    /// shaders must not sample `handle` in frames submitted after this
+    pub fn unregister(&mut self, handle: u32) {
+        debug_assert!(handle < self.next_slot, "{handle} was never registered");
+        debug_assert!(
+            !self.free_slots.contains(&handle)
+                && !self.retired_slots.iter().any(|&(_, slot)| slot == handle),
+            "{handle} was unregistered twice"
+        );
+        self.retired_slots
+            .push_back((self.device.current_timeline_counter(), handle));
+    }
+
+    /// Binds the texture array as `set`
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state and `layout` must have [`BindlessTextures::set_layout`] at `set`
+    pub unsafe fn cmd_bind(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        layout: vk::PipelineLayout,
+        set: u32,
+    ) {
+        unsafe {
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                bind_point,
+                layout,
+                set,
+                &[self.descriptor_set],
+                &[],
+            );
+        }
+    }
+}
+
+impl Drop for BindlessTextures<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            let counter = self.device.current_timeline_counter();
+            self.device.schedule_destroy_resource(
+                counter,
+                ResourceToDestroy::DescriptorPool(self.descriptor_pool),
+            );
+            self.device.schedule_destroy_resource(
+                counter,
+                ResourceToDestroy::DescriptorSetLayout(self.set_layout),
+            );
+        }
+    }
+}
@@ -10,10 +10,13 @@ use std::{mem::ManuallyDrop, ptr::NonNull, sync::Arc};
 pub struct Buffer<'allocator> {
     device: Arc<Device<'allocator>>,
     buffer: vk::Buffer,
+    usage: vk::BufferUsageFlags,
     allocation: ManuallyDrop<Allocation>,
 }
 
 impl<'allocator> Buffer<'allocator> {
+    /// `priority` is only applied to dedicated allocations, as shared memory blocks can only have a single priority,
+    /// and only with [`DeviceFeature::MemoryPriority`](crate::DeviceFeature::MemoryPriority), see [`Device::set_memory_priority`]
     pub fn new(
         device: Arc<Device<'allocator>>,
         name: &str,
@@ -21,6 +24,7 @@ impl<'allocator> Buffer<'allocator> {
         size: u64,
         usage: vk::BufferUsageFlags,
         dedicated_allocation: bool,
+        priority: Option<f32>,
     ) -> Self {
         let buffer_create_info = vk::BufferCreateInfo::default()
             .size(size)
@@ -57,8 +61,15 @@ impl<'allocator> Buffer<'allocator> {
         unsafe { device.bind_buffer_memory(*buffer, allocation.memory(), allocation.offset()) }
             .unwrap();
 
+        device.track_resource(*buffer, name);
+
+        unsafe {
+            device.apply_memory_priority(name, allocation.memory(), dedicated_allocation, priority);
+        }
+
         Self {
             buffer: buffer.into_inner(),
+            usage,
             allocation: ManuallyDrop::new(allocation.into_inner()),
             device,
         }
@@ -80,6 +91,10 @@ impl<'allocator> Buffer<'allocator> {
         self.buffer
     }
 
+    pub fn usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+
     pub fn memory(&self) -> vk::DeviceMemory {
         unsafe { self.allocation.memory() }
     }
@@ -95,6 +110,11 @@ impl<'allocator> Buffer<'allocator> {
     /// # Safety
     /// This buffer must have been created with [vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS]
     pub unsafe fn device_address(&self) -> vk::DeviceAddress {
+        debug_assert!(
+            self.usage
+                .contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS),
+            "buffers need SHADER_DEVICE_ADDRESS usage to have a device address",
+        );
         let device_address_info = vk::BufferDeviceAddressInfo::default().buffer(self.buffer);
         unsafe { self.device.get_buffer_device_address(&device_address_info) }
     }
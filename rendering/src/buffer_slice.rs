@@ -0,0 +1,156 @@
+use crate::{Buffer, GpuPtr};
+use ash::vk;
+use bytemuck::AnyBitPattern;
+use std::{fmt, marker::PhantomData, ops::Range};
+
+/// A range of `T`s in a [`Buffer`], so one buffer can hold several arrays that are each passed to shaders on their own
+pub struct BufferSlice<'a, 'allocator, T> {
+    buffer: &'a Buffer<'allocator>,
+    /// In bytes from the start of the buffer
+    offset: u64,
+    len: u64,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, 'allocator, T> BufferSlice<'a, 'allocator, T> {
+    pub fn buffer(&self) -> &'a Buffer<'allocator> {
+        self.buffer
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer.handle()
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The number of `T`s
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// In bytes
+    pub fn size(&self) -> u64 {
+        self.len * size_of::<T>() as u64
+    }
+
+    /// A range of `T`s within this slice
+    pub fn slice(&self, range: Range<u64>) -> Self {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "{range:?} is out of bounds of a slice of {} elements",
+            self.len,
+        );
+        Self {
+            buffer: self.buffer,
+            offset: self.offset + range.start * size_of::<T>() as u64,
+            len: range.end - range.start,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Reinterprets the bytes of this slice as `U`s, the size must be a multiple of `U`'s
+    pub fn cast<U>(&self) -> BufferSlice<'a, 'allocator, U> {
+        assert!(
+            self.size().is_multiple_of(size_of::<U>() as u64),
+            "{} bytes can't be split into {}s",
+            self.size(),
+            std::any::type_name::<U>(),
+        );
+        BufferSlice::new(
+            self.buffer,
+            self.offset,
+            self.size() / size_of::<U>() as u64,
+        )
+    }
+
+    pub fn descriptor_info(&self) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo::default()
+            .buffer(self.buffer.handle())
+            .offset(self.offset)
+            .range(self.size())
+    }
+
+    /// # Safety
+    /// The buffer must have been created with [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`]
+    pub unsafe fn device_address(&self) -> vk::DeviceAddress {
+        unsafe { self.buffer.device_address() + self.offset }
+    }
+
+    /// The device address of the first element, for pointer fields of push constants
+    ///
+    /// # Safety
+    /// See [`BufferSlice::device_address`]
+    pub unsafe fn device_ptr(&self) -> GpuPtr<T> {
+        unsafe { GpuPtr::from_address(self.device_address()) }
+    }
+
+    /// # Safety
+    /// The GPU must not be writing to this part of the buffer, to avoid data races
+    pub unsafe fn get_mapped(&self) -> Option<&'a [T]>
+    where
+        T: AnyBitPattern,
+    {
+        let bytes = unsafe { self.buffer.get_mapped() }?;
+        Some(bytemuck::cast_slice(
+            &bytes[self.offset as usize..][..self.size() as usize],
+        ))
+    }
+
+    fn new(buffer: &'a Buffer<'allocator>, offset: u64, len: u64) -> Self {
+        debug_assert!(
+            offset.is_multiple_of(align_of::<T>() as u64),
+            "a {} can't start at offset {offset}",
+            std::any::type_name::<T>(),
+        );
+        Self {
+            buffer,
+            offset,
+            len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+// implemented by hand so none of these need `T` to implement them too
+impl<T> Clone for BufferSlice<'_, '_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for BufferSlice<'_, '_, T> {}
+
+impl<T> fmt::Debug for BufferSlice<'_, '_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferSlice")
+            .field("buffer", &self.buffer.handle())
+            .field("offset", &self.offset)
+            .field("len", &self.len)
+            .field("element", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<'allocator> Buffer<'allocator> {
+    /// `range` is in `T`s from the start of the buffer
+    pub fn slice<T>(&self, range: Range<u64>) -> BufferSlice<'_, 'allocator, T> {
+        BufferSlice::new(self, 0, self.size() / size_of::<T>() as u64).slice(range)
+    }
+
+    /// `offset` is in bytes and has to be aligned for `T`, for when the arrays in the buffer aren't all the same type
+    pub fn slice_at<T>(&self, offset: u64, len: u64) -> BufferSlice<'_, 'allocator, T> {
+        assert!(
+            offset + len * size_of::<T>() as u64 <= self.size(),
+            "{len} {}s at offset {offset} don't fit in a {} byte buffer",
+            std::any::type_name::<T>(),
+            self.size(),
+        );
+        BufferSlice::new(self, offset, len)
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{Device, Instance, ResourceToDestroy};
+use ash::vk;
+use std::sync::Arc;
+
+/// A command pool for the graphics queue, its command buffers are freed along with it
+/// once everything submitted before it was dropped has finished
+pub struct CommandPool<'allocator> {
+    device: Arc<Device<'allocator>>,
+    command_pool: vk::CommandPool,
+}
+
+impl<'allocator> CommandPool<'allocator> {
+    pub fn new(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        flags: vk::CommandPoolCreateFlags,
+    ) -> Self {
+        let command_pool_create_info = vk::CommandPoolCreateInfo::default()
+            .flags(flags)
+            .queue_family_index(device.graphics_queue_family_index());
+        let command_pool =
+            unsafe { device.create_command_pool(&command_pool_create_info, device.allocator()) }
+                .unwrap();
+        device.track_resource(command_pool, name);
+        Self {
+            device,
+            command_pool,
+        }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::CommandPool {
+        self.command_pool
+    }
+
+    /// Primary command buffers, which stay valid until the pool is dropped
+    pub fn allocate_command_buffers(&self, count: u32) -> Vec<vk::CommandBuffer> {
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(count);
+        unsafe {
+            self.device
+                .allocate_command_buffers(&command_buffer_allocate_info)
+        }
+        .unwrap()
+    }
+
+    pub fn allocate_command_buffer(&self) -> vk::CommandBuffer {
+        self.allocate_command_buffers(1)[0]
+    }
+
+    /// Resets every command buffer allocated from this pool
+    ///
+    /// # Safety
+    /// None of the command buffers may be used by a submission that hasn't finished
+    pub unsafe fn reset(&self) {
+        unsafe {
+            self.device
+                .reset_command_pool(self.command_pool, vk::CommandPoolResetFlags::empty())
+        }
+        .unwrap();
+    }
+}
+
+impl Drop for CommandPool<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::CommandPool(self.command_pool),
+            );
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{Buffer, Image};
+use ash::vk;
+
+impl Image<'_> {
+    /// The size of `mip_level`
+    pub fn level_extent(&self, mip_level: u32) -> vk::Extent2D {
+        assert!(
+            mip_level < self.mip_levels(),
+            "mip level {mip_level} is past the {} levels of the image",
+            self.mip_levels(),
+        );
+        let extent = self.extent();
+        vk::Extent2D {
+            width: (extent.width >> mip_level).max(1),
+            height: (extent.height >> mip_level).max(1),
+        }
+    }
+
+    /// The size in bytes of `mip_level` of every layer, tightly packed, `None` when [`format_texel_size`] doesn't know the format
+    pub fn level_size(&self, mip_level: u32) -> Option<u64> {
+        let extent = self.level_extent(mip_level);
+        let texel_size = format_texel_size(self.format())?;
+        Some(
+            u64::from(extent.width)
+                * u64::from(extent.height)
+                * u64::from(self.array_layers())
+                * texel_size,
+        )
+    }
+
+    fn level_subresource(&self, mip_level: u32) -> vk::ImageSubresourceLayers {
+        vk::ImageSubresourceLayers::default()
+            .aspect_mask(self.aspect())
+            .mip_level(mip_level)
+            .base_array_layer(0)
+            .layer_count(self.array_layers())
+    }
+
+    fn buffer_copy(
+        &self,
+        buffer: &Buffer<'_>,
+        buffer_offset: u64,
+        mip_level: u32,
+    ) -> vk::BufferImageCopy {
+        assert!(
+            self.aspect().as_raw().is_power_of_two(),
+            "buffer copies of {:?} have to be done an aspect at a time",
+            self.format(),
+        );
+        let extent = self.level_extent(mip_level);
+        if let Some(level_size) = self.level_size(mip_level) {
+            assert!(
+                buffer_offset + level_size <= buffer.size(),
+                "mip level {mip_level} needs {level_size} bytes at offset {buffer_offset} of a {} byte buffer",
+                buffer.size(),
+            );
+        }
+        vk::BufferImageCopy::default()
+            .buffer_offset(buffer_offset)
+            .image_subresource(self.level_subresource(mip_level))
+            .image_extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+    }
+
+    /// Copies a whole mip level of every layer from tightly packed texels in `buffer` at `buffer_offset`,
+    /// the image is left in [`vk::ImageLayout::TRANSFER_DST_OPTIMAL`]
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state, the image must have been created with [`vk::ImageUsageFlags::TRANSFER_DST`],
+    /// and `buffer` must have been created with [`vk::BufferUsageFlags::TRANSFER_SRC`]
+    pub unsafe fn cmd_copy_from_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: &Buffer<'_>,
+        buffer_offset: u64,
+        mip_level: u32,
+    ) {
+        let region = self.buffer_copy(buffer, buffer_offset, mip_level);
+        unsafe {
+            self.cmd_transition(command_buffer, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+            self.device().cmd_copy_buffer_to_image(
+                command_buffer,
+                buffer.handle(),
+                self.handle(),
+                self.layout(),
+                &[region],
+            );
+        }
+    }
+
+    /// Copies a whole mip level of every layer into `buffer` at `buffer_offset` as tightly packed texels, for reading back,
+    /// the image is left in [`vk::ImageLayout::TRANSFER_SRC_OPTIMAL`]
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state, the image must have been created with [`vk::ImageUsageFlags::TRANSFER_SRC`],
+    /// and `buffer` must have been created with [`vk::BufferUsageFlags::TRANSFER_DST`]
+    pub unsafe fn cmd_copy_to_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: &Buffer<'_>,
+        buffer_offset: u64,
+        mip_level: u32,
+    ) {
+        let region = self.buffer_copy(buffer, buffer_offset, mip_level);
+        unsafe {
+            self.cmd_transition(command_buffer, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+            self.device().cmd_copy_image_to_buffer(
+                command_buffer,
+                self.handle(),
+                self.layout(),
+                buffer.handle(),
+                &[region],
+            );
+        }
+    }
+
+    /// Scales `src_level` of every layer of `src` over the whole of `mip_level` of this image,
+    /// leaving `src` in [`vk::ImageLayout::TRANSFER_SRC_OPTIMAL`] and this image in [`vk::ImageLayout::TRANSFER_DST_OPTIMAL`]
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state, `src` must have been created with [`vk::ImageUsageFlags::TRANSFER_SRC`]
+    /// and this image with [`vk::ImageUsageFlags::TRANSFER_DST`]
+    pub unsafe fn cmd_blit_from(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        mip_level: u32,
+        src: &Image<'_>,
+        src_level: u32,
+        filter: vk::Filter,
+    ) {
+        assert_ne!(
+            self.handle(),
+            src.handle(),
+            "blitting between levels of the same image needs per level layouts, see Image::generate_mipmaps",
+        );
+        assert_eq!(
+            self.array_layers(),
+            src.array_layers(),
+            "blits need the same number of layers on both sides",
+        );
+        assert_eq!(
+            self.aspect(),
+            src.aspect(),
+            "blits can't convert between color and depth/stencil",
+        );
+        let src_features = self
+            .device()
+            .format_features(src.format(), vk::ImageTiling::OPTIMAL);
+        let dst_features = self
+            .device()
+            .format_features(self.format(), vk::ImageTiling::OPTIMAL);
+        assert!(
+            src_features.contains(vk::FormatFeatureFlags::BLIT_SRC)
+                && dst_features.contains(vk::FormatFeatureFlags::BLIT_DST),
+            "{:?} can't be blitted to {:?}",
+            src.format(),
+            self.format(),
+        );
+        assert!(
+            filter != vk::Filter::LINEAR
+                || src_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR),
+            "{:?} doesn't support linear filtering",
+            src.format(),
+        );
+
+        let corner = |extent: vk::Extent2D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: 1,
+        };
+        let blit = vk::ImageBlit::default()
+            .src_subresource(src.level_subresource(src_level))
+            .src_offsets([vk::Offset3D::default(), corner(src.level_extent(src_level))])
+            .dst_subresource(self.level_subresource(mip_level))
+            .dst_offsets([
+                vk::Offset3D::default(),
+                corner(self.level_extent(mip_level)),
+            ]);
+        unsafe {
+            src.cmd_transition(command_buffer, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+            self.cmd_transition(command_buffer, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+            self.device().cmd_blit_image(
+                command_buffer,
+                src.handle(),
+                src.layout(),
+                self.handle(),
+                self.layout(),
+                &[blit],
+                filter,
+            );
+        }
+    }
+}
+
+/// The size in bytes of one texel of the common uncompressed formats, `None` for block compressed and unusual formats
+pub fn format_texel_size(format: vk::Format) -> Option<u64> {
+    Some(match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB | vk::Format::R8_UINT | vk::Format::S8_UINT => 1,
+        vk::Format::R8G8_UNORM
+        | vk::Format::R8G8_SRGB
+        | vk::Format::R16_SFLOAT
+        | vk::Format::R16_UNORM
+        | vk::Format::R16_UINT
+        | vk::Format::D16_UNORM => 2,
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::B10G11R11_UFLOAT_PACK32
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_SFLOAT
+        | vk::Format::R32_UINT
+        | vk::Format::X8_D24_UNORM_PACK32
+        | vk::Format::D32_SFLOAT => 4,
+        vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R32G32_SFLOAT
+        | vk::Format::R32G32_UINT => 8,
+        vk::Format::R32G32B32A32_SFLOAT | vk::Format::R32G32B32A32_UINT => 16,
+        _ => return None,
+    })
+}
+
+/// The size in bytes and the extent in texels of one texel block of `format`, uncompressed formats have 1x1 blocks
+///
+/// Covers the formats of [`format_texel_size`], the 3 component 8 bit formats, and the BC, ETC2, EAC and ASTC formats
+pub fn format_block(format: vk::Format) -> Option<(u64, vk::Extent2D)> {
+    let extent = |width, height| vk::Extent2D { width, height };
+    if let Some(texel_size) = format_texel_size(format) {
+        return Some((texel_size, extent(1, 1)));
+    }
+    Some(match format {
+        vk::Format::R8G8B8_UNORM
+        | vk::Format::R8G8B8_SRGB
+        | vk::Format::B8G8R8_UNORM
+        | vk::Format::B8G8R8_SRGB => (3, extent(1, 1)),
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC4_SNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK
+        | vk::Format::EAC_R11_UNORM_BLOCK
+        | vk::Format::EAC_R11_SNORM_BLOCK => (8, extent(4, 4)),
+        vk::Format::BC2_UNORM_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC6H_UFLOAT_BLOCK
+        | vk::Format::BC6H_SFLOAT_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK
+        | vk::Format::EAC_R11G11_UNORM_BLOCK
+        | vk::Format::EAC_R11G11_SNORM_BLOCK
+        | vk::Format::ASTC_4X4_UNORM_BLOCK
+        | vk::Format::ASTC_4X4_SRGB_BLOCK => (16, extent(4, 4)),
+        // every ASTC block is 16 bytes, only the number of texels it covers changes
+        vk::Format::ASTC_5X4_UNORM_BLOCK | vk::Format::ASTC_5X4_SRGB_BLOCK => (16, extent(5, 4)),
+        vk::Format::ASTC_5X5_UNORM_BLOCK | vk::Format::ASTC_5X5_SRGB_BLOCK => (16, extent(5, 5)),
+        vk::Format::ASTC_6X5_UNORM_BLOCK | vk::Format::ASTC_6X5_SRGB_BLOCK => (16, extent(6, 5)),
+        vk::Format::ASTC_6X6_UNORM_BLOCK | vk::Format::ASTC_6X6_SRGB_BLOCK => (16, extent(6, 6)),
+        vk::Format::ASTC_8X5_UNORM_BLOCK | vk::Format::ASTC_8X5_SRGB_BLOCK => (16, extent(8, 5)),
+        vk::Format::ASTC_8X6_UNORM_BLOCK | vk::Format::ASTC_8X6_SRGB_BLOCK => (16, extent(8, 6)),
+        vk::Format::ASTC_8X8_UNORM_BLOCK | vk::Format::ASTC_8X8_SRGB_BLOCK => (16, extent(8, 8)),
+        vk::Format::ASTC_10X5_UNORM_BLOCK | vk::Format::ASTC_10X5_SRGB_BLOCK => (16, extent(10, 5)),
+        vk::Format::ASTC_10X6_UNORM_BLOCK | vk::Format::ASTC_10X6_SRGB_BLOCK => (16, extent(10, 6)),
+        vk::Format::ASTC_10X8_UNORM_BLOCK | vk::Format::ASTC_10X8_SRGB_BLOCK => (16, extent(10, 8)),
+        vk::Format::ASTC_10X10_UNORM_BLOCK | vk::Format::ASTC_10X10_SRGB_BLOCK => {
+            (16, extent(10, 10))
+        }
+        vk::Format::ASTC_12X10_UNORM_BLOCK | vk::Format::ASTC_12X10_SRGB_BLOCK => {
+            (16, extent(12, 10))
+        }
+        vk::Format::ASTC_12X12_UNORM_BLOCK | vk::Format::ASTC_12X12_SRGB_BLOCK => {
+            (16, extent(12, 12))
+        }
+        _ => return None,
+    })
+}
+
+/// The size in bytes of a `width` by `height` level of `format`, counting partially covered blocks in full,
+/// `None` when [`format_block`] doesn't know the format
+pub fn format_level_size(format: vk::Format, width: u32, height: u32) -> Option<u64> {
+    let (block_size, block_extent) = format_block(format)?;
+    let blocks_wide = width.div_ceil(block_extent.width) as u64;
+    let blocks_high = height.div_ceil(block_extent.height) as u64;
+    Some(blocks_wide * blocks_high * block_size)
+}
@@ -0,0 +1,165 @@
+use crate::{DeviceCapabilities, DeviceFeature};
+use ash::vk;
+use gpu_allocator::{
+    MemoryLocation,
+    vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator},
+};
+use parking_lot::Mutex;
+use std::ffi::{CStr, CString};
+
+/// Records which labelled passes the gpu got to, so a lost device can be blamed on a pass,
+/// see [`DeviceFeature::DiagnosticCheckpoints`] and [`DeviceFeature::BufferMarker`]
+pub(crate) struct CrashDiagnostics {
+    checkpoint_funcs: Option<ash::nv::device_diagnostic_checkpoints::Device>,
+    buffer_marker: Option<BufferMarker>,
+    /// Every label name seen so far, markers are indices into this plus one so 0 means no pass
+    names: Mutex<Vec<CString>>,
+}
+
+struct BufferMarker {
+    funcs: ash::amd::buffer_marker::Device,
+    /// The marker of the latest pass to start, then the marker of the latest pass that everything before has finished for
+    buffer: vk::Buffer,
+    allocation: Allocation,
+}
+
+impl CrashDiagnostics {
+    /// `None` when neither feature is enabled
+    pub fn new(
+        instance: &ash::Instance,
+        device: &ash::Device,
+        capabilities: &DeviceCapabilities,
+        allocator: &mut Allocator,
+        allocation_callbacks: Option<&vk::AllocationCallbacks<'_>>,
+    ) -> Option<Self> {
+        let checkpoint_funcs = capabilities
+            .has_feature(DeviceFeature::DiagnosticCheckpoints)
+            .then(|| ash::nv::device_diagnostic_checkpoints::Device::new(instance, device));
+
+        let buffer_marker = capabilities
+            .has_feature(DeviceFeature::BufferMarker)
+            .then(|| {
+                let buffer_create_info = vk::BufferCreateInfo::default()
+                    .size(2 * size_of::<u32>() as u64)
+                    .usage(vk::BufferUsageFlags::TRANSFER_DST)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE);
+                let buffer =
+                    unsafe { device.create_buffer(&buffer_create_info, allocation_callbacks) }
+                        .unwrap();
+                let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+                let mut allocation = allocator
+                    .allocate(&AllocationCreateDesc {
+                        name: "Crash Diagnostics Buffer Marker",
+                        requirements,
+                        location: MemoryLocation::GpuToCpu,
+                        linear: true,
+                        allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+                    })
+                    .unwrap();
+                unsafe {
+                    device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset())
+                }
+                .unwrap();
+                // so a device lost before the first pass doesn't report garbage
+                allocation.mapped_slice_mut().unwrap().fill(0);
+                BufferMarker {
+                    funcs: ash::amd::buffer_marker::Device::new(instance, device),
+                    buffer,
+                    allocation,
+                }
+            });
+
+        (checkpoint_funcs.is_some() || buffer_marker.is_some()).then(|| Self {
+            checkpoint_funcs,
+            buffer_marker,
+            names: Mutex::new(vec![]),
+        })
+    }
+
+    /// # Safety
+    /// `command_buffer` must be in the recording state
+    pub unsafe fn cmd_checkpoint(&self, command_buffer: vk::CommandBuffer, name: &CStr) {
+        let marker = {
+            let mut names = self.names.lock();
+            let index = match names.iter().position(|known| known.as_c_str() == name) {
+                Some(index) => index,
+                None => {
+                    names.push(name.to_owned());
+                    names.len() - 1
+                }
+            };
+            index as u32 + 1
+        };
+
+        if let Some(funcs) = &self.checkpoint_funcs {
+            // the marker is only ever compared, never dereferenced
+            unsafe { funcs.cmd_set_checkpoint(command_buffer, marker as usize as *const _) };
+        }
+        if let Some(buffer_marker) = &self.buffer_marker {
+            for (stage, offset) in [
+                (vk::PipelineStageFlags::TOP_OF_PIPE, 0),
+                (
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    size_of::<u32>() as u64,
+                ),
+            ] {
+                unsafe {
+                    buffer_marker.funcs.cmd_write_buffer_marker(
+                        command_buffer,
+                        stage,
+                        buffer_marker.buffer,
+                        offset,
+                        marker,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Logs the last passes the gpu got to on `queue`
+    pub fn report(&self, queue: vk::Queue) {
+        let names = self.names.lock();
+        let name = |marker: u32| match marker.checked_sub(1) {
+            Some(index) => names
+                .get(index as usize)
+                .map_or("<unknown>".into(), |name| name.to_string_lossy()),
+            None => "<none>".into(),
+        };
+
+        if let Some(funcs) = &self.checkpoint_funcs {
+            let mut checkpoints = vec![
+                vk::CheckpointDataNV::default();
+                unsafe { funcs.get_queue_checkpoint_data_len(queue) }
+            ];
+            unsafe { funcs.get_queue_checkpoint_data(queue, &mut checkpoints) };
+            for checkpoint in checkpoints {
+                tracing::error!(
+                    stage = ?checkpoint.stage,
+                    "Last checkpoint reached: '{}'",
+                    name(checkpoint.p_checkpoint_marker as usize as u32)
+                );
+            }
+        }
+        if let Some(buffer_marker) = &self.buffer_marker {
+            let markers: &[u32] =
+                bytemuck::cast_slice(buffer_marker.allocation.mapped_slice().unwrap());
+            tracing::error!(
+                "Last pass started: '{}', last pass everything before had finished: '{}'",
+                name(markers[0]),
+                name(markers[1]),
+            );
+        }
+    }
+
+    pub fn destroy(
+        self,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+        allocation_callbacks: Option<&vk::AllocationCallbacks<'_>>,
+    ) {
+        if let Some(buffer_marker) = self.buffer_marker {
+            unsafe { device.destroy_buffer(buffer_marker.buffer, allocation_callbacks) };
+            allocator.free(buffer_marker.allocation).unwrap();
+        }
+    }
+}
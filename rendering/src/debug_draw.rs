@@ -0,0 +1,359 @@
+use crate::{
+    BarrierBuilder, Buffer, Device, GpuPtr, GraphicsPipelineBuilder, ImageUsage, Instance,
+    PerFrame, Pipeline, PipelineLayout, Shader, transition_image,
+};
+use ash::vk;
+use bytemuck::NoUninit;
+use gpu_allocator::MemoryLocation;
+use std::{f32::consts::TAU, sync::Arc};
+
+const DEBUG_DRAW_SPIRV: &[u32] =
+    crate::include_spirv!(concat!(env!("OUT_DIR"), "/shaders/debug_draw.spv"));
+
+/// How many line segments a circle is drawn with
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// How long the head of an arrow is compared to the arrow
+const ARROW_HEAD_FRACTION: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, NoUninit)]
+#[repr(C)]
+pub struct DebugVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Copy, NoUninit)]
+#[repr(C)]
+struct PushConstants {
+    vertices: GpuPtr<DebugVertex>,
+    center: [f32; 2],
+    half_extent: [f32; 2],
+}
+
+/// Collects lines and shapes over a frame and draws them all at once over whatever was rendered,
+/// for seeing what the code is doing without touching the real shaders
+///
+/// Everything is in the units of [`DebugDraw::set_view`] with y up, colors are linear and blended by their alpha,
+/// lines are always 1 pixel wide
+pub struct DebugDraw<'allocator> {
+    device: Arc<Device<'allocator>>,
+    pipeline_layout: PipelineLayout<'allocator>,
+    triangle_pipeline: Pipeline<'allocator>,
+    line_pipeline: Pipeline<'allocator>,
+    color_format: vk::Format,
+    /// One per frame in flight, so a buffer is only rewritten once the frame that last used it has finished,
+    /// grown when a frame draws more than fits
+    vertex_buffers: PerFrame<Option<Buffer<'allocator>>>,
+    triangle_vertices: Vec<DebugVertex>,
+    line_vertices: Vec<DebugVertex>,
+    center: [f32; 2],
+    half_extent: [f32; 2],
+}
+
+impl<'allocator> DebugDraw<'allocator> {
+    /// `color_format` is the format of the images [`DebugDraw::cmd_draw`] draws to, usually [`Swapchain::render_format`](crate::Swapchain::render_format)
+    pub fn new(device: Arc<Device<'allocator>>, color_format: vk::Format) -> Self {
+        let shader = unsafe { Shader::new(device.clone(), "Debug Draw Shader", DEBUG_DRAW_SPIRV) };
+        let pipeline_layout =
+            PipelineLayout::from_shaders(device.clone(), "Debug Draw Pipeline Layout", &[&shader]);
+        let (triangle_pipeline, line_pipeline) =
+            create_pipelines(&device, &pipeline_layout, &shader, color_format);
+
+        Self {
+            pipeline_layout,
+            triangle_pipeline,
+            line_pipeline,
+            color_format,
+            vertex_buffers: PerFrame::default(),
+            triangle_vertices: vec![],
+            line_vertices: vec![],
+            center: [0.0, 0.0],
+            half_extent: [1.0, 1.0],
+            device,
+        }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn color_format(&self) -> vk::Format {
+        self.color_format
+    }
+
+    /// Rebuilds the pipelines for a new format, like after [`Swapchain::set_tonemap`](crate::Swapchain::set_tonemap)
+    pub fn set_color_format(&mut self, color_format: vk::Format) {
+        if color_format == self.color_format {
+            return;
+        }
+        let shader =
+            unsafe { Shader::new(self.device.clone(), "Debug Draw Shader", DEBUG_DRAW_SPIRV) };
+        (self.triangle_pipeline, self.line_pipeline) =
+            create_pipelines(&self.device, &self.pipeline_layout, &shader, color_format);
+        self.color_format = color_format;
+    }
+
+    /// `center` is shown in the middle of the render area, and the edges of the render area are `half_extent` away from it,
+    /// the default shows `-1..1` on both axes like clip space
+    pub fn set_view(&mut self, center: [f32; 2], half_extent: [f32; 2]) {
+        self.center = center;
+        self.half_extent = half_extent;
+    }
+
+    /// Whether nothing has been drawn since the last [`DebugDraw::cmd_draw`]
+    pub fn is_empty(&self) -> bool {
+        self.triangle_vertices.is_empty() && self.line_vertices.is_empty()
+    }
+
+    /// Throws away everything drawn since the last [`DebugDraw::cmd_draw`]
+    pub fn clear(&mut self) {
+        self.triangle_vertices.clear();
+        self.line_vertices.clear();
+    }
+
+    pub fn line(&mut self, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+        self.line_vertices.extend([
+            DebugVertex {
+                position: from,
+                color,
+            },
+            DebugVertex {
+                position: to,
+                color,
+            },
+        ]);
+    }
+
+    /// Lines between each point and the next, like a path
+    pub fn polyline(&mut self, points: &[[f32; 2]], color: [f32; 4]) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], color);
+        }
+    }
+
+    /// A line with a head at `to`
+    pub fn arrow(&mut self, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+        self.line(from, to, color);
+
+        let back = [
+            (from[0] - to[0]) * ARROW_HEAD_FRACTION,
+            (from[1] - to[1]) * ARROW_HEAD_FRACTION,
+        ];
+        let side = [-back[1] * 0.5, back[0] * 0.5];
+        self.line(
+            to,
+            [to[0] + back[0] + side[0], to[1] + back[1] + side[1]],
+            color,
+        );
+        self.line(
+            to,
+            [to[0] + back[0] - side[0], to[1] + back[1] - side[1]],
+            color,
+        );
+    }
+
+    pub fn circle(&mut self, center: [f32; 2], radius: f32, color: [f32; 4]) {
+        let points: [[f32; 2]; CIRCLE_SEGMENTS + 1] = std::array::from_fn(|index| {
+            let angle = index as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+            [
+                center[0] + angle.cos() * radius,
+                center[1] + angle.sin() * radius,
+            ]
+        });
+        self.polyline(&points, color);
+    }
+
+    /// The outline of a closed polygon
+    pub fn polygon_outline(&mut self, points: &[[f32; 2]], color: [f32; 4]) {
+        self.polyline(points, color);
+        if let [first, .., last] = points {
+            self.line(*last, *first, color);
+        }
+    }
+
+    /// A filled polygon, which has to be convex as it is drawn as a fan from the first point
+    pub fn polygon(&mut self, points: &[[f32; 2]], color: [f32; 4]) {
+        let Some((&first, rest)) = points.split_first() else {
+            return;
+        };
+        for pair in rest.windows(2) {
+            self.triangle_vertices
+                .extend([first, pair[0], pair[1]].map(|position| DebugVertex { position, color }));
+        }
+    }
+
+    /// Draws everything drawn since the last call over `render_area` of `image`, then clears it,
+    /// `image` is left in [`vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL`]
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state, the frame that last used `frame_index` must have finished,
+    /// and `image` must be in `image_layout` and be created with [`vk::ImageUsageFlags::COLOR_ATTACHMENT`] and [`DebugDraw::color_format`]
+    pub unsafe fn cmd_draw(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        image: vk::Image,
+        image_view: vk::ImageView,
+        image_layout: &mut vk::ImageLayout,
+        render_area: vk::Rect2D,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+
+        let _label = unsafe {
+            self.device
+                .cmd_label(command_buffer, c"Debug Draw", [1.0, 0.2, 0.8, 1.0])
+        };
+
+        let triangle_count = self.triangle_vertices.len() as u32;
+        let line_count = self.line_vertices.len() as u32;
+        let size = (size_of::<DebugVertex>()
+            * (self.triangle_vertices.len() + self.line_vertices.len())) as u64;
+        let vertex_buffer = &mut self.vertex_buffers[frame_index];
+        if vertex_buffer
+            .as_ref()
+            .is_none_or(|vertex_buffer| vertex_buffer.size() < size)
+        {
+            // the old buffer is destroyed once the frames using it have finished
+            *vertex_buffer = Some(Buffer::new(
+                self.device.clone(),
+                "Debug Draw Vertex Buffer",
+                MemoryLocation::CpuToGpu,
+                size.next_power_of_two(),
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                false,
+                None,
+            ));
+        }
+        let vertex_buffer = vertex_buffer.as_mut().unwrap();
+        {
+            let mapped = unsafe { vertex_buffer.get_mapped_mut() }.unwrap();
+            let triangle_bytes: &[u8] = bytemuck::cast_slice(&self.triangle_vertices);
+            let line_bytes: &[u8] = bytemuck::cast_slice(&self.line_vertices);
+            mapped[..triangle_bytes.len()].copy_from_slice(triangle_bytes);
+            mapped[triangle_bytes.len()..][..line_bytes.len()].copy_from_slice(line_bytes);
+        }
+        self.triangle_vertices.clear();
+        self.line_vertices.clear();
+
+        match ImageUsage::from_layout(*image_layout).map(|usage| match usage {
+            ImageUsage::Undefined => ImageUsage::Acquired,
+            usage => usage,
+        }) {
+            Some(usage) => unsafe {
+                BarrierBuilder::new()
+                    .image(
+                        image,
+                        vk::ImageAspectFlags::COLOR,
+                        usage,
+                        ImageUsage::ColorAttachment,
+                    )
+                    .record(&self.device, command_buffer);
+            },
+            None => unsafe {
+                transition_image(
+                    &self.device,
+                    command_buffer,
+                    image,
+                    image_layout,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                );
+            },
+        }
+        *image_layout = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+
+        let color_attachment_info = vk::RenderingAttachmentInfo::default()
+            .image_view(image_view)
+            .image_layout(*image_layout)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE);
+        let rendering_info = vk::RenderingInfo::default()
+            .render_area(render_area)
+            .layer_count(1)
+            .color_attachments(core::slice::from_ref(&color_attachment_info));
+
+        // flipped so y is up
+        let viewport = vk::Viewport::default()
+            .x(render_area.offset.x as _)
+            .y((render_area.offset.y as u32 + render_area.extent.height) as _)
+            .width(render_area.extent.width as _)
+            .height(-(render_area.extent.height as f32))
+            .max_depth(1.0);
+
+        unsafe {
+            self.device
+                .cmd_begin_rendering(command_buffer, &rendering_info);
+            self.device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            self.device
+                .cmd_set_scissor(command_buffer, 0, &[render_area]);
+
+            let push_constants = PushConstants {
+                vertices: vertex_buffer.device_ptr(),
+                center: self.center,
+                half_extent: self.half_extent,
+            };
+            for (pipeline, vertex_count, first_vertex) in [
+                (&self.triangle_pipeline, triangle_count, 0),
+                (&self.line_pipeline, line_count, triangle_count),
+            ] {
+                if vertex_count == 0 {
+                    continue;
+                }
+                self.device.cmd_bind_pipeline(
+                    command_buffer,
+                    pipeline.bind_point(),
+                    pipeline.handle(),
+                );
+                self.pipeline_layout.cmd_push_constants(
+                    command_buffer,
+                    self.pipeline_layout.push_constant_stages(),
+                    0,
+                    &push_constants,
+                );
+                self.device
+                    .cmd_draw(command_buffer, vertex_count, 1, first_vertex, 0);
+            }
+
+            self.device.cmd_end_rendering(command_buffer);
+        }
+    }
+}
+
+fn create_pipelines<'allocator>(
+    device: &Arc<Device<'allocator>>,
+    pipeline_layout: &PipelineLayout<'allocator>,
+    shader: &Shader<'allocator>,
+    color_format: vk::Format,
+) -> (Pipeline<'allocator>, Pipeline<'allocator>) {
+    let blend = vk::PipelineColorBlendAttachmentState::default()
+        .blend_enable(true)
+        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ONE)
+        .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .color_write_mask(vk::ColorComponentFlags::RGBA);
+    let builder = || {
+        GraphicsPipelineBuilder::new(pipeline_layout, color_format)
+            .vertex(shader, c"vertex")
+            .fragment(shader, c"fragment")
+            .blend(blend)
+    };
+    (
+        builder().build(device.clone(), "Debug Draw Triangle Pipeline"),
+        builder()
+            .topology(vk::PrimitiveTopology::LINE_LIST)
+            .build(device.clone(), "Debug Draw Line Pipeline"),
+    )
+}
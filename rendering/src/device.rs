@@ -1,11 +1,23 @@
-use crate::Instance;
-use ash::vk::{self, Handle};
+use crate::{
+    DeviceCapabilities, DeviceConfig, DeviceFeature, EnabledFeatures, ExternalSemaphoreHandle,
+    Instance,
+    crash_diagnostics::CrashDiagnostics,
+    external_memory::ExternalMemoryFuncs,
+    external_semaphore::{ExternalSemaphoreFuncs, SEMAPHORE_HANDLE_TYPE, export_semaphore},
+    pipeline_cache::PipelineCache,
+    render_pass_fallback::{ImageViewInfo, RenderPassFallback},
+};
+use ash::{
+    prelude::VkResult,
+    vk::{self, Handle},
+};
 use gpu_allocator::vulkan::{Allocation, Allocator, AllocatorCreateDesc};
 use parking_lot::Mutex;
 use scope_guard::scope_guard;
 use std::{
-    collections::VecDeque,
-    ffi::CStr,
+    backtrace::Backtrace,
+    collections::{HashMap, VecDeque},
+    ffi::{CStr, CString},
     mem::ManuallyDrop,
     ops::Deref,
     sync::{
@@ -14,14 +26,75 @@ use std::{
     },
 };
 
+/// The [`Device::set_memory_priority`] of resources every frame needs, like render targets,
+/// which stall the whole frame when they are demoted to system memory
+pub const HIGH_MEMORY_PRIORITY: f32 = 1.0;
+
 pub enum ResourceToDestroy {
     ImageView(vk::ImageView),
     Semaphore(vk::Semaphore),
     Fence(vk::Fence),
     Buffer(vk::Buffer, Allocation),
+    Image(vk::Image, Allocation),
+    /// An image and the view of it, the view is destroyed first
+    ImageViewWithImage(vk::ImageView, vk::Image, Allocation),
+    /// Like [`ResourceToDestroy::ImageViewWithImage`], for an image with memory from outside of the allocator, see [`Image::new_exportable`](crate::Image::new_exportable)
+    ImageViewWithExternalImage(vk::ImageView, vk::Image, vk::DeviceMemory),
+    Sampler(vk::Sampler),
+    CommandPool(vk::CommandPool),
     ShaderModule(vk::ShaderModule),
+    DescriptorSetLayout(vk::DescriptorSetLayout),
+    DescriptorPool(vk::DescriptorPool),
     PipelineLayout(vk::PipelineLayout),
     Pipeline(vk::Pipeline),
+    QueryPool(vk::QueryPool),
+    /// Runs arbitrary cleanup, for resources that don't have a variant or that need more than a destroy call
+    Custom(Box<dyn FnOnce(&Device<'_>) + Send>),
+}
+
+impl ResourceToDestroy {
+    /// The handle being destroyed, `None` for [`ResourceToDestroy::Custom`]
+    pub fn object(&self) -> Option<(vk::ObjectType, u64)> {
+        fn object<H: Handle>(handle: H) -> (vk::ObjectType, u64) {
+            (H::TYPE, handle.as_raw())
+        }
+
+        Some(match self {
+            ResourceToDestroy::ImageView(image_view) => object(*image_view),
+            ResourceToDestroy::Semaphore(semaphore) => object(*semaphore),
+            ResourceToDestroy::Fence(fence) => object(*fence),
+            ResourceToDestroy::Buffer(buffer, _) => object(*buffer),
+            ResourceToDestroy::Image(image, _) => object(*image),
+            ResourceToDestroy::ImageViewWithImage(image_view, _, _)
+            | ResourceToDestroy::ImageViewWithExternalImage(image_view, _, _) => {
+                object(*image_view)
+            }
+            ResourceToDestroy::Sampler(sampler) => object(*sampler),
+            ResourceToDestroy::CommandPool(command_pool) => object(*command_pool),
+            ResourceToDestroy::ShaderModule(shader_module) => object(*shader_module),
+            ResourceToDestroy::DescriptorSetLayout(descriptor_set_layout) => {
+                object(*descriptor_set_layout)
+            }
+            ResourceToDestroy::DescriptorPool(descriptor_pool) => object(*descriptor_pool),
+            ResourceToDestroy::PipelineLayout(pipeline_layout) => object(*pipeline_layout),
+            ResourceToDestroy::Pipeline(pipeline) => object(*pipeline),
+            ResourceToDestroy::QueryPool(query_pool) => object(*query_pool),
+            ResourceToDestroy::Custom(_) => return None,
+        })
+    }
+}
+
+/// A submission for [`Device::batch_submit`], owning its arrays so it can wait until the batch is flushed
+#[derive(Debug, Clone, Default)]
+pub struct BatchedSubmit {
+    pub wait_semaphore_infos: Vec<vk::SemaphoreSubmitInfo<'static>>,
+    pub command_buffer_infos: Vec<vk::CommandBufferSubmitInfo<'static>>,
+    pub signal_semaphore_infos: Vec<vk::SemaphoreSubmitInfo<'static>>,
+}
+
+struct TrackedResource {
+    name: String,
+    backtrace: Backtrace,
 }
 
 pub struct Device<'allocator> {
@@ -30,19 +103,45 @@ pub struct Device<'allocator> {
     device: ash::Device,
     graphics_queue_family_index: u32,
     graphics_queue: Mutex<vk::Queue>,
+    api_version: u32,
+    capabilities: DeviceCapabilities,
+    limits: vk::PhysicalDeviceLimits,
+    /// Only loaded on vulkan 1.2 devices, where synchronization2 and dynamic rendering are extensions
+    synchronization2_funcs: Option<ash::khr::synchronization2::Device>,
+    dynamic_rendering_funcs: Option<ash::khr::dynamic_rendering::Device>,
+    /// Only on vulkan 1.2 devices without `VK_KHR_dynamic_rendering`, see [`Device::cmd_begin_rendering`]
+    render_pass_fallback: Option<RenderPassFallback>,
+    pageable_device_local_memory_funcs: Option<ash::ext::pageable_device_local_memory::Device>,
+    /// Only loaded when the instance has `VK_EXT_debug_utils` enabled, which it does whenever the extension is available
+    debug_utils_funcs: Option<ash::ext::debug_utils::Device>,
+    /// Only loaded with [`DeviceFeature::ExternalMemory`]
+    external_memory_funcs: Option<ExternalMemoryFuncs>,
+    /// Only loaded with [`DeviceFeature::ExternalSemaphore`]
+    external_semaphore_funcs: Option<ExternalSemaphoreFuncs>,
+    /// Only with [`DeviceFeature::DiagnosticCheckpoints`] or [`DeviceFeature::BufferMarker`]
+    crash_diagnostics: Option<CrashDiagnostics>,
     timeline_counter: AtomicU64,
     timeline_semaphore: vk::Semaphore,
+    pipeline_cache: PipelineCache,
     resources_to_destroy: Mutex<VecDeque<(u64, ResourceToDestroy)>>,
+    /// Only taken while the graphics queue is locked, so batches are submitted in the order they were queued
+    batched_submits: Mutex<Vec<BatchedSubmit>>,
+    /// Counters from [`Device::reserve_host_counter`] that haven't been signaled yet
+    host_counters: Mutex<Vec<u64>>,
+    tracked_resources: Mutex<HashMap<(vk::ObjectType, u64), TrackedResource>>,
     allocator: ManuallyDrop<Mutex<Allocator>>,
 }
 
 impl<'allocator> Device<'allocator> {
-    pub fn new(instance: Arc<Instance<'allocator>>) -> Self {
-        let required_version = vk::API_VERSION_1_3;
-        let required_extensions: [&CStr; _] =
-            [vk::KHR_SWAPCHAIN_NAME, vk::EXT_SWAPCHAIN_MAINTENANCE1_NAME];
+    pub fn new(instance: Arc<Instance<'allocator>>, config: DeviceConfig) -> Self {
+        let required_version = vk::API_VERSION_1_2;
+        let vulkan12_extensions: [&CStr; _] = [vk::KHR_SYNCHRONIZATION2_NAME];
+        let mut required_extensions: Vec<&CStr> = vec![vk::KHR_SWAPCHAIN_NAME];
+        required_extensions.extend(config.required_extensions.iter().map(CString::as_c_str));
+        for &feature in &config.required_features {
+            required_extensions.extend(feature.extensions());
+        }
 
-        let device_features = vk::PhysicalDeviceFeatures::default();
         let mut device_features11 = vk::PhysicalDeviceVulkan11Features::default();
         let mut device_features12 = vk::PhysicalDeviceVulkan12Features::default()
             .shader_int8(true)
@@ -55,32 +154,42 @@ impl<'allocator> Device<'allocator> {
         let mut device_features13 = vk::PhysicalDeviceVulkan13Features::default()
             .synchronization2(true)
             .dynamic_rendering(true);
+        let mut synchronization2_features =
+            vk::PhysicalDeviceSynchronization2Features::default().synchronization2(true);
+        let mut dynamic_rendering_features =
+            vk::PhysicalDeviceDynamicRenderingFeatures::default().dynamic_rendering(true);
 
-        let mut swapchain_maintenance1_features =
-            vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT::default()
-                .swapchain_maintenance1(true);
-
-        let mut device_features2 = vk::PhysicalDeviceFeatures2::default()
-            .push_next(&mut swapchain_maintenance1_features)
-            .push_next(&mut device_features13)
-            .push_next(&mut device_features12)
-            .push_next(&mut device_features11)
-            .features(device_features);
-
-        let (physical_device, graphics_queue_family_index) = {
+        let (
+            physical_device,
+            graphics_queue_family_index,
+            api_version,
+            capabilities,
+            render_pass_fallback,
+        ) = {
+            let _span = tracing::info_span!("select_physical_device").entered();
             let mut chosen_physical_device = vk::PhysicalDevice::null();
             let mut chosen_graphics_queue_family_index = vk::QUEUE_FAMILY_IGNORED;
+            let mut chosen_api_version = 0;
+            let mut chosen_capabilities = DeviceCapabilities::default();
+            let mut chosen_render_pass_fallback = false;
 
             let physical_devices = unsafe { instance.enumerate_physical_devices() }.unwrap();
             'search: for physical_device in physical_devices {
+                if config
+                    .physical_device
+                    .is_some_and(|chosen| chosen != physical_device)
+                {
+                    continue 'search;
+                }
+
                 let properties =
                     unsafe { instance.get_physical_device_properties(physical_device) };
 
                 let name = properties.device_name_as_c_str().unwrap().to_string_lossy();
-                println!("Checking physical device '{name}'");
+                let _span = tracing::debug_span!("physical_device", %name).entered();
 
                 if properties.api_version < required_version {
-                    println!(
+                    tracing::info!(
                         "Expected at least physical device version {}.{}.{}.{} but got version {}.{}.{}.{}, skipping this physical device",
                         vk::api_version_variant(required_version),
                         vk::api_version_major(required_version),
@@ -93,12 +202,28 @@ impl<'allocator> Device<'allocator> {
                     );
                     continue 'search;
                 }
+                let api_version = properties.api_version.min(vk::API_VERSION_1_3);
+                let vulkan12_extensions: &[&CStr] = if api_version < vk::API_VERSION_1_3 {
+                    &vulkan12_extensions
+                } else {
+                    &[]
+                };
 
+                let mut capabilities = DeviceCapabilities::default();
+                let mut render_pass_fallback = false;
                 {
                     let extensions =
                         unsafe { instance.enumerate_device_extension_properties(physical_device) }
                             .unwrap();
-                    'checks: for required_extension in required_extensions {
+                    let has_extension = |name: &CStr| {
+                        extensions
+                            .iter()
+                            .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+                    };
+
+                    'checks: for &required_extension in
+                        required_extensions.iter().chain(vulkan12_extensions)
+                    {
                         for extension in &extensions {
                             let Ok(extension) = extension.extension_name_as_c_str() else {
                                 continue;
@@ -109,11 +234,85 @@ impl<'allocator> Device<'allocator> {
                         }
 
                         let required_extension_name = required_extension.to_string_lossy();
-                        println!(
+                        tracing::info!(
                             "Unable to find vulkan device extension '{required_extension_name}', skipping this physical device"
                         );
                         continue 'search;
                     }
+
+                    if !vulkan12_extensions.is_empty() {
+                        let mut synchronization2_features =
+                            vk::PhysicalDeviceSynchronization2Features::default();
+                        let mut dynamic_rendering_features =
+                            vk::PhysicalDeviceDynamicRenderingFeatures::default();
+                        let mut features12 = vk::PhysicalDeviceVulkan12Features::default();
+                        let mut features = vk::PhysicalDeviceFeatures2::default()
+                            .push_next(&mut synchronization2_features)
+                            .push_next(&mut dynamic_rendering_features)
+                            .push_next(&mut features12);
+                        unsafe {
+                            instance.get_physical_device_features2(physical_device, &mut features)
+                        };
+                        if synchronization2_features.synchronization2 != vk::TRUE {
+                            tracing::info!(
+                                "Vulkan 1.2 device without synchronization2 support, skipping this physical device"
+                            );
+                            continue 'search;
+                        }
+                        if !has_extension(vk::KHR_DYNAMIC_RENDERING_NAME)
+                            || dynamic_rendering_features.dynamic_rendering != vk::TRUE
+                        {
+                            if features12.imageless_framebuffer != vk::TRUE {
+                                tracing::info!(
+                                    "Vulkan 1.2 device without dynamic rendering or imageless framebuffer support, skipping this physical device"
+                                );
+                                continue 'search;
+                            }
+                            tracing::info!(
+                                "Vulkan 1.2 device without dynamic rendering support, falling back to render passes"
+                            );
+                            render_pass_fallback = true;
+                        }
+                    }
+
+                    for &required_feature in &config.required_features {
+                        if capabilities.has_feature(required_feature) {
+                            continue;
+                        }
+                        if !required_feature.is_supported(&instance, physical_device) {
+                            tracing::info!(
+                                "Required feature {required_feature:?} is not supported, skipping this physical device"
+                            );
+                            continue 'search;
+                        }
+                        capabilities.features.push(required_feature);
+                    }
+
+                    for &optional_feature in &config.optional_features {
+                        if capabilities.has_feature(optional_feature) {
+                            continue;
+                        }
+                        if optional_feature
+                            .extensions()
+                            .iter()
+                            .all(|&extension| has_extension(extension))
+                            && optional_feature.is_supported(&instance, physical_device)
+                        {
+                            capabilities.features.push(optional_feature);
+                        } else {
+                            tracing::debug!(
+                                "Optional feature {optional_feature:?} is not supported"
+                            );
+                        }
+                    }
+
+                    capabilities.extensions.extend(
+                        config
+                            .optional_extensions
+                            .iter()
+                            .filter(|extension| has_extension(extension))
+                            .cloned(),
+                    );
                 }
 
                 let mut graphics_queue_family_index = vk::QUEUE_FAMILY_IGNORED;
@@ -132,7 +331,7 @@ impl<'allocator> Device<'allocator> {
                     }
                 }
                 if graphics_queue_family_index == vk::QUEUE_FAMILY_IGNORED {
-                    println!(
+                    tracing::info!(
                         "Unable to find suitable graphics queue family, skipping this physical device"
                     );
                     continue 'search;
@@ -140,26 +339,90 @@ impl<'allocator> Device<'allocator> {
 
                 chosen_physical_device = physical_device;
                 chosen_graphics_queue_family_index = graphics_queue_family_index;
-                println!("Chose physical device '{name}'");
+                chosen_api_version = api_version;
+                chosen_capabilities = capabilities;
+                chosen_render_pass_fallback = render_pass_fallback;
+                tracing::info!(
+                    features = ?chosen_capabilities.features,
+                    "Chose physical device '{name}'"
+                );
                 break 'search;
             }
 
             if chosen_physical_device.is_null() {
                 panic!("Unable to find a suitable vulkan physical device");
             }
-            (chosen_physical_device, chosen_graphics_queue_family_index)
+            (
+                chosen_physical_device,
+                chosen_graphics_queue_family_index,
+                chosen_api_version,
+                chosen_capabilities,
+                chosen_render_pass_fallback,
+            )
         };
 
+        let mut capabilities = capabilities;
+        let mut enabled_features = EnabledFeatures::default();
+        let vulkan12 = api_version < vk::API_VERSION_1_3;
+        if vulkan12 {
+            required_extensions.extend(vulkan12_extensions);
+            if render_pass_fallback {
+                device_features12 = device_features12.imageless_framebuffer(true);
+            } else {
+                required_extensions.push(vk::KHR_DYNAMIC_RENDERING_NAME);
+            }
+        }
+        let mut enabled_extensions = required_extensions
+            .into_iter()
+            .map(CStr::to_owned)
+            .collect::<Vec<_>>();
+        for &feature in &capabilities.features {
+            enabled_features.enable(feature);
+            enabled_extensions.extend(
+                feature
+                    .extensions()
+                    .iter()
+                    .map(|&extension| extension.to_owned()),
+            );
+        }
+        enabled_extensions.append(&mut capabilities.extensions);
+        for extension in enabled_extensions {
+            if !capabilities.extensions.contains(&extension) {
+                capabilities.extensions.push(extension);
+            }
+        }
+
+        device_features12 = enabled_features.merge_into_vulkan12(device_features12);
+        let device_features = enabled_features.features;
+        let mut device_features2 =
+            enabled_features.push_onto(vk::PhysicalDeviceFeatures2::default());
+        if vulkan12 {
+            device_features2 = device_features2.push_next(&mut synchronization2_features);
+            if !render_pass_fallback {
+                device_features2 = device_features2.push_next(&mut dynamic_rendering_features);
+            }
+        } else {
+            device_features2 = device_features2.push_next(&mut device_features13);
+        }
+        let mut device_features2 = device_features2
+            .push_next(&mut device_features12)
+            .push_next(&mut device_features11)
+            .features(device_features);
+
         let graphics_queue_create_info = vk::DeviceQueueCreateInfo::default()
             .queue_family_index(graphics_queue_family_index)
             .queue_priorities(&[1.0]);
         let queue_create_infos = [graphics_queue_create_info];
 
-        let required_extension_ptrs = required_extensions.map(|extension| extension.as_ptr());
+        let enabled_extension_ptrs = capabilities
+            .extensions
+            .iter()
+            .map(|extension| extension.as_ptr())
+            .collect::<Vec<_>>();
         let device_create_info = vk::DeviceCreateInfo::default()
             .push_next(&mut device_features2)
             .queue_create_infos(&queue_create_infos)
-            .enabled_extension_names(&required_extension_ptrs);
+            .enabled_extension_names(&enabled_extension_ptrs);
 
         let device = unsafe {
             instance.create_device(physical_device, &device_create_info, instance.allocator())
@@ -169,13 +432,41 @@ impl<'allocator> Device<'allocator> {
 
         let graphics_queue = unsafe { device.get_device_queue(graphics_queue_family_index, 0) };
 
+        let synchronization2_funcs =
+            vulkan12.then(|| ash::khr::synchronization2::Device::new(&instance, &device));
+        let dynamic_rendering_funcs = (vulkan12 && !render_pass_fallback)
+            .then(|| ash::khr::dynamic_rendering::Device::new(&instance, &device));
+        let render_pass_fallback = render_pass_fallback.then(RenderPassFallback::default);
+
+        let pageable_device_local_memory_funcs = capabilities
+            .has_feature(DeviceFeature::MemoryPriority)
+            .then(|| ash::ext::pageable_device_local_memory::Device::new(&instance, &device));
+
+        let debug_utils_funcs = instance
+            .has_extension(vk::EXT_DEBUG_UTILS_NAME)
+            .then(|| ash::ext::debug_utils::Device::new(&instance, &device));
+
+        let external_memory_funcs = capabilities
+            .has_feature(DeviceFeature::ExternalMemory)
+            .then(|| ExternalMemoryFuncs::new(&instance, &device));
+        let external_semaphore_funcs = capabilities
+            .has_feature(DeviceFeature::ExternalSemaphore)
+            .then(|| ExternalSemaphoreFuncs::new(&instance, &device));
+
         let timeline_counter = 0;
 
         let mut timline_semaphore_create_info = vk::SemaphoreTypeCreateInfo::default()
             .semaphore_type(vk::SemaphoreType::TIMELINE)
             .initial_value(timeline_counter);
-        let timeline_semaphore_create_info =
+        let mut export_semaphore_create_info =
+            vk::ExportSemaphoreCreateInfo::default().handle_types(SEMAPHORE_HANDLE_TYPE);
+        let mut timeline_semaphore_create_info =
             vk::SemaphoreCreateInfo::default().push_next(&mut timline_semaphore_create_info);
+        // so other apis and processes can wait for frames to finish, see Device::export_timeline_semaphore
+        if capabilities.has_feature(DeviceFeature::ExternalSemaphore) {
+            timeline_semaphore_create_info =
+                timeline_semaphore_create_info.push_next(&mut export_semaphore_create_info);
+        }
 
         let timeline_semaphore = unsafe {
             device.create_semaphore(&timeline_semaphore_create_info, instance.allocator())
@@ -185,7 +476,17 @@ impl<'allocator> Device<'allocator> {
             device.destroy_semaphore(timeline_semaphore, instance.allocator())
         });
 
-        let allocator = Allocator::new(&AllocatorCreateDesc {
+        let pipeline_cache = PipelineCache::new(
+            &device,
+            instance.allocator(),
+            &unsafe { instance.get_physical_device_properties(physical_device) },
+            config.pipeline_cache_directory.as_deref(),
+        );
+        let cleanup = cleanup.stack(|()| unsafe {
+            device.destroy_pipeline_cache(pipeline_cache.handle, instance.allocator())
+        });
+
+        let mut allocator = Allocator::new(&AllocatorCreateDesc {
             instance: (**instance).clone(),
             device: device.clone(),
             physical_device,
@@ -195,6 +496,16 @@ impl<'allocator> Device<'allocator> {
         })
         .unwrap();
 
+        let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+
+        let crash_diagnostics = CrashDiagnostics::new(
+            &instance,
+            &device,
+            &capabilities,
+            &mut allocator,
+            instance.allocator(),
+        );
+
         cleanup.forget();
         Self {
             instance,
@@ -202,9 +513,24 @@ impl<'allocator> Device<'allocator> {
             device,
             graphics_queue_family_index,
             graphics_queue: Mutex::new(graphics_queue),
+            api_version,
+            capabilities,
+            limits,
+            synchronization2_funcs,
+            dynamic_rendering_funcs,
+            render_pass_fallback,
+            pageable_device_local_memory_funcs,
+            debug_utils_funcs,
+            external_memory_funcs,
+            external_semaphore_funcs,
+            crash_diagnostics,
             timeline_counter: AtomicU64::new(timeline_counter),
             timeline_semaphore,
+            pipeline_cache,
             resources_to_destroy: Mutex::new(VecDeque::new()),
+            batched_submits: Mutex::new(vec![]),
+            host_counters: Mutex::new(vec![]),
+            tracked_resources: Mutex::new(HashMap::new()),
             allocator: ManuallyDrop::new(Mutex::new(allocator)),
         }
     }
@@ -230,20 +556,577 @@ impl<'allocator> Device<'allocator> {
         f(*graphics_queue)
     }
 
+    /// The vulkan version used by this device, either 1.2 or 1.3
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
+    pub fn capabilities(&self) -> &DeviceCapabilities {
+        &self.capabilities
+    }
+
+    pub(crate) fn external_memory_funcs(&self) -> &ExternalMemoryFuncs {
+        self.external_memory_funcs
+            .as_ref()
+            .expect("memory can't be exported without DeviceFeature::ExternalMemory")
+    }
+
+    pub(crate) fn external_semaphore_funcs(&self) -> &ExternalSemaphoreFuncs {
+        self.external_semaphore_funcs
+            .as_ref()
+            .expect("semaphores can't be exported without DeviceFeature::ExternalSemaphore")
+    }
+
+    pub fn limits(&self) -> &vk::PhysicalDeviceLimits {
+        &self.limits
+    }
+
+    /// What `format` supports with `tiling`, buffer features are not included
+    pub fn format_features(
+        &self,
+        format: vk::Format,
+        tiling: vk::ImageTiling,
+    ) -> vk::FormatFeatureFlags {
+        let format_properties = unsafe {
+            self.instance
+                .get_physical_device_format_properties(self.physical_device, format)
+        };
+        match tiling {
+            vk::ImageTiling::LINEAR => format_properties.linear_tiling_features,
+            _ => format_properties.optimal_tiling_features,
+        }
+    }
+
+    /// The first of `candidates` that supports all of `features` with `tiling`, so callers can fall back
+    /// to formats that are guaranteed to exist instead of assuming e.g. `D32_SFLOAT` or `BC7` everywhere
+    pub fn find_supported_format(
+        &self,
+        candidates: &[vk::Format],
+        tiling: vk::ImageTiling,
+        features: vk::FormatFeatureFlags,
+    ) -> Option<vk::Format> {
+        candidates
+            .iter()
+            .copied()
+            .find(|&format| self.format_features(format, tiling).contains(features))
+    }
+
+    /// Whether a 2D optimally tiled image of `format` can be created with `usage`
+    pub fn supports_format_usage(&self, format: vk::Format, usage: vk::ImageUsageFlags) -> bool {
+        match unsafe {
+            self.instance.get_physical_device_image_format_properties(
+                self.physical_device,
+                format,
+                vk::ImageType::TYPE_2D,
+                vk::ImageTiling::OPTIMAL,
+                usage,
+                vk::ImageCreateFlags::empty(),
+            )
+        } {
+            Ok(_) => true,
+            Err(vk::Result::ERROR_FORMAT_NOT_SUPPORTED) => false,
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    /// The most precise depth format that can be rendered to and sampled, `D16_UNORM` always can be
+    pub fn depth_format(&self) -> vk::Format {
+        self.find_supported_format(
+            &[
+                vk::Format::D32_SFLOAT,
+                vk::Format::X8_D24_UNORM_PACK32,
+                vk::Format::D16_UNORM,
+            ],
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+        )
+        .unwrap()
+    }
+
+    /// Uses `VK_KHR_synchronization2` on vulkan 1.2 devices
+    ///
+    /// # Safety
+    /// See [`ash::Device::cmd_pipeline_barrier2`]
+    pub unsafe fn cmd_pipeline_barrier2(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        dependency_info: &vk::DependencyInfo<'_>,
+    ) {
+        match &self.synchronization2_funcs {
+            Some(funcs) => unsafe { funcs.cmd_pipeline_barrier2(command_buffer, dependency_info) },
+            None => unsafe {
+                self.device
+                    .cmd_pipeline_barrier2(command_buffer, dependency_info)
+            },
+        }
+    }
+
+    /// Uses `VK_KHR_synchronization2` on vulkan 1.2 devices
+    ///
+    /// # Safety
+    /// See [`ash::Device::queue_submit2`]
+    pub unsafe fn queue_submit2(
+        &self,
+        queue: vk::Queue,
+        submits: &[vk::SubmitInfo2<'_>],
+        fence: vk::Fence,
+    ) -> ash::prelude::VkResult<()> {
+        match &self.synchronization2_funcs {
+            Some(funcs) => unsafe { funcs.queue_submit2(queue, submits, fence) },
+            None => unsafe { self.device.queue_submit2(queue, submits, fence) },
+        }
+    }
+
+    /// Uses `VK_KHR_dynamic_rendering` on vulkan 1.2 devices,
+    /// or begins a render pass with an imageless framebuffer on 1.2 devices without it
+    ///
+    /// # Safety
+    /// See [`ash::Device::cmd_begin_rendering`], without dynamic rendering the image views have to be created by this crate
+    pub unsafe fn cmd_begin_rendering(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        rendering_info: &vk::RenderingInfo<'_>,
+    ) {
+        if let Some(fallback) = &self.render_pass_fallback {
+            unsafe {
+                fallback.cmd_begin_rendering(
+                    &self.device,
+                    self.allocator(),
+                    command_buffer,
+                    rendering_info,
+                );
+            }
+            return;
+        }
+        match &self.dynamic_rendering_funcs {
+            Some(funcs) => unsafe { funcs.cmd_begin_rendering(command_buffer, rendering_info) },
+            None => unsafe {
+                self.device
+                    .cmd_begin_rendering(command_buffer, rendering_info)
+            },
+        }
+    }
+
+    /// Uses `VK_KHR_dynamic_rendering` on vulkan 1.2 devices, or ends the render pass from [`Device::cmd_begin_rendering`] on 1.2 devices without it
+    ///
+    /// # Safety
+    /// See [`ash::Device::cmd_end_rendering`]
+    pub unsafe fn cmd_end_rendering(&self, command_buffer: vk::CommandBuffer) {
+        if self.render_pass_fallback.is_some() {
+            unsafe { self.device.cmd_end_render_pass(command_buffer) };
+            return;
+        }
+        match &self.dynamic_rendering_funcs {
+            Some(funcs) => unsafe { funcs.cmd_end_rendering(command_buffer) },
+            None => unsafe { self.device.cmd_end_rendering(command_buffer) },
+        }
+    }
+
+    /// Remembers what `image_view` was created from, so it can be rendered to without dynamic rendering
+    pub(crate) fn register_image_view(&self, image_view: vk::ImageView, info: ImageViewInfo) {
+        if let Some(fallback) = &self.render_pass_fallback {
+            fallback.register_image_view(image_view, info);
+        }
+    }
+
+    pub(crate) fn unregister_image_view(&self, image_view: vk::ImageView) {
+        if let Some(fallback) = &self.render_pass_fallback {
+            fallback.unregister_image_view(image_view);
+        }
+    }
+
+    /// The render pass graphics pipelines have to be created with when there is no dynamic rendering
+    pub(crate) fn compatible_render_pass(
+        &self,
+        color_formats: &[vk::Format],
+        depth_format: vk::Format,
+    ) -> Option<vk::RenderPass> {
+        self.render_pass_fallback.as_ref().map(|fallback| {
+            fallback.compatible_render_pass(
+                &self.device,
+                self.allocator(),
+                color_formats,
+                depth_format,
+            )
+        })
+    }
+
+    /// Opens a named region of commands that shows up in captures from tools like RenderDoc and Nsight,
+    /// regions can be nested and each one has to be closed with [`Device::cmd_end_label`] in the same command buffer
+    ///
+    /// Does nothing when the instance doesn't have `VK_EXT_debug_utils` enabled,
+    /// except for recording a checkpoint with [`DeviceFeature::DiagnosticCheckpoints`] or [`DeviceFeature::BufferMarker`]
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state
+    pub unsafe fn cmd_begin_label(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        name: &CStr,
+        color: [f32; 4],
+    ) {
+        if let Some(crash_diagnostics) = &self.crash_diagnostics {
+            unsafe { crash_diagnostics.cmd_checkpoint(command_buffer, name) };
+        }
+
+        let Some(funcs) = &self.debug_utils_funcs else {
+            return;
+        };
+        let label = vk::DebugUtilsLabelEXT::default()
+            .label_name(name)
+            .color(color);
+        unsafe { funcs.cmd_begin_debug_utils_label(command_buffer, &label) };
+    }
+
+    /// # Safety
+    /// `command_buffer` must be in the recording state with a region opened by [`Device::cmd_begin_label`]
+    pub unsafe fn cmd_end_label(&self, command_buffer: vk::CommandBuffer) {
+        let Some(funcs) = &self.debug_utils_funcs else {
+            return;
+        };
+        unsafe { funcs.cmd_end_debug_utils_label(command_buffer) };
+    }
+
+    /// Like [`Device::cmd_begin_label`], but the region is closed when the returned guard is dropped
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state, and still be when the guard is dropped
+    pub unsafe fn cmd_label(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        name: &CStr,
+        color: [f32; 4],
+    ) -> CommandLabel<'_, 'allocator> {
+        unsafe { self.cmd_begin_label(command_buffer, name, color) };
+        CommandLabel {
+            device: self,
+            command_buffer,
+        }
+    }
+
+    /// Passes `result` through, logging the last labels the gpu got to when it is [`vk::Result::ERROR_DEVICE_LOST`],
+    /// see [`DeviceFeature::DiagnosticCheckpoints`]
+    ///
+    /// Must not be called while the graphics queue is locked by [`Device::with_graphics_queue`]
+    pub fn report_device_lost<T>(&self, result: VkResult<T>) -> VkResult<T> {
+        if result.as_ref().err() == Some(&vk::Result::ERROR_DEVICE_LOST) {
+            tracing::error!("The device was lost");
+            match &self.crash_diagnostics {
+                Some(crash_diagnostics) => self.with_graphics_queue(|graphics_queue| {
+                    crash_diagnostics.report(graphics_queue);
+                }),
+                None => tracing::error!(
+                    "Enable DeviceFeature::DiagnosticCheckpoints or DeviceFeature::BufferMarker to find out where"
+                ),
+            }
+        }
+        result
+    }
+
+    /// Sets the priority of `memory` in the range `0.0..=1.0`, higher priority memory is less likely to be demoted to system memory
+    ///
+    /// Returns `false` without [`DeviceFeature::MemoryPriority`], which is only enabled when both `VK_EXT_memory_priority`
+    /// and `VK_EXT_pageable_device_local_memory` are supported, in which case this does nothing
+    ///
+    /// # Safety
+    /// `memory` must be a valid allocation from this device
+    pub unsafe fn set_memory_priority(&self, memory: vk::DeviceMemory, priority: f32) -> bool {
+        debug_assert!((0.0..=1.0).contains(&priority));
+
+        let Some(funcs) = &self.pageable_device_local_memory_funcs else {
+            return false;
+        };
+        unsafe { (funcs.fp().set_device_memory_priority_ext)(self.handle(), memory, priority) };
+        true
+    }
+
+    /// Sets the priority a resource's memory was created with, see [`Buffer::new`](crate::Buffer::new) and [`Image::new`](crate::Image::new),
+    /// shared memory blocks can only have a single priority so it is ignored unless the allocation is dedicated
+    ///
+    /// # Safety
+    /// `memory` must be a valid allocation from this device
+    pub(crate) unsafe fn apply_memory_priority(
+        &self,
+        name: &str,
+        memory: vk::DeviceMemory,
+        dedicated_allocation: bool,
+        priority: Option<f32>,
+    ) {
+        let Some(priority) = priority else {
+            return;
+        };
+        if !dedicated_allocation {
+            tracing::debug!(
+                name,
+                priority,
+                "Ignoring the memory priority of an allocation that isn't dedicated"
+            );
+            return;
+        }
+        // without the feature the allocation keeps the driver's default priority
+        unsafe { self.set_memory_priority(memory, priority) };
+    }
+
+    /// Pass this when creating pipelines, it is saved to [`DeviceConfig::pipeline_cache_directory`] when this device is dropped
+    pub fn pipeline_cache(&self) -> vk::PipelineCache {
+        self.pipeline_cache.handle
+    }
+
     pub fn current_timeline_counter(&self) -> u64 {
         self.timeline_counter.load(Ordering::Relaxed)
     }
 
+    /// The timeline counter the gpu has reached, everything submitted with a lower or equal counter has finished
+    pub fn completed_timeline_counter(&self) -> u64 {
+        unsafe { self.get_semaphore_counter_value(self.timeline_semaphore) }.unwrap()
+    }
+
     pub fn get_and_then_increment_timeline_counter(&self) -> u64 {
         self.timeline_counter.fetch_add(1, Ordering::Relaxed)
     }
 
+    /// A new handle to the timeline semaphore, which reaches [`Device::current_timeline_counter`] once everything submitted
+    /// so far has finished, so other apis or processes can wait for frames without the cpu in between
+    ///
+    /// The importer must only wait on it, signaling it would break the counter, use [`TimelineSemaphore::new_exportable`](crate::TimelineSemaphore::new_exportable)
+    /// for a semaphore that can be signaled from both sides. Needs [`DeviceFeature::ExternalSemaphore`]
+    pub fn export_timeline_semaphore(&self) -> ExternalSemaphoreHandle {
+        export_semaphore(self, self.timeline_semaphore)
+    }
+
     pub fn signal_timeline_submit_info(&self) -> vk::SemaphoreSubmitInfo<'_> {
         vk::SemaphoreSubmitInfo::default()
             .semaphore(self.timeline_semaphore)
             .value(self.get_and_then_increment_timeline_counter() + 1)
     }
 
+    /// Records `f` into a transient command buffer and submits it on the graphics queue with a timeline signal,
+    /// for uploads and other one off work, returns the timeline counter that is reached once it has finished
+    ///
+    /// The submission is ordered before every later one on the graphics queue, so nothing needs to wait for it,
+    /// and the command buffer is destroyed once it has finished
+    ///
+    /// # Safety
+    /// Everything `f` records must be valid to submit, and the resources it uses must stay alive until the returned counter,
+    /// dropping them after this returns is enough for resources that are destroyed through [`Device::schedule_destroy_resource`]
+    pub unsafe fn immediate_submit(&self, f: impl FnOnce(vk::CommandBuffer)) -> u64 {
+        let command_pool_create_info = vk::CommandPoolCreateInfo::default()
+            .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+            .queue_family_index(self.graphics_queue_family_index());
+        let command_pool = scope_guard!(
+            |command_pool| unsafe { self.destroy_command_pool(command_pool, self.allocator()) },
+            unsafe { self.create_command_pool(&command_pool_create_info, self.allocator()) }
+                .unwrap()
+        );
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(*command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        let command_buffer =
+            unsafe { self.allocate_command_buffers(&command_buffer_allocate_info) }.unwrap()[0];
+
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe { self.begin_command_buffer(command_buffer, &command_buffer_begin_info) }.unwrap();
+        f(command_buffer);
+        unsafe { self.end_command_buffer(command_buffer) }.unwrap();
+
+        let command_infos = [vk::CommandBufferSubmitInfo::default().command_buffer(command_buffer)];
+        let signal_infos = [self.signal_timeline_submit_info()];
+        unsafe {
+            self.flush_submits(
+                &[vk::SubmitInfo2::default()
+                    .command_buffer_infos(&command_infos)
+                    .signal_semaphore_infos(&signal_infos)],
+                vk::Fence::null(),
+            )
+        }
+        .unwrap();
+
+        let counter = signal_infos[0].value;
+        unsafe {
+            self.schedule_destroy_resource(
+                counter,
+                ResourceToDestroy::CommandPool(command_pool.into_inner()),
+            );
+        }
+        counter
+    }
+
+    /// Queues `submit` for the graphics queue until the next [`Device::flush_submits`], which [`Swapchain::try_next_frame`](crate::Swapchain::try_next_frame)
+    /// and [`Device::immediate_submit`] call, so work from several places in a frame costs one `vkQueueSubmit2`
+    ///
+    /// # Safety
+    /// `submit` must be valid to submit once it is flushed, and everything it uses must stay alive until then
+    pub unsafe fn batch_submit(&self, submit: BatchedSubmit) {
+        self.batched_submits.lock().push(submit);
+    }
+
+    /// Submits everything queued with [`Device::batch_submit`] followed by `submits` in one `vkQueueSubmit2` on the graphics queue,
+    /// `fence` is signaled once all of them have finished
+    ///
+    /// While there are counters from [`Device::reserve_host_counter`] that haven't been signaled,
+    /// each submission also waits for the ones that are lower than the counter it signals
+    ///
+    /// Must not be called while the graphics queue is locked by [`Device::with_graphics_queue`]
+    ///
+    /// # Safety
+    /// See [`ash::Device::queue_submit2`]
+    pub unsafe fn flush_submits(
+        &self,
+        submits: &[vk::SubmitInfo2<'_>],
+        fence: vk::Fence,
+    ) -> VkResult<()> {
+        self.report_device_lost(self.with_graphics_queue(|graphics_queue| {
+            let batched_submits = core::mem::take(&mut *self.batched_submits.lock());
+            if batched_submits.is_empty() && submits.is_empty() && fence == vk::Fence::null() {
+                return Ok(());
+            }
+            let mut submit_infos = batched_submits
+                .iter()
+                .map(|submit| {
+                    vk::SubmitInfo2::default()
+                        .wait_semaphore_infos(&submit.wait_semaphore_infos)
+                        .command_buffer_infos(&submit.command_buffer_infos)
+                        .signal_semaphore_infos(&submit.signal_semaphore_infos)
+                })
+                .chain(submits.iter().copied())
+                .collect::<Vec<_>>();
+
+            let host_counters = self.host_counters.lock();
+            let host_wait_infos = if host_counters.is_empty() {
+                vec![]
+            } else {
+                submit_infos
+                    .iter_mut()
+                    .map(|submit_info| {
+                        let wait_infos = unsafe {
+                            slice_from_raw_parts(
+                                submit_info.p_wait_semaphore_infos,
+                                submit_info.wait_semaphore_info_count,
+                            )
+                        };
+                        let signal_infos = unsafe {
+                            slice_from_raw_parts(
+                                submit_info.p_signal_semaphore_infos,
+                                submit_info.signal_semaphore_info_count,
+                            )
+                        };
+                        let signal_counter = signal_infos
+                            .iter()
+                            .filter(|info| info.semaphore == self.timeline_semaphore)
+                            .map(|info| info.value)
+                            .max()
+                            .unwrap_or(u64::MAX);
+                        let host_counter = host_counters
+                            .iter()
+                            .copied()
+                            .filter(|&counter| counter < signal_counter)
+                            .max();
+                        let mut wait_infos = wait_infos.to_vec();
+                        wait_infos.extend(host_counter.map(|counter| {
+                            vk::SemaphoreSubmitInfo::default()
+                                .semaphore(self.timeline_semaphore)
+                                .value(counter)
+                                .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                        }));
+                        wait_infos
+                    })
+                    .collect::<Vec<_>>()
+            };
+            drop(host_counters);
+            for (submit_info, wait_infos) in submit_infos.iter_mut().zip(&host_wait_infos) {
+                *submit_info = submit_info.wait_semaphore_infos(wait_infos);
+            }
+
+            unsafe { self.queue_submit2(graphics_queue, &submit_infos, fence) }
+        }))
+    }
+
+    /// Changes which memory backs sparse buffers with one `vkQueueBindSparse` on the graphics queue,
+    /// returns the timeline counter that is reached once the new bindings can be used
+    ///
+    /// The binding waits for everything submitted before it, so memory that is unbound is no longer in use,
+    /// and it flushes [`Device::batch_submit`] first, so it must not be called while the graphics queue is locked
+    ///
+    /// # Safety
+    /// The binds must be valid, see `vkQueueBindSparse`, and memory that is bound must stay alive until it is unbound again
+    /// and the counter of that has been reached
+    pub unsafe fn bind_sparse(&self, buffer_binds: &[vk::SparseBufferMemoryBindInfo<'_>]) -> u64 {
+        unsafe { self.flush_submits(&[], vk::Fence::null()) }.unwrap();
+
+        let wait_counter = self.get_and_then_increment_timeline_counter();
+        let signal_counter = wait_counter + 1;
+        let mut timeline_submit_info = vk::TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(core::slice::from_ref(&wait_counter))
+            .signal_semaphore_values(core::slice::from_ref(&signal_counter));
+        let bind_sparse_info = vk::BindSparseInfo::default()
+            .wait_semaphores(core::slice::from_ref(&self.timeline_semaphore))
+            .signal_semaphores(core::slice::from_ref(&self.timeline_semaphore))
+            .buffer_binds(buffer_binds)
+            .push_next(&mut timeline_submit_info);
+        self.report_device_lost(self.with_graphics_queue(|graphics_queue| unsafe {
+            self.queue_bind_sparse(graphics_queue, &[bind_sparse_info], vk::Fence::null())
+        }))
+        .unwrap();
+        signal_counter
+    }
+
+    /// Takes the next timeline counter for the cpu to signal with [`Device::signal_counter`], e.g. once it has finished
+    /// writing mapped memory the gpu is going to read, every submission flushed until then waits for it
+    pub fn reserve_host_counter(&self) -> u64 {
+        let mut host_counters = self.host_counters.lock();
+        let counter = self.get_and_then_increment_timeline_counter() + 1;
+        host_counters.push(counter);
+        counter
+    }
+
+    /// Signals a counter from [`Device::reserve_host_counter`] from the cpu with `vkSignalSemaphore`,
+    /// after waiting for everything submitted before it was reserved, as the counter can only go up
+    ///
+    /// Must not be called while the graphics queue is locked by [`Device::with_graphics_queue`]
+    pub fn signal_counter(&self, value: u64) {
+        {
+            let host_counters = self.host_counters.lock();
+            assert!(
+                host_counters.contains(&value),
+                "{value} wasn't reserved with Device::reserve_host_counter",
+            );
+            // waiting for everything before it would never finish otherwise
+            assert!(
+                host_counters.iter().all(|&counter| counter >= value),
+                "host counters have to be signaled in the order they were reserved",
+            );
+        }
+
+        // earlier submissions could be waiting in the batch, which would never finish
+        unsafe { self.flush_submits(&[], vk::Fence::null()) }.unwrap();
+        self.wait_for_counter(value - 1, u64::MAX);
+
+        let signal_info = vk::SemaphoreSignalInfo::default()
+            .semaphore(self.timeline_semaphore)
+            .value(value);
+        self.report_device_lost(unsafe { self.signal_semaphore(&signal_info) })
+            .unwrap();
+        self.host_counters
+            .lock()
+            .retain(|&counter| counter != value);
+    }
+
+    /// Waits for every frame and everything else submitted so far to finish, without waiting for the whole device to go idle
+    ///
+    /// Must not be called while a counter from [`Device::reserve_host_counter`] is waiting to be signaled,
+    /// or while the graphics queue is locked by [`Device::with_graphics_queue`]
+    pub fn wait_idle_frames(&self) {
+        debug_assert!(
+            self.host_counters.lock().is_empty(),
+            "waiting for the timeline would never finish while a host counter hasn't been signaled",
+        );
+        unsafe { self.flush_submits(&[], vk::Fence::null()) }.unwrap();
+        self.wait_for_counter(self.current_timeline_counter(), u64::MAX);
+    }
+
     pub fn wait_for_counter(&self, counter: u64, timeout: u64) -> bool {
         debug_assert!(counter <= self.current_timeline_counter());
 
@@ -251,7 +1134,7 @@ impl<'allocator> Device<'allocator> {
             .semaphores(core::slice::from_ref(&self.timeline_semaphore))
             .values(core::slice::from_ref(&counter));
 
-        match unsafe { self.wait_semaphores(&wait_info, timeout) } {
+        match self.report_device_lost(unsafe { self.wait_semaphores(&wait_info, timeout) }) {
             Ok(()) => true,
             Err(vk::Result::TIMEOUT) => false,
             e => {
@@ -261,29 +1144,78 @@ impl<'allocator> Device<'allocator> {
         }
     }
 
+    /// Records `handle` so that it is reported if it is still alive when this device is dropped,
+    /// it is forgotten again once it is passed to [`Device::schedule_destroy_resource`] or [`Device::untrack_resource`]
+    ///
+    /// The backtrace of where it was created is only captured when `RUST_BACKTRACE` is set, see [`Backtrace::capture`]
+    pub fn track_resource<H: Handle>(&self, handle: H, name: &str) {
+        let resource = TrackedResource {
+            name: name.to_owned(),
+            backtrace: Backtrace::capture(),
+        };
+        let previous = self
+            .tracked_resources
+            .lock()
+            .insert((H::TYPE, handle.as_raw()), resource);
+        debug_assert!(previous.is_none(), "'{name}' was tracked twice");
+    }
+
+    pub fn untrack_resource<H: Handle>(&self, handle: H) {
+        self.tracked_resources
+            .lock()
+            .remove(&(H::TYPE, handle.as_raw()));
+    }
+
     /// # Safety
     /// `resource` must be valid to destroy after the timeline semaphore reaches `counter`
     pub unsafe fn schedule_destroy_resource(&self, counter: u64, resource: ResourceToDestroy) {
         debug_assert!(counter <= self.current_timeline_counter());
 
+        {
+            let mut tracked_resources = self.tracked_resources.lock();
+            if let Some(object) = resource.object() {
+                tracked_resources.remove(&object);
+            }
+            if let ResourceToDestroy::ImageViewWithImage(_, image, _)
+            | ResourceToDestroy::ImageViewWithExternalImage(_, image, _) = &resource
+            {
+                tracked_resources.remove(&(vk::ObjectType::IMAGE, image.as_raw()));
+            }
+        }
+
         let mut resources = self.resources_to_destroy.lock();
         let (Ok(index) | Err(index)) =
             resources.binary_search_by_key(&counter, |&(counter, _)| counter);
         resources.insert(index, (counter, resource));
     }
 
-    pub fn destroy_resources(&self) {
-        let mut resources = self.resources_to_destroy.lock();
+    /// Runs `f` once everything submitted so far has finished, from [`Device::destroy_resources`]
+    pub fn defer(&self, f: impl FnOnce(&Device<'_>) + Send + 'static) {
+        unsafe {
+            self.schedule_destroy_resource(
+                self.current_timeline_counter(),
+                ResourceToDestroy::Custom(Box::new(f)),
+            );
+        }
+    }
 
-        let current_counter =
-            unsafe { self.get_semaphore_counter_value(self.timeline_semaphore) }.unwrap();
+    pub fn destroy_resources(&self) {
+        let current_counter = self.completed_timeline_counter();
 
         let allocator = self.allocator();
-        while let Some((_, resource)) =
-            resources.pop_front_if(|&mut (required_counter, _)| required_counter <= current_counter)
-        {
+        let mut destroyed = 0usize;
+        loop {
+            // not held while destroying, so custom cleanup can schedule more destruction
+            let Some((_, resource)) = self
+                .resources_to_destroy
+                .lock()
+                .pop_front_if(|&mut (required_counter, _)| required_counter <= current_counter)
+            else {
+                break;
+            };
             match resource {
                 ResourceToDestroy::ImageView(image_view) => {
+                    self.unregister_image_view(image_view);
                     unsafe { self.destroy_image_view(image_view, allocator) };
                 }
                 ResourceToDestroy::Semaphore(semaphore) => {
@@ -297,25 +1229,90 @@ impl<'allocator> Device<'allocator> {
                     self.with_allocator(|allocator| allocator.free(allocation))
                         .unwrap();
                 }
+                ResourceToDestroy::Image(image, allocation) => {
+                    unsafe { self.destroy_image(image, allocator) };
+                    self.with_allocator(|allocator| allocator.free(allocation))
+                        .unwrap();
+                }
+                ResourceToDestroy::ImageViewWithImage(image_view, image, allocation) => {
+                    self.unregister_image_view(image_view);
+                    unsafe { self.destroy_image_view(image_view, allocator) };
+                    unsafe { self.destroy_image(image, allocator) };
+                    self.with_allocator(|allocator| allocator.free(allocation))
+                        .unwrap();
+                }
+                ResourceToDestroy::ImageViewWithExternalImage(image_view, image, memory) => {
+                    self.unregister_image_view(image_view);
+                    unsafe { self.destroy_image_view(image_view, allocator) };
+                    unsafe { self.destroy_image(image, allocator) };
+                    unsafe { self.free_memory(memory, allocator) };
+                }
+                ResourceToDestroy::Sampler(sampler) => {
+                    unsafe { self.destroy_sampler(sampler, allocator) };
+                }
+                ResourceToDestroy::CommandPool(command_pool) => {
+                    unsafe { self.destroy_command_pool(command_pool, allocator) };
+                }
                 ResourceToDestroy::ShaderModule(shader_module) => {
                     unsafe { self.destroy_shader_module(shader_module, allocator) };
                 }
+                ResourceToDestroy::DescriptorSetLayout(descriptor_set_layout) => {
+                    unsafe { self.destroy_descriptor_set_layout(descriptor_set_layout, allocator) };
+                }
+                ResourceToDestroy::DescriptorPool(descriptor_pool) => {
+                    unsafe { self.destroy_descriptor_pool(descriptor_pool, allocator) };
+                }
                 ResourceToDestroy::PipelineLayout(pipeline_layout) => {
                     unsafe { self.destroy_pipeline_layout(pipeline_layout, allocator) };
                 }
                 ResourceToDestroy::Pipeline(pipeline) => {
                     unsafe { self.destroy_pipeline(pipeline, allocator) };
                 }
+                ResourceToDestroy::QueryPool(query_pool) => {
+                    unsafe { self.destroy_query_pool(query_pool, allocator) };
+                }
+                ResourceToDestroy::Custom(f) => f(self),
             }
+            destroyed += 1;
+        }
+        if destroyed > 0 {
+            tracing::trace!(
+                destroyed,
+                pending = self.pending_destroy_count(),
+                "Destroyed resources"
+            );
         }
     }
 
+    /// The number of resources passed to [`Device::schedule_destroy_resource`] that haven't been destroyed yet
+    pub fn pending_destroy_count(&self) -> usize {
+        self.resources_to_destroy.lock().len()
+    }
+
+    /// The number of resources passed to [`Device::track_resource`] that are still alive
+    pub fn tracked_resource_count(&self) -> usize {
+        self.tracked_resources.lock().len()
+    }
+
     pub fn with_allocator<R>(&self, f: impl FnOnce(&mut Allocator) -> R) -> R {
         let mut allocator = self.allocator.lock();
         f(&mut allocator)
     }
 }
 
+/// A region of commands opened by [`Device::cmd_label`], which is closed when this is dropped
+#[must_use = "the region is closed as soon as the label is dropped"]
+pub struct CommandLabel<'device, 'allocator> {
+    device: &'device Device<'allocator>,
+    command_buffer: vk::CommandBuffer,
+}
+
+impl Drop for CommandLabel<'_, '_> {
+    fn drop(&mut self) {
+        unsafe { self.device.cmd_end_label(self.command_buffer) };
+    }
+}
+
 impl Deref for Device<'_> {
     type Target = ash::Device;
 
@@ -326,14 +1323,57 @@ impl Deref for Device<'_> {
 
 impl Drop for Device<'_> {
     fn drop(&mut self) {
+        // submissions waiting for them would never finish otherwise
+        let mut host_counters = self.host_counters.get_mut().clone();
+        host_counters.sort_unstable();
+        for counter in host_counters {
+            self.signal_counter(counter);
+        }
+        // resources can be waiting on the timeline values of batches that were never flushed
+        unsafe { self.flush_submits(&[], vk::Fence::null()) }.unwrap();
         unsafe { self.device_wait_idle() }.unwrap();
 
         self.destroy_resources();
         debug_assert!(self.resources_to_destroy.get_mut().is_empty());
 
+        for ((object_type, handle), resource) in self.tracked_resources.get_mut().drain() {
+            let TrackedResource { name, backtrace } = resource;
+            tracing::error!(
+                "Leaked {object_type:?} '{name}' (0x{handle:x}) was never destroyed, created at:\n{backtrace}"
+            );
+        }
+
         unsafe { self.destroy_semaphore(self.timeline_semaphore, self.allocator()) };
 
+        if let Some(mut fallback) = self.render_pass_fallback.take() {
+            unsafe { fallback.destroy(&self.device, self.instance.allocator()) };
+        }
+
+        self.pipeline_cache.save(&self.device);
+        unsafe {
+            self.destroy_pipeline_cache(self.pipeline_cache.handle, self.allocator());
+        }
+
+        if let Some(crash_diagnostics) = self.crash_diagnostics.take() {
+            crash_diagnostics.destroy(
+                &self.device,
+                self.allocator.get_mut(),
+                self.instance.allocator(),
+            );
+        }
         unsafe { ManuallyDrop::drop(&mut self.allocator) };
         unsafe { self.destroy_device(self.allocator()) };
     }
 }
+
+/// `std::slice::from_raw_parts` that allows a null pointer with a count of 0, like vulkan does
+///
+/// # Safety
+/// See [`std::slice::from_raw_parts`]
+pub(crate) unsafe fn slice_from_raw_parts<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if count == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(ptr, count as usize) }
+    }
+}
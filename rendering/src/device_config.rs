@@ -0,0 +1,428 @@
+use crate::Instance;
+use ash::vk;
+use std::{
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+};
+
+/// Optional device functionality that [`DeviceConfig`] can require or request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceFeature {
+    /// `VK_EXT_swapchain_maintenance1`, lets [`Swapchain`](crate::Swapchain) know when presentation finishes with present fences,
+    /// without it the swapchain waits for the graphics queue to go idle before recreating or destroying itself
+    SwapchainMaintenance1,
+    /// `VK_EXT_memory_priority` + `VK_EXT_pageable_device_local_memory`, see [`Device::set_memory_priority`](crate::Device::set_memory_priority)
+    MemoryPriority,
+    SamplerAnisotropy,
+    /// `VK_EXT_mesh_shader` with task shaders
+    MeshShader,
+    /// `VK_KHR_acceleration_structure` + `VK_KHR_ray_query`
+    RayQuery,
+    /// `VK_KHR_pipeline_library` + `VK_EXT_graphics_pipeline_library`, see [`GraphicsPipelineBuilder::build_library`](crate::GraphicsPipelineBuilder::build_library)
+    GraphicsPipelineLibrary,
+    /// `VK_KHR_present_id` + `VK_KHR_present_wait`, see [`Swapchain::set_latency_mode`](crate::Swapchain::set_latency_mode)
+    PresentWait,
+    /// `VK_NV_device_diagnostic_checkpoints`, when the device is lost [`Device::report_device_lost`](crate::Device::report_device_lost)
+    /// logs the last [`Device::cmd_begin_label`](crate::Device::cmd_begin_label) each pipeline stage got to
+    DiagnosticCheckpoints,
+    /// `VK_AMD_buffer_marker`, like [`DeviceFeature::DiagnosticCheckpoints`] but only tells the last label started and finished
+    BufferMarker,
+    /// `VK_KHR_external_memory_fd` or `VK_KHR_external_memory_win32` on windows, see [`Image::new_exportable`](crate::Image::new_exportable)
+    ExternalMemory,
+    /// See [`PipelineStatistics`](crate::PipelineStatistics)
+    PipelineStatisticsQuery,
+    /// Sparse binding and residency for buffers on the graphics queue, see [`SparseBuffer`](crate::SparseBuffer)
+    SparseBinding,
+    /// `VK_KHR_external_semaphore_fd` or `VK_KHR_external_semaphore_win32` on windows,
+    /// see [`Device::export_timeline_semaphore`](crate::Device::export_timeline_semaphore)
+    ExternalSemaphore,
+    /// `descriptorBindingPartiallyBound`, `descriptorBindingSampledImageUpdateAfterBind` and `descriptorBindingUpdateUnusedWhilePending`,
+    /// see [`BindlessTextures`](crate::BindlessTextures)
+    BindlessTextures,
+}
+
+impl DeviceFeature {
+    /// Every feature, in declaration order
+    pub const ALL: [DeviceFeature; 14] = [
+        DeviceFeature::SwapchainMaintenance1,
+        DeviceFeature::MemoryPriority,
+        DeviceFeature::SamplerAnisotropy,
+        DeviceFeature::MeshShader,
+        DeviceFeature::RayQuery,
+        DeviceFeature::GraphicsPipelineLibrary,
+        DeviceFeature::PresentWait,
+        DeviceFeature::DiagnosticCheckpoints,
+        DeviceFeature::BufferMarker,
+        DeviceFeature::ExternalMemory,
+        DeviceFeature::PipelineStatisticsQuery,
+        DeviceFeature::SparseBinding,
+        DeviceFeature::ExternalSemaphore,
+        DeviceFeature::BindlessTextures,
+    ];
+
+    pub fn extensions(self) -> &'static [&'static CStr] {
+        match self {
+            DeviceFeature::SwapchainMaintenance1 => &[vk::EXT_SWAPCHAIN_MAINTENANCE1_NAME],
+            DeviceFeature::MemoryPriority => &[
+                vk::EXT_MEMORY_PRIORITY_NAME,
+                vk::EXT_PAGEABLE_DEVICE_LOCAL_MEMORY_NAME,
+            ],
+            DeviceFeature::SamplerAnisotropy => &[],
+            DeviceFeature::MeshShader => &[vk::EXT_MESH_SHADER_NAME],
+            DeviceFeature::RayQuery => &[
+                vk::KHR_DEFERRED_HOST_OPERATIONS_NAME,
+                vk::KHR_ACCELERATION_STRUCTURE_NAME,
+                vk::KHR_RAY_QUERY_NAME,
+            ],
+            DeviceFeature::GraphicsPipelineLibrary => &[
+                vk::KHR_PIPELINE_LIBRARY_NAME,
+                vk::EXT_GRAPHICS_PIPELINE_LIBRARY_NAME,
+            ],
+            DeviceFeature::PresentWait => &[vk::KHR_PRESENT_ID_NAME, vk::KHR_PRESENT_WAIT_NAME],
+            DeviceFeature::DiagnosticCheckpoints => &[vk::NV_DEVICE_DIAGNOSTIC_CHECKPOINTS_NAME],
+            DeviceFeature::BufferMarker => &[vk::AMD_BUFFER_MARKER_NAME],
+            #[cfg(windows)]
+            DeviceFeature::ExternalMemory => &[vk::KHR_EXTERNAL_MEMORY_WIN32_NAME],
+            #[cfg(not(windows))]
+            DeviceFeature::ExternalMemory => &[vk::KHR_EXTERNAL_MEMORY_FD_NAME],
+            DeviceFeature::PipelineStatisticsQuery
+            | DeviceFeature::SparseBinding
+            | DeviceFeature::BindlessTextures => &[],
+            #[cfg(windows)]
+            DeviceFeature::ExternalSemaphore => &[vk::KHR_EXTERNAL_SEMAPHORE_WIN32_NAME],
+            #[cfg(not(windows))]
+            DeviceFeature::ExternalSemaphore => &[vk::KHR_EXTERNAL_SEMAPHORE_FD_NAME],
+        }
+    }
+
+    fn set(self, features: &mut EnabledFeatures) {
+        match self {
+            DeviceFeature::SwapchainMaintenance1 => {
+                features.swapchain_maintenance1.swapchain_maintenance1 = vk::TRUE;
+            }
+            DeviceFeature::MemoryPriority => {
+                features.memory_priority.memory_priority = vk::TRUE;
+                features
+                    .pageable_device_local_memory
+                    .pageable_device_local_memory = vk::TRUE;
+            }
+            DeviceFeature::SamplerAnisotropy => {
+                features.features.sampler_anisotropy = vk::TRUE;
+            }
+            DeviceFeature::PipelineStatisticsQuery => {
+                features.features.pipeline_statistics_query = vk::TRUE;
+            }
+            DeviceFeature::SparseBinding => {
+                features.features.sparse_binding = vk::TRUE;
+                features.features.sparse_residency_buffer = vk::TRUE;
+            }
+            DeviceFeature::MeshShader => {
+                features.mesh_shader.mesh_shader = vk::TRUE;
+                features.mesh_shader.task_shader = vk::TRUE;
+            }
+            DeviceFeature::RayQuery => {
+                features.acceleration_structure.acceleration_structure = vk::TRUE;
+                features.ray_query.ray_query = vk::TRUE;
+            }
+            DeviceFeature::GraphicsPipelineLibrary => {
+                features.graphics_pipeline_library.graphics_pipeline_library = vk::TRUE;
+            }
+            DeviceFeature::PresentWait => {
+                features.present_id.present_id = vk::TRUE;
+                features.present_wait.present_wait = vk::TRUE;
+            }
+            DeviceFeature::BindlessTextures => {
+                let descriptor_indexing = &mut features.descriptor_indexing;
+                descriptor_indexing.descriptor_binding_partially_bound = vk::TRUE;
+                descriptor_indexing.descriptor_binding_sampled_image_update_after_bind = vk::TRUE;
+                descriptor_indexing.descriptor_binding_update_unused_while_pending = vk::TRUE;
+            }
+            // only extensions, without any feature to enable
+            DeviceFeature::DiagnosticCheckpoints
+            | DeviceFeature::BufferMarker
+            | DeviceFeature::ExternalMemory
+            | DeviceFeature::ExternalSemaphore => {}
+        }
+    }
+
+    fn is_set(self, features: &EnabledFeatures) -> bool {
+        match self {
+            DeviceFeature::SwapchainMaintenance1 => {
+                features.swapchain_maintenance1.swapchain_maintenance1 == vk::TRUE
+            }
+            DeviceFeature::MemoryPriority => {
+                features.memory_priority.memory_priority == vk::TRUE
+                    && features
+                        .pageable_device_local_memory
+                        .pageable_device_local_memory
+                        == vk::TRUE
+            }
+            DeviceFeature::SamplerAnisotropy => features.features.sampler_anisotropy == vk::TRUE,
+            DeviceFeature::PipelineStatisticsQuery => {
+                features.features.pipeline_statistics_query == vk::TRUE
+            }
+            DeviceFeature::SparseBinding => {
+                features.features.sparse_binding == vk::TRUE
+                    && features.features.sparse_residency_buffer == vk::TRUE
+            }
+            DeviceFeature::MeshShader => {
+                features.mesh_shader.mesh_shader == vk::TRUE
+                    && features.mesh_shader.task_shader == vk::TRUE
+            }
+            DeviceFeature::RayQuery => {
+                features.acceleration_structure.acceleration_structure == vk::TRUE
+                    && features.ray_query.ray_query == vk::TRUE
+            }
+            DeviceFeature::GraphicsPipelineLibrary => {
+                features.graphics_pipeline_library.graphics_pipeline_library == vk::TRUE
+            }
+            DeviceFeature::PresentWait => {
+                features.present_id.present_id == vk::TRUE
+                    && features.present_wait.present_wait == vk::TRUE
+            }
+            DeviceFeature::BindlessTextures => {
+                let descriptor_indexing = &features.descriptor_indexing;
+                descriptor_indexing.descriptor_binding_partially_bound == vk::TRUE
+                    && descriptor_indexing.descriptor_binding_sampled_image_update_after_bind
+                        == vk::TRUE
+                    && descriptor_indexing.descriptor_binding_update_unused_while_pending
+                        == vk::TRUE
+            }
+            DeviceFeature::DiagnosticCheckpoints
+            | DeviceFeature::BufferMarker
+            | DeviceFeature::ExternalMemory
+            | DeviceFeature::ExternalSemaphore => true,
+        }
+    }
+
+    /// Whether `physical_device` has the extensions of this feature and every feature struct they need,
+    /// e.g. [`DeviceFeature::MemoryPriority`] needs both `memoryPriority` and `pageableDeviceLocalMemory`
+    pub(crate) fn is_supported(
+        self,
+        instance: &Instance<'_>,
+        physical_device: vk::PhysicalDevice,
+    ) -> bool {
+        // the feature structs of extensions that aren't there can't be queried
+        let extensions =
+            unsafe { instance.enumerate_device_extension_properties(physical_device) }.unwrap();
+        if !self.extensions().iter().all(|&name| {
+            extensions
+                .iter()
+                .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+        }) {
+            return false;
+        }
+        if self == DeviceFeature::SwapchainMaintenance1 && !instance.surface_maintenance1() {
+            return false;
+        }
+        if self == DeviceFeature::ExternalSemaphore
+            && !crate::external_semaphore::supports_timeline_export(instance, physical_device)
+        {
+            return false;
+        }
+        // binding happens on the graphics queue, which is the first queue family with graphics and compute
+        if self == DeviceFeature::SparseBinding {
+            let queue_families =
+                unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+            let graphics_queue_family = queue_families.iter().find(|queue_family| {
+                queue_family
+                    .queue_flags
+                    .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            });
+            if !graphics_queue_family.is_some_and(|queue_family| {
+                queue_family
+                    .queue_flags
+                    .contains(vk::QueueFlags::SPARSE_BINDING)
+            }) {
+                return false;
+            }
+        }
+
+        let mut supported = EnabledFeatures::default();
+        supported.used.push(self);
+        let mut features2 = supported.push_onto(vk::PhysicalDeviceFeatures2::default());
+        unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+        supported.features = features2.features;
+        self.is_set(&supported)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceConfig {
+    /// Physical devices without these features are skipped
+    pub required_features: Vec<DeviceFeature>,
+    /// Enabled when supported, see [`DeviceCapabilities::has_feature`]
+    pub optional_features: Vec<DeviceFeature>,
+    /// Device extensions to enable on top of the ones this crate requires
+    pub required_extensions: Vec<CString>,
+    /// Enabled when supported, see [`DeviceCapabilities::has_extension`]
+    pub optional_extensions: Vec<CString>,
+    /// Where [`Device::pipeline_cache`](crate::Device::pipeline_cache) is loaded from and saved to, one file per physical device
+    pub pipeline_cache_directory: Option<PathBuf>,
+    /// Only this physical device is considered instead of the first suitable one, see [`Instance::enumerate_adapters`]
+    pub physical_device: Option<vk::PhysicalDevice>,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self {
+            required_features: vec![],
+            optional_features: vec![
+                DeviceFeature::SwapchainMaintenance1,
+                DeviceFeature::MemoryPriority,
+            ],
+            required_extensions: vec![],
+            optional_extensions: vec![],
+            pipeline_cache_directory: None,
+            physical_device: None,
+        }
+    }
+}
+
+impl DeviceConfig {
+    pub fn require_feature(mut self, feature: DeviceFeature) -> Self {
+        if !self.required_features.contains(&feature) {
+            self.required_features.push(feature);
+        }
+        // required features are never optional as well
+        self.optional_features
+            .retain(|&optional| optional != feature);
+        self
+    }
+
+    /// Does nothing for features that are already required or requested, like the ones [`DeviceConfig::default`] requests
+    pub fn request_feature(mut self, feature: DeviceFeature) -> Self {
+        if !self.required_features.contains(&feature) && !self.optional_features.contains(&feature)
+        {
+            self.optional_features.push(feature);
+        }
+        self
+    }
+
+    pub fn require_extension(mut self, extension: &CStr) -> Self {
+        self.required_extensions.push(extension.to_owned());
+        self
+    }
+
+    pub fn request_extension(mut self, extension: &CStr) -> Self {
+        self.optional_extensions.push(extension.to_owned());
+        self
+    }
+
+    pub fn pipeline_cache_directory(mut self, pipeline_cache_directory: &Path) -> Self {
+        self.pipeline_cache_directory = Some(pipeline_cache_directory.to_owned());
+        self
+    }
+
+    pub fn physical_device(mut self, physical_device: vk::PhysicalDevice) -> Self {
+        self.physical_device = Some(physical_device);
+        self
+    }
+}
+
+/// The features and extensions that were actually enabled on a [`Device`](crate::Device)
+#[derive(Debug, Clone, Default)]
+pub struct DeviceCapabilities {
+    pub features: Vec<DeviceFeature>,
+    pub extensions: Vec<CString>,
+}
+
+impl DeviceCapabilities {
+    pub fn has_feature(&self, feature: DeviceFeature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    pub fn has_extension(&self, extension: &CStr) -> bool {
+        self.extensions
+            .iter()
+            .any(|enabled| enabled.as_c_str() == extension)
+    }
+}
+
+/// Storage for the feature structs of every enabled [`DeviceFeature`], so they can be chained onto [`vk::PhysicalDeviceFeatures2`]
+#[derive(Default)]
+pub(crate) struct EnabledFeatures {
+    pub features: vk::PhysicalDeviceFeatures,
+    swapchain_maintenance1: vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT<'static>,
+    memory_priority: vk::PhysicalDeviceMemoryPriorityFeaturesEXT<'static>,
+    pageable_device_local_memory: vk::PhysicalDevicePageableDeviceLocalMemoryFeaturesEXT<'static>,
+    mesh_shader: vk::PhysicalDeviceMeshShaderFeaturesEXT<'static>,
+    acceleration_structure: vk::PhysicalDeviceAccelerationStructureFeaturesKHR<'static>,
+    ray_query: vk::PhysicalDeviceRayQueryFeaturesKHR<'static>,
+    graphics_pipeline_library: vk::PhysicalDeviceGraphicsPipelineLibraryFeaturesEXT<'static>,
+    present_id: vk::PhysicalDevicePresentIdFeaturesKHR<'static>,
+    present_wait: vk::PhysicalDevicePresentWaitFeaturesKHR<'static>,
+    descriptor_indexing: vk::PhysicalDeviceDescriptorIndexingFeatures<'static>,
+    /// Set by [`EnabledFeatures::merge_into_vulkan12`], after which `descriptor_indexing` isn't chained anymore
+    descriptor_indexing_merged: bool,
+    used: Vec<DeviceFeature>,
+}
+
+impl EnabledFeatures {
+    pub fn enable(&mut self, feature: DeviceFeature) {
+        feature.set(self);
+        self.used.push(feature);
+    }
+
+    /// Moves the descriptor indexing features into `features12`, as creating a device with
+    /// [`vk::PhysicalDeviceVulkan12Features`] can't chain [`vk::PhysicalDeviceDescriptorIndexingFeatures`] as well
+    pub fn merge_into_vulkan12<'a>(
+        &mut self,
+        mut features12: vk::PhysicalDeviceVulkan12Features<'a>,
+    ) -> vk::PhysicalDeviceVulkan12Features<'a> {
+        let descriptor_indexing = &self.descriptor_indexing;
+        features12.descriptor_binding_partially_bound |=
+            descriptor_indexing.descriptor_binding_partially_bound;
+        features12.descriptor_binding_sampled_image_update_after_bind |=
+            descriptor_indexing.descriptor_binding_sampled_image_update_after_bind;
+        features12.descriptor_binding_update_unused_while_pending |=
+            descriptor_indexing.descriptor_binding_update_unused_while_pending;
+        self.descriptor_indexing_merged = true;
+        features12
+    }
+
+    /// Does not touch [`vk::PhysicalDeviceFeatures2::features`]
+    pub fn push_onto<'a>(
+        &'a mut self,
+        mut features2: vk::PhysicalDeviceFeatures2<'a>,
+    ) -> vk::PhysicalDeviceFeatures2<'a> {
+        let uses = |feature| self.used.contains(&feature);
+        let swapchain_maintenance1 = uses(DeviceFeature::SwapchainMaintenance1);
+        let memory_priority = uses(DeviceFeature::MemoryPriority);
+        let mesh_shader = uses(DeviceFeature::MeshShader);
+        let ray_query = uses(DeviceFeature::RayQuery);
+        let graphics_pipeline_library = uses(DeviceFeature::GraphicsPipelineLibrary);
+        let present_wait = uses(DeviceFeature::PresentWait);
+        let descriptor_indexing =
+            uses(DeviceFeature::BindlessTextures) && !self.descriptor_indexing_merged;
+
+        if swapchain_maintenance1 {
+            features2 = features2.push_next(&mut self.swapchain_maintenance1);
+        }
+        if memory_priority {
+            features2 = features2
+                .push_next(&mut self.memory_priority)
+                .push_next(&mut self.pageable_device_local_memory);
+        }
+        if mesh_shader {
+            features2 = features2.push_next(&mut self.mesh_shader);
+        }
+        if ray_query {
+            features2 = features2
+                .push_next(&mut self.acceleration_structure)
+                .push_next(&mut self.ray_query);
+        }
+        if graphics_pipeline_library {
+            features2 = features2.push_next(&mut self.graphics_pipeline_library);
+        }
+        if present_wait {
+            features2 = features2
+                .push_next(&mut self.present_id)
+                .push_next(&mut self.present_wait);
+        }
+        if descriptor_indexing {
+            features2 = features2.push_next(&mut self.descriptor_indexing);
+        }
+        features2
+    }
+}
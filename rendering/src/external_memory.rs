@@ -0,0 +1,253 @@
+use crate::{Device, DeviceFeature, Image, image::ImageMemory};
+use ash::vk;
+use std::sync::Arc;
+
+/// An os handle to image memory that another api or process can import, see [`Image::export_memory`]
+#[cfg(unix)]
+pub type ExternalMemoryHandle = std::os::fd::OwnedFd;
+/// An os handle to image memory that another api or process can import, see [`Image::export_memory`]
+#[cfg(windows)]
+pub type ExternalMemoryHandle = std::os::windows::io::OwnedHandle;
+
+#[cfg(unix)]
+const HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags = vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+#[cfg(windows)]
+const HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
+
+/// Loaded once by the device, see [`Device::external_memory_funcs`]
+#[cfg(unix)]
+pub(crate) type ExternalMemoryFuncs = ash::khr::external_memory_fd::Device;
+/// Loaded once by the device, see [`Device::external_memory_funcs`]
+#[cfg(windows)]
+pub(crate) type ExternalMemoryFuncs = ash::khr::external_memory_win32::Device;
+
+impl<'allocator> Image<'allocator> {
+    /// Like [`Image::new`] with a single mip level, but with its own memory that [`Image::export_memory`] can share
+    /// with other apis or processes without a round trip through the cpu, e.g. a capture tool or cuda post processing
+    ///
+    /// The memory is a dedicated allocation only when the driver requires one for `format` and `usage`
+    ///
+    /// Needs [`DeviceFeature::ExternalMemory`]
+    pub fn new_exportable(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        usage: vk::ImageUsageFlags,
+    ) -> Self {
+        assert!(
+            device
+                .capabilities()
+                .has_feature(DeviceFeature::ExternalMemory),
+            "'{name}' can't be exported without DeviceFeature::ExternalMemory",
+        );
+        let dedicated = external_memory_features(&device, format, usage)
+            .filter(|features| features.contains(vk::ExternalMemoryFeatureFlags::EXPORTABLE))
+            .unwrap_or_else(|| {
+                panic!("'{name}' can't be exported, {format:?} with {usage:?} can't be exported as {HANDLE_TYPE:?} on this device")
+            })
+            .contains(vk::ExternalMemoryFeatureFlags::DEDICATED_ONLY);
+
+        Self::create_with_memory(
+            device,
+            name,
+            format,
+            vk::Extent2D { width, height },
+            1,
+            vk::ImageViewType::TYPE_2D,
+            usage,
+            HANDLE_TYPE,
+            |device, image, requirements| {
+                let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+                let mut export_info =
+                    vk::ExportMemoryAllocateInfo::default().handle_types(HANDLE_TYPE);
+                let mut allocate_info = vk::MemoryAllocateInfo::default()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(device_local_memory_type(device, requirements))
+                    .push_next(&mut export_info);
+                if dedicated {
+                    allocate_info = allocate_info.push_next(&mut dedicated_info);
+                }
+                ImageMemory::External(
+                    unsafe { device.allocate_memory(&allocate_info, device.allocator()) }.unwrap(),
+                )
+            },
+        )
+    }
+
+    /// Creates an image on top of memory another api or process exported,
+    /// which has to be created the same way the exporter created it
+    ///
+    /// Needs [`DeviceFeature::ExternalMemory`]
+    ///
+    /// # Safety
+    /// `handle` must be memory exported as an opaque handle from a device with the same uuid,
+    /// for a 2D single level image with the same `format`, size and `usage`,
+    /// in a dedicated allocation exactly when the driver requires one, like [`Image::new_exportable`] does
+    pub unsafe fn import_memory(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        usage: vk::ImageUsageFlags,
+        handle: ExternalMemoryHandle,
+    ) -> Self {
+        assert!(
+            device
+                .capabilities()
+                .has_feature(DeviceFeature::ExternalMemory),
+            "'{name}' can't be imported without DeviceFeature::ExternalMemory",
+        );
+        let dedicated = external_memory_features(&device, format, usage)
+            .filter(|features| features.contains(vk::ExternalMemoryFeatureFlags::IMPORTABLE))
+            .unwrap_or_else(|| {
+                panic!("'{name}' can't be imported, {format:?} with {usage:?} can't be imported as {HANDLE_TYPE:?} on this device")
+            })
+            .contains(vk::ExternalMemoryFeatureFlags::DEDICATED_ONLY);
+
+        Self::create_with_memory(
+            device,
+            name,
+            format,
+            vk::Extent2D { width, height },
+            1,
+            vk::ImageViewType::TYPE_2D,
+            usage,
+            HANDLE_TYPE,
+            |device, image, requirements| {
+                let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+
+                #[cfg(unix)]
+                let memory = {
+                    use std::os::fd::{FromRawFd, IntoRawFd};
+
+                    // vulkan only takes ownership of the fd when the import succeeds
+                    let fd = handle.into_raw_fd();
+                    let mut import_info = vk::ImportMemoryFdInfoKHR::default()
+                        .handle_type(HANDLE_TYPE)
+                        .fd(fd);
+                    let mut allocate_info = vk::MemoryAllocateInfo::default()
+                        .allocation_size(requirements.size)
+                        .memory_type_index(device_local_memory_type(device, requirements))
+                        .push_next(&mut import_info);
+                    if dedicated {
+                        allocate_info = allocate_info.push_next(&mut dedicated_info);
+                    }
+                    unsafe { device.allocate_memory(&allocate_info, device.allocator()) }
+                        .inspect_err(|_| drop(unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) }))
+                        .unwrap()
+                };
+
+                #[cfg(windows)]
+                let memory = {
+                    use std::os::windows::io::AsRawHandle;
+
+                    // importing a win32 handle doesn't take ownership of it, so it is closed when `handle` is dropped
+                    let mut import_info = vk::ImportMemoryWin32HandleInfoKHR::default()
+                        .handle_type(HANDLE_TYPE)
+                        .handle(handle.as_raw_handle() as vk::HANDLE);
+                    let mut allocate_info = vk::MemoryAllocateInfo::default()
+                        .allocation_size(requirements.size)
+                        .memory_type_index(device_local_memory_type(device, requirements))
+                        .push_next(&mut import_info);
+                    if dedicated {
+                        allocate_info = allocate_info.push_next(&mut dedicated_info);
+                    }
+                    unsafe { device.allocate_memory(&allocate_info, device.allocator()) }.unwrap()
+                };
+
+                ImageMemory::External(memory)
+            },
+        )
+    }
+
+    /// A new handle to the memory of an image made with [`Image::new_exportable`], every handle keeps the memory alive
+    /// in the importer even after this image is dropped
+    pub fn export_memory(&self) -> ExternalMemoryHandle {
+        assert!(
+            self.has_external_memory(),
+            "Only images made with Image::new_exportable can be exported",
+        );
+        let funcs = self.device().external_memory_funcs();
+
+        #[cfg(unix)]
+        {
+            use std::os::fd::FromRawFd;
+
+            let get_info = vk::MemoryGetFdInfoKHR::default()
+                .memory(self.memory())
+                .handle_type(HANDLE_TYPE);
+            let fd = unsafe { funcs.get_memory_fd(&get_info) }.unwrap();
+            unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) }
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::io::FromRawHandle;
+
+            let get_info = vk::MemoryGetWin32HandleInfoKHR::default()
+                .memory(self.memory())
+                .handle_type(HANDLE_TYPE);
+            let handle = unsafe { funcs.get_memory_win32_handle(&get_info) }.unwrap();
+            unsafe { std::os::windows::io::OwnedHandle::from_raw_handle(handle as _) }
+        }
+    }
+}
+
+/// How 2D single level images with `format` and `usage` can be shared as [`HANDLE_TYPE`], `None` when they can't be created with it at all
+fn external_memory_features(
+    device: &Device<'_>,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+) -> Option<vk::ExternalMemoryFeatureFlags> {
+    let mut external_image_format_info =
+        vk::PhysicalDeviceExternalImageFormatInfo::default().handle_type(HANDLE_TYPE);
+    let image_format_info = vk::PhysicalDeviceImageFormatInfo2::default()
+        .format(format)
+        .ty(vk::ImageType::TYPE_2D)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(usage)
+        .push_next(&mut external_image_format_info);
+    let mut external_image_format_properties = vk::ExternalImageFormatProperties::default();
+    let mut image_format_properties =
+        vk::ImageFormatProperties2::default().push_next(&mut external_image_format_properties);
+    match unsafe {
+        device
+            .instance()
+            .get_physical_device_image_format_properties2(
+                device.physical_device(),
+                &image_format_info,
+                &mut image_format_properties,
+            )
+    } {
+        Ok(()) => Some(
+            external_image_format_properties
+                .external_memory_properties
+                .external_memory_features,
+        ),
+        Err(vk::Result::ERROR_FORMAT_NOT_SUPPORTED) => None,
+        Err(error) => panic!("Unable to get the external memory properties of {format:?}: {error}"),
+    }
+}
+
+fn device_local_memory_type(device: &Device<'_>, requirements: vk::MemoryRequirements) -> u32 {
+    let memory_properties = unsafe {
+        device
+            .instance()
+            .get_physical_device_memory_properties(device.physical_device())
+    };
+    memory_properties
+        .memory_types_as_slice()
+        .iter()
+        .enumerate()
+        .position(|(index, memory_type)| {
+            requirements.memory_type_bits & (1 << index) != 0
+                && memory_type
+                    .property_flags
+                    .contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+        })
+        .expect("Unable to find a device local memory type for the external image") as u32
+}
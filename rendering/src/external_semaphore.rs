@@ -0,0 +1,99 @@
+use crate::{Device, DeviceFeature, Instance, TimelineSemaphore};
+use ash::vk;
+use std::sync::Arc;
+
+/// An os handle to a semaphore that another api or process can import, see [`Device::export_timeline_semaphore`]
+#[cfg(unix)]
+pub type ExternalSemaphoreHandle = std::os::fd::OwnedFd;
+/// An os handle to a semaphore that another api or process can import, see [`Device::export_timeline_semaphore`]
+#[cfg(windows)]
+pub type ExternalSemaphoreHandle = std::os::windows::io::OwnedHandle;
+
+#[cfg(unix)]
+pub(crate) const SEMAPHORE_HANDLE_TYPE: vk::ExternalSemaphoreHandleTypeFlags =
+    vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD;
+#[cfg(windows)]
+pub(crate) const SEMAPHORE_HANDLE_TYPE: vk::ExternalSemaphoreHandleTypeFlags =
+    vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_WIN32;
+
+/// Loaded once by the device, see [`Device::external_semaphore_funcs`]
+#[cfg(unix)]
+pub(crate) type ExternalSemaphoreFuncs = ash::khr::external_semaphore_fd::Device;
+/// Loaded once by the device, see [`Device::external_semaphore_funcs`]
+#[cfg(windows)]
+pub(crate) type ExternalSemaphoreFuncs = ash::khr::external_semaphore_win32::Device;
+
+/// Whether timeline semaphores can be exported as [`ExternalSemaphoreHandle`]s
+pub(crate) fn supports_timeline_export(
+    instance: &Instance<'_>,
+    physical_device: vk::PhysicalDevice,
+) -> bool {
+    let mut semaphore_type_create_info =
+        vk::SemaphoreTypeCreateInfo::default().semaphore_type(vk::SemaphoreType::TIMELINE);
+    let external_semaphore_info = vk::PhysicalDeviceExternalSemaphoreInfo::default()
+        .handle_type(SEMAPHORE_HANDLE_TYPE)
+        .push_next(&mut semaphore_type_create_info);
+    let mut external_semaphore_properties = vk::ExternalSemaphoreProperties::default();
+    unsafe {
+        instance.get_physical_device_external_semaphore_properties(
+            physical_device,
+            &external_semaphore_info,
+            &mut external_semaphore_properties,
+        );
+    }
+    external_semaphore_properties
+        .external_semaphore_features
+        .contains(vk::ExternalSemaphoreFeatureFlags::EXPORTABLE)
+}
+
+/// A new handle to `semaphore`, which must have been created with [`SEMAPHORE_HANDLE_TYPE`]
+pub(crate) fn export_semaphore(
+    device: &Device<'_>,
+    semaphore: vk::Semaphore,
+) -> ExternalSemaphoreHandle {
+    let funcs = device.external_semaphore_funcs();
+
+    #[cfg(unix)]
+    {
+        use std::os::fd::FromRawFd;
+
+        let get_info = vk::SemaphoreGetFdInfoKHR::default()
+            .semaphore(semaphore)
+            .handle_type(SEMAPHORE_HANDLE_TYPE);
+        let fd = unsafe { funcs.get_semaphore_fd(&get_info) }.unwrap();
+        unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) }
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::FromRawHandle;
+
+        let get_info = vk::SemaphoreGetWin32HandleInfoKHR::default()
+            .semaphore(semaphore)
+            .handle_type(SEMAPHORE_HANDLE_TYPE);
+        let handle = unsafe { funcs.get_semaphore_win32_handle(&get_info) }.unwrap();
+        unsafe { std::os::windows::io::OwnedHandle::from_raw_handle(handle as _) }
+    }
+}
+
+impl<'allocator> TimelineSemaphore<'allocator> {
+    /// Like [`TimelineSemaphore::new`], but [`TimelineSemaphore::export`] can share it with other apis or processes,
+    /// e.g. for the cpu to tell a cuda kernel when its input is ready with [`TimelineSemaphore::signal`]
+    ///
+    /// Needs [`DeviceFeature::ExternalSemaphore`]
+    pub fn new_exportable(device: Arc<Device<'allocator>>, name: &str, initial_value: u64) -> Self {
+        assert!(
+            device
+                .capabilities()
+                .has_feature(DeviceFeature::ExternalSemaphore),
+            "'{name}' can't be exported without DeviceFeature::ExternalSemaphore",
+        );
+        Self::create(device, name, initial_value, SEMAPHORE_HANDLE_TYPE)
+    }
+
+    /// A new handle to a semaphore made with [`TimelineSemaphore::new_exportable`], every handle keeps the semaphore alive
+    /// in the importer even after this is dropped
+    pub fn export(&self) -> ExternalSemaphoreHandle {
+        export_semaphore(self.device(), self.handle())
+    }
+}
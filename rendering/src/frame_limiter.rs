@@ -0,0 +1,164 @@
+use crate::Device;
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// How often the timeline is checked while a presented frame is still rendering, which bounds how late its end is noticed
+const TIMELINE_POLL_INTERVAL: Duration = Duration::from_micros(500);
+
+/// How many of the latest finished frames [`FrameLimiter::pacing`] is measured over
+const PACING_HISTORY_LENGTH: usize = 120;
+
+/// How evenly frames have finished recently, see [`FrameLimiter::pacing`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FramePacing {
+    pub average_frame_time: Duration,
+    pub min_frame_time: Duration,
+    pub max_frame_time: Duration,
+    /// The standard deviation of the frame times, 0 when every frame took exactly as long
+    pub jitter: Duration,
+}
+
+impl FramePacing {
+    pub fn fps(&self) -> f64 {
+        1.0 / self.average_frame_time.as_secs_f64()
+    }
+}
+
+/// Caps how often frames are rendered, without it a render loop presenting with
+/// [`vk::PresentModeKHR::MAILBOX`](ash::vk::PresentModeKHR::MAILBOX) renders as fast as it can
+///
+/// Frames are paced from when the gpu finished them, as seen on the device's timeline semaphore,
+/// so how long recording and submitting took on the cpu doesn't move the schedule
+///
+/// Nothing here blocks, [`FrameLimiter::update`] goes at the start of each event loop iteration, a frame is only rendered when
+/// [`FrameLimiter::is_frame_due`] and otherwise the event loop waits until [`FrameLimiter::next_wakeup`] with
+/// [`ControlFlow::WaitUntil`](winit::event_loop::ControlFlow::WaitUntil), [`FrameLimiter::presented`] goes after every frame that was actually presented
+pub struct FrameLimiter<'allocator> {
+    device: Arc<Device<'allocator>>,
+    target_frame_time: Option<Duration>,
+    next_deadline: Option<Instant>,
+    /// The timeline counters of presented frames the gpu hasn't been seen finishing yet
+    rendering_frames: VecDeque<u64>,
+    finish_times: VecDeque<Instant>,
+}
+
+impl<'allocator> FrameLimiter<'allocator> {
+    /// `target_fps` of `None` doesn't limit anything, but pacing is still measured
+    pub fn new(device: Arc<Device<'allocator>>, target_fps: Option<f64>) -> Self {
+        let mut frame_limiter = Self {
+            device,
+            target_frame_time: None,
+            next_deadline: None,
+            rendering_frames: VecDeque::new(),
+            finish_times: VecDeque::with_capacity(PACING_HISTORY_LENGTH + 1),
+        };
+        frame_limiter.set_target_fps(target_fps);
+        frame_limiter
+    }
+
+    pub fn target_fps(&self) -> Option<f64> {
+        self.target_frame_time
+            .map(|target_frame_time| 1.0 / target_frame_time.as_secs_f64())
+    }
+
+    /// Non positive and non finite targets are treated as `None`
+    pub fn set_target_fps(&mut self, target_fps: Option<f64>) {
+        self.target_frame_time = target_fps
+            .filter(|&target_fps| target_fps > 0.0 && target_fps.is_finite())
+            .map(|target_fps| Duration::from_secs_f64(1.0 / target_fps));
+        self.next_deadline = None;
+    }
+
+    /// Checks the timeline for presented frames the gpu has finished, scheduling the next frame from the latest one
+    pub fn update(&mut self) {
+        if self.rendering_frames.is_empty() {
+            return;
+        }
+        let completed = self.device.completed_timeline_counter();
+        let now = Instant::now();
+        while self
+            .rendering_frames
+            .front()
+            .is_some_and(|&counter| counter <= completed)
+        {
+            self.rendering_frames.pop_front();
+            self.finished(now);
+        }
+    }
+
+    /// Whether the next frame should be rendered now, when limiting that is once the previous frame has finished and its deadline has passed
+    pub fn is_frame_due(&self) -> bool {
+        self.target_frame_time.is_none()
+            || self.rendering_frames.is_empty()
+                && self
+                    .next_deadline
+                    .is_none_or(|deadline| Instant::now() >= deadline)
+    }
+
+    /// When the event loop should wake up next to check [`FrameLimiter::is_frame_due`] again, `None` when it already is,
+    /// while the previous frame is rendering that is a short poll of the timeline as its deadline isn't known yet
+    pub fn next_wakeup(&self) -> Option<Instant> {
+        if self.is_frame_due() {
+            return None;
+        }
+        if !self.rendering_frames.is_empty() {
+            return Some(Instant::now() + TIMELINE_POLL_INTERVAL);
+        }
+        self.next_deadline
+    }
+
+    /// Records that a frame was presented, must be called before anything else is submitted
+    /// as the frame is tracked by the device's current timeline counter
+    pub fn presented(&mut self) {
+        self.rendering_frames
+            .push_back(self.device.current_timeline_counter());
+    }
+
+    fn finished(&mut self, now: Instant) {
+        if self.finish_times.len() > PACING_HISTORY_LENGTH {
+            self.finish_times.pop_front();
+        }
+        self.finish_times.push_back(now);
+
+        self.next_deadline = self.target_frame_time.map(|target_frame_time| {
+            match self.next_deadline {
+                // keeping to the schedule instead of counting from now stops the wakeup overshoot adding up,
+                // unless the frame was so late that catching up would mean rendering a burst of frames
+                Some(deadline) if now < deadline + target_frame_time => {
+                    deadline + target_frame_time
+                }
+                _ => now + target_frame_time,
+            }
+        });
+    }
+
+    /// Statistics over the time between the latest frames finishing on the gpu, `None` until two frames have finished
+    pub fn pacing(&self) -> Option<FramePacing> {
+        let frame_times = self
+            .finish_times
+            .iter()
+            .zip(self.finish_times.iter().skip(1))
+            .map(|(&previous, &current)| current - previous);
+        let count = frame_times.len();
+        if count == 0 {
+            return None;
+        }
+
+        let total: Duration = frame_times.clone().sum();
+        let average = total / count as u32;
+        let variance = frame_times
+            .clone()
+            .map(|frame_time| (frame_time.as_secs_f64() - average.as_secs_f64()).powi(2))
+            .sum::<f64>()
+            / count as f64;
+        Some(FramePacing {
+            average_frame_time: average,
+            min_frame_time: frame_times.clone().min().unwrap(),
+            max_frame_time: frame_times.max().unwrap(),
+            jitter: Duration::from_secs_f64(variance.sqrt()),
+        })
+    }
+}
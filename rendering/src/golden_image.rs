@@ -0,0 +1,209 @@
+use crate::{Buffer, Device, Image};
+use ash::vk;
+use gpu_allocator::MemoryLocation;
+use std::{path::Path, sync::Arc};
+
+/// Environment variable that makes [`assert_golden_image`] overwrite the reference images instead of comparing against them
+pub const UPDATE_GOLDEN_IMAGES_ENV_VAR: &str = "UPDATE_GOLDEN_IMAGES";
+
+/// The format [`render_offscreen`] renders in, so the pixels read back are exactly what is stored in [`GoldenImage`]s
+pub const GOLDEN_IMAGE_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+/// Tightly packed `R8G8B8A8` pixels, stored as binary PAM files so no image decoder is needed to compare them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// How far apart two [`GoldenImage`]s are, see [`GoldenImage::compare`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageDifference {
+    /// The biggest difference of any channel of any pixel
+    pub max_difference: u8,
+    /// How many pixels have a channel that differs by more than the tolerance
+    pub differing_pixels: usize,
+}
+
+impl GoldenImage {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|error| format!("Unable to read '{}': {error}", path.display()))?;
+
+        let mut header_length = 0;
+        let mut width = None;
+        let mut height = None;
+        for line in bytes.split(|&byte| byte == b'\n') {
+            header_length += line.len() + 1;
+            let line = std::str::from_utf8(line)
+                .map_err(|_| format!("'{}' has an invalid PAM header", path.display()))?;
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("P7"), None) | (Some("DEPTH"), Some("4")) | (Some("MAXVAL"), Some("255")) => {
+                }
+                (Some("TUPLTYPE"), Some("RGB_ALPHA")) => {}
+                (Some("WIDTH"), Some(value)) => width = value.parse().ok(),
+                (Some("HEIGHT"), Some(value)) => height = value.parse().ok(),
+                (Some("ENDHDR"), None) => break,
+                _ => {
+                    return Err(format!(
+                        "'{}' is not an 8 bit RGBA PAM file, unexpected '{line}'",
+                        path.display(),
+                    ));
+                }
+            }
+        }
+
+        let (Some(width), Some(height)) = (width, height) else {
+            return Err(format!("'{}' is missing its size", path.display()));
+        };
+        let pixels = bytes.get(header_length..).unwrap_or_default().to_vec();
+        if pixels.len() != width as usize * height as usize * 4 {
+            return Err(format!(
+                "'{}' doesn't have {width}x{height} pixels",
+                path.display(),
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut bytes = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            self.width, self.height,
+        )
+        .into_bytes();
+        bytes.extend_from_slice(&self.pixels);
+
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)
+                .map_err(|error| format!("Unable to create '{}': {error}", directory.display()))?;
+        }
+        std::fs::write(path, bytes)
+            .map_err(|error| format!("Unable to write '{}': {error}", path.display()))
+    }
+
+    /// `None` when the sizes don't match, channels that differ by at most `tolerance` count as the same
+    pub fn compare(&self, other: &GoldenImage, tolerance: u8) -> Option<ImageDifference> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+
+        let mut difference = ImageDifference {
+            max_difference: 0,
+            differing_pixels: 0,
+        };
+        for (a, b) in self
+            .pixels
+            .chunks_exact(4)
+            .zip(other.pixels.chunks_exact(4))
+        {
+            let pixel_difference = a.iter().zip(b).map(|(&a, &b)| a.abs_diff(b)).max().unwrap();
+            difference.max_difference = difference.max_difference.max(pixel_difference);
+            if pixel_difference > tolerance {
+                difference.differing_pixels += 1;
+            }
+        }
+        Some(difference)
+    }
+}
+
+/// Renders a frame without a window or swapchain and reads it back, `f` records into a [`GOLDEN_IMAGE_FORMAT`] image
+/// that starts out in [`vk::ImageLayout::UNDEFINED`] and has to update the image's layout through [`Image::set_layout`] if it changes it
+/// without going through the image
+///
+/// Blocks until the frame has finished
+///
+/// # Safety
+/// Everything `f` records must be valid to submit
+pub unsafe fn render_offscreen(
+    device: &Arc<Device<'_>>,
+    width: u32,
+    height: u32,
+    f: impl FnOnce(vk::CommandBuffer, &Image<'_>),
+) -> GoldenImage {
+    let image = Image::new(
+        device.clone(),
+        "Offscreen Target",
+        GOLDEN_IMAGE_FORMAT,
+        width,
+        height,
+        1,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        false,
+        None,
+    );
+    let readback_buffer = Buffer::new(
+        device.clone(),
+        "Offscreen Readback Buffer",
+        MemoryLocation::GpuToCpu,
+        image.level_size(0).unwrap(),
+        vk::BufferUsageFlags::TRANSFER_DST,
+        false,
+        None,
+    );
+
+    let counter = unsafe {
+        device.immediate_submit(|command_buffer| {
+            f(command_buffer, &image);
+            image.cmd_copy_to_buffer(command_buffer, &readback_buffer, 0, 0);
+        })
+    };
+    device.wait_for_counter(counter, u64::MAX);
+
+    GoldenImage {
+        width,
+        height,
+        pixels: unsafe { readback_buffer.get_mapped() }.unwrap().to_vec(),
+    }
+}
+
+/// Compares `actual` against the reference image at `path`, panicking when more than `max_differing_pixels`
+/// differ by more than `tolerance`, the mismatching image is written next to the reference with an `actual.pam` extension
+///
+/// A missing reference is written from `actual` and fails the check so it gets reviewed and checked in,
+/// with [`UPDATE_GOLDEN_IMAGES_ENV_VAR`] set every reference is overwritten instead
+pub fn assert_golden_image(
+    actual: &GoldenImage,
+    path: &Path,
+    tolerance: u8,
+    max_differing_pixels: usize,
+) {
+    if std::env::var_os(UPDATE_GOLDEN_IMAGES_ENV_VAR).is_some() {
+        actual.save(path).unwrap();
+        return;
+    }
+    if !path.exists() {
+        actual.save(path).unwrap();
+        panic!(
+            "'{}' didn't exist so it was created, check that it looks right and commit it",
+            path.display(),
+        );
+    }
+
+    let expected = GoldenImage::load(path).unwrap();
+    let reason = match expected.compare(actual, tolerance) {
+        Some(difference) if difference.differing_pixels <= max_differing_pixels => return,
+        Some(difference) => format!(
+            "{} pixels differ by up to {}",
+            difference.differing_pixels, difference.max_difference,
+        ),
+        None => format!(
+            "the size changed from {}x{} to {}x{}",
+            expected.width, expected.height, actual.width, actual.height,
+        ),
+    };
+
+    let actual_path = path.with_extension("actual.pam");
+    actual.save(&actual_path).unwrap();
+    panic!(
+        "'{}' doesn't match, {reason}, the rendered image was written to '{}'",
+        path.display(),
+        actual_path.display(),
+    );
+}
@@ -0,0 +1,132 @@
+use crate::Buffer;
+use ash::vk;
+use bytemuck::{NoUninit, Zeroable};
+use std::{fmt, hash::Hash, marker::PhantomData};
+
+/// A buffer device address that points at a `T`, laid out exactly like [`vk::DeviceAddress`]
+/// so it can be used for pointer fields of push constants and buffer contents
+#[repr(transparent)]
+pub struct GpuPtr<T> {
+    address: vk::DeviceAddress,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> GpuPtr<T> {
+    /// The null pointer, shaders can check for it with `!= nullptr`
+    pub const fn null() -> Self {
+        Self {
+            address: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// # Safety
+    /// `address` must point at a `T` in a buffer created with [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`], or be 0
+    pub const unsafe fn from_address(address: vk::DeviceAddress) -> Self {
+        Self {
+            address,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn address(self) -> vk::DeviceAddress {
+        self.address
+    }
+
+    pub const fn is_null(self) -> bool {
+        self.address == 0
+    }
+
+    /// Offsets the pointer by `count` elements of `T`, like [`pointer::add`]
+    ///
+    /// # Safety
+    /// The result must still point into the same buffer, or one past its end
+    pub const unsafe fn add(self, count: u64) -> Self {
+        debug_assert!(!self.is_null(), "offsetting a null GpuPtr");
+        Self {
+            address: self.address + count * size_of::<T>() as u64,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Offsets the pointer by `bytes`, without caring about the size of `T`
+    ///
+    /// # Safety
+    /// See [`GpuPtr::add`]
+    pub const unsafe fn byte_add(self, bytes: u64) -> Self {
+        debug_assert!(!self.is_null(), "offsetting a null GpuPtr");
+        Self {
+            address: self.address + bytes,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn cast<U>(self) -> GpuPtr<U> {
+        GpuPtr {
+            address: self.address,
+            _marker: PhantomData,
+        }
+    }
+}
+
+// implemented by hand so none of these need `T` to implement them too
+impl<T> Clone for GpuPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GpuPtr<T> {}
+
+impl<T> PartialEq for GpuPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address
+    }
+}
+
+impl<T> Eq for GpuPtr<T> {}
+
+impl<T> Hash for GpuPtr<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.address.hash(state);
+    }
+}
+
+impl<T> Default for GpuPtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> fmt::Debug for GpuPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "GpuPtr<{}>({:#x})",
+            std::any::type_name::<T>(),
+            self.address
+        )
+    }
+}
+
+// SAFETY: a `GpuPtr<T>` is just a `u64`, a zeroed one is the null pointer
+unsafe impl<T> Zeroable for GpuPtr<T> {}
+// SAFETY: a `GpuPtr<T>` is just a `u64`, which has no padding
+unsafe impl<T: 'static> NoUninit for GpuPtr<T> {}
+
+impl Buffer<'_> {
+    /// The device address of the start of this buffer, typed as pointing at `T`s
+    ///
+    /// # Safety
+    /// This buffer must have been created with [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`]
+    /// and hold `T`s at its start
+    pub unsafe fn device_ptr<T>(&self) -> GpuPtr<T> {
+        debug_assert!(
+            size_of::<T>() as u64 <= self.size(),
+            "a {} byte buffer can't hold a {}",
+            self.size(),
+            std::any::type_name::<T>(),
+        );
+        unsafe { GpuPtr::from_address(self.device_address()) }
+    }
+}
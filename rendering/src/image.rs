@@ -0,0 +1,798 @@
+use crate::{
+    Buffer, Device, Instance, ResourceToDestroy, format_block, is_read_only_layout, lcm,
+    make_subresource_range, render_pass_fallback::ImageViewInfo, transition_image,
+};
+use ash::vk;
+use gpu_allocator::{
+    MemoryLocation,
+    vulkan::{Allocation, AllocationCreateDesc, AllocationScheme},
+};
+use scope_guard::scope_guard;
+use std::{
+    mem::ManuallyDrop,
+    sync::{
+        Arc,
+        atomic::{AtomicI32, Ordering},
+    },
+};
+
+/// A 2D or cube, device local, image with a view of every mip level and layer
+///
+/// The image tracks its own layout, see [`Image::layout`], so the `cmd_` methods only need the layout they want
+pub struct Image<'allocator> {
+    device: Arc<Device<'allocator>>,
+    image: vk::Image,
+    image_view: vk::ImageView,
+    memory: ManuallyDrop<ImageMemory>,
+    format: vk::Format,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+    array_layers: u32,
+    view_type: vk::ImageViewType,
+    layout: AtomicI32,
+}
+
+/// Where the memory bound to an [`Image`] came from
+pub(crate) enum ImageMemory {
+    Allocation(Allocation),
+    /// A dedicated allocation made outside of the allocator, which can be shared with other apis, see [`Image::new_exportable`]
+    External(vk::DeviceMemory),
+}
+
+impl ImageMemory {
+    fn memory_and_offset(&self) -> (vk::DeviceMemory, vk::DeviceSize) {
+        match self {
+            ImageMemory::Allocation(allocation) => {
+                (unsafe { allocation.memory() }, allocation.offset())
+            }
+            &ImageMemory::External(memory) => (memory, 0),
+        }
+    }
+
+    fn free(self, device: &Device<'_>) {
+        match self {
+            ImageMemory::Allocation(allocation) => device
+                .with_allocator(|allocator| allocator.free(allocation))
+                .unwrap(),
+            ImageMemory::External(memory) => unsafe {
+                device.free_memory(memory, device.allocator())
+            },
+        }
+    }
+}
+
+impl<'allocator> Image<'allocator> {
+    /// `priority` is only applied to dedicated allocations, like with [`Buffer::new`]
+    #[expect(clippy::too_many_arguments)]
+    pub fn new(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+        usage: vk::ImageUsageFlags,
+        dedicated_allocation: bool,
+        priority: Option<f32>,
+    ) -> Self {
+        Self::create(
+            device,
+            name,
+            format,
+            vk::Extent2D { width, height },
+            mip_levels,
+            vk::ImageViewType::TYPE_2D,
+            usage,
+            dedicated_allocation,
+            priority,
+        )
+    }
+
+    /// A cube compatible image with 6 square layers, in the `+X, -X, +Y, -Y, +Z, -Z` face order, and a cube view
+    #[expect(clippy::too_many_arguments)]
+    pub fn new_cube(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        size: u32,
+        mip_levels: u32,
+        usage: vk::ImageUsageFlags,
+        dedicated_allocation: bool,
+        priority: Option<f32>,
+    ) -> Self {
+        Self::create(
+            device,
+            name,
+            format,
+            vk::Extent2D {
+                width: size,
+                height: size,
+            },
+            mip_levels,
+            vk::ImageViewType::CUBE,
+            usage,
+            dedicated_allocation,
+            priority,
+        )
+    }
+
+    /// A single level image a compute shader can write to, which can then be sampled or blitted from in a graphics pass,
+    /// see [`Image::cmd_prepare_storage_write`]
+    pub fn new_storage(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        assert!(
+            device
+                .format_features(format, vk::ImageTiling::OPTIMAL)
+                .contains(vk::FormatFeatureFlags::STORAGE_IMAGE | vk::FormatFeatureFlags::BLIT_SRC),
+            "{format:?} can't be used as a storage image",
+        );
+
+        Self::new(
+            device,
+            name,
+            format,
+            width,
+            height,
+            1,
+            vk::ImageUsageFlags::STORAGE
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_SRC,
+            false,
+            None,
+        )
+    }
+
+    #[expect(clippy::too_many_arguments)]
+    fn create(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        mip_levels: u32,
+        view_type: vk::ImageViewType,
+        usage: vk::ImageUsageFlags,
+        dedicated_allocation: bool,
+        priority: Option<f32>,
+    ) -> Self {
+        Self::create_with_memory(
+            device,
+            name,
+            format,
+            extent,
+            mip_levels,
+            view_type,
+            usage,
+            vk::ExternalMemoryHandleTypeFlags::empty(),
+            |device, image, requirements| {
+                let allocation = device
+                    .with_allocator(|allocator| {
+                        allocator.allocate(&AllocationCreateDesc {
+                            name,
+                            requirements,
+                            location: MemoryLocation::GpuOnly,
+                            linear: false,
+                            allocation_scheme: if dedicated_allocation {
+                                AllocationScheme::DedicatedImage(image)
+                            } else {
+                                AllocationScheme::GpuAllocatorManaged
+                            },
+                        })
+                    })
+                    .unwrap();
+                unsafe {
+                    device.apply_memory_priority(
+                        name,
+                        allocation.memory(),
+                        dedicated_allocation,
+                        priority,
+                    );
+                }
+                ImageMemory::Allocation(allocation)
+            },
+        )
+    }
+
+    /// `allocate` is given the image and its requirements, `handle_types` are passed on through [`vk::ExternalMemoryImageCreateInfo`] when not empty
+    #[expect(clippy::too_many_arguments)]
+    pub(crate) fn create_with_memory(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        mip_levels: u32,
+        view_type: vk::ImageViewType,
+        usage: vk::ImageUsageFlags,
+        handle_types: vk::ExternalMemoryHandleTypeFlags,
+        allocate: impl FnOnce(&Device<'allocator>, vk::Image, vk::MemoryRequirements) -> ImageMemory,
+    ) -> Self {
+        assert!(
+            device.supports_format_usage(format, usage),
+            "'{name}' can't be created, {format:?} doesn't support {usage:?} on this device",
+        );
+
+        let (flags, array_layers) = match view_type {
+            vk::ImageViewType::CUBE => (vk::ImageCreateFlags::CUBE_COMPATIBLE, 6),
+            _ => (vk::ImageCreateFlags::empty(), 1),
+        };
+        let image_create_info = vk::ImageCreateInfo::default()
+            .flags(flags)
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(extent.into())
+            .mip_levels(mip_levels)
+            .array_layers(array_layers)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let mut external_memory_create_info =
+            vk::ExternalMemoryImageCreateInfo::default().handle_types(handle_types);
+        let image_create_info = if handle_types.is_empty() {
+            image_create_info
+        } else {
+            image_create_info.push_next(&mut external_memory_create_info)
+        };
+
+        let image = scope_guard!(
+            |image| unsafe { device.destroy_image(image, device.allocator()) },
+            unsafe { device.create_image(&image_create_info, device.allocator()) }.unwrap()
+        );
+        let requirements = unsafe { device.get_image_memory_requirements(*image) };
+
+        let memory = scope_guard!(
+            |memory: ImageMemory| memory.free(&device),
+            allocate(&device, *image, requirements)
+        );
+
+        let (device_memory, offset) = memory.memory_and_offset();
+        unsafe { device.bind_image_memory(*image, device_memory, offset) }.unwrap();
+
+        let image_view_create_info = vk::ImageViewCreateInfo::default()
+            .image(*image)
+            .view_type(view_type)
+            .format(format)
+            .subresource_range(make_subresource_range(format_aspect(format)));
+        let image_view =
+            unsafe { device.create_image_view(&image_view_create_info, device.allocator()) }
+                .unwrap();
+
+        device.track_resource(*image, name);
+        device.track_resource(image_view, &format!("{name} View"));
+        device.register_image_view(
+            image_view,
+            ImageViewInfo {
+                flags,
+                usage,
+                format,
+                extent,
+                layer_count: array_layers,
+            },
+        );
+
+        Self {
+            image: image.into_inner(),
+            image_view,
+            memory: ManuallyDrop::new(memory.into_inner()),
+            format,
+            extent,
+            mip_levels,
+            array_layers,
+            view_type,
+            layout: AtomicI32::new(vk::ImageLayout::UNDEFINED.as_raw()),
+            device,
+        }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.image_view
+    }
+
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory.memory_and_offset().0
+    }
+
+    /// Whether the memory is a dedicated allocation from outside of the allocator, see [`Image::new_exportable`]
+    pub fn has_external_memory(&self) -> bool {
+        matches!(*self.memory, ImageMemory::External(_))
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    /// 6 for cube images, 1 otherwise
+    pub fn array_layers(&self) -> u32 {
+        self.array_layers
+    }
+
+    pub fn view_type(&self) -> vk::ImageViewType {
+        self.view_type
+    }
+
+    pub fn aspect(&self) -> vk::ImageAspectFlags {
+        format_aspect(self.format)
+    }
+
+    /// The layout every mip level and layer will be in once the commands recorded through the image so far have run,
+    /// commands are expected to be submitted in the order they were recorded
+    pub fn layout(&self) -> vk::ImageLayout {
+        vk::ImageLayout::from_raw(self.layout.load(Ordering::Relaxed))
+    }
+
+    /// Records a layout change that was made without going through the image, like a render pass's final layout
+    ///
+    /// # Safety
+    /// Every mip level and layer must be in `layout` once the commands recorded so far have run
+    pub unsafe fn set_layout(&self, layout: vk::ImageLayout) {
+        self.layout.store(layout.as_raw(), Ordering::Relaxed);
+    }
+
+    /// Moves every mip level and layer into `new_layout`, waiting for earlier writes to finish,
+    /// nothing is recorded when the image is already in `new_layout` and it's read only
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state
+    pub unsafe fn cmd_transition(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        new_layout: vk::ImageLayout,
+    ) {
+        let old_layout = self.layout();
+        if old_layout == new_layout && is_read_only_layout(new_layout) {
+            return;
+        }
+
+        let image_barrier = vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+            .src_access_mask(vk::AccessFlags2::MEMORY_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+            .dst_access_mask(vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE)
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .subresource_range(make_subresource_range(self.aspect()))
+            .image(self.image);
+        let dependency_info = vk::DependencyInfo::default()
+            .image_memory_barriers(core::slice::from_ref(&image_barrier));
+        unsafe {
+            self.device
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+        };
+        unsafe { self.set_layout(new_layout) };
+    }
+
+    /// Uploads tightly packed `R8G8B8A8_SRGB` pixels and generates a full mip chain from them, see [`Image::from_mip_levels`]
+    pub fn from_rgba8(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width as usize * height as usize * 4,
+            "'{name}' is not {width}x{height} RGBA8 pixels",
+        );
+
+        Self::from_mip_levels(
+            device,
+            name,
+            vk::Format::R8G8B8A8_SRGB,
+            width,
+            height,
+            mip_level_count(width, height),
+            &[pixels],
+        )
+    }
+
+    /// Uploads `levels` through a staging buffer on the graphics queue, starting from the full size level,
+    /// the rest of the `mip_levels` are filled in with [`Image::generate_mipmaps`]
+    ///
+    /// The image is left in [`vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL`]
+    /// and the upload is ordered before every later submission so it can be sampled right away
+    pub fn from_mip_levels(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+        levels: &[&[u8]],
+    ) -> Self {
+        let image = Image::new(
+            device,
+            name,
+            format,
+            width,
+            height,
+            mip_levels,
+            upload_usage(levels.len(), mip_levels),
+            false,
+            None,
+        );
+        image.upload(name, &[levels])
+    }
+
+    /// Uploads the full size level of each face, in the `+X, -X, +Y, -Y, +Z, -Z` order, into a cube image,
+    /// the rest of the `mip_levels` are generated, see [`Image::from_mip_levels`]
+    pub fn from_cube_faces(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        format: vk::Format,
+        size: u32,
+        mip_levels: u32,
+        faces: [&[u8]; 6],
+    ) -> Self {
+        let image = Image::new_cube(
+            device,
+            name,
+            format,
+            size,
+            mip_levels,
+            upload_usage(1, mip_levels),
+            false,
+            None,
+        );
+        image.upload(name, &faces.each_ref().map(core::slice::from_ref))
+    }
+
+    /// `layers` has the data of the first few mip levels of each array layer
+    fn upload(self, name: &str, layers: &[&[&[u8]]]) -> Self {
+        let device = self.device.clone();
+        let level_count = layers[0].len();
+        assert!(
+            level_count > 0 && level_count <= self.mip_levels as usize,
+            "'{name}' has {level_count} levels of data for {} mip levels",
+            self.mip_levels,
+        );
+        assert!(
+            layers.len() == self.array_layers as usize
+                && layers.iter().all(|levels| levels.len() == level_count),
+            "'{name}' needs the same number of levels for each of its {} layers",
+            self.array_layers,
+        );
+        let generate_mipmaps = level_count < self.mip_levels as usize;
+
+        // buffer offsets have to be a multiple of the texel block size, and of 4 for transfers on any queue,
+        // 16 is a multiple of every block size format_block doesn't know about except for unusual 3 component ones
+        let level_alignment =
+            format_block(self.format).map_or(16, |(block_size, _)| lcm(block_size, 4) as usize);
+
+        let mut regions = vec![];
+        let mut size = 0_usize;
+        for (layer, levels) in layers.iter().enumerate() {
+            for (mip_level, level) in levels.iter().enumerate() {
+                size = size.next_multiple_of(level_alignment);
+                regions.push(
+                    vk::BufferImageCopy::default()
+                        .buffer_offset(size as _)
+                        .image_subresource(
+                            vk::ImageSubresourceLayers::default()
+                                .aspect_mask(self.aspect())
+                                .mip_level(mip_level as u32)
+                                .base_array_layer(layer as u32)
+                                .layer_count(1),
+                        )
+                        .image_extent(vk::Extent3D {
+                            width: (self.extent.width >> mip_level).max(1),
+                            height: (self.extent.height >> mip_level).max(1),
+                            depth: 1,
+                        }),
+                );
+                size += level.len();
+            }
+        }
+
+        let mut staging_buffer = Buffer::new(
+            device.clone(),
+            &format!("{name} Staging Buffer"),
+            MemoryLocation::CpuToGpu,
+            size as _,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            false,
+            None,
+        );
+        {
+            let staging_buffer = unsafe { staging_buffer.get_mapped_mut() }.unwrap();
+            for (level, region) in layers.iter().flat_map(|levels| levels.iter()).zip(&regions) {
+                let offset = region.buffer_offset as usize;
+                staging_buffer[offset..offset + level.len()].copy_from_slice(level);
+            }
+        }
+        let image = self;
+
+        unsafe {
+            device.immediate_submit(|command_buffer| {
+                let _label =
+                    device.cmd_label(command_buffer, c"Image Upload", [0.2, 0.8, 0.2, 1.0]);
+                image.cmd_transition(command_buffer, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+                device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    staging_buffer.handle(),
+                    image.handle(),
+                    image.layout(),
+                    &regions,
+                );
+                if generate_mipmaps {
+                    image.generate_mip_levels(command_buffer, level_count as u32);
+                }
+                image.cmd_transition(command_buffer, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+            });
+        }
+
+        // the timeline counter now includes the upload, so this is only destroyed once it has finished
+        drop(staging_buffer);
+
+        image
+    }
+}
+
+impl Image<'_> {
+    /// Fills every mip level past the first by repeatedly blitting each level into the next one down,
+    /// the image must have been created with [`vk::ImageUsageFlags::TRANSFER_SRC`] and [`vk::ImageUsageFlags::TRANSFER_DST`]
+    /// and its format must support linear filtering of blits
+    ///
+    /// Afterwards every level is in [`vk::ImageLayout::TRANSFER_SRC_OPTIMAL`]
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state
+    pub unsafe fn generate_mipmaps(&self, command_buffer: vk::CommandBuffer) {
+        unsafe { self.generate_mip_levels(command_buffer, 1) };
+    }
+
+    /// Like [`Image::generate_mipmaps`], but keeps the contents of the levels before `first_level`
+    unsafe fn generate_mip_levels(&self, command_buffer: vk::CommandBuffer, first_level: u32) {
+        debug_assert!(first_level >= 1);
+        let _label = unsafe {
+            self.device
+                .cmd_label(command_buffer, c"Generate Mipmaps", [0.2, 0.8, 0.2, 1.0])
+        };
+
+        assert!(
+            self.device
+                .format_features(self.format, vk::ImageTiling::OPTIMAL)
+                .contains(
+                    vk::FormatFeatureFlags::BLIT_SRC
+                        | vk::FormatFeatureFlags::BLIT_DST
+                        | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR
+                ),
+            "{:?} doesn't support linear blits, so mipmaps can't be generated",
+            self.format,
+        );
+
+        let level_barrier = |base_level, level_count, old_layout, new_layout| {
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::ALL_TRANSFER)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::ALL_TRANSFER)
+                .dst_access_mask(vk::AccessFlags2::TRANSFER_READ | vk::AccessFlags2::TRANSFER_WRITE)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .subresource_range(
+                    make_subresource_range(self.aspect())
+                        .base_mip_level(base_level)
+                        .level_count(level_count),
+                )
+                .image(self.image)
+        };
+        let barrier = |image_barrier: vk::ImageMemoryBarrier2<'_>| {
+            let dependency_info = vk::DependencyInfo::default()
+                .image_memory_barriers(core::slice::from_ref(&image_barrier));
+            unsafe {
+                self.device
+                    .cmd_pipeline_barrier2(command_buffer, &dependency_info)
+            };
+        };
+        let level_offset = |level: u32| vk::Offset3D {
+            x: (self.extent.width >> level).max(1) as i32,
+            y: (self.extent.height >> level).max(1) as i32,
+            z: 1,
+        };
+        let level_subresource = |level| {
+            vk::ImageSubresourceLayers::default()
+                .aspect_mask(self.aspect())
+                .mip_level(level)
+                .base_array_layer(0)
+                .layer_count(self.array_layers)
+        };
+
+        unsafe { self.cmd_transition(command_buffer, vk::ImageLayout::TRANSFER_DST_OPTIMAL) };
+        for level in first_level..self.mip_levels {
+            barrier(level_barrier(
+                level - 1,
+                1,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ));
+
+            let blit = vk::ImageBlit::default()
+                .src_subresource(level_subresource(level - 1))
+                .src_offsets([vk::Offset3D::default(), level_offset(level - 1)])
+                .dst_subresource(level_subresource(level))
+                .dst_offsets([vk::Offset3D::default(), level_offset(level)]);
+            unsafe {
+                self.device.cmd_blit_image(
+                    command_buffer,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    vk::Filter::LINEAR,
+                );
+            }
+        }
+
+        // the last level was only written, and the levels before first_level - 1 were never read
+        barrier(level_barrier(
+            self.mip_levels - 1,
+            1,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        ));
+        if first_level > 1 {
+            barrier(level_barrier(
+                0,
+                first_level - 1,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ));
+        }
+        unsafe { self.set_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL) };
+    }
+
+    /// The descriptor for binding the image as a storage image, it has to be in [`vk::ImageLayout::GENERAL`] when the shader runs
+    pub fn storage_image_info(&self) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo::default()
+            .image_view(self.image_view)
+            .image_layout(vk::ImageLayout::GENERAL)
+    }
+
+    /// Moves the image into [`vk::ImageLayout::GENERAL`] for a compute shader to write to,
+    /// waiting for any earlier sampling or blits of it to finish
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state
+    pub unsafe fn cmd_prepare_storage_write(&self, command_buffer: vk::CommandBuffer) {
+        unsafe { self.cmd_transition(command_buffer, vk::ImageLayout::GENERAL) };
+    }
+
+    /// Makes what a compute shader wrote visible to shaders sampling the image in [`vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL`]
+    ///
+    /// # Safety
+    /// See [`Image::cmd_prepare_storage_write`]
+    pub unsafe fn cmd_prepare_sampling(&self, command_buffer: vk::CommandBuffer) {
+        unsafe { self.cmd_transition(command_buffer, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL) };
+    }
+
+    /// Scales the first mip level over the whole of `dst_image`, which is how a reduced resolution image
+    /// written by a compute shader ends up on a swapchain image, `dst_image` is left in [`vk::ImageLayout::TRANSFER_DST_OPTIMAL`]
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state,
+    /// and `dst_image` must be a color image of `dst_extent` in `dst_layout` that was created with [`vk::ImageUsageFlags::TRANSFER_DST`]
+    pub unsafe fn cmd_blit_to(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        dst_image: vk::Image,
+        dst_layout: &mut vk::ImageLayout,
+        dst_extent: vk::Extent2D,
+        filter: vk::Filter,
+    ) {
+        unsafe {
+            self.cmd_transition(command_buffer, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+            transition_image(
+                &self.device,
+                command_buffer,
+                dst_image,
+                dst_layout,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            );
+        }
+
+        let subresource = vk::ImageSubresourceLayers::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1);
+        let corner = |extent: vk::Extent2D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: 1,
+        };
+        let blit = vk::ImageBlit::default()
+            .src_subresource(subresource)
+            .src_offsets([vk::Offset3D::default(), corner(self.extent)])
+            .dst_subresource(subresource)
+            .dst_offsets([vk::Offset3D::default(), corner(dst_extent)]);
+        unsafe {
+            self.device.cmd_blit_image(
+                command_buffer,
+                self.image,
+                self.layout(),
+                dst_image,
+                *dst_layout,
+                &[blit],
+                filter,
+            );
+        }
+    }
+}
+
+impl Drop for Image<'_> {
+    fn drop(&mut self) {
+        let resource = match unsafe { ManuallyDrop::take(&mut self.memory) } {
+            ImageMemory::Allocation(allocation) => {
+                ResourceToDestroy::ImageViewWithImage(self.image_view, self.image, allocation)
+            }
+            ImageMemory::External(memory) => {
+                ResourceToDestroy::ImageViewWithExternalImage(self.image_view, self.image, memory)
+            }
+        };
+        unsafe {
+            self.device
+                .schedule_destroy_resource(self.device.current_timeline_counter(), resource);
+        }
+    }
+}
+
+/// Images that are uploaded to are also blitted from when some of their mip levels are generated
+fn upload_usage(level_count: usize, mip_levels: u32) -> vk::ImageUsageFlags {
+    let usage = vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST;
+    if level_count < mip_levels as usize {
+        usage | vk::ImageUsageFlags::TRANSFER_SRC
+    } else {
+        usage
+    }
+}
+
+/// The number of mip levels in a full chain down to 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    width.max(height).max(1).ilog2() + 1
+}
+
+/// The aspects a view of every part of an image with `format` needs
+pub fn format_aspect(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
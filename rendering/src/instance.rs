@@ -1,14 +1,213 @@
 use ash::vk;
 use scope_guard::scope_guard;
 use std::{
-    ffi::{CStr, c_void},
+    ffi::{CStr, CString, c_void},
     ops::Deref,
+    sync::Arc,
 };
 
+/// Environment variable that overrides [`InstanceConfig::validation`] at runtime, `1`/`true` enables and `0`/`false` disables validation
+pub const VALIDATION_ENV_VAR: &str = "RENDERING_VALIDATION";
+
+/// Receives every debug messenger message that passes [`InstanceConfig::debug_message_severity`],
+/// shader printf output from [`ValidationFeatures::debug_printf`] goes to [`log_shader_printf`] instead
+pub type DebugCallback = dyn Fn(vk::DebugUtilsMessageSeverityFlagsEXT, vk::DebugUtilsMessageTypeFlagsEXT, &str)
+    + Send
+    + Sync;
+
+#[derive(Clone)]
+pub struct InstanceConfig {
+    pub application_name: CString,
+    pub application_version: u32,
+    pub engine_name: CString,
+    pub engine_version: u32,
+    /// Instance extensions to enable on top of the ones this crate requires
+    pub extensions: Vec<CString>,
+    /// Instance layers to enable on top of the ones this crate requires
+    pub layers: Vec<CString>,
+    /// Enables `VK_LAYER_KHRONOS_validation` and the debug messenger, defaults to `cfg!(debug_assertions)`
+    pub validation: bool,
+    pub validation_features: ValidationFeatures,
+    pub debug_message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    /// Defaults to [`log_debug_message`]
+    pub debug_callback: Arc<DebugCallback>,
+}
+
+impl Default for InstanceConfig {
+    fn default() -> Self {
+        Self {
+            application_name: c"Renderer".to_owned(),
+            application_version: vk::make_api_version(0, 1, 0, 0),
+            engine_name: c"Renderer".to_owned(),
+            engine_version: vk::make_api_version(0, 1, 0, 0),
+            extensions: vec![],
+            layers: vec![],
+            validation: cfg!(debug_assertions),
+            validation_features: ValidationFeatures::default(),
+            debug_message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            debug_callback: Arc::new(log_debug_message),
+        }
+    }
+}
+
+impl InstanceConfig {
+    pub fn application_name(mut self, application_name: &CStr) -> Self {
+        self.application_name = application_name.to_owned();
+        self
+    }
+
+    /// See [`vk::make_api_version`]
+    pub fn application_version(mut self, application_version: u32) -> Self {
+        self.application_version = application_version;
+        self
+    }
+
+    pub fn engine_name(mut self, engine_name: &CStr) -> Self {
+        self.engine_name = engine_name.to_owned();
+        self
+    }
+
+    /// See [`vk::make_api_version`]
+    pub fn engine_version(mut self, engine_version: u32) -> Self {
+        self.engine_version = engine_version;
+        self
+    }
+
+    pub fn push_extension(mut self, extension: &CStr) -> Self {
+        self.extensions.push(extension.to_owned());
+        self
+    }
+
+    pub fn push_layer(mut self, layer: &CStr) -> Self {
+        self.layers.push(layer.to_owned());
+        self
+    }
+
+    pub fn validation(mut self, validation: bool) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn validation_features(mut self, validation_features: ValidationFeatures) -> Self {
+        self.validation_features = validation_features;
+        self
+    }
+
+    pub fn debug_message_severity(
+        mut self,
+        debug_message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    ) -> Self {
+        self.debug_message_severity = debug_message_severity;
+        self
+    }
+
+    pub fn debug_callback(
+        mut self,
+        debug_callback: impl Fn(
+            vk::DebugUtilsMessageSeverityFlagsEXT,
+            vk::DebugUtilsMessageTypeFlagsEXT,
+            &str,
+        ) + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        self.debug_callback = Arc::new(debug_callback);
+        self
+    }
+}
+
+/// The default [`DebugCallback`], forwards messages to [`tracing`] at the matching level
+pub fn log_debug_message(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+    message: &str,
+) {
+    if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        tracing::error!(?message_types, "{message}");
+    } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        tracing::warn!(?message_types, "{message}");
+    } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        tracing::info!(?message_types, "{message}");
+    } else {
+        tracing::trace!(?message_types, "{message}");
+    }
+}
+
+/// Forwards shader `printf` output to [`tracing`] at the info level with the `shader_printf` target
+pub fn log_shader_printf(message: &str) {
+    tracing::info!(target: "shader_printf", "{message}");
+}
+
+/// Extra `VK_EXT_validation_features` checks, only used when validation is enabled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationFeatures {
+    pub gpu_assisted: bool,
+    pub synchronization: bool,
+    pub best_practices: bool,
+    /// Routes shader `printf` output to [`log_shader_printf`], the validation layer rejects this together with [`Self::gpu_assisted`]
+    pub debug_printf: bool,
+}
+
+impl ValidationFeatures {
+    pub fn gpu_assisted(mut self, gpu_assisted: bool) -> Self {
+        self.gpu_assisted = gpu_assisted;
+        self
+    }
+
+    pub fn synchronization(mut self, synchronization: bool) -> Self {
+        self.synchronization = synchronization;
+        self
+    }
+
+    pub fn best_practices(mut self, best_practices: bool) -> Self {
+        self.best_practices = best_practices;
+        self
+    }
+
+    pub fn debug_printf(mut self, debug_printf: bool) -> Self {
+        self.debug_printf = debug_printf;
+        self
+    }
+
+    fn enables(&self) -> Vec<vk::ValidationFeatureEnableEXT> {
+        let mut enables = vec![];
+        if self.gpu_assisted {
+            enables.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+            enables.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+        }
+        if self.synchronization {
+            enables.push(vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION);
+        }
+        if self.best_practices {
+            enables.push(vk::ValidationFeatureEnableEXT::BEST_PRACTICES);
+        }
+        if self.debug_printf {
+            enables.push(vk::ValidationFeatureEnableEXT::DEBUG_PRINTF);
+        }
+        enables
+    }
+}
+
 pub struct Instance<'allocator> {
     entry: ash::Entry,
     allocator: Option<vk::AllocationCallbacks<'allocator>>,
     instance: ash::Instance,
+    validation: bool,
+    surface_maintenance1: bool,
+    extensions: Vec<CString>,
+    debug_utils: Option<(ash::ext::debug_utils::Instance, vk::DebugUtilsMessengerEXT)>,
+    #[expect(
+        unused,
+        reason = "this is only accessed through the debug messenger user data"
+    )]
+    debug_callback: Box<DebugMessengerData>,
+}
+
+struct DebugMessengerData {
+    callback: Arc<DebugCallback>,
+    /// The messenger also receives info messages when debug printf is enabled, this filters the rest of them out
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
 }
 
 impl<'allocator> Instance<'allocator> {
@@ -18,21 +217,47 @@ impl<'allocator> Instance<'allocator> {
     pub unsafe fn new(
         entry: ash::Entry,
         allocator: Option<vk::AllocationCallbacks<'allocator>>,
+        config: InstanceConfig,
     ) -> Self {
-        let required_version = vk::API_VERSION_1_3;
-        let required_layers: [&CStr; _] = [
-            #[cfg(debug_assertions)]
-            c"VK_LAYER_KHRONOS_validation",
-        ];
-        let required_extensions: [&CStr; _] = [
+        let _span = tracing::info_span!("create_instance").entered();
+
+        let validation = match std::env::var(VALIDATION_ENV_VAR).as_deref() {
+            Ok("1" | "true") => true,
+            Ok("0" | "false") => false,
+            _ => config.validation,
+        };
+        let validation_feature_enables = config.validation_features.enables();
+        let debug_callback = Box::new(DebugMessengerData {
+            callback: config.debug_callback,
+            severity: config.debug_message_severity,
+        });
+        let mut debug_message_severity = config.debug_message_severity;
+        if config.validation_features.debug_printf {
+            debug_message_severity |= vk::DebugUtilsMessageSeverityFlagsEXT::INFO;
+        }
+
+        // 1.2 is enough when the device supports the synchronization2 and dynamic rendering extensions
+        let required_version = vk::API_VERSION_1_2;
+        let api_version = vk::API_VERSION_1_3;
+        let mut required_layers: Vec<&CStr> = vec![];
+        let mut required_extensions: Vec<&CStr> = vec![
             #[cfg(windows)]
             vk::KHR_WIN32_SURFACE_NAME,
             vk::KHR_SURFACE_NAME,
+        ];
+        let surface_maintenance1_extensions: [&CStr; _] = [
             vk::KHR_GET_SURFACE_CAPABILITIES2_NAME,
             vk::EXT_SURFACE_MAINTENANCE1_NAME,
-            #[cfg(debug_assertions)]
-            vk::EXT_DEBUG_UTILS_NAME,
         ];
+        required_layers.extend(config.layers.iter().map(CString::as_c_str));
+        required_extensions.extend(config.extensions.iter().map(CString::as_c_str));
+        if validation {
+            required_layers.push(c"VK_LAYER_KHRONOS_validation");
+            required_extensions.push(vk::EXT_DEBUG_UTILS_NAME);
+            if !validation_feature_enables.is_empty() {
+                required_extensions.push(vk::EXT_VALIDATION_FEATURES_NAME);
+            }
+        }
 
         {
             let version = unsafe { entry.try_enumerate_instance_version() }
@@ -55,7 +280,7 @@ impl<'allocator> Instance<'allocator> {
 
         {
             let layers = unsafe { entry.enumerate_instance_layer_properties() }.unwrap();
-            'checks: for required_layer in required_layers {
+            'checks: for &required_layer in &required_layers {
                 for layer in &layers {
                     let Ok(layer) = layer.layer_name_as_c_str() else {
                         continue;
@@ -70,10 +295,16 @@ impl<'allocator> Instance<'allocator> {
             }
         }
 
-        {
-            let extensions =
+        let surface_maintenance1 = {
+            let mut extensions =
                 unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap();
-            'checks: for required_extension in required_extensions {
+            for &required_layer in &required_layers {
+                extensions.extend(
+                    unsafe { entry.enumerate_instance_extension_properties(Some(required_layer)) }
+                        .unwrap(),
+                );
+            }
+            'checks: for &required_extension in &required_extensions {
                 for extension in &extensions {
                     let Ok(extension) = extension.extension_name_as_c_str() else {
                         continue;
@@ -86,17 +317,63 @@ impl<'allocator> Instance<'allocator> {
                 let required_extension_name = required_extension.to_string_lossy();
                 panic!("Unable to find vulkan extension '{required_extension_name}'");
             }
-        }
+
+            let surface_maintenance1 = surface_maintenance1_extensions.iter().all(|&name| {
+                extensions
+                    .iter()
+                    .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+            });
+            if surface_maintenance1 {
+                required_extensions.extend(surface_maintenance1_extensions);
+            } else {
+                tracing::warn!(
+                    "Unable to find vulkan extension '{}', falling back to presenting without present fences",
+                    vk::EXT_SURFACE_MAINTENANCE1_NAME.to_string_lossy(),
+                );
+            }
+
+            // every window system that is available, which one a window uses is only known once a surface is made
+            #[cfg(not(windows))]
+            required_extensions.extend(
+                [
+                    vk::KHR_XLIB_SURFACE_NAME,
+                    vk::KHR_XCB_SURFACE_NAME,
+                    vk::KHR_WAYLAND_SURFACE_NAME,
+                ]
+                .into_iter()
+                .filter(|&name| {
+                    extensions
+                        .iter()
+                        .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+                }),
+            );
+
+            // labels show up in tools like RenderDoc and Nsight, which don't need validation to be enabled
+            let debug_utils_available = extensions.iter().any(|extension| {
+                extension.extension_name_as_c_str() == Ok(vk::EXT_DEBUG_UTILS_NAME)
+            });
+            if debug_utils_available && !required_extensions.contains(&vk::EXT_DEBUG_UTILS_NAME) {
+                required_extensions.push(vk::EXT_DEBUG_UTILS_NAME);
+            }
+
+            surface_maintenance1
+        };
 
         let application_info = vk::ApplicationInfo::default()
-            .application_name(c"Renderer")
-            .application_version(vk::make_api_version(0, 1, 0, 0))
-            .engine_name(c"Renderer")
-            .engine_version(vk::make_api_version(0, 1, 0, 0))
-            .api_version(required_version);
-
-        let required_layer_ptrs = required_layers.map(|layer| layer.as_ptr());
-        let required_extension_ptrs = required_extensions.map(|extension| extension.as_ptr());
+            .application_name(&config.application_name)
+            .application_version(config.application_version)
+            .engine_name(&config.engine_name)
+            .engine_version(config.engine_version)
+            .api_version(api_version);
+
+        let required_layer_ptrs = required_layers
+            .iter()
+            .map(|layer| layer.as_ptr())
+            .collect::<Vec<_>>();
+        let required_extension_ptrs = required_extensions
+            .iter()
+            .map(|extension| extension.as_ptr())
+            .collect::<Vec<_>>();
         let mut instance_create_info = vk::InstanceCreateInfo::default()
             .application_info(&application_info)
             .enabled_layer_names(&required_layer_ptrs)
@@ -106,42 +383,76 @@ impl<'allocator> Instance<'allocator> {
             message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
             message_types: vk::DebugUtilsMessageTypeFlagsEXT,
             p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
-            #[expect(unused)] p_user_data: *mut c_void,
+            p_user_data: *mut c_void,
         ) -> vk::Bool32 {
-            let message = unsafe {
-                (*p_callback_data)
-                    .message_as_c_str()
-                    .unwrap_or(c"")
-                    .to_string_lossy()
-            };
-            eprintln!("{message_severity:?} {message_types:?} {message}");
+            let callback_data = unsafe { &*p_callback_data };
+            let message = unsafe { callback_data.message_as_c_str() }
+                .unwrap_or(c"")
+                .to_string_lossy();
+            let debug_printf = unsafe { callback_data.message_id_name_as_c_str() }
+                .is_some_and(|name| name.to_bytes().ends_with(b"DEBUG-PRINTF"));
+            let debug_callback = unsafe { &*p_user_data.cast::<DebugMessengerData>() };
+            if debug_printf {
+                log_shader_printf(&message);
+            } else if debug_callback.severity.intersects(message_severity) {
+                (debug_callback.callback)(message_severity, message_types, &message);
+            }
             vk::FALSE
         }
 
         let mut debug_messenger_create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
-            .message_severity(
-                vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                    | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
-            )
+            .message_severity(debug_message_severity)
             .message_type(
                 vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
                     | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
                     | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
             )
-            .pfn_user_callback(Some(debug_message_callback));
-        if cfg!(debug_assertions) {
+            .pfn_user_callback(Some(debug_message_callback))
+            .user_data((&raw const *debug_callback).cast_mut().cast());
+        let mut validation_features = vk::ValidationFeaturesEXT::default()
+            .enabled_validation_features(&validation_feature_enables);
+        if validation {
             instance_create_info = instance_create_info.push_next(&mut debug_messenger_create_info);
+            if !validation_feature_enables.is_empty() {
+                instance_create_info = instance_create_info.push_next(&mut validation_features);
+            }
         }
 
         let instance =
             unsafe { entry.create_instance(&instance_create_info, allocator.as_ref()) }.unwrap();
+        tracing::info!(
+            validation,
+            layers = ?required_layers,
+            extensions = ?required_extensions,
+            "Created vulkan instance"
+        );
         let cleanup = scope_guard!(|| unsafe { instance.destroy_instance(allocator.as_ref()) });
 
+        let debug_utils = if validation {
+            let debug_utils_funcs = ash::ext::debug_utils::Instance::new(&entry, &instance);
+            let debug_messenger = unsafe {
+                debug_utils_funcs
+                    .create_debug_utils_messenger(&debug_messenger_create_info, allocator.as_ref())
+            }
+            .unwrap();
+            Some((debug_utils_funcs, debug_messenger))
+        } else {
+            None
+        };
+
         cleanup.forget();
         Self {
             entry,
             allocator,
             instance,
+            validation,
+            surface_maintenance1,
+            extensions: required_extensions
+                .iter()
+                .map(|&extension| extension.to_owned())
+                .collect(),
+            debug_utils,
+            debug_callback,
         }
     }
 
@@ -152,6 +463,23 @@ impl<'allocator> Instance<'allocator> {
     pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
         self.allocator.as_ref()
     }
+
+    /// Whether validation layers are enabled, `VK_EXT_debug_utils` is always enabled with them
+    pub fn validation(&self) -> bool {
+        self.validation
+    }
+
+    /// Whether `extension` was enabled, either by [`InstanceConfig::extensions`] or by this crate
+    pub fn has_extension(&self, extension: &CStr) -> bool {
+        self.extensions
+            .iter()
+            .any(|enabled| enabled.as_c_str() == extension)
+    }
+
+    /// Whether `VK_EXT_surface_maintenance1` is enabled, which is needed for [`DeviceFeature::SwapchainMaintenance1`](crate::DeviceFeature::SwapchainMaintenance1)
+    pub fn surface_maintenance1(&self) -> bool {
+        self.surface_maintenance1
+    }
 }
 
 impl Deref for Instance<'_> {
@@ -164,6 +492,11 @@ impl Deref for Instance<'_> {
 
 impl Drop for Instance<'_> {
     fn drop(&mut self) {
+        if let Some((debug_utils_funcs, debug_messenger)) = &self.debug_utils {
+            unsafe {
+                debug_utils_funcs.destroy_debug_utils_messenger(*debug_messenger, self.allocator())
+            };
+        }
         unsafe { self.instance.destroy_instance(self.allocator()) };
     }
 }
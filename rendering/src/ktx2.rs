@@ -0,0 +1,268 @@
+use crate::{Device, Image, format_level_size};
+use ash::vk;
+use ktx2::{Reader, SupercompressionScheme};
+use std::{io::Read, path::Path, sync::Arc};
+
+impl<'allocator> Image<'allocator> {
+    /// Loads a KTX2 file, see [`Image::from_ktx2_bytes`]
+    pub fn from_ktx2_path(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        path: &Path,
+    ) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|error| format!("Unable to read '{}': {error}", path.display()))?;
+        Self::from_ktx2_bytes(device, name, &bytes)
+    }
+
+    /// Uploads every mip level of a 2D KTX2 texture at once, the levels are copied as is so no decoding happens at load time
+    ///
+    /// The texture must already be in a format the device can sample, optionally Zstandard supercompressed,
+    /// Basis Universal textures (BasisLZ or UASTC) need transcoding, which is not supported
+    ///
+    /// Levels shorter than their format and size need are an error, as is Zstandard data that doesn't decompress to the length the file says
+    pub fn from_ktx2_bytes(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        bytes: &[u8],
+    ) -> Result<Self, String> {
+        let texture = Ktx2Texture::parse(bytes)?;
+        let format = texture.format;
+
+        let required_features =
+            vk::FormatFeatureFlags::SAMPLED_IMAGE | vk::FormatFeatureFlags::TRANSFER_DST;
+        if !device
+            .format_features(format, vk::ImageTiling::OPTIMAL)
+            .contains(required_features)
+        {
+            return Err(format!("{format:?} can't be sampled on this device"));
+        }
+
+        let levels = texture.levels.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        Ok(Self::from_mip_levels(
+            device,
+            name,
+            format,
+            texture.width,
+            texture.height,
+            levels.len() as u32,
+            &levels,
+        ))
+    }
+}
+
+/// A 2D KTX2 texture with every mip level decompressed and checked against its format and size,
+/// parsed separately from the upload so malformed files are rejected before anything is created on the device
+struct Ktx2Texture {
+    format: vk::Format,
+    width: u32,
+    height: u32,
+    levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2Texture {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let reader = Reader::new(bytes).map_err(|error| format!("Invalid KTX2 file: {error:?}"))?;
+        let header = reader.header();
+
+        match header.supercompression_scheme {
+            None | Some(SupercompressionScheme::Zstandard) => {}
+            Some(SupercompressionScheme::BasisLZ) => {
+                return Err(
+                    "BasisLZ supercompressed Basis Universal textures need transcoding, which is not supported"
+                        .to_owned(),
+                );
+            }
+            Some(scheme) => return Err(format!("{scheme:?} supercompression is not supported")),
+        }
+        let Some(format) = header.format else {
+            return Err(
+                "UASTC Basis Universal textures need transcoding, which is not supported"
+                    .to_owned(),
+            );
+        };
+        let format = vk::Format::from_raw(format.value() as i32);
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            return Err(format!(
+                "Only 2D textures are supported, but this has a depth of {}, {} layers, and {} faces",
+                header.pixel_depth, header.layer_count, header.face_count,
+            ));
+        }
+
+        // the reader doesn't check the width, which it only makes sure isn't 0
+        let width = header.pixel_width;
+        let height = header.pixel_height.max(1);
+        let max_level_count = u32::BITS - width.max(height).leading_zeros();
+        if header.level_count > max_level_count {
+            return Err(format!(
+                "A {width}x{height} texture has at most {max_level_count} mip levels, but this has {}",
+                header.level_count,
+            ));
+        }
+
+        let levels = reader
+            .levels()
+            .enumerate()
+            .map(|(mip_level, level)| {
+                let mut data = if header.supercompression_scheme.is_some() {
+                    // the length comes from the file, so it only limits the decompression rather than being allocated up front
+                    let mut data = vec![];
+                    ruzstd::decoding::StreamingDecoder::new(level.data)
+                        .map_err(|error| error.to_string())
+                        .and_then(|decoder| {
+                            decoder
+                                .take(level.uncompressed_byte_length.saturating_add(1))
+                                .read_to_end(&mut data)
+                                .map_err(|error| error.to_string())
+                        })
+                        .map_err(|error| {
+                            format!("Unable to decompress mip level {mip_level}: {error}")
+                        })?;
+                    if data.len() as u64 != level.uncompressed_byte_length {
+                        return Err(format!(
+                            "Mip level {mip_level} doesn't decompress to the {} bytes the file says",
+                            level.uncompressed_byte_length,
+                        ));
+                    }
+                    data
+                } else {
+                    level.data.to_vec()
+                };
+
+                let level_width = (width >> mip_level).max(1);
+                let level_height = (height >> mip_level).max(1);
+                let Some(expected_length) = format_level_size(format, level_width, level_height)
+                else {
+                    return Err(format!("Level sizes of {format:?} are unknown"));
+                };
+                if (data.len() as u64) < expected_length {
+                    return Err(format!(
+                        "Mip level {mip_level} has {} bytes, but a {level_width}x{level_height} {format:?} level needs {expected_length} bytes",
+                        data.len(),
+                    ));
+                }
+                // padding would shift where the next level starts in the staging buffer
+                data.truncate(expected_length as usize);
+                Ok(data)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASIS_LZ: u32 = 1;
+    const ZSTANDARD: u32 = 2;
+
+    /// A KTX2 file with `level_count` entries in its level index, filled from `levels` as (data, uncompressed length),
+    /// and an empty data format descriptor, which the reader only bounds checks
+    fn file(
+        format: vk::Format,
+        [width, height]: [u32; 2],
+        level_count: u32,
+        supercompression_scheme: u32,
+        levels: &[(&[u8], u64)],
+    ) -> Vec<u8> {
+        let index_entries = level_count.max(1) as usize;
+        let dfd_offset = 80 + 24 * index_entries;
+        let mut data_offset = dfd_offset + 4;
+
+        let mut bytes = b"\xABKTX 20\xBB\r\n\x1A\n".to_vec();
+        for value in [
+            format.as_raw() as u32,
+            1,
+            width,
+            height,
+            0,
+            0,
+            1,
+            level_count,
+            supercompression_scheme,
+            dfd_offset as u32,
+            4,
+            0,
+            0,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend([0; 16]);
+        for index in 0..index_entries {
+            let (data, uncompressed_length) = levels.get(index).copied().unwrap_or((&[], 0));
+            for value in [data_offset as u64, data.len() as u64, uncompressed_length] {
+                bytes.extend(value.to_le_bytes());
+            }
+            data_offset += data.len();
+        }
+        bytes.extend(4u32.to_le_bytes());
+        for (data, _) in levels {
+            bytes.extend_from_slice(data);
+        }
+        // the reader wants the data format descriptor to end before the file does, even without any levels
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn every_level_is_read() {
+        let levels = [[1; 32].as_slice(), &[2; 8], &[3; 4]];
+        let bytes = file(
+            vk::Format::R8G8B8A8_UNORM,
+            [4, 2],
+            3,
+            0,
+            &levels.map(|level| (level, level.len() as u64)),
+        );
+
+        let texture = Ktx2Texture::parse(&bytes).unwrap();
+        assert_eq!(texture.format, vk::Format::R8G8B8A8_UNORM);
+        assert_eq!([texture.width, texture.height], [4, 2]);
+        assert_eq!(texture.levels, levels.map(<[u8]>::to_vec));
+    }
+
+    #[test]
+    fn more_levels_than_the_size_allows_are_rejected() {
+        let bytes = file(vk::Format::R8G8B8A8_UNORM, [2, 2], 3, 0, &[]);
+        assert!(Ktx2Texture::parse(&bytes).is_err());
+
+        // more levels than bits in the width would shift the width out of range
+        let bytes = file(vk::Format::R8G8B8A8_UNORM, [1, 1], 40, 0, &[]);
+        assert!(Ktx2Texture::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn short_levels_are_rejected() {
+        let bytes = file(vk::Format::R8G8B8A8_UNORM, [2, 2], 1, 0, &[(&[0; 15], 15)]);
+        assert!(Ktx2Texture::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn uncompressed_lengths_are_not_trusted() {
+        // allocating the length up front would abort rather than fail
+        let bytes = file(
+            vk::Format::R8G8B8A8_UNORM,
+            [1, 1],
+            1,
+            ZSTANDARD,
+            &[(&[0; 8], u64::MAX)],
+        );
+        assert!(Ktx2Texture::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn basis_universal_textures_are_rejected() {
+        let basis_lz = file(vk::Format::UNDEFINED, [1, 1], 1, BASIS_LZ, &[]);
+        assert!(Ktx2Texture::parse(&basis_lz).is_err());
+
+        let uastc = file(vk::Format::UNDEFINED, [1, 1], 1, 0, &[(&[0; 16], 16)]);
+        assert!(Ktx2Texture::parse(&uastc).is_err());
+    }
+}
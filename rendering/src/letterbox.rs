@@ -0,0 +1,78 @@
+use crate::{BarrierBuilder, Device, ImageUsage, make_subresource_range};
+use ash::vk;
+
+/// The largest rectangle with the aspect ratio of `logical_size` that fits centered in `extent`,
+/// the rest of `extent` is left for the black bars
+pub fn letterbox_rect(extent: vk::Extent2D, logical_size: vk::Extent2D) -> vk::Rect2D {
+    let (width, height) = (extent.width as u64, extent.height as u64);
+    let (logical_width, logical_height) = (
+        logical_size.width.max(1) as u64,
+        logical_size.height.max(1) as u64,
+    );
+    let inner = if width * logical_height > height * logical_width {
+        // wider than the logical size, so the bars go on the left and right
+        vk::Extent2D {
+            width: (height * logical_width / logical_height).max(1) as u32,
+            height: extent.height,
+        }
+    } else {
+        vk::Extent2D {
+            width: extent.width,
+            height: (width * logical_height / logical_width).max(1) as u32,
+        }
+    };
+    vk::Rect2D {
+        offset: vk::Offset2D {
+            x: ((extent.width - inner.width) / 2) as i32,
+            y: ((extent.height - inner.height) / 2) as i32,
+        },
+        extent: inner,
+    }
+}
+
+/// Maps `position`, in pixels of the whole image, into `0..logical_size` within `rect`,
+/// `None` when it is in the bars outside of `rect`
+pub fn map_to_logical(
+    rect: vk::Rect2D,
+    logical_size: vk::Extent2D,
+    position: [f64; 2],
+) -> Option<[f64; 2]> {
+    let x = (position[0] - rect.offset.x as f64) / rect.extent.width as f64;
+    let y = (position[1] - rect.offset.y as f64) / rect.extent.height as f64;
+    ((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y)).then_some([
+        x * logical_size.width as f64,
+        y * logical_size.height as f64,
+    ])
+}
+
+/// Clears all of a freshly acquired swapchain image to `color`, leaving it in [`vk::ImageLayout::TRANSFER_DST_OPTIMAL`]
+///
+/// # Safety
+/// `command_buffer` must be in the recording state and `image` must have been created with [`vk::ImageUsageFlags::TRANSFER_DST`]
+pub(crate) unsafe fn cmd_clear_bars(
+    device: &Device<'_>,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    image_layout: &mut vk::ImageLayout,
+    color: [f32; 4],
+) {
+    debug_assert_eq!(*image_layout, vk::ImageLayout::UNDEFINED);
+    unsafe {
+        BarrierBuilder::new()
+            .image(
+                image,
+                vk::ImageAspectFlags::COLOR,
+                ImageUsage::Acquired,
+                ImageUsage::TransferDst,
+            )
+            .record(device, command_buffer);
+        device.cmd_clear_color_image(
+            command_buffer,
+            image,
+            ImageUsage::TransferDst.layout(),
+            &vk::ClearColorValue { float32: color },
+            &[make_subresource_range(vk::ImageAspectFlags::COLOR)],
+        );
+    }
+    *image_layout = ImageUsage::TransferDst.layout();
+}
@@ -1,13 +1,82 @@
+mod adapter;
+mod alignment;
+mod allocator_report;
+mod anti_aliasing;
+mod barrier;
+mod bindless_textures;
 mod buffer;
+mod buffer_slice;
+mod command_pool;
+mod copy;
+mod crash_diagnostics;
+mod debug_draw;
 mod device;
+mod device_config;
+mod external_memory;
+mod external_semaphore;
+mod frame_limiter;
+#[cfg(feature = "test-support")]
+mod golden_image;
+mod gpu_ptr;
+mod image;
 mod instance;
+#[cfg(feature = "ktx2")]
+mod ktx2;
+mod letterbox;
+mod per_frame;
+mod pipeline;
+mod pipeline_cache;
+mod query_pool;
+mod render_pass_fallback;
+mod sampler;
 mod shader;
+#[cfg(feature = "shader-compiler")]
+mod shader_compiler;
+mod shader_reflection;
+mod shader_watcher;
+mod sparse_buffer;
 mod surface;
 mod swapchain;
+mod sync_objects;
+mod sync_tracker;
+#[cfg(feature = "image")]
+mod texture;
+mod tonemap;
 
+pub use adapter::*;
+pub use alignment::*;
+pub use allocator_report::*;
+pub use anti_aliasing::*;
+pub use barrier::*;
+pub use bindless_textures::*;
 pub use buffer::*;
+pub use buffer_slice::*;
+pub use command_pool::*;
+pub use copy::*;
+pub use debug_draw::*;
 pub use device::*;
+pub use device_config::*;
+pub use external_memory::*;
+pub use external_semaphore::*;
+pub use frame_limiter::*;
+#[cfg(feature = "test-support")]
+pub use golden_image::*;
+pub use gpu_ptr::*;
+pub use image::*;
 pub use instance::*;
+pub use letterbox::*;
+pub use per_frame::*;
+pub use pipeline::*;
+pub use query_pool::*;
+pub use sampler::*;
 pub use shader::*;
+#[cfg(feature = "shader-compiler")]
+pub use shader_compiler::*;
+pub use shader_reflection::*;
+pub use shader_watcher::*;
+pub use sparse_buffer::*;
 pub use surface::*;
 pub use swapchain::*;
+pub use sync_objects::*;
+pub use sync_tracker::*;
+pub use tonemap::*;
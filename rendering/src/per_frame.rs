@@ -0,0 +1,90 @@
+use crate::FRAMES_IN_FLIGHT_COUNT;
+use std::ops::{Deref, DerefMut};
+
+/// One `T` per frame in flight, indexed with the frame index [`Swapchain::try_next_frame`](crate::Swapchain::try_next_frame) gives its callback,
+/// for anything the cpu writes to while earlier frames could still be reading their own copy
+///
+/// Derefs to a slice, so it is indexed and iterated like the array it replaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PerFrame<T>([T; FRAMES_IN_FLIGHT_COUNT]);
+
+impl<T> PerFrame<T> {
+    /// Calls `f` with every frame index in order
+    pub fn new(f: impl FnMut(usize) -> T) -> Self {
+        Self(std::array::from_fn(f))
+    }
+
+    pub const fn from_array(values: [T; FRAMES_IN_FLIGHT_COUNT]) -> Self {
+        Self(values)
+    }
+
+    pub fn into_array(self) -> [T; FRAMES_IN_FLIGHT_COUNT] {
+        self.0
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> PerFrame<U> {
+        PerFrame(self.0.map(f))
+    }
+}
+
+impl<T: Clone> PerFrame<T> {
+    pub fn splat(value: T) -> Self {
+        Self::new(|_| value.clone())
+    }
+}
+
+impl<T: Default> Default for PerFrame<T> {
+    fn default() -> Self {
+        Self::new(|_| T::default())
+    }
+}
+
+impl<T> Deref for PerFrame<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for PerFrame<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> IntoIterator for PerFrame<T> {
+    type Item = T;
+    type IntoIter = std::array::IntoIter<T, FRAMES_IN_FLIGHT_COUNT>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PerFrame<T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut PerFrame<T> {
+    type Item = &'a mut T;
+    type IntoIter = core::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+impl<T> TryFrom<Vec<T>> for PerFrame<T> {
+    type Error = Vec<T>;
+
+    /// Fails when there isn't exactly one value per frame in flight
+    fn try_from(values: Vec<T>) -> Result<Self, Self::Error> {
+        values.try_into().map(Self)
+    }
+}
@@ -0,0 +1,722 @@
+use crate::{Device, DeviceFeature, Instance, ResourceToDestroy, Shader, ShaderReflection};
+use ash::vk;
+use bytemuck::NoUninit;
+use std::{ffi::CStr, sync::Arc};
+
+/// A push constant range holding a whole `T` at `offset`, for [`PipelineLayout::new`]
+pub fn push_constant_range<T: NoUninit>(
+    stage_flags: vk::ShaderStageFlags,
+    offset: u32,
+) -> vk::PushConstantRange {
+    vk::PushConstantRange {
+        stage_flags,
+        offset,
+        size: size_of::<T>() as u32,
+    }
+}
+
+pub struct PipelineLayout<'allocator> {
+    device: Arc<Device<'allocator>>,
+    pipeline_layout: vk::PipelineLayout,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    push_constant_stages: vk::ShaderStageFlags,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+}
+
+impl<'allocator> PipelineLayout<'allocator> {
+    pub fn new(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
+    ) -> Self {
+        let max_size = device.limits().max_push_constants_size;
+        let mut seen_stages = vk::ShaderStageFlags::empty();
+        for range in push_constant_ranges {
+            assert!(
+                range.offset.is_multiple_of(4) && range.size.is_multiple_of(4) && range.size > 0,
+                "the push constant range {range:?} of '{name}' has to be a non empty multiple of 4 bytes",
+            );
+            assert!(
+                range.offset + range.size <= max_size,
+                "the push constant range {range:?} of '{name}' goes past the {max_size} bytes of push constants the device supports",
+            );
+            assert!(
+                !seen_stages.intersects(range.stage_flags),
+                "the push constant ranges of '{name}' share {:?}, each stage can only be in one range",
+                seen_stages & range.stage_flags,
+            );
+            seen_stages |= range.stage_flags;
+        }
+
+        let create_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(set_layouts)
+            .push_constant_ranges(push_constant_ranges);
+        let pipeline_layout =
+            unsafe { device.create_pipeline_layout(&create_info, device.allocator()) }.unwrap();
+        device.track_resource(pipeline_layout, name);
+        Self {
+            device,
+            pipeline_layout,
+            push_constant_ranges: push_constant_ranges.to_vec(),
+            push_constant_stages: seen_stages,
+            set_layouts: vec![],
+        }
+    }
+
+    /// Creates the descriptor set layouts and push constant range from the [`Shader::reflection`] of every shader,
+    /// resources used by several shaders get the stages of all of them
+    ///
+    /// Runtime sized descriptor arrays aren't supported, use [`PipelineLayout::new`] for those
+    pub fn from_shaders(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        shaders: &[&Shader<'allocator>],
+    ) -> Self {
+        let ShaderReflection {
+            push_constant_range,
+            descriptor_bindings: bindings,
+            ..
+        } = ShaderReflection::merge(
+            &shaders
+                .iter()
+                .map(|shader| shader.reflection())
+                .collect::<Vec<_>>(),
+        );
+
+        let set_count = bindings.iter().map(|binding| binding.set + 1).max();
+        let set_layouts = (0..set_count.unwrap_or(0))
+            .map(|set| {
+                let set_bindings = bindings
+                    .iter()
+                    .filter(|binding| binding.set == set)
+                    .map(|binding| {
+                        vk::DescriptorSetLayoutBinding::default()
+                            .binding(binding.binding)
+                            .descriptor_type(binding.descriptor_type)
+                            .descriptor_count(binding.descriptor_count.unwrap_or_else(|| {
+                                panic!(
+                                    "set {set} binding {} is a runtime sized array",
+                                    binding.binding
+                                )
+                            }))
+                            .stage_flags(binding.stage_flags)
+                    })
+                    .collect::<Vec<_>>();
+                let create_info =
+                    vk::DescriptorSetLayoutCreateInfo::default().bindings(&set_bindings);
+                let set_layout = unsafe {
+                    device.create_descriptor_set_layout(&create_info, device.allocator())
+                }
+                .unwrap();
+                device.track_resource(set_layout, &format!("{name} Set {set}"));
+                set_layout
+            })
+            .collect::<Vec<_>>();
+
+        let mut pipeline_layout =
+            Self::new(device, name, &set_layouts, push_constant_range.as_slice());
+        pipeline_layout.set_layouts = set_layouts;
+        pipeline_layout
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::PipelineLayout {
+        self.pipeline_layout
+    }
+
+    /// Every stage that can access push constants, which is what `cmd_push_constants` has to be called with
+    pub fn push_constant_stages(&self) -> vk::ShaderStageFlags {
+        self.push_constant_stages
+    }
+
+    pub fn push_constant_ranges(&self) -> &[vk::PushConstantRange] {
+        &self.push_constant_ranges
+    }
+
+    /// Pushes `value` at `offset`, panicking instead of silently corrupting the push constants
+    /// when it doesn't fit in the ranges of this layout or `stage_flags` don't match them
+    ///
+    /// # Safety
+    /// See [`ash::Device::cmd_push_constants`], `command_buffer` must be in the recording state
+    pub unsafe fn cmd_push_constants<T: NoUninit>(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        value: &T,
+    ) {
+        let bytes = bytemuck::bytes_of(value);
+        self.validate_push_constants(stage_flags, offset, bytes.len() as u32);
+        unsafe {
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                stage_flags,
+                offset,
+                bytes,
+            );
+        };
+    }
+
+    fn validate_push_constants(&self, stage_flags: vk::ShaderStageFlags, offset: u32, size: u32) {
+        assert!(
+            offset.is_multiple_of(4) && size.is_multiple_of(4) && size > 0,
+            "push constants have to be pushed as a non empty multiple of 4 bytes, got {size} bytes at offset {offset}",
+        );
+        assert!(
+            !stage_flags.is_empty(),
+            "push constants have to be pushed for at least one stage"
+        );
+        let end = offset + size;
+        for range in &self.push_constant_ranges {
+            let range_end = range.offset + range.size;
+            if range.stage_flags.intersects(stage_flags) {
+                assert!(
+                    range.offset <= offset && end <= range_end,
+                    "pushing {size} bytes at offset {offset} for {stage_flags:?} doesn't fit in the push constant range {range:?}",
+                );
+            }
+            if range.offset < end && offset < range_end {
+                assert!(
+                    stage_flags.contains(range.stage_flags),
+                    "pushing bytes {offset}..{end} for {stage_flags:?} has to include every stage of the push constant range {range:?} it overlaps",
+                );
+            }
+        }
+        assert!(
+            self.push_constant_stages.contains(stage_flags),
+            "{:?} can't access push constants in this layout, only {:?} can",
+            stage_flags & !self.push_constant_stages,
+            self.push_constant_stages,
+        );
+    }
+
+    /// The descriptor set layouts created by [`PipelineLayout::from_shaders`], indexed by set number
+    pub fn set_layouts(&self) -> &[vk::DescriptorSetLayout] {
+        &self.set_layouts
+    }
+}
+
+impl Drop for PipelineLayout<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::PipelineLayout(self.pipeline_layout),
+            );
+            for &set_layout in &self.set_layouts {
+                self.device.schedule_destroy_resource(
+                    self.device.current_timeline_counter(),
+                    ResourceToDestroy::DescriptorSetLayout(set_layout),
+                );
+            }
+        }
+    }
+}
+
+pub struct Pipeline<'allocator> {
+    device: Arc<Device<'allocator>>,
+    pipeline: vk::Pipeline,
+    bind_point: vk::PipelineBindPoint,
+}
+
+impl<'allocator> Pipeline<'allocator> {
+    /// # Safety
+    /// `pipeline` must have been created from `device` and must not be destroyed by anything else
+    pub unsafe fn from_raw(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        pipeline: vk::Pipeline,
+        bind_point: vk::PipelineBindPoint,
+    ) -> Self {
+        device.track_resource(pipeline, name);
+        Self {
+            device,
+            pipeline,
+            bind_point,
+        }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn bind_point(&self) -> vk::PipelineBindPoint {
+        self.bind_point
+    }
+}
+
+impl Drop for Pipeline<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::Pipeline(self.pipeline),
+            );
+        }
+    }
+}
+
+/// Builds graphics pipelines for dynamic rendering, with a dynamic viewport and scissor
+///
+/// Defaults to a triangle list with no culling, no depth attachment, and a single unblended color attachment
+pub struct GraphicsPipelineBuilder<'a> {
+    layout: vk::PipelineLayout,
+    stages: Vec<vk::PipelineShaderStageCreateInfo<'a>>,
+    topology: vk::PrimitiveTopology,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    polygon_mode: vk::PolygonMode,
+    color_attachment_formats: Vec<vk::Format>,
+    blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    depth_attachment_format: vk::Format,
+    depth_compare_op: vk::CompareOp,
+    depth_write: bool,
+}
+
+impl<'a> GraphicsPipelineBuilder<'a> {
+    /// `color_attachment_format` is usually [`Swapchain::format`](crate::Swapchain::format)
+    pub fn new(layout: &PipelineLayout<'_>, color_attachment_format: vk::Format) -> Self {
+        Self {
+            layout: layout.handle(),
+            stages: vec![],
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            polygon_mode: vk::PolygonMode::FILL,
+            color_attachment_formats: vec![color_attachment_format],
+            blend_attachments: vec![
+                vk::PipelineColorBlendAttachmentState::default()
+                    .color_write_mask(vk::ColorComponentFlags::RGBA),
+            ],
+            depth_attachment_format: vk::Format::UNDEFINED,
+            depth_compare_op: vk::CompareOp::LESS,
+            depth_write: true,
+        }
+    }
+
+    pub fn stage(
+        mut self,
+        stage: vk::ShaderStageFlags,
+        shader: &Shader<'_>,
+        entry_point: &'a CStr,
+    ) -> Self {
+        self.stages.push(
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(stage)
+                .module(shader.handle())
+                .name(entry_point),
+        );
+        self
+    }
+
+    pub fn vertex(self, shader: &Shader<'_>, entry_point: &'a CStr) -> Self {
+        self.stage(vk::ShaderStageFlags::VERTEX, shader, entry_point)
+    }
+
+    pub fn fragment(self, shader: &Shader<'_>, entry_point: &'a CStr) -> Self {
+        self.stage(vk::ShaderStageFlags::FRAGMENT, shader, entry_point)
+    }
+
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags, front_face: vk::FrontFace) -> Self {
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    /// Replaces the blend state of every color attachment
+    pub fn blend(mut self, blend_attachment: vk::PipelineColorBlendAttachmentState) -> Self {
+        self.blend_attachments.fill(blend_attachment);
+        self
+    }
+
+    /// Replaces the default color attachment, with one blend state per attachment
+    pub fn color_attachments(
+        mut self,
+        formats: &[vk::Format],
+        blend_attachment: vk::PipelineColorBlendAttachmentState,
+    ) -> Self {
+        self.color_attachment_formats = formats.to_vec();
+        self.blend_attachments = vec![blend_attachment; formats.len()];
+        self
+    }
+
+    pub fn depth_attachment(
+        mut self,
+        format: vk::Format,
+        compare_op: vk::CompareOp,
+        write: bool,
+    ) -> Self {
+        self.depth_attachment_format = format;
+        self.depth_compare_op = compare_op;
+        self.depth_write = write;
+        self
+    }
+
+    pub fn build<'allocator>(
+        self,
+        device: Arc<Device<'allocator>>,
+        name: &str,
+    ) -> Pipeline<'allocator> {
+        let pipeline = self.create(&device, GraphicsPipelineLibrary::ALL_PARTS, false);
+        unsafe { Pipeline::from_raw(device, name, pipeline, vk::PipelineBindPoint::GRAPHICS) }
+    }
+
+    /// Builds only the `parts` of the pipeline, which are linked into a full pipeline with [`GraphicsPipelineLibrary::link`],
+    /// shader stages that don't belong to `parts` are left out
+    ///
+    /// Requires [`DeviceFeature::GraphicsPipelineLibrary`](crate::DeviceFeature::GraphicsPipelineLibrary)
+    pub fn build_library<'allocator>(
+        &self,
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        parts: vk::GraphicsPipelineLibraryFlagsEXT,
+    ) -> GraphicsPipelineLibrary<'allocator> {
+        assert!(
+            device
+                .capabilities()
+                .has_feature(DeviceFeature::GraphicsPipelineLibrary),
+            "Building a graphics pipeline library requires {:?}",
+            DeviceFeature::GraphicsPipelineLibrary,
+        );
+        assert!(
+            !parts.is_empty() && GraphicsPipelineLibrary::ALL_PARTS.contains(parts),
+            "Invalid graphics pipeline library parts {parts:?}",
+        );
+
+        let pipeline = self.create(&device, parts, true);
+        GraphicsPipelineLibrary {
+            pipeline: unsafe {
+                Pipeline::from_raw(device, name, pipeline, vk::PipelineBindPoint::GRAPHICS)
+            },
+            parts,
+        }
+    }
+
+    fn create(
+        &self,
+        device: &Device<'_>,
+        parts: vk::GraphicsPipelineLibraryFlagsEXT,
+        library: bool,
+    ) -> vk::Pipeline {
+        let pre_rasterization =
+            parts.contains(vk::GraphicsPipelineLibraryFlagsEXT::PRE_RASTERIZATION_SHADERS);
+        let fragment_shader = parts.contains(vk::GraphicsPipelineLibraryFlagsEXT::FRAGMENT_SHADER);
+        let stages = self
+            .stages
+            .iter()
+            .copied()
+            .filter(|stage| {
+                if stage.stage == vk::ShaderStageFlags::FRAGMENT {
+                    fragment_shader
+                } else {
+                    pre_rasterization
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default();
+        let input_assembly_state =
+            vk::PipelineInputAssemblyStateCreateInfo::default().topology(self.topology);
+        let viewport_state = vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::default()
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+        let mut rendering_create_info = vk::PipelineRenderingCreateInfo::default()
+            .color_attachment_formats(&self.color_attachment_formats)
+            .depth_attachment_format(self.depth_attachment_format);
+        let color_blend_state =
+            vk::PipelineColorBlendStateCreateInfo::default().attachments(&self.blend_attachments);
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(self.polygon_mode)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .line_width(1.0);
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(self.depth_attachment_format != vk::Format::UNDEFINED)
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_compare_op);
+
+        let mut library_create_info =
+            vk::GraphicsPipelineLibraryCreateInfoEXT::default().flags(parts);
+
+        let mut pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state);
+        // without dynamic rendering the pipeline is made for a render pass with the same formats instead
+        match device
+            .compatible_render_pass(&self.color_attachment_formats, self.depth_attachment_format)
+        {
+            Some(render_pass) => {
+                pipeline_create_info = pipeline_create_info.render_pass(render_pass);
+            }
+            None => {
+                pipeline_create_info = pipeline_create_info.push_next(&mut rendering_create_info);
+            }
+        }
+        if pre_rasterization {
+            pipeline_create_info = pipeline_create_info.dynamic_state(&dynamic_state);
+        }
+        if pre_rasterization || fragment_shader {
+            pipeline_create_info = pipeline_create_info.layout(self.layout);
+        }
+        if library {
+            pipeline_create_info = pipeline_create_info
+                .flags(
+                    vk::PipelineCreateFlags::LIBRARY_KHR
+                        | vk::PipelineCreateFlags::RETAIN_LINK_TIME_OPTIMIZATION_INFO_EXT,
+                )
+                .push_next(&mut library_create_info);
+        }
+
+        unsafe {
+            device.create_graphics_pipelines(
+                device.pipeline_cache(),
+                &[pipeline_create_info],
+                device.allocator(),
+            )
+        }
+        .unwrap()[0]
+    }
+}
+
+/// Part of a graphics pipeline built with [`GraphicsPipelineBuilder::build_library`],
+/// the parts that don't change can be kept around so swapping shaders only compiles the shader stages
+pub struct GraphicsPipelineLibrary<'allocator> {
+    pipeline: Pipeline<'allocator>,
+    parts: vk::GraphicsPipelineLibraryFlagsEXT,
+}
+
+impl<'allocator> GraphicsPipelineLibrary<'allocator> {
+    pub const ALL_PARTS: vk::GraphicsPipelineLibraryFlagsEXT =
+        vk::GraphicsPipelineLibraryFlagsEXT::from_raw(
+            vk::GraphicsPipelineLibraryFlagsEXT::VERTEX_INPUT_INTERFACE.as_raw()
+                | vk::GraphicsPipelineLibraryFlagsEXT::PRE_RASTERIZATION_SHADERS.as_raw()
+                | vk::GraphicsPipelineLibraryFlagsEXT::FRAGMENT_SHADER.as_raw()
+                | vk::GraphicsPipelineLibraryFlagsEXT::FRAGMENT_OUTPUT_INTERFACE.as_raw(),
+        );
+
+    /// Links `libraries` into a full pipeline, they must cover every part exactly once and can be dropped afterwards
+    ///
+    /// Linking without `optimize` is fast enough to do in the middle of a frame,
+    /// an optimized pipeline can be linked later on to replace it
+    pub fn link(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        layout: &PipelineLayout<'allocator>,
+        libraries: &[&GraphicsPipelineLibrary<'allocator>],
+        optimize: bool,
+    ) -> Pipeline<'allocator> {
+        let parts = libraries.iter().try_fold(
+            vk::GraphicsPipelineLibraryFlagsEXT::empty(),
+            |parts, library| (!parts.intersects(library.parts)).then_some(parts | library.parts),
+        );
+        assert_eq!(
+            parts,
+            Some(Self::ALL_PARTS),
+            "Graphics pipeline libraries must cover every part exactly once",
+        );
+
+        let library_handles = libraries
+            .iter()
+            .map(|library| library.handle())
+            .collect::<Vec<_>>();
+        let mut library_info =
+            vk::PipelineLibraryCreateInfoKHR::default().libraries(&library_handles);
+        let flags = if optimize {
+            vk::PipelineCreateFlags::LINK_TIME_OPTIMIZATION_EXT
+        } else {
+            vk::PipelineCreateFlags::empty()
+        };
+        let pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
+            .push_next(&mut library_info)
+            .flags(flags)
+            .layout(layout.handle());
+
+        let pipeline = unsafe {
+            device.create_graphics_pipelines(
+                device.pipeline_cache(),
+                &[pipeline_create_info],
+                device.allocator(),
+            )
+        }
+        .unwrap()[0];
+
+        unsafe { Pipeline::from_raw(device, name, pipeline, vk::PipelineBindPoint::GRAPHICS) }
+    }
+
+    pub fn pipeline(&self) -> &Pipeline<'allocator> {
+        &self.pipeline
+    }
+
+    pub fn handle(&self) -> vk::Pipeline {
+        self.pipeline.handle()
+    }
+
+    pub fn parts(&self) -> vk::GraphicsPipelineLibraryFlagsEXT {
+        self.parts
+    }
+}
+
+/// A compute [`Pipeline`] that remembers its layout and workgroup size, so dispatches can be sized in invocations
+pub struct ComputePipeline<'allocator> {
+    pipeline: Pipeline<'allocator>,
+    layout: vk::PipelineLayout,
+    workgroup_size: [u32; 3],
+}
+
+impl<'allocator> ComputePipeline<'allocator> {
+    /// `workgroup_size` must match the `numthreads` of `entry_point`
+    pub fn new(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        layout: &PipelineLayout<'allocator>,
+        shader: &Shader<'allocator>,
+        entry_point: &CStr,
+        workgroup_size: [u32; 3],
+    ) -> Self {
+        let stage = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader.handle())
+            .name(entry_point);
+        let pipeline_create_info = vk::ComputePipelineCreateInfo::default()
+            .stage(stage)
+            .layout(layout.handle());
+
+        let pipeline = unsafe {
+            device.create_compute_pipelines(
+                device.pipeline_cache(),
+                &[pipeline_create_info],
+                device.allocator(),
+            )
+        }
+        .unwrap()[0];
+
+        Self {
+            pipeline: unsafe {
+                Pipeline::from_raw(device, name, pipeline, vk::PipelineBindPoint::COMPUTE)
+            },
+            layout: layout.handle(),
+            workgroup_size,
+        }
+    }
+
+    pub fn pipeline(&self) -> &Pipeline<'allocator> {
+        &self.pipeline
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        self.pipeline.device()
+    }
+
+    pub fn handle(&self) -> vk::Pipeline {
+        self.pipeline.handle()
+    }
+
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// The number of workgroups needed to cover `invocations`, see [`workgroup_count`]
+    pub fn workgroup_count(&self, invocations: [u32; 3]) -> [u32; 3] {
+        std::array::from_fn(|i| workgroup_count(invocations[i], self.workgroup_size[i]))
+    }
+
+    /// # Safety
+    /// `command_buffer` must be in the recording state
+    pub unsafe fn cmd_bind(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device().cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.handle(),
+            );
+        }
+    }
+
+    /// Binds the pipeline and dispatches enough workgroups to cover `invocations`,
+    /// the shader must bounds check as the last workgroup in each dimension can go past `invocations`
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state,
+    /// and everything the shader accesses (push constants, descriptor sets) must already be bound
+    pub unsafe fn cmd_dispatch(&self, command_buffer: vk::CommandBuffer, invocations: [u32; 3]) {
+        let [x, y, z] = self.workgroup_count(invocations);
+        unsafe {
+            self.cmd_bind(command_buffer);
+            self.device().cmd_dispatch(command_buffer, x, y, z);
+        }
+    }
+
+    /// # Safety
+    /// See [`ComputePipeline::cmd_dispatch`], `buffer` must contain a [`vk::DispatchIndirectCommand`] at `offset`
+    pub unsafe fn cmd_dispatch_indirect(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+    ) {
+        unsafe {
+            self.cmd_bind(command_buffer);
+            self.device()
+                .cmd_dispatch_indirect(command_buffer, buffer, offset);
+        }
+    }
+}
+
+/// Rounds `invocations` up to a whole number of workgroups of `workgroup_size`
+pub fn workgroup_count(invocations: u32, workgroup_size: u32) -> u32 {
+    invocations.div_ceil(workgroup_size)
+}
+
+/// Rounds `invocations` up to a multiple of `workgroup_size`
+pub fn round_up_to_workgroup_size(invocations: u32, workgroup_size: u32) -> u32 {
+    workgroup_count(invocations, workgroup_size) * workgroup_size
+}
@@ -0,0 +1,99 @@
+use ash::vk;
+use std::path::{Path, PathBuf};
+
+const HEADER_SIZE: usize = size_of::<vk::PipelineCacheHeaderVersionOne>();
+
+/// A [`vk::PipelineCache`] that is loaded from and saved to a file specific to the physical device
+pub(crate) struct PipelineCache {
+    pub handle: vk::PipelineCache,
+    pub path: Option<PathBuf>,
+}
+
+impl PipelineCache {
+    pub fn new(
+        device: &ash::Device,
+        allocator: Option<&vk::AllocationCallbacks<'_>>,
+        properties: &vk::PhysicalDeviceProperties,
+        directory: Option<&Path>,
+    ) -> Self {
+        let path = directory.map(|directory| {
+            let uuid = properties
+                .pipeline_cache_uuid
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>();
+            directory.join(format!(
+                "pipeline_cache_{:04x}_{:04x}_{uuid}.bin",
+                properties.vendor_id, properties.device_id,
+            ))
+        });
+
+        let initial_data = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .filter(|data| {
+                let valid = is_header_valid(data, properties);
+                if !valid {
+                    tracing::warn!(
+                        "Pipeline cache data doesn't match this physical device, discarding it"
+                    );
+                }
+                valid
+            })
+            .unwrap_or_default();
+
+        let create_info = vk::PipelineCacheCreateInfo::default().initial_data(&initial_data);
+        let handle = unsafe { device.create_pipeline_cache(&create_info, allocator) }.unwrap();
+        Self { handle, path }
+    }
+
+    /// Writes the cache data to [`PipelineCache::path`], failures are only reported as the cache is just an optimisation
+    pub fn save(&self, device: &ash::Device) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let data = match unsafe { device.get_pipeline_cache_data(self.handle) } {
+            Ok(data) => data,
+            Err(error) => {
+                tracing::warn!("Unable to get pipeline cache data: {error}");
+                return;
+            }
+        };
+
+        if let Some(directory) = path.parent()
+            && let Err(error) = std::fs::create_dir_all(directory)
+        {
+            tracing::warn!(
+                "Unable to create pipeline cache directory '{}': {error}",
+                directory.display()
+            );
+            return;
+        }
+        if let Err(error) = std::fs::write(path, data) {
+            tracing::warn!(
+                "Unable to write pipeline cache to '{}': {error}",
+                path.display()
+            );
+        }
+    }
+}
+
+fn is_header_valid(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool {
+    if data.len() < HEADER_SIZE {
+        return false;
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+    let header_size = read_u32(0);
+    let header_version = read_u32(4);
+    let vendor_id = read_u32(8);
+    let device_id = read_u32(12);
+    let uuid = &data[16..16 + vk::UUID_SIZE];
+
+    header_size as usize >= HEADER_SIZE
+        && header_version == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+        && vendor_id == properties.vendor_id
+        && device_id == properties.device_id
+        && uuid == properties.pipeline_cache_uuid
+}
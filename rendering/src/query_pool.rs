@@ -0,0 +1,298 @@
+use crate::{Device, FRAMES_IN_FLIGHT_COUNT, Instance, PerFrame, ResourceToDestroy};
+use ash::vk;
+use std::{sync::Arc, time::Duration};
+
+/// What a [`QueryPool`] measures and how its raw values are turned into results
+pub trait QueryKind {
+    type Result;
+
+    fn query_type(&self) -> vk::QueryType;
+
+    fn pipeline_statistics(&self) -> vk::QueryPipelineStatisticFlags {
+        vk::QueryPipelineStatisticFlags::empty()
+    }
+
+    /// How many 64 bit values each query writes
+    fn value_count(&self) -> usize {
+        1
+    }
+
+    /// `values` has [`QueryKind::value_count`] values
+    fn result(&self, values: &[u64]) -> Self::Result;
+}
+
+/// Gives the raw tick count, see [`QueryPool::duration`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    /// Timestamps wrap around at this mask
+    mask: u64,
+}
+
+impl Timestamp {
+    /// `None` when the graphics queue doesn't support timestamps
+    pub fn new(device: &Device<'_>) -> Option<Self> {
+        let timestamp_valid_bits = unsafe {
+            device
+                .instance()
+                .get_physical_device_queue_family_properties(device.physical_device())
+        }[device.graphics_queue_family_index() as usize]
+            .timestamp_valid_bits;
+        (timestamp_valid_bits > 0).then(|| Self {
+            mask: u64::MAX >> (64 - timestamp_valid_bits),
+        })
+    }
+}
+
+impl QueryKind for Timestamp {
+    type Result = u64;
+
+    fn query_type(&self) -> vk::QueryType {
+        vk::QueryType::TIMESTAMP
+    }
+
+    fn result(&self, values: &[u64]) -> Self::Result {
+        values[0] & self.mask
+    }
+}
+
+/// Gives the number of samples that passed the depth and stencil tests, which is only exact
+/// when the query was begun with [`vk::QueryControlFlags::PRECISE`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Occlusion;
+
+impl QueryKind for Occlusion {
+    type Result = u64;
+
+    fn query_type(&self) -> vk::QueryType {
+        vk::QueryType::OCCLUSION
+    }
+
+    fn result(&self, values: &[u64]) -> Self::Result {
+        values[0]
+    }
+}
+
+/// Gives each of the counters, requires [`DeviceFeature::PipelineStatisticsQuery`](crate::DeviceFeature::PipelineStatisticsQuery)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineStatistics(pub vk::QueryPipelineStatisticFlags);
+
+impl QueryKind for PipelineStatistics {
+    /// One value for each flag, from the lowest bit to the highest
+    type Result = Vec<(vk::QueryPipelineStatisticFlags, u64)>;
+
+    fn query_type(&self) -> vk::QueryType {
+        vk::QueryType::PIPELINE_STATISTICS
+    }
+
+    fn pipeline_statistics(&self) -> vk::QueryPipelineStatisticFlags {
+        self.0
+    }
+
+    fn value_count(&self) -> usize {
+        self.0.as_raw().count_ones() as usize
+    }
+
+    fn result(&self, values: &[u64]) -> Self::Result {
+        (0..u32::BITS)
+            .map(|bit| vk::QueryPipelineStatisticFlags::from_raw(1 << bit))
+            .filter(|&flag| self.0.contains(flag))
+            .zip(values.iter().copied())
+            .collect()
+    }
+}
+
+/// `queries_per_frame` queries for each frame index, which are reset with [`QueryPool::cmd_reset`] in that frame's command buffer
+/// and read back with [`QueryPool::results`] once the frame has finished
+pub struct QueryPool<'allocator, K: QueryKind> {
+    device: Arc<Device<'allocator>>,
+    kind: K,
+    query_pool: vk::QueryPool,
+    queries_per_frame: u32,
+    /// Queries can't be read before their first reset
+    reset: PerFrame<bool>,
+}
+
+impl<'allocator, K: QueryKind> QueryPool<'allocator, K> {
+    pub fn new(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        kind: K,
+        queries_per_frame: u32,
+    ) -> Self {
+        let query_pool_create_info = vk::QueryPoolCreateInfo::default()
+            .query_type(kind.query_type())
+            .query_count(queries_per_frame * FRAMES_IN_FLIGHT_COUNT as u32)
+            .pipeline_statistics(kind.pipeline_statistics());
+        let query_pool =
+            unsafe { device.create_query_pool(&query_pool_create_info, device.allocator()) }
+                .unwrap();
+        device.track_resource(query_pool, name);
+        Self {
+            device,
+            kind,
+            query_pool,
+            queries_per_frame,
+            reset: PerFrame::default(),
+        }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::QueryPool {
+        self.query_pool
+    }
+
+    pub fn kind(&self) -> &K {
+        &self.kind
+    }
+
+    pub fn queries_per_frame(&self) -> u32 {
+        self.queries_per_frame
+    }
+
+    /// The index in the whole pool of `query` for `frame_index`
+    pub fn query_index(&self, frame_index: usize, query: u32) -> u32 {
+        assert!(query < self.queries_per_frame);
+        frame_index as u32 * self.queries_per_frame + query
+    }
+
+    /// Resets every query of `frame_index`, which has to happen before they are written again
+    ///
+    /// # Safety
+    /// `command_buffer` must be recording outside of a render pass, and any earlier frame with `frame_index` must have finished
+    pub unsafe fn cmd_reset(&mut self, command_buffer: vk::CommandBuffer, frame_index: usize) {
+        unsafe {
+            self.device.cmd_reset_query_pool(
+                command_buffer,
+                self.query_pool,
+                self.query_index(frame_index, 0),
+                self.queries_per_frame,
+            );
+        }
+        self.reset[frame_index] = true;
+    }
+
+    /// # Safety
+    /// `command_buffer` must be recording and the query must have been reset since it was last written,
+    /// only for occlusion and pipeline statistics queries
+    pub unsafe fn cmd_begin(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        query: u32,
+        flags: vk::QueryControlFlags,
+    ) {
+        unsafe {
+            self.device.cmd_begin_query(
+                command_buffer,
+                self.query_pool,
+                self.query_index(frame_index, query),
+                flags,
+            );
+        }
+    }
+
+    /// # Safety
+    /// `command_buffer` must be recording and the query must have been begun in it
+    pub unsafe fn cmd_end(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        query: u32,
+    ) {
+        unsafe {
+            self.device.cmd_end_query(
+                command_buffer,
+                self.query_pool,
+                self.query_index(frame_index, query),
+            );
+        }
+    }
+
+    /// The results of every query of `frame_index`, `None` for the ones that haven't been written since they were reset
+    /// or that the gpu hasn't finished yet, and for all of them before the first [`QueryPool::cmd_reset`]
+    pub fn results(&self, frame_index: usize) -> Vec<Option<K::Result>> {
+        if !self.reset[frame_index] {
+            return (0..self.queries_per_frame).map(|_| None).collect();
+        }
+
+        // each query is followed by whether it is available
+        let stride = self.kind.value_count() + 1;
+        let mut values = vec![0u64; stride * self.queries_per_frame as usize];
+        let result = unsafe {
+            (self.device.fp_v1_0().get_query_pool_results)(
+                self.device.handle(),
+                self.query_pool,
+                self.query_index(frame_index, 0),
+                self.queries_per_frame,
+                size_of_val(values.as_slice()),
+                values.as_mut_ptr().cast(),
+                (stride * size_of::<u64>()) as vk::DeviceSize,
+                vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WITH_AVAILABILITY,
+            )
+        };
+        match result {
+            // some queries aren't available, which the availability values say
+            vk::Result::SUCCESS | vk::Result::NOT_READY => {}
+            error => {
+                self.device.report_device_lost(Err::<(), _>(error)).unwrap();
+            }
+        }
+
+        values
+            .chunks_exact(stride)
+            .map(|values| {
+                let (&available, values) = values.split_last().unwrap();
+                (available != 0).then(|| self.kind.result(values))
+            })
+            .collect()
+    }
+}
+
+impl<'allocator> QueryPool<'allocator, Timestamp> {
+    /// # Safety
+    /// `command_buffer` must be recording and the query must have been reset since it was last written
+    pub unsafe fn cmd_write_timestamp(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        stage: vk::PipelineStageFlags2,
+        frame_index: usize,
+        query: u32,
+    ) {
+        unsafe {
+            self.device.cmd_write_timestamp2(
+                command_buffer,
+                stage,
+                self.query_pool,
+                self.query_index(frame_index, query),
+            );
+        }
+    }
+
+    /// The time between two timestamps, handling them wrapping around
+    pub fn duration(&self, start: u64, end: u64) -> Duration {
+        let ticks = end.wrapping_sub(start) & self.kind.mask;
+        Duration::from_nanos((ticks as f64 * self.device.limits().timestamp_period as f64) as u64)
+    }
+}
+
+impl<K: QueryKind> Drop for QueryPool<'_, K> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::QueryPool(self.query_pool),
+            );
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use ash::vk;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+/// What an image view was created from, which [`vk::RenderingInfo`] doesn't carry but render passes and framebuffers need
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ImageViewInfo {
+    pub flags: vk::ImageCreateFlags,
+    pub usage: vk::ImageUsageFlags,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub layer_count: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct AttachmentKey {
+    format: vk::Format,
+    load_op: vk::AttachmentLoadOp,
+    store_op: vk::AttachmentStoreOp,
+    layout: vk::ImageLayout,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RenderPassKey {
+    /// `None` for attachments without an image view, like [`vk::RenderingInfo`] allows
+    color_attachments: Vec<Option<AttachmentKey>>,
+    depth_attachment: Option<AttachmentKey>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct FramebufferKey {
+    render_pass: vk::RenderPass,
+    attachments: Vec<ImageViewInfo>,
+    extent: vk::Extent2D,
+    layers: u32,
+}
+
+/// Emulates dynamic rendering with render passes and imageless framebuffers, on vulkan 1.2 devices without `VK_KHR_dynamic_rendering`
+///
+/// Render passes and framebuffers only depend on formats, sizes and load/store ops, so they are cached until the device is destroyed
+#[derive(Default)]
+pub(crate) struct RenderPassFallback {
+    image_views: Mutex<HashMap<vk::ImageView, ImageViewInfo>>,
+    render_passes: Mutex<HashMap<RenderPassKey, vk::RenderPass>>,
+    framebuffers: Mutex<HashMap<FramebufferKey, vk::Framebuffer>>,
+}
+
+impl RenderPassFallback {
+    pub fn register_image_view(&self, image_view: vk::ImageView, info: ImageViewInfo) {
+        self.image_views.lock().insert(image_view, info);
+    }
+
+    pub fn unregister_image_view(&self, image_view: vk::ImageView) {
+        self.image_views.lock().remove(&image_view);
+    }
+
+    /// A render pass that pipelines rendering to these formats are compatible with,
+    /// compatibility only depends on the formats so the ops and layouts don't matter
+    pub fn compatible_render_pass(
+        &self,
+        device: &ash::Device,
+        allocator: Option<&vk::AllocationCallbacks<'_>>,
+        color_formats: &[vk::Format],
+        depth_format: vk::Format,
+    ) -> vk::RenderPass {
+        let attachment = |format: vk::Format, layout: vk::ImageLayout| {
+            (format != vk::Format::UNDEFINED).then_some(AttachmentKey {
+                format,
+                load_op: vk::AttachmentLoadOp::LOAD,
+                store_op: vk::AttachmentStoreOp::STORE,
+                layout,
+            })
+        };
+        let key = RenderPassKey {
+            color_attachments: color_formats
+                .iter()
+                .map(|&format| attachment(format, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL))
+                .collect(),
+            depth_attachment: attachment(
+                depth_format,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ),
+        };
+        self.render_pass(device, allocator, key)
+    }
+
+    /// # Safety
+    /// See [`ash::Device::cmd_begin_render_pass`], every image view in `rendering_info` must have been registered
+    pub unsafe fn cmd_begin_rendering(
+        &self,
+        device: &ash::Device,
+        allocator: Option<&vk::AllocationCallbacks<'_>>,
+        command_buffer: vk::CommandBuffer,
+        rendering_info: &vk::RenderingInfo<'_>,
+    ) {
+        assert_eq!(
+            rendering_info.view_mask, 0,
+            "multiview isn't supported without dynamic rendering"
+        );
+
+        let color_attachments = unsafe {
+            crate::device::slice_from_raw_parts(
+                rendering_info.p_color_attachments,
+                rendering_info.color_attachment_count,
+            )
+        };
+        let depth_attachment = unsafe { rendering_info.p_depth_attachment.as_ref() }
+            .filter(|attachment| attachment.image_view != vk::ImageView::null());
+
+        let image_views = self.image_views.lock();
+        let view_info = |attachment: &vk::RenderingAttachmentInfo<'_>| {
+            assert!(
+                attachment.resolve_mode == vk::ResolveModeFlags::NONE,
+                "resolve attachments aren't supported without dynamic rendering"
+            );
+            *image_views.get(&attachment.image_view).expect(
+                "image views rendered to without dynamic rendering have to be created by this crate",
+            )
+        };
+        let attachment_key = |attachment: &vk::RenderingAttachmentInfo<'_>| AttachmentKey {
+            format: view_info(attachment).format,
+            load_op: attachment.load_op,
+            store_op: attachment.store_op,
+            layout: attachment.image_layout,
+        };
+
+        let key = RenderPassKey {
+            color_attachments: color_attachments
+                .iter()
+                .map(|attachment| {
+                    (attachment.image_view != vk::ImageView::null())
+                        .then(|| attachment_key(attachment))
+                })
+                .collect(),
+            depth_attachment: depth_attachment.map(attachment_key),
+        };
+
+        // attachments without an image view are VK_ATTACHMENT_UNUSED, so they aren't part of the framebuffer
+        let attachments = color_attachments
+            .iter()
+            .filter(|attachment| attachment.image_view != vk::ImageView::null())
+            .chain(depth_attachment)
+            .collect::<Vec<_>>();
+        let infos = attachments
+            .iter()
+            .map(|attachment| view_info(attachment))
+            .collect::<Vec<_>>();
+        drop(image_views);
+
+        let render_pass = self.render_pass(device, allocator, key);
+
+        let render_area = rendering_info.render_area;
+        // the framebuffer can't be bigger than any of its attachments
+        let extent = infos
+            .iter()
+            .map(|info| info.extent)
+            .reduce(|a, b| vk::Extent2D {
+                width: a.width.min(b.width),
+                height: a.height.min(b.height),
+            })
+            .unwrap_or(vk::Extent2D {
+                width: render_area.offset.x as u32 + render_area.extent.width,
+                height: render_area.offset.y as u32 + render_area.extent.height,
+            });
+        let framebuffer = self.framebuffer(
+            device,
+            allocator,
+            FramebufferKey {
+                render_pass,
+                attachments: infos,
+                extent,
+                layers: rendering_info.layer_count,
+            },
+        );
+
+        let image_views = attachments
+            .iter()
+            .map(|attachment| attachment.image_view)
+            .collect::<Vec<_>>();
+        let clear_values = attachments
+            .iter()
+            .map(|attachment| attachment.clear_value)
+            .collect::<Vec<_>>();
+        let mut attachment_begin_info =
+            vk::RenderPassAttachmentBeginInfo::default().attachments(&image_views);
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .push_next(&mut attachment_begin_info)
+            .render_pass(render_pass)
+            .framebuffer(framebuffer)
+            .render_area(render_area)
+            .clear_values(&clear_values);
+        let contents = if rendering_info
+            .flags
+            .contains(vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS)
+        {
+            vk::SubpassContents::SECONDARY_COMMAND_BUFFERS
+        } else {
+            vk::SubpassContents::INLINE
+        };
+        unsafe { device.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, contents) };
+    }
+
+    fn render_pass(
+        &self,
+        device: &ash::Device,
+        allocator: Option<&vk::AllocationCallbacks<'_>>,
+        key: RenderPassKey,
+    ) -> vk::RenderPass {
+        let mut render_passes = self.render_passes.lock();
+        if let Some(&render_pass) = render_passes.get(&key) {
+            return render_pass;
+        }
+
+        let mut descriptions = vec![];
+        let mut reference = |attachment: Option<AttachmentKey>| {
+            let Some(attachment) = attachment else {
+                return vk::AttachmentReference::default()
+                    .attachment(vk::ATTACHMENT_UNUSED)
+                    .layout(vk::ImageLayout::UNDEFINED);
+            };
+            // dynamic rendering doesn't transition layouts, so neither does the render pass
+            descriptions.push(
+                vk::AttachmentDescription::default()
+                    .format(attachment.format)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .load_op(attachment.load_op)
+                    .store_op(attachment.store_op)
+                    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                    .initial_layout(attachment.layout)
+                    .final_layout(attachment.layout),
+            );
+            vk::AttachmentReference::default()
+                .attachment(descriptions.len() as u32 - 1)
+                .layout(attachment.layout)
+        };
+        let color_references = key
+            .color_attachments
+            .iter()
+            .map(|&attachment| reference(attachment))
+            .collect::<Vec<_>>();
+        let depth_reference = key
+            .depth_attachment
+            .map(|attachment| reference(Some(attachment)));
+
+        let mut subpass = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_references);
+        if let Some(depth_reference) = &depth_reference {
+            subpass = subpass.depth_stencil_attachment(depth_reference);
+        }
+        let subpasses = [subpass];
+        let render_pass_create_info = vk::RenderPassCreateInfo::default()
+            .attachments(&descriptions)
+            .subpasses(&subpasses);
+        let render_pass =
+            unsafe { device.create_render_pass(&render_pass_create_info, allocator) }.unwrap();
+
+        tracing::debug!(
+            color_attachments = key.color_attachments.len(),
+            depth_attachment = key.depth_attachment.is_some(),
+            "Created fallback render pass"
+        );
+        render_passes.insert(key, render_pass);
+        render_pass
+    }
+
+    fn framebuffer(
+        &self,
+        device: &ash::Device,
+        allocator: Option<&vk::AllocationCallbacks<'_>>,
+        key: FramebufferKey,
+    ) -> vk::Framebuffer {
+        let mut framebuffers = self.framebuffers.lock();
+        if let Some(&framebuffer) = framebuffers.get(&key) {
+            return framebuffer;
+        }
+
+        let view_formats = key
+            .attachments
+            .iter()
+            .map(|info| [info.format])
+            .collect::<Vec<_>>();
+        let attachment_image_infos = key
+            .attachments
+            .iter()
+            .zip(&view_formats)
+            .map(|(info, view_formats)| {
+                vk::FramebufferAttachmentImageInfo::default()
+                    .flags(info.flags)
+                    .usage(info.usage)
+                    .width(info.extent.width)
+                    .height(info.extent.height)
+                    .layer_count(info.layer_count)
+                    .view_formats(view_formats)
+            })
+            .collect::<Vec<_>>();
+        let mut attachments_create_info = vk::FramebufferAttachmentsCreateInfo::default()
+            .attachment_image_infos(&attachment_image_infos);
+        let mut framebuffer_create_info = vk::FramebufferCreateInfo::default()
+            .push_next(&mut attachments_create_info)
+            .flags(vk::FramebufferCreateFlags::IMAGELESS)
+            .render_pass(key.render_pass)
+            .width(key.extent.width)
+            .height(key.extent.height)
+            .layers(key.layers);
+        framebuffer_create_info.attachment_count = attachment_image_infos.len() as u32;
+        let framebuffer =
+            unsafe { device.create_framebuffer(&framebuffer_create_info, allocator) }.unwrap();
+
+        framebuffers.insert(key, framebuffer);
+        framebuffer
+    }
+
+    /// # Safety
+    /// Nothing using the cached render passes or framebuffers can still be executing
+    pub unsafe fn destroy(
+        &mut self,
+        device: &ash::Device,
+        allocator: Option<&vk::AllocationCallbacks<'_>>,
+    ) {
+        for (_, framebuffer) in self.framebuffers.get_mut().drain() {
+            unsafe { device.destroy_framebuffer(framebuffer, allocator) };
+        }
+        for (_, render_pass) in self.render_passes.get_mut().drain() {
+            unsafe { device.destroy_render_pass(render_pass, allocator) };
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{Device, Instance, ResourceToDestroy};
+use ash::vk;
+use std::sync::Arc;
+
+pub struct Sampler<'allocator> {
+    device: Arc<Device<'allocator>>,
+    sampler: vk::Sampler,
+}
+
+impl<'allocator> Sampler<'allocator> {
+    pub fn new(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        create_info: &vk::SamplerCreateInfo<'_>,
+    ) -> Self {
+        let sampler = unsafe { device.create_sampler(create_info, device.allocator()) }.unwrap();
+        device.track_resource(sampler, name);
+        Self { device, sampler }
+    }
+
+    /// Trilinear filtering with repeating coordinates, what most textures want
+    pub fn linear_repeat(device: Arc<Device<'allocator>>, name: &str) -> Self {
+        let create_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::REPEAT)
+            .address_mode_v(vk::SamplerAddressMode::REPEAT)
+            .address_mode_w(vk::SamplerAddressMode::REPEAT)
+            .max_lod(vk::LOD_CLAMP_NONE);
+        Self::new(device, name, &create_info)
+    }
+
+    /// Bilinear filtering of the first mip level with clamped coordinates, for full screen passes
+    pub fn linear_clamp(device: Arc<Device<'allocator>>, name: &str) -> Self {
+        let create_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .max_lod(0.0);
+        Self::new(device, name, &create_info)
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::Sampler {
+        self.sampler
+    }
+}
+
+impl Drop for Sampler<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::Sampler(self.sampler),
+            );
+        }
+    }
+}
@@ -1,20 +1,26 @@
-use crate::{Device, Instance, ResourceToDestroy};
+use crate::{Device, Instance, ResourceToDestroy, ShaderReflection};
 use ash::vk;
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 pub struct Shader<'allocator> {
     device: Arc<Device<'allocator>>,
     shader: vk::ShaderModule,
+    reflection: ShaderReflection,
 }
 
 impl<'allocator> Shader<'allocator> {
     /// # Safety
     /// `spirv_code` must be valid SPIR-V code
-    pub unsafe fn new(device: Arc<Device<'allocator>>, spirv_code: &[u32]) -> Self {
+    pub unsafe fn new(device: Arc<Device<'allocator>>, name: &str, spirv_code: &[u32]) -> Self {
         let create_info = vk::ShaderModuleCreateInfo::default().code(spirv_code);
         let shader =
             unsafe { device.create_shader_module(&create_info, device.allocator()) }.unwrap();
-        Self { device, shader }
+        device.track_resource(shader, name);
+        Self {
+            device,
+            shader,
+            reflection: ShaderReflection::new(spirv_code),
+        }
     }
 
     pub fn instance(&self) -> &Arc<Instance<'allocator>> {
@@ -32,6 +38,10 @@ impl<'allocator> Shader<'allocator> {
     pub fn handle(&self) -> vk::ShaderModule {
         self.shader
     }
+
+    pub fn reflection(&self) -> &ShaderReflection {
+        &self.reflection
+    }
 }
 
 impl Drop for Shader<'_> {
@@ -45,6 +55,11 @@ impl Drop for Shader<'_> {
     }
 }
 
+/// Reads a SPIR-V file at runtime, the runtime counterpart to [`include_spirv!`]
+pub fn read_spirv(path: &Path) -> std::io::Result<Vec<u32>> {
+    ash::util::read_spv(&mut std::fs::File::open(path)?)
+}
+
 #[macro_export]
 macro_rules! include_spirv {
     ($($path:tt)*) => {
@@ -60,7 +75,20 @@ macro_rules! include_spirv {
                 bytes: *include_bytes!($($path)*),
             };
 
-            assert!(BYTES.bytes.len().is_multiple_of(4));
+            assert!(
+                BYTES.bytes.len().is_multiple_of(4),
+                "SPIR-V must be a whole number of words"
+            );
+            assert!(
+                BYTES.bytes.len() >= 20,
+                "SPIR-V is too short to contain a header"
+            );
+            let magic_number = [BYTES.bytes[0], BYTES.bytes[1], BYTES.bytes[2], BYTES.bytes[3]];
+            assert!(
+                u32::from_le_bytes(magic_number) == 0x07230203,
+                "SPIR-V magic number is missing, the file may be stale or not SPIR-V at all"
+            );
+            assert!(BYTES.bytes[6] == 1, "SPIR-V major version must be 1");
             unsafe {
                 core::slice::from_raw_parts(
                     BYTES.bytes.as_ptr().cast::<u32>(),
@@ -0,0 +1,65 @@
+use crate::{Device, Shader};
+use ash::vk;
+use std::sync::Arc;
+
+/// Compiles GLSL `source` for a single `stage` to SPIR-V, errors are formatted with the offending source lines
+pub fn compile_glsl(source: &str, stage: vk::ShaderStageFlags) -> Result<Vec<u32>, String> {
+    let stage = match stage {
+        vk::ShaderStageFlags::VERTEX => naga::ShaderStage::Vertex,
+        vk::ShaderStageFlags::FRAGMENT => naga::ShaderStage::Fragment,
+        vk::ShaderStageFlags::COMPUTE => naga::ShaderStage::Compute,
+        vk::ShaderStageFlags::TASK_EXT => naga::ShaderStage::Task,
+        vk::ShaderStageFlags::MESH_EXT => naga::ShaderStage::Mesh,
+        _ => return Err(format!("Unsupported GLSL shader stage {stage:?}")),
+    };
+    let module = naga::front::glsl::Frontend::default()
+        .parse(&naga::front::glsl::Options::from(stage), source)
+        .map_err(|errors| errors.emit_to_string(source))?;
+    write_spirv(&module, source)
+}
+
+/// Compiles WGSL `source` to SPIR-V, every entry point in the source is kept
+pub fn compile_wgsl(source: &str) -> Result<Vec<u32>, String> {
+    let module =
+        naga::front::wgsl::parse_str(source).map_err(|error| error.emit_to_string(source))?;
+    write_spirv(&module, source)
+}
+
+fn write_spirv(module: &naga::Module, source: &str) -> Result<Vec<u32>, String> {
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(module)
+    .map_err(|error| error.emit_to_string(source))?;
+
+    // vulkan 1.2 is the minimum supported version, which supports SPIR-V 1.5
+    let options = naga::back::spv::Options {
+        lang_version: (1, 5),
+        ..Default::default()
+    };
+    naga::back::spv::write_vec(module, &info, &options, None).map_err(|error| error.to_string())
+}
+
+impl<'allocator> Shader<'allocator> {
+    /// See [`compile_glsl`], unlike [`Shader::new`] this is safe as the generated SPIR-V has been validated
+    pub fn from_glsl(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        source: &str,
+        stage: vk::ShaderStageFlags,
+    ) -> Result<Self, String> {
+        let spirv_code = compile_glsl(source, stage)?;
+        Ok(unsafe { Shader::new(device, name, &spirv_code) })
+    }
+
+    /// See [`compile_wgsl`], unlike [`Shader::new`] this is safe as the generated SPIR-V has been validated
+    pub fn from_wgsl(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        source: &str,
+    ) -> Result<Self, String> {
+        let spirv_code = compile_wgsl(source)?;
+        Ok(unsafe { Shader::new(device, name, &spirv_code) })
+    }
+}
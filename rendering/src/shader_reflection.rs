@@ -0,0 +1,656 @@
+use ash::vk;
+use shader_tools::{SPIRV_MAGIC_NUMBER, parse_spirv_string, spirv_instructions};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+};
+
+mod op {
+    pub const ENTRY_POINT: u32 = 15;
+    pub const EXECUTION_MODE: u32 = 16;
+    pub const TYPE_BOOL: u32 = 20;
+    pub const TYPE_INT: u32 = 21;
+    pub const TYPE_FLOAT: u32 = 22;
+    pub const TYPE_VECTOR: u32 = 23;
+    pub const TYPE_MATRIX: u32 = 24;
+    pub const TYPE_IMAGE: u32 = 25;
+    pub const TYPE_SAMPLER: u32 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u32 = 27;
+    pub const TYPE_ARRAY: u32 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u32 = 29;
+    pub const TYPE_STRUCT: u32 = 30;
+    pub const TYPE_POINTER: u32 = 32;
+    pub const CONSTANT: u32 = 43;
+    pub const VARIABLE: u32 = 59;
+    pub const DECORATE: u32 = 71;
+    pub const MEMBER_DECORATE: u32 = 72;
+    pub const TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+}
+
+mod decoration {
+    pub const BLOCK: u32 = 2;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+mod storage_class {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const UNIFORM: u32 = 2;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+    pub const PHYSICAL_STORAGE_BUFFER: u32 = 5349;
+}
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Debug, Clone)]
+pub struct ShaderEntryPoint {
+    pub name: CString,
+    pub stage: vk::ShaderStageFlags,
+    /// Only set for compute-like stages, see [`ComputePipeline::new`](crate::ComputePipeline::new)
+    pub workgroup_size: Option<[u32; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShaderDescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// `None` for runtime sized arrays
+    pub descriptor_count: Option<u32>,
+    pub stage_flags: vk::ShaderStageFlags,
+}
+
+/// The interface of a SPIR-V module, as needed to build a [`PipelineLayout`](crate::PipelineLayout) for it
+///
+/// Parsing is deliberately shallow, anything that isn't understood is skipped
+#[derive(Debug, Clone, Default)]
+pub struct ShaderReflection {
+    pub entry_points: Vec<ShaderEntryPoint>,
+    /// The whole push constant block, from offset 0 to the end of its last member
+    pub push_constant_range: Option<vk::PushConstantRange>,
+    pub descriptor_bindings: Vec<ShaderDescriptorBinding>,
+}
+
+enum Type {
+    Scalar { size: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Array { element: u32, length: Option<u32> },
+    Struct { members: Vec<u32> },
+    Pointer { storage_class: u32, pointee: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    AccelerationStructure,
+}
+
+#[derive(Default)]
+struct Module {
+    version: u32,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    /// Variable id to pointer type id and storage class
+    variables: Vec<(u32, u32, u32)>,
+    decorations: HashMap<(u32, u32), u32>,
+    member_offsets: HashMap<(u32, u32), u32>,
+    /// Entry point id, name, stage, interface ids
+    entry_points: Vec<(u32, CString, vk::ShaderStageFlags, Vec<u32>)>,
+    workgroup_sizes: HashMap<u32, [u32; 3]>,
+}
+
+impl ShaderReflection {
+    pub fn new(spirv_code: &[u32]) -> Self {
+        let module = Module::parse(spirv_code);
+
+        // before SPIR-V 1.4 the entry point interface only lists inputs and outputs,
+        // so every resource has to be assumed to be used by every entry point
+        let stages_using = |variable: u32| {
+            module
+                .entry_points
+                .iter()
+                .filter(|(_, _, _, interface)| {
+                    module.version < 0x00010400 || interface.contains(&variable)
+                })
+                .fold(
+                    vk::ShaderStageFlags::empty(),
+                    |stages, &(_, _, stage, _)| stages | stage,
+                )
+        };
+
+        let mut push_constant_range = None;
+        let mut descriptor_bindings = vec![];
+        for &(variable, pointer_type, storage_class) in &module.variables {
+            let Some(&Type::Pointer { pointee, .. }) = module.types.get(&pointer_type) else {
+                continue;
+            };
+
+            if storage_class == storage_class::PUSH_CONSTANT {
+                push_constant_range = Some(
+                    vk::PushConstantRange::default()
+                        .stage_flags(stages_using(variable))
+                        .offset(0)
+                        .size(module.size_of(pointee)),
+                );
+                continue;
+            }
+
+            let (Some(&set), Some(&binding)) = (
+                module
+                    .decorations
+                    .get(&(variable, decoration::DESCRIPTOR_SET)),
+                module.decorations.get(&(variable, decoration::BINDING)),
+            ) else {
+                continue;
+            };
+
+            let (element, descriptor_count) = match module.types.get(&pointee) {
+                Some(&Type::Array { element, length }) => (element, length),
+                _ => (pointee, Some(1)),
+            };
+            let Some(descriptor_type) = module.descriptor_type(element, storage_class) else {
+                continue;
+            };
+
+            descriptor_bindings.push(ShaderDescriptorBinding {
+                set,
+                binding,
+                descriptor_type,
+                descriptor_count,
+                stage_flags: stages_using(variable),
+            });
+        }
+
+        Self {
+            entry_points: module
+                .entry_points
+                .iter()
+                .map(|(id, name, stage, _)| ShaderEntryPoint {
+                    name: name.clone(),
+                    stage: *stage,
+                    workgroup_size: module.workgroup_sizes.get(id).copied(),
+                })
+                .collect(),
+            push_constant_range,
+            descriptor_bindings,
+        }
+    }
+
+    pub fn entry_point(&self, name: &CStr) -> Option<&ShaderEntryPoint> {
+        self.entry_points
+            .iter()
+            .find(|entry_point| entry_point.name.as_c_str() == name)
+    }
+
+    /// The interface of several shaders used together in one pipeline,
+    /// resources used by several of them get the stages of all of them
+    pub fn merge(reflections: &[&ShaderReflection]) -> Self {
+        let mut merged = ShaderReflection::default();
+        for reflection in reflections {
+            merged
+                .entry_points
+                .extend(reflection.entry_points.iter().cloned());
+
+            if let Some(range) = reflection.push_constant_range {
+                let merged_range = merged.push_constant_range.get_or_insert(range);
+                merged_range.stage_flags |= range.stage_flags;
+                merged_range.size = merged_range.size.max(range.size);
+            }
+
+            for binding in &reflection.descriptor_bindings {
+                if let Some(merged_binding) = merged
+                    .descriptor_bindings
+                    .iter_mut()
+                    .find(|merged| (merged.set, merged.binding) == (binding.set, binding.binding))
+                {
+                    assert_eq!(
+                        (
+                            merged_binding.descriptor_type,
+                            merged_binding.descriptor_count
+                        ),
+                        (binding.descriptor_type, binding.descriptor_count),
+                        "set {} binding {} is declared differently between shaders",
+                        binding.set,
+                        binding.binding,
+                    );
+                    merged_binding.stage_flags |= binding.stage_flags;
+                } else {
+                    merged.descriptor_bindings.push(*binding);
+                }
+            }
+        }
+        merged
+    }
+}
+
+impl Module {
+    fn parse(spirv_code: &[u32]) -> Self {
+        let mut module = Module::default();
+        if let [SPIRV_MAGIC_NUMBER, version, ..] = *spirv_code {
+            module.version = version;
+        }
+
+        for (opcode, operands) in spirv_instructions(spirv_code) {
+            match (opcode, operands) {
+                (op::ENTRY_POINT, &[execution_model, id, ref rest @ ..]) => {
+                    let (name, interface) = parse_string(rest);
+                    if let Some(stage) = execution_model_stage(execution_model) {
+                        module
+                            .entry_points
+                            .push((id, name, stage, interface.to_vec()));
+                    }
+                }
+                (op::EXECUTION_MODE, &[id, EXECUTION_MODE_LOCAL_SIZE, x, y, z]) => {
+                    module.workgroup_sizes.insert(id, [x, y, z]);
+                }
+
+                (op::TYPE_BOOL, &[id]) => {
+                    module.types.insert(id, Type::Scalar { size: 4 });
+                }
+                (op::TYPE_INT | op::TYPE_FLOAT, &[id, width, ..]) => {
+                    module.types.insert(id, Type::Scalar { size: width / 8 });
+                }
+                (op::TYPE_VECTOR, &[id, component, count]) => {
+                    module.types.insert(id, Type::Vector { component, count });
+                }
+                (op::TYPE_MATRIX, &[id, column, count]) => {
+                    module.types.insert(id, Type::Matrix { column, count });
+                }
+                (op::TYPE_IMAGE, &[id, _, dim, _, _, _, sampled, ..]) => {
+                    module.types.insert(id, Type::Image { dim, sampled });
+                }
+                (op::TYPE_SAMPLER, &[id]) => {
+                    module.types.insert(id, Type::Sampler);
+                }
+                (op::TYPE_SAMPLED_IMAGE, &[id, _]) => {
+                    module.types.insert(id, Type::SampledImage);
+                }
+                (op::TYPE_ARRAY, &[id, element, length]) => {
+                    let length = module.constants.get(&length).copied();
+                    module.types.insert(id, Type::Array { element, length });
+                }
+                (op::TYPE_RUNTIME_ARRAY, &[id, element]) => {
+                    let length = None;
+                    module.types.insert(id, Type::Array { element, length });
+                }
+                (op::TYPE_STRUCT, &[id, ref members @ ..]) => {
+                    let members = members.to_vec();
+                    module.types.insert(id, Type::Struct { members });
+                }
+                (op::TYPE_POINTER, &[id, storage_class, pointee]) => {
+                    module.types.insert(
+                        id,
+                        Type::Pointer {
+                            storage_class,
+                            pointee,
+                        },
+                    );
+                }
+                (op::TYPE_ACCELERATION_STRUCTURE, &[id]) => {
+                    module.types.insert(id, Type::AccelerationStructure);
+                }
+
+                (op::CONSTANT, &[_, id, value, ..]) => {
+                    module.constants.insert(id, value);
+                }
+                (op::VARIABLE, &[pointer_type, id, storage_class, ..]) => {
+                    module.variables.push((id, pointer_type, storage_class));
+                }
+
+                (op::DECORATE, &[target, decoration, ref values @ ..]) => {
+                    let value = values.first().copied().unwrap_or(0);
+                    module.decorations.insert((target, decoration), value);
+                }
+                (op::MEMBER_DECORATE, &[target, member, decoration::OFFSET, offset]) => {
+                    module.member_offsets.insert((target, member), offset);
+                }
+
+                _ => {}
+            }
+        }
+
+        module
+    }
+
+    /// The size of `id` in bytes when used in a block, only as precise as needed for push constant ranges
+    fn size_of(&self, id: u32) -> u32 {
+        match self.types.get(&id) {
+            Some(&Type::Scalar { size }) => size,
+            Some(&Type::Vector { component, count }) => self.size_of(component) * count,
+            Some(&Type::Matrix { column, count }) => self.size_of(column) * count,
+            Some(&Type::Array { element, length }) => {
+                let stride = self
+                    .decorations
+                    .get(&(id, decoration::ARRAY_STRIDE))
+                    .copied()
+                    .unwrap_or_else(|| self.size_of(element));
+                stride * length.unwrap_or(0)
+            }
+            Some(Type::Struct { members }) => members
+                .iter()
+                .enumerate()
+                .map(|(index, &member)| {
+                    let offset = self
+                        .member_offsets
+                        .get(&(id, index as u32))
+                        .copied()
+                        .unwrap_or(0);
+                    offset + self.size_of(member)
+                })
+                .max()
+                .unwrap_or(0),
+            Some(&Type::Pointer {
+                storage_class: storage_class::PHYSICAL_STORAGE_BUFFER,
+                ..
+            }) => size_of::<vk::DeviceAddress>() as u32,
+            _ => 0,
+        }
+    }
+
+    fn descriptor_type(&self, id: u32, storage_class: u32) -> Option<vk::DescriptorType> {
+        Some(match (storage_class, self.types.get(&id)?) {
+            (storage_class::UNIFORM_CONSTANT, &Type::Image { dim, sampled }) => {
+                match (dim, sampled) {
+                    (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                    (DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                    (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                    (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                    _ => vk::DescriptorType::SAMPLED_IMAGE,
+                }
+            }
+            (storage_class::UNIFORM_CONSTANT, Type::Sampler) => vk::DescriptorType::SAMPLER,
+            (storage_class::UNIFORM_CONSTANT, Type::SampledImage) => {
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+            }
+            (storage_class::UNIFORM_CONSTANT, Type::AccelerationStructure) => {
+                vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
+            }
+            (storage_class::UNIFORM, Type::Struct { .. }) => {
+                if self
+                    .decorations
+                    .contains_key(&(id, decoration::BUFFER_BLOCK))
+                {
+                    vk::DescriptorType::STORAGE_BUFFER
+                } else if self.decorations.contains_key(&(id, decoration::BLOCK)) {
+                    vk::DescriptorType::UNIFORM_BUFFER
+                } else {
+                    return None;
+                }
+            }
+            (storage_class::STORAGE_BUFFER, Type::Struct { .. }) => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// Splits a nul terminated SPIR-V literal string from the operands that follow it
+fn parse_string(words: &[u32]) -> (CString, &[u32]) {
+    let (bytes, rest) = parse_spirv_string(words);
+    (CString::new(bytes).unwrap(), rest)
+}
+
+fn execution_model_stage(execution_model: u32) -> Option<vk::ShaderStageFlags> {
+    Some(match execution_model {
+        0 => vk::ShaderStageFlags::VERTEX,
+        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => vk::ShaderStageFlags::GEOMETRY,
+        4 => vk::ShaderStageFlags::FRAGMENT,
+        5 => vk::ShaderStageFlags::COMPUTE,
+        5313 => vk::ShaderStageFlags::RAYGEN_KHR,
+        5314 => vk::ShaderStageFlags::INTERSECTION_KHR,
+        5315 => vk::ShaderStageFlags::ANY_HIT_KHR,
+        5316 => vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+        5317 => vk::ShaderStageFlags::MISS_KHR,
+        5318 => vk::ShaderStageFlags::CALLABLE_KHR,
+        5364 => vk::ShaderStageFlags::TASK_EXT,
+        5365 => vk::ShaderStageFlags::MESH_EXT,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION_1_3: u32 = 0x00010300;
+    const VERSION_1_5: u32 = 0x00010500;
+    const EXECUTION_MODEL_VERTEX: u32 = 0;
+    const EXECUTION_MODEL_FRAGMENT: u32 = 4;
+    const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+    const DIM_2D: u32 = 1;
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    /// A nul terminated literal string, padded to whole words
+    fn string(string: &str) -> Vec<u32> {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.resize(bytes.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    fn entry_point(execution_model: u32, id: u32, name: &str, interface: &[u32]) -> Vec<u32> {
+        let mut operands = vec![execution_model, id];
+        operands.extend(string(name));
+        operands.extend_from_slice(interface);
+        instruction(op::ENTRY_POINT, &operands)
+    }
+
+    fn module(version: u32, instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut words = vec![SPIRV_MAGIC_NUMBER, version, 0, 100, 0];
+        words.extend(instructions.iter().flatten());
+        words
+    }
+
+    /// The types shared by the test modules
+    ///
+    /// `%3` float, `%4` float4, `%5` uint, `%6` the uint constant 4, `%7` a sampled 2D image, `%8` a combined image sampler
+    fn types() -> Vec<Vec<u32>> {
+        vec![
+            instruction(op::TYPE_FLOAT, &[3, 32]),
+            instruction(op::TYPE_VECTOR, &[4, 3, 4]),
+            instruction(op::TYPE_INT, &[5, 32, 0]),
+            instruction(op::CONSTANT, &[5, 6, 4]),
+            instruction(op::TYPE_IMAGE, &[7, 3, DIM_2D, 0, 0, 0, 1, 0]),
+            instruction(op::TYPE_SAMPLED_IMAGE, &[8, 7]),
+        ]
+    }
+
+    /// A vertex and a fragment entry point sharing a push constant block,
+    /// with an array of 4 combined image samplers only used by the fragment entry point
+    /// and a storage buffer only used by the vertex entry point
+    fn vertex_and_fragment_module(version: u32) -> Vec<u32> {
+        let mut instructions = vec![
+            entry_point(EXECUTION_MODEL_VERTEX, 1, "vertex", &[20, 24]),
+            entry_point(EXECUTION_MODEL_FRAGMENT, 2, "fragment", &[20, 21]),
+            instruction(op::DECORATE, &[10, decoration::BLOCK]),
+            instruction(op::MEMBER_DECORATE, &[10, 0, decoration::OFFSET, 0]),
+            instruction(op::MEMBER_DECORATE, &[10, 1, decoration::OFFSET, 16]),
+            instruction(op::DECORATE, &[21, decoration::DESCRIPTOR_SET, 0]),
+            instruction(op::DECORATE, &[21, decoration::BINDING, 1]),
+            instruction(op::DECORATE, &[19, decoration::BLOCK]),
+            instruction(op::DECORATE, &[24, decoration::DESCRIPTOR_SET, 1]),
+            instruction(op::DECORATE, &[24, decoration::BINDING, 0]),
+        ];
+        instructions.extend(types());
+        instructions.extend([
+            // push constants: struct { float4; uint; }
+            instruction(op::TYPE_STRUCT, &[10, 4, 5]),
+            instruction(op::TYPE_POINTER, &[11, storage_class::PUSH_CONSTANT, 10]),
+            instruction(op::VARIABLE, &[11, 20, storage_class::PUSH_CONSTANT]),
+            // Sampler2D[4]
+            instruction(op::TYPE_ARRAY, &[16, 8, 6]),
+            instruction(op::TYPE_POINTER, &[17, storage_class::UNIFORM_CONSTANT, 16]),
+            instruction(op::VARIABLE, &[17, 21, storage_class::UNIFORM_CONSTANT]),
+            // struct { float4[]; }
+            instruction(op::TYPE_RUNTIME_ARRAY, &[18, 4]),
+            instruction(op::TYPE_STRUCT, &[19, 18]),
+            instruction(op::TYPE_POINTER, &[23, storage_class::STORAGE_BUFFER, 19]),
+            instruction(op::VARIABLE, &[23, 24, storage_class::STORAGE_BUFFER]),
+        ]);
+        module(version, &instructions)
+    }
+
+    /// The stages, offset and size of `range`, as [`vk::PushConstantRange`] isn't comparable
+    fn range_parts(range: vk::PushConstantRange) -> (vk::ShaderStageFlags, u32, u32) {
+        (range.stage_flags, range.offset, range.size)
+    }
+
+    fn binding(reflection: &ShaderReflection, set: u32, binding: u32) -> ShaderDescriptorBinding {
+        *reflection
+            .descriptor_bindings
+            .iter()
+            .find(|descriptor| (descriptor.set, descriptor.binding) == (set, binding))
+            .unwrap_or_else(|| panic!("set {set} binding {binding} wasn't reflected"))
+    }
+
+    #[test]
+    fn entry_points() {
+        let reflection = ShaderReflection::new(&vertex_and_fragment_module(VERSION_1_5));
+
+        let entry_points = reflection
+            .entry_points
+            .iter()
+            .map(|entry_point| (entry_point.name.to_str().unwrap(), entry_point.stage))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entry_points,
+            [
+                ("vertex", vk::ShaderStageFlags::VERTEX),
+                ("fragment", vk::ShaderStageFlags::FRAGMENT),
+            ]
+        );
+        let fragment = reflection.entry_point(c"fragment").unwrap();
+        assert_eq!(fragment.stage, vk::ShaderStageFlags::FRAGMENT);
+        assert_eq!(fragment.workgroup_size, None);
+        assert!(reflection.entry_point(c"main").is_none());
+    }
+
+    #[test]
+    fn compute_workgroup_size() {
+        let spirv_code = module(
+            VERSION_1_5,
+            &[
+                entry_point(EXECUTION_MODEL_GL_COMPUTE, 1, "main", &[]),
+                instruction(op::EXECUTION_MODE, &[1, EXECUTION_MODE_LOCAL_SIZE, 8, 4, 1]),
+            ],
+        );
+        let reflection = ShaderReflection::new(&spirv_code);
+
+        let main = reflection.entry_point(c"main").unwrap();
+        assert_eq!(main.stage, vk::ShaderStageFlags::COMPUTE);
+        assert_eq!(main.workgroup_size, Some([8, 4, 1]));
+    }
+
+    #[test]
+    fn descriptor_bindings() {
+        let reflection = ShaderReflection::new(&vertex_and_fragment_module(VERSION_1_5));
+
+        assert_eq!(reflection.descriptor_bindings.len(), 2);
+        assert_eq!(
+            binding(&reflection, 0, 1),
+            ShaderDescriptorBinding {
+                set: 0,
+                binding: 1,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: Some(4),
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            }
+        );
+        assert_eq!(
+            binding(&reflection, 1, 0),
+            ShaderDescriptorBinding {
+                set: 1,
+                binding: 0,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: Some(1),
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+            }
+        );
+    }
+
+    #[test]
+    fn push_constant_range() {
+        let reflection = ShaderReflection::new(&vertex_and_fragment_module(VERSION_1_5));
+
+        // the uint at offset 16 ends the block
+        assert_eq!(
+            reflection.push_constant_range.map(range_parts),
+            Some((
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                20
+            ))
+        );
+    }
+
+    #[test]
+    fn resources_used_by_every_stage_before_spirv_1_4() {
+        let reflection = ShaderReflection::new(&vertex_and_fragment_module(VERSION_1_3));
+
+        let all_stages = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+        assert_eq!(binding(&reflection, 0, 1).stage_flags, all_stages);
+        assert_eq!(binding(&reflection, 1, 0).stage_flags, all_stages);
+        assert_eq!(
+            reflection.push_constant_range.unwrap().stage_flags,
+            all_stages
+        );
+    }
+
+    #[test]
+    fn merge_vertex_and_fragment() {
+        // struct { float; float; }
+        let mut vertex = vec![
+            entry_point(EXECUTION_MODEL_VERTEX, 1, "vertex", &[20]),
+            instruction(op::DECORATE, &[10, decoration::BLOCK]),
+            instruction(op::MEMBER_DECORATE, &[10, 0, decoration::OFFSET, 0]),
+            instruction(op::MEMBER_DECORATE, &[10, 1, decoration::OFFSET, 4]),
+        ];
+        vertex.extend(types());
+        vertex.extend([
+            instruction(op::TYPE_STRUCT, &[10, 3, 3]),
+            instruction(op::TYPE_POINTER, &[11, storage_class::PUSH_CONSTANT, 10]),
+            instruction(op::VARIABLE, &[11, 20, storage_class::PUSH_CONSTANT]),
+        ]);
+        let vertex = ShaderReflection::new(&module(VERSION_1_5, &vertex));
+        let fragment = ShaderReflection::new(&vertex_and_fragment_module(VERSION_1_5));
+        assert_eq!(vertex.push_constant_range.unwrap().size, 8);
+
+        let merged = ShaderReflection::merge(&[&vertex, &fragment]);
+
+        assert_eq!(merged.entry_points.len(), 3);
+        assert_eq!(
+            merged.push_constant_range.map(range_parts),
+            Some((
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                20
+            ))
+        );
+        assert_eq!(merged.descriptor_bindings, fragment.descriptor_bindings);
+    }
+
+    #[test]
+    #[should_panic = "set 0 binding 1 is declared differently between shaders"]
+    fn merge_conflicting_bindings() {
+        let reflection = ShaderReflection::new(&vertex_and_fragment_module(VERSION_1_5));
+        let mut conflicting = reflection.clone();
+        conflicting.descriptor_bindings[0].descriptor_count = Some(2);
+
+        ShaderReflection::merge(&[&reflection, &conflicting]);
+    }
+}
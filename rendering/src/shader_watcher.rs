@@ -0,0 +1,51 @@
+use notify::{RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+/// Watches shader files on disk so they can be reloaded without restarting the app
+///
+/// Changes are collected on a background thread and handed out by [`ShaderWatcher::poll`], usually once per frame.
+/// Pipelines rebuilt from the reloaded shaders can simply replace the old ones,
+/// as dropping a [`Pipeline`](crate::Pipeline) goes through [`Device::schedule_destroy_resource`](crate::Device::schedule_destroy_resource)
+pub struct ShaderWatcher {
+    watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(sender)?;
+        Ok(Self { watcher, events })
+    }
+
+    /// Watches `path`, recursing into it if it is a directory
+    pub fn watch(&mut self, path: &Path) -> notify::Result<()> {
+        self.watcher.watch(path, RecursiveMode::Recursive)
+    }
+
+    pub fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        self.watcher.unwatch(path)
+    }
+
+    /// Returns every file that was created or modified since the last call, without duplicates
+    pub fn poll(&self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+                    for path in event.paths {
+                        if !changed.contains(&path) {
+                            changed.push(path);
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(error) => tracing::warn!("Shader watcher error: {error}"),
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{Device, DeviceFeature, Instance};
+use ash::vk;
+use gpu_allocator::{
+    MemoryLocation,
+    vulkan::{Allocation, AllocationCreateDesc, AllocationScheme},
+};
+use std::{mem::ManuallyDrop, sync::Arc};
+
+enum Page {
+    Unbound,
+    Bound {
+        allocation: Allocation,
+        /// The timeline counter at which the binding can be used
+        resident_at: u64,
+    },
+}
+
+/// A buffer that only reserves its address range, with memory bound to it one page at a time,
+/// so it can be far larger than the memory on the device, requires [`DeviceFeature::SparseBinding`]
+///
+/// Shaders must not read pages that aren't resident, see [`SparseBuffer::is_resident`]
+pub struct SparseBuffer<'allocator> {
+    device: Arc<Device<'allocator>>,
+    name: String,
+    buffer: vk::Buffer,
+    usage: vk::BufferUsageFlags,
+    size: u64,
+    requirements: vk::MemoryRequirements,
+    pages: ManuallyDrop<Vec<Page>>,
+}
+
+impl<'allocator> SparseBuffer<'allocator> {
+    pub fn new(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        size: u64,
+        usage: vk::BufferUsageFlags,
+    ) -> Self {
+        assert!(
+            device
+                .capabilities()
+                .has_feature(DeviceFeature::SparseBinding),
+            "sparse buffers need DeviceFeature::SparseBinding",
+        );
+
+        let buffer_create_info = vk::BufferCreateInfo::default()
+            .flags(vk::BufferCreateFlags::SPARSE_BINDING | vk::BufferCreateFlags::SPARSE_RESIDENCY)
+            .size(size)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let buffer =
+            unsafe { device.create_buffer(&buffer_create_info, device.allocator()) }.unwrap();
+        device.track_resource(buffer, name);
+
+        // for sparse buffers the alignment is the size of a page
+        let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let page_count = requirements.size.div_ceil(requirements.alignment);
+
+        Self {
+            device,
+            name: name.to_owned(),
+            buffer,
+            usage,
+            size,
+            requirements,
+            pages: ManuallyDrop::new((0..page_count).map(|_| Page::Unbound).collect()),
+        }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    pub fn usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+
+    /// The reserved size, not how much memory is bound
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Usually 64KB
+    pub fn page_size(&self) -> u64 {
+        self.requirements.alignment
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// The page that holds the byte at `offset`
+    pub fn page_of(&self, offset: u64) -> usize {
+        (offset / self.page_size()) as usize
+    }
+
+    /// Whether memory is bound to `page` and the binding has finished, so shaders can use it
+    pub fn is_resident(&self, page: usize) -> bool {
+        match self.pages[page] {
+            Page::Unbound => false,
+            Page::Bound { resident_at, .. } => {
+                resident_at <= self.device.completed_timeline_counter()
+            }
+        }
+    }
+
+    /// Whether memory is bound to `page` or is going to be, see [`SparseBuffer::is_resident`]
+    pub fn is_bound(&self, page: usize) -> bool {
+        matches!(self.pages[page], Page::Bound { .. })
+    }
+
+    /// How much memory is bound
+    pub fn resident_size(&self) -> u64 {
+        self.pages
+            .iter()
+            .filter(|page| matches!(page, Page::Bound { .. }))
+            .count() as u64
+            * self.page_size()
+    }
+
+    /// # Safety
+    /// This buffer must have been created with [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`]
+    pub unsafe fn device_address(&self) -> vk::DeviceAddress {
+        debug_assert!(
+            self.usage
+                .contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS),
+            "buffers need SHADER_DEVICE_ADDRESS usage to have a device address",
+        );
+        let device_address_info = vk::BufferDeviceAddressInfo::default().buffer(self.buffer);
+        unsafe { self.device.get_buffer_device_address(&device_address_info) }
+    }
+
+    /// Binds memory to every page in `pages` that doesn't have any, returns the timeline counter at which they are resident,
+    /// which is after everything submitted so far has finished
+    pub fn make_resident(&mut self, pages: impl IntoIterator<Item = usize>) -> u64 {
+        let mut binds = vec![];
+        let mut allocations = vec![];
+        for page in pages {
+            if !matches!(self.pages[page], Page::Unbound)
+                || allocations.iter().any(|&(p, _)| p == page)
+            {
+                continue;
+            }
+            let allocation = self
+                .device
+                .with_allocator(|allocator| {
+                    allocator.allocate(&AllocationCreateDesc {
+                        name: &self.name,
+                        requirements: vk::MemoryRequirements {
+                            size: self.page_size(),
+                            ..self.requirements
+                        },
+                        location: MemoryLocation::GpuOnly,
+                        linear: true,
+                        allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+                    })
+                })
+                .unwrap();
+            binds.push(
+                vk::SparseMemoryBind::default()
+                    .resource_offset(page as u64 * self.page_size())
+                    .size(self.bind_size(page))
+                    .memory(unsafe { allocation.memory() })
+                    .memory_offset(allocation.offset()),
+            );
+            allocations.push((page, allocation));
+        }
+        if binds.is_empty() {
+            return self.device.current_timeline_counter();
+        }
+
+        let buffer_bind_info = vk::SparseBufferMemoryBindInfo::default()
+            .buffer(self.buffer)
+            .binds(&binds);
+        let resident_at = unsafe { self.device.bind_sparse(&[buffer_bind_info]) };
+        for (page, allocation) in allocations {
+            self.pages[page] = Page::Bound {
+                allocation,
+                resident_at,
+            };
+        }
+        tracing::trace!(
+            name = self.name,
+            pages = binds.len(),
+            resident_at,
+            "Made sparse buffer pages resident"
+        );
+        resident_at
+    }
+
+    /// Unbinds the memory of every page in `pages` once everything submitted so far has finished,
+    /// returns the timeline counter at which that has happened, the memory is freed after it
+    pub fn evict(&mut self, pages: impl IntoIterator<Item = usize>) -> u64 {
+        let mut binds = vec![];
+        let mut allocations = vec![];
+        for page in pages {
+            let Page::Bound { allocation, .. } =
+                std::mem::replace(&mut self.pages[page], Page::Unbound)
+            else {
+                continue;
+            };
+            binds.push(
+                vk::SparseMemoryBind::default()
+                    .resource_offset(page as u64 * self.page_size())
+                    .size(self.bind_size(page)),
+            );
+            allocations.push(allocation);
+        }
+        if binds.is_empty() {
+            return self.device.current_timeline_counter();
+        }
+
+        let buffer_bind_info = vk::SparseBufferMemoryBindInfo::default()
+            .buffer(self.buffer)
+            .binds(&binds);
+        let unbound_at = unsafe { self.device.bind_sparse(&[buffer_bind_info]) };
+        // the unbinding was the last thing submitted, so deferring frees the memory once it has finished
+        self.device.defer(move |device| {
+            device.with_allocator(|allocator| {
+                for allocation in allocations {
+                    allocator.free(allocation).unwrap();
+                }
+            });
+        });
+        tracing::trace!(
+            name = self.name,
+            pages = binds.len(),
+            unbound_at,
+            "Evicted sparse buffer pages"
+        );
+        unbound_at
+    }
+
+    /// The last page can be smaller than the others
+    fn bind_size(&self, page: usize) -> u64 {
+        let offset = page as u64 * self.page_size();
+        self.page_size().min(self.requirements.size - offset)
+    }
+}
+
+impl Drop for SparseBuffer<'_> {
+    fn drop(&mut self) {
+        let buffer = self.buffer;
+        let allocations = unsafe { ManuallyDrop::take(&mut self.pages) }
+            .into_iter()
+            .filter_map(|page| match page {
+                Page::Unbound => None,
+                Page::Bound { allocation, .. } => Some(allocation),
+            })
+            .collect::<Vec<_>>();
+        self.device.untrack_resource(buffer);
+        // the buffer has to be destroyed before the memory bound to it is freed
+        self.device.defer(move |device| {
+            unsafe { device.destroy_buffer(buffer, device.allocator()) };
+            device.with_allocator(|allocator| {
+                for allocation in allocations {
+                    allocator.free(allocation).unwrap();
+                }
+            });
+        });
+    }
+}
@@ -1,30 +1,60 @@
 use crate::Instance;
 use ash::vk;
-use scope_guard::scope_guard;
-use std::{ops::Deref, sync::Arc};
-use winit::raw_window_handle::{
-    HasDisplayHandle, HasWindowHandle, RawWindowHandle, Win32WindowHandle,
+use raw_window_handle::{
+    HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle,
+    WaylandWindowHandle, Win32WindowHandle, XcbDisplayHandle, XcbWindowHandle, XlibDisplayHandle,
+    XlibWindowHandle,
 };
+use std::{ffi::CStr, ops::Deref, sync::Arc};
 
 pub struct Surface<'allocator, 'window> {
     instance: Arc<Instance<'allocator>>,
-    #[expect(unused)]
+    /// Kept alive for as long as the surface, `()` when the window handles were passed in raw
     window: Box<dyn 'window + Send + Sync>,
     surface: vk::SurfaceKHR,
     surface_funcs: ash::khr::surface::Instance,
 }
 
 impl<'allocator, 'window> Surface<'allocator, 'window> {
+    /// Works with any window that exposes [`raw_window_handle`] handles, e.g. from winit, SDL2 or glfw,
+    /// the window is kept alive for as long as the surface
     pub fn new(
         instance: Arc<Instance<'allocator>>,
         window: impl 'window + HasWindowHandle + HasDisplayHandle + Send + Sync,
     ) -> Self {
-        let surface_funcs = ash::khr::surface::Instance::new(instance.entry(), &instance);
+        let display_handle = window.display_handle().unwrap().as_raw();
+        let window_handle = window.window_handle().unwrap().as_raw();
+        let mut surface =
+            unsafe { Self::from_raw_handles(instance, display_handle, window_handle) };
+        surface.window = Box::new(window);
+        surface
+    }
 
-        let surface = match window.window_handle().unwrap().as_raw() {
-            RawWindowHandle::Win32(Win32WindowHandle {
-                hwnd, hinstance, ..
-            }) => {
+    /// Supports Win32, Xlib, Xcb and Wayland windows
+    ///
+    /// # Safety
+    /// `display_handle` and `window_handle` must be valid and stay valid for as long as the surface
+    pub unsafe fn from_raw_handles(
+        instance: Arc<Instance<'allocator>>,
+        display_handle: RawDisplayHandle,
+        window_handle: RawWindowHandle,
+    ) -> Self {
+        let require_extension = |extension: &CStr| {
+            assert!(
+                instance.has_extension(extension),
+                "Unable to create a surface for {window_handle:?} without the '{}' instance extension",
+                extension.to_string_lossy(),
+            );
+        };
+
+        let surface = match (display_handle, window_handle) {
+            (
+                _,
+                RawWindowHandle::Win32(Win32WindowHandle {
+                    hwnd, hinstance, ..
+                }),
+            ) => {
+                require_extension(vk::KHR_WIN32_SURFACE_NAME);
                 let win32_funcs =
                     ash::khr::win32_surface::Instance::new(instance.entry(), &instance);
 
@@ -38,16 +68,77 @@ impl<'allocator, 'window> Surface<'allocator, 'window> {
                 .unwrap()
             }
 
-            _ => panic!("Unsupported platform"),
+            (
+                RawDisplayHandle::Xlib(XlibDisplayHandle {
+                    display: Some(display),
+                    ..
+                }),
+                RawWindowHandle::Xlib(XlibWindowHandle { window, .. }),
+            ) => {
+                require_extension(vk::KHR_XLIB_SURFACE_NAME);
+                let xlib_funcs = ash::khr::xlib_surface::Instance::new(instance.entry(), &instance);
+
+                let surface_create_info = vk::XlibSurfaceCreateInfoKHR::default()
+                    .dpy(display.as_ptr().cast())
+                    .window(window);
+
+                unsafe {
+                    xlib_funcs.create_xlib_surface(&surface_create_info, instance.allocator())
+                }
+                .unwrap()
+            }
+
+            (
+                RawDisplayHandle::Xcb(XcbDisplayHandle {
+                    connection: Some(connection),
+                    ..
+                }),
+                RawWindowHandle::Xcb(XcbWindowHandle { window, .. }),
+            ) => {
+                require_extension(vk::KHR_XCB_SURFACE_NAME);
+                let xcb_funcs = ash::khr::xcb_surface::Instance::new(instance.entry(), &instance);
+
+                let surface_create_info = vk::XcbSurfaceCreateInfoKHR::default()
+                    .connection(connection.as_ptr())
+                    .window(window.get());
+
+                unsafe { xcb_funcs.create_xcb_surface(&surface_create_info, instance.allocator()) }
+                    .unwrap()
+            }
+
+            (
+                RawDisplayHandle::Wayland(WaylandDisplayHandle { display, .. }),
+                RawWindowHandle::Wayland(WaylandWindowHandle { surface, .. }),
+            ) => {
+                require_extension(vk::KHR_WAYLAND_SURFACE_NAME);
+                let wayland_funcs =
+                    ash::khr::wayland_surface::Instance::new(instance.entry(), &instance);
+
+                let surface_create_info = vk::WaylandSurfaceCreateInfoKHR::default()
+                    .display(display.as_ptr())
+                    .surface(surface.as_ptr());
+
+                unsafe {
+                    wayland_funcs.create_wayland_surface(&surface_create_info, instance.allocator())
+                }
+                .unwrap()
+            }
+
+            _ => panic!("Unsupported platform {window_handle:?}"),
         };
-        let cleanup = scope_guard!(|| unsafe {
-            surface_funcs.destroy_surface(surface, instance.allocator())
-        });
 
-        cleanup.forget();
+        unsafe { Self::from_raw(instance, surface) }
+    }
+
+    /// Takes ownership of a surface that was created outside of this crate, it is destroyed when this is dropped
+    ///
+    /// # Safety
+    /// `surface` must have been created from `instance`, and whatever it presents to must stay valid for as long as the surface
+    pub unsafe fn from_raw(instance: Arc<Instance<'allocator>>, surface: vk::SurfaceKHR) -> Self {
+        let surface_funcs = ash::khr::surface::Instance::new(instance.entry(), &instance);
         Self {
             instance,
-            window: Box::new(window),
+            window: Box::new(()),
             surface,
             surface_funcs,
         }
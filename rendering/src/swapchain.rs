@@ -1,30 +1,246 @@
-use crate::{Device, Instance, Surface};
+use crate::{
+    BarrierBuilder, BinarySemaphore, Buffer, CommandPool, Device, DeviceFeature, Fence, HDR_FORMAT,
+    ImageUsage, Instance, PerFrame, QueryPool, Surface, Timestamp, TonemapOperator, Tonemapper,
+    cmd_clear_bars, is_srgb_format, letterbox_rect, map_to_logical,
+    render_pass_fallback::ImageViewInfo,
+};
 use ash::vk;
+use gpu_allocator::MemoryLocation;
 use scope_guard::scope_guard;
-use std::{ops::Deref, sync::Arc};
+use std::{
+    collections::VecDeque,
+    ops::Deref,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub const FRAMES_IN_FLIGHT_COUNT: usize = 2;
 
+const PRESENT_MODE: vk::PresentModeKHR = vk::PresentModeKHR::MAILBOX;
+
+/// How many [`FrameStats`] are kept for [`Swapchain::drain_frame_stats`], older ones are dropped
+pub const MAX_FRAME_STATS: usize = 256;
+
+const UNORM_FORMAT: vk::Format = vk::Format::B8G8R8A8_UNORM;
+const SRGB_FORMAT: vk::Format = vk::Format::B8G8R8A8_SRGB;
+
+/// What gamma encodes the colors [`Swapchain::try_next_frame`]'s callback renders before they are shown,
+/// as the swapchain's color space is always [`vk::ColorSpaceKHR::SRGB_NONLINEAR`], see [`Swapchain::set_srgb_encoding`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SrgbEncoding {
+    /// Nothing does, so the callback has to render colors that are already encoded
+    #[default]
+    None,
+    /// The swapchain has an `_SRGB` format, so the hardware encodes every write to it
+    SwapchainFormat,
+    /// The [`Tonemapper`] encodes them as it copies its target onto the swapchain image,
+    /// for surfaces without an `_SRGB` format
+    FinalPass,
+}
+
+/// How the compositor blends the window with what is behind it, see [`Swapchain::set_window_alpha`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowAlpha {
+    /// Alpha is ignored and the window covers everything behind it
+    #[default]
+    Opaque,
+    /// The colors rendered have already been multiplied by their alpha
+    PreMultiplied,
+    /// The compositor multiplies the colors by their alpha
+    PostMultiplied,
+}
+
+impl WindowAlpha {
+    pub fn composite_alpha(self) -> vk::CompositeAlphaFlagsKHR {
+        match self {
+            WindowAlpha::Opaque => vk::CompositeAlphaFlagsKHR::OPAQUE,
+            WindowAlpha::PreMultiplied => vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+            WindowAlpha::PostMultiplied => vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+        }
+    }
+}
+
+/// How the compositor shows images that don't match the window's size, like while the window is being resized
+/// and the swapchain hasn't been recreated yet, see [`Swapchain::set_present_scaling`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentScaling {
+    /// Whatever the platform does, which is often stretching the image over the window
+    #[default]
+    Default,
+    /// Not scaled at all, centered in the window
+    OneToOne,
+    /// Scaled as big as fits while keeping its aspect ratio, centered in the window
+    AspectRatioStretch,
+    /// Stretched over the whole window
+    Stretch,
+}
+
+impl PresentScaling {
+    /// Empty for [`PresentScaling::Default`]
+    pub fn scaling_behavior(self) -> vk::PresentScalingFlagsEXT {
+        match self {
+            PresentScaling::Default => vk::PresentScalingFlagsEXT::empty(),
+            PresentScaling::OneToOne => vk::PresentScalingFlagsEXT::ONE_TO_ONE,
+            PresentScaling::AspectRatioStretch => vk::PresentScalingFlagsEXT::ASPECT_RATIO_STRETCH,
+            PresentScaling::Stretch => vk::PresentScalingFlagsEXT::STRETCH,
+        }
+    }
+}
+
+/// How far ahead of the display [`Swapchain::try_next_frame`] lets rendering get, see [`Swapchain::set_latency_mode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LatencyMode {
+    /// Up to [`FRAMES_IN_FLIGHT_COUNT`] frames can be queued up, which keeps the gpu busy but shows input a few frames late
+    #[default]
+    Throughput,
+    /// A frame isn't started until the previous one is actually on screen, so it is rendered with the latest input
+    LowLatency,
+}
+
+/// Where the time of one call to [`Swapchain::try_next_frame`] or [`Swapchain::try_next_cached_frame`] went,
+/// see [`Swapchain::drain_frame_stats`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// The frame index that was tried, which only moves on once an image has been acquired
+    pub frame_index: usize,
+    /// `None` when no image was acquired
+    pub image_index: Option<u32>,
+    /// Whether nothing was submitted, usually because the call returned [`RenderResult::NotReady`]
+    pub skipped: bool,
+    /// Checking the frame index's fences, and with [`LatencyMode::LowLatency`] the previous present
+    pub fence_wait_time: Duration,
+    pub acquire_time: Duration,
+    /// Recording the command buffer, which includes everything the callback did
+    pub record_time: Duration,
+    pub submit_time: Duration,
+    pub present_time: Duration,
+    /// How long the gpu took for the previous frame with the same frame index, as it is only known once that has finished,
+    /// `None` when the graphics queue doesn't support timestamps
+    pub gpu_time: Option<Duration>,
+}
+
+impl FrameStats {
+    /// The cpu time spent in the call
+    pub fn cpu_time(&self) -> Duration {
+        self.fence_wait_time
+            + self.acquire_time
+            + self.record_time
+            + self.submit_time
+            + self.present_time
+    }
+}
+
+/// A swapchain that was replaced while frames presenting to it could still be in flight,
+/// it is destroyed once every frame index has been waited on since, see [`Swapchain::try_next_frame`]
+struct RetiredSwapchain {
+    swapchain: vk::SwapchainKHR,
+    image_views: Vec<vk::ImageView>,
+    render_finished: Vec<vk::Semaphore>,
+    /// Bit `n` is set until frame index `n` has been waited on since the swapchain was retired
+    pending_frame_indices: u32,
+}
+
+/// The command buffers and per frame data of [`Swapchain::set_cached_commands`]
+struct CachedCommands<'allocator> {
+    /// Separate from the swapchain's, so every cached command buffer can be freed at once when the mode is turned off
+    command_pool: CommandPool<'allocator>,
+    /// Indexed by swapchain image then frame index, along with whether each has been recorded since the last invalidation
+    command_buffers: Vec<PerFrame<(vk::CommandBuffer, bool)>>,
+    frame_data: PerFrame<Buffer<'allocator>>,
+}
+
+impl<'allocator> CachedCommands<'allocator> {
+    fn new(device: &Arc<Device<'allocator>>, frame_data_size: u64, image_count: usize) -> Self {
+        let mut cached_commands = Self {
+            command_pool: CommandPool::new(
+                device.clone(),
+                "Cached Command Pool",
+                vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            ),
+            command_buffers: vec![],
+            frame_data: PerFrame::new(|_| {
+                Buffer::new(
+                    device.clone(),
+                    "Frame Data Buffer",
+                    MemoryLocation::CpuToGpu,
+                    frame_data_size,
+                    vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                    false,
+                    None,
+                )
+            }),
+        };
+        cached_commands.reserve(image_count);
+        cached_commands
+    }
+
+    /// Allocates command buffers for swapchain images up to `image_count`
+    fn reserve(&mut self, image_count: usize) {
+        while self.command_buffers.len() < image_count {
+            let command_buffers = self
+                .command_pool
+                .allocate_command_buffers(FRAMES_IN_FLIGHT_COUNT as _);
+            self.command_buffers
+                .push(PerFrame::new(|index| (command_buffers[index], false)));
+        }
+    }
+
+    fn invalidate(&mut self) {
+        for (_, recorded) in self.command_buffers.iter_mut().flatten() {
+            *recorded = false;
+        }
+    }
+}
+
 pub struct Swapchain<'allocator, 'window> {
     device: Arc<Device<'allocator>>,
     surface: Arc<Surface<'allocator, 'window>>,
 
     width: u32,
     height: u32,
+    scale_factor: f64,
+    format: vk::Format,
     swapchain: vk::SwapchainKHR,
     swapchain_funcs: ash::khr::swapchain::Device,
 
     images: Vec<vk::Image>,
     image_views: Vec<vk::ImageView>,
+    /// One per image, as without present fences the only guarantee that a present has finished with its semaphore is the image being aquired again
+    render_finished: Vec<vk::Semaphore>,
 
-    command_pool: vk::CommandPool,
+    /// Only kept so `command_buffers` stay valid, they are freed along with it
+    _command_pool: CommandPool<'allocator>,
 
     frame_counter: usize,
-    aquired_image: [vk::Semaphore; FRAMES_IN_FLIGHT_COUNT],
-    command_buffers: [vk::CommandBuffer; FRAMES_IN_FLIGHT_COUNT],
-    render_finished: [vk::Semaphore; FRAMES_IN_FLIGHT_COUNT],
-    render_finished_fences: [vk::Fence; FRAMES_IN_FLIGHT_COUNT],
-    finished_presenting: [vk::Fence; FRAMES_IN_FLIGHT_COUNT],
+    aquired_image: PerFrame<BinarySemaphore<'allocator>>,
+    command_buffers: PerFrame<vk::CommandBuffer>,
+    render_finished_fences: PerFrame<Fence<'allocator>>,
+    /// Only signaled by presentation when [`Swapchain::present_fences`] is true, otherwise these always stay signaled
+    finished_presenting: PerFrame<Fence<'allocator>>,
+    present_fences: bool,
+    /// Only loaded with [`DeviceFeature::PresentWait`]
+    present_wait_funcs: Option<ash::khr::present_wait::Device>,
+    latency_mode: LatencyMode,
+    /// The id of the latest present to the current swapchain, 0 before the first one
+    last_present_id: u64,
+    destroy_resources_each_frame: bool,
+    /// When set, frames are rendered into its target and tonemapped onto the swapchain image
+    tonemapper: Option<Tonemapper<'allocator>>,
+    /// When set, frames keep this aspect ratio and are rendered centered between black bars
+    letterbox: Option<vk::Extent2D>,
+    window_alpha: WindowAlpha,
+    present_scaling: PresentScaling,
+    /// Where images are placed when they aren't scaled over the whole window, empty for [`PresentScaling::Default`]
+    present_gravity: vk::PresentGravityFlagsEXT,
+    /// Only used with [`Swapchain::present_fences`], otherwise recreating waits for everything to finish instead
+    retired: Vec<RetiredSwapchain>,
+    /// Only with [`Swapchain::set_cached_commands`]
+    cached_commands: Option<CachedCommands<'allocator>>,
+    frame_stats: VecDeque<FrameStats>,
+    /// Two queries per frame index for the start and end of its command buffer, `None` when the graphics queue doesn't support timestamps
+    timestamp_query_pool: Option<QueryPool<'allocator, Timestamp>>,
+    /// Whether the frame index's queries have been written since the frame index was last waited on
+    timestamps_written: PerFrame<bool>,
 }
 
 impl<'allocator, 'window> Swapchain<'allocator, 'window> {
@@ -35,6 +251,9 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
         assert!(Arc::ptr_eq(device.instance(), surface.instance()));
 
         let swapchain_funcs = ash::khr::swapchain::Device::new(device.instance(), &device);
+        let present_fences = device
+            .capabilities()
+            .has_feature(DeviceFeature::SwapchainMaintenance1);
 
         let capabilities = unsafe {
             surface.get_physical_device_surface_capabilities(
@@ -52,6 +271,8 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
             surface.handle(),
             vk::Extent2D { width, height },
             &graphics_queue_family_index,
+            UNORM_FORMAT,
+            WindowAlpha::Opaque.composite_alpha(),
             vk::SwapchainKHR::null(),
         );
 
@@ -62,6 +283,14 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
         );
 
         let images = unsafe { swapchain_funcs.get_swapchain_images(*swapchain) }.unwrap();
+        tracing::info!(
+            width,
+            height,
+            format = ?swapchain_create_info.image_format,
+            image_count = images.len(),
+            present_fences,
+            "Created swapchain"
+        );
 
         let mut image_views = scope_guard!(
             |image_views| {
@@ -89,100 +318,89 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
             let image_view =
                 unsafe { device.create_image_view(&image_view_create_info, device.allocator()) }
                     .unwrap();
+            device.register_image_view(
+                image_view,
+                swapchain_image_view_info(&swapchain_create_info),
+            );
             image_views.push(image_view);
         }
 
-        let command_pool_create_info = vk::CommandPoolCreateInfo::default()
-            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
-            .queue_family_index(graphics_queue_family_index);
-
-        let command_pool = scope_guard!(
-            |command_pool| unsafe { device.destroy_command_pool(command_pool, device.allocator()) },
-            unsafe { device.create_command_pool(&command_pool_create_info, device.allocator()) }
-                .unwrap()
-        );
-
-        let aquired_image = scope_guard!(
-            |aquired_image| {
-                for semaphore in aquired_image {
-                    unsafe { device.destroy_semaphore(semaphore, device.allocator()) };
-                }
-            },
-            std::array::from_fn(|_| {
-                let semaphore_create_info = vk::SemaphoreCreateInfo::default();
-                unsafe { device.create_semaphore(&semaphore_create_info, device.allocator()) }
-                    .unwrap()
-            })
-        );
-
-        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
-            .command_pool(*command_pool)
-            .level(vk::CommandBufferLevel::PRIMARY)
-            .command_buffer_count(FRAMES_IN_FLIGHT_COUNT as _);
-        let command_buffers =
-            unsafe { device.allocate_command_buffers(&command_buffer_allocate_info) }
-                .unwrap()
-                .try_into()
-                .unwrap();
-
         let render_finished = scope_guard!(
             |render_finished| {
                 for semaphore in render_finished {
                     unsafe { device.destroy_semaphore(semaphore, device.allocator()) };
                 }
             },
-            std::array::from_fn(|_| {
-                let semaphore_create_info = vk::SemaphoreCreateInfo::default();
-                unsafe { device.create_semaphore(&semaphore_create_info, device.allocator()) }
-                    .unwrap()
-            })
+            images
+                .iter()
+                .map(|_| {
+                    let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+                    unsafe { device.create_semaphore(&semaphore_create_info, device.allocator()) }
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
         );
 
-        let render_finished_fences = scope_guard!(
-            |render_finished| {
-                for fence in render_finished {
-                    unsafe { device.destroy_fence(fence, device.allocator()) };
-                }
-            },
-            std::array::from_fn(|_| {
-                let fence_create_info =
-                    vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
-                unsafe { device.create_fence(&fence_create_info, device.allocator()) }.unwrap()
-            })
+        let command_pool = CommandPool::new(
+            device.clone(),
+            "Swapchain Command Pool",
+            vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
         );
+        let aquired_image =
+            PerFrame::new(|_| BinarySemaphore::new(device.clone(), "Aquired Image Semaphore"));
+        let command_buffers = command_pool
+            .allocate_command_buffers(FRAMES_IN_FLIGHT_COUNT as _)
+            .try_into()
+            .unwrap();
+        let render_finished_fences =
+            PerFrame::new(|_| Fence::new(device.clone(), "Render Finished Fence", true));
 
-        let finished_presenting = scope_guard!(
-            |finished_presenting| {
-                for fence in finished_presenting {
-                    unsafe { device.destroy_fence(fence, device.allocator()) };
-                }
-            },
-            std::array::from_fn(|_| {
-                let fence_create_info =
-                    vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
-                unsafe { device.create_fence(&fence_create_info, device.allocator()) }.unwrap()
-            })
-        );
+        let timestamp_query_pool = Timestamp::new(&device).map(|timestamp| {
+            QueryPool::new(device.clone(), "Frame Timestamp Query Pool", timestamp, 2)
+        });
+
+        let finished_presenting =
+            PerFrame::new(|_| Fence::new(device.clone(), "Finished Presenting Fence", true));
 
         Self {
             surface,
 
             width,
             height,
+            scale_factor: 1.0,
+            format: swapchain_create_info.image_format,
             swapchain: swapchain.into_inner(),
             swapchain_funcs,
 
             images,
             image_views: image_views.into_inner(),
+            render_finished: render_finished.into_inner(),
 
-            command_pool: command_pool.into_inner(),
+            _command_pool: command_pool,
 
             frame_counter: 0,
-            aquired_image: aquired_image.into_inner(),
+            aquired_image,
             command_buffers,
-            render_finished: render_finished.into_inner(),
-            render_finished_fences: render_finished_fences.into_inner(),
-            finished_presenting: finished_presenting.into_inner(),
+            render_finished_fences,
+            finished_presenting,
+            present_fences,
+            present_wait_funcs: device
+                .capabilities()
+                .has_feature(DeviceFeature::PresentWait)
+                .then(|| ash::khr::present_wait::Device::new(device.instance(), &device)),
+            latency_mode: LatencyMode::Throughput,
+            last_present_id: 0,
+            destroy_resources_each_frame: true,
+            tonemapper: None,
+            letterbox: None,
+            window_alpha: WindowAlpha::Opaque,
+            present_scaling: PresentScaling::Default,
+            present_gravity: vk::PresentGravityFlagsEXT::empty(),
+            retired: vec![],
+            cached_commands: None,
+            frame_stats: VecDeque::with_capacity(MAX_FRAME_STATS),
+            timestamp_query_pool,
+            timestamps_written: PerFrame::default(),
 
             device,
         }
@@ -212,22 +430,365 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
         self.height
     }
 
-    pub fn resize(&mut self, mut width: u32, mut height: u32) {
-        if width == 0 || height == 0 || (width == self.width && height == self.height) {
-            return;
+    /// The ratio of physical pixels to logical pixels of the window, [`Swapchain::width`] and [`Swapchain::height`] are always physical
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Should be called with the window's scale factor on creation and on every `ScaleFactorChanged`
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    /// Whether presentation completion is tracked with `VK_EXT_swapchain_maintenance1` present fences,
+    /// if not then recreating or destroying the swapchain waits for the graphics queue to go idle
+    pub fn present_fences(&self) -> bool {
+        self.present_fences
+    }
+
+    /// Whether presents are tracked with `VK_KHR_present_wait`, which [`LatencyMode::LowLatency`] needs
+    pub fn present_wait(&self) -> bool {
+        self.present_wait_funcs.is_some()
+    }
+
+    pub fn latency_mode(&self) -> LatencyMode {
+        self.latency_mode
+    }
+
+    /// [`LatencyMode::LowLatency`] needs [`DeviceFeature::PresentWait`], without it this keeps [`LatencyMode::Throughput`]
+    pub fn set_latency_mode(&mut self, latency_mode: LatencyMode) {
+        self.latency_mode = match self.present_wait_funcs {
+            Some(_) => latency_mode,
+            None => LatencyMode::Throughput,
+        };
+    }
+
+    /// The id of the latest frame [`Swapchain::try_next_frame`] presented, 0 when there hasn't been one since the swapchain was last recreated
+    /// or without [`DeviceFeature::PresentWait`]
+    pub fn last_present_id(&self) -> u64 {
+        self.last_present_id
+    }
+
+    /// Blocks until the latest presented frame is on screen or `timeout` passes, returning whether it is,
+    /// always true when there is nothing to wait for
+    pub fn wait_for_last_present(&self, timeout: Duration) -> bool {
+        let Some(present_wait_funcs) = &self.present_wait_funcs else {
+            return true;
+        };
+        if self.last_present_id == 0 {
+            return true;
+        }
+        match unsafe {
+            present_wait_funcs.wait_for_present(
+                self.swapchain,
+                self.last_present_id,
+                timeout.as_nanos().try_into().unwrap_or(u64::MAX),
+            )
+        } {
+            Ok(()) => true,
+            Err(vk::Result::TIMEOUT) => false,
+            // an out of date swapchain is going to be recreated anyway, so there is nothing left to wait for
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
+            Err(error) => panic!("{error}"),
         }
+    }
 
-        unsafe {
-            self.device
-                .wait_for_fences(&self.render_finished_fences, true, u64::MAX)
+    /// Whether [`Swapchain::try_next_frame`] calls [`Device::destroy_resources`] once the frame's fences have signaled, on by default
+    pub fn destroy_resources_each_frame(&self) -> bool {
+        self.destroy_resources_each_frame
+    }
+
+    /// Turn this off to call [`Device::destroy_resources`] manually, like when several swapchains share a device
+    pub fn set_destroy_resources_each_frame(&mut self, destroy_resources_each_frame: bool) {
+        self.destroy_resources_each_frame = destroy_resources_each_frame;
+    }
+
+    /// The format of the image passed to [`Swapchain::try_next_frame`]'s callback,
+    /// [`HDR_FORMAT`] when tonemapping and [`Swapchain::format`] otherwise
+    pub fn render_format(&self) -> vk::Format {
+        match self.tonemapper {
+            Some(_) => HDR_FORMAT,
+            None => self.format,
+        }
+    }
+
+    /// With an operator, [`Swapchain::try_next_frame`]'s callback renders into an [`HDR_FORMAT`] image that is then
+    /// tonemapped onto the swapchain image, pipelines rendering in the callback have to use [`Swapchain::render_format`]
+    ///
+    /// The callback is given the area of that image to render to, which is all of it,
+    /// and differs in size from [`Swapchain::viewport`] with [`Tonemapper::set_render_scale`]
+    pub fn set_tonemap(&mut self, operator: Option<TonemapOperator>) {
+        match (operator, &mut self.tonemapper) {
+            (Some(operator), Some(tonemapper)) => tonemapper.set_operator(operator),
+            (Some(operator), None) => {
+                let viewport = self.viewport();
+                self.tonemapper = Some(Tonemapper::new(
+                    self.device.clone(),
+                    self.format,
+                    viewport.extent.width,
+                    viewport.extent.height,
+                    operator,
+                ));
+            }
+            (None, _) => self.tonemapper = None,
+        }
+        // frames recorded without the tonemapper render straight to the swapchain image
+        self.invalidate_cached_commands();
+    }
+
+    /// Worked out from [`Swapchain::format`] and the [`Tonemapper`], so turning tonemapping off
+    /// or [`Tonemapper::set_encode_srgb`] changes it too
+    pub fn srgb_encoding(&self) -> SrgbEncoding {
+        if is_srgb_format(self.format) {
+            SrgbEncoding::SwapchainFormat
+        } else if self
+            .tonemapper
+            .as_ref()
+            .is_some_and(|tonemapper| tonemapper.encode_srgb())
+        {
+            SrgbEncoding::FinalPass
+        } else {
+            SrgbEncoding::None
+        }
+    }
+
+    /// Recreates the swapchain with the format `srgb_encoding` needs, returning false and leaving it as it was
+    /// when the surface has no `_SRGB` format for [`SrgbEncoding::SwapchainFormat`], in which case [`SrgbEncoding::FinalPass`] still works
+    ///
+    /// [`SrgbEncoding::FinalPass`] turns on tonemapping with [`TonemapOperator::Clamp`] if it is off,
+    /// and pipelines rendering in the callback have to be recreated when [`Swapchain::render_format`] changes
+    pub fn set_srgb_encoding(&mut self, srgb_encoding: SrgbEncoding) -> bool {
+        let format = match srgb_encoding {
+            SrgbEncoding::SwapchainFormat => {
+                let surface_formats = unsafe {
+                    self.surface.get_physical_device_surface_formats(
+                        self.device.physical_device(),
+                        self.surface.handle(),
+                    )
+                }
+                .unwrap();
+                if !surface_formats.iter().any(|surface_format| {
+                    surface_format.format == SRGB_FORMAT
+                        && surface_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+                }) {
+                    return false;
+                }
+                SRGB_FORMAT
+            }
+            SrgbEncoding::None | SrgbEncoding::FinalPass => UNORM_FORMAT,
+        };
+
+        if format != self.format {
+            self.format = format;
+            self.recreate(self.width, self.height);
+            if let Some(tonemapper) = &mut self.tonemapper {
+                tonemapper.set_output_format(format);
+            }
+        }
+        match srgb_encoding {
+            SrgbEncoding::None => {
+                if let Some(tonemapper) = &mut self.tonemapper {
+                    tonemapper.set_encode_srgb(false);
+                }
+            }
+            SrgbEncoding::SwapchainFormat => {}
+            SrgbEncoding::FinalPass => {
+                if self.tonemapper.is_none() {
+                    self.set_tonemap(Some(TonemapOperator::Clamp));
+                }
+                self.tonemapper.as_mut().unwrap().set_encode_srgb(true);
+            }
+        }
+        self.invalidate_cached_commands();
+        true
+    }
+
+    pub fn tonemapper(&self) -> Option<&Tonemapper<'allocator>> {
+        self.tonemapper.as_ref()
+    }
+
+    /// For changing the exposure, see [`Tonemapper::set_exposure`]
+    pub fn tonemapper_mut(&mut self) -> Option<&mut Tonemapper<'allocator>> {
+        self.tonemapper.as_mut()
+    }
+
+    pub fn window_alpha(&self) -> WindowAlpha {
+        self.window_alpha
+    }
+
+    /// Recreates the swapchain to have the window blended with what is behind it, returning false and leaving it as it was
+    /// when the surface doesn't support `window_alpha`, which is common for anything other than [`WindowAlpha::Opaque`]
+    ///
+    /// The window itself has to be created transparent, and while it isn't opaque the black bars of [`Swapchain::set_letterbox`]
+    /// are cleared to transparent and the callback of [`Swapchain::try_next_frame`] should render with a transparent clear color,
+    /// see [`Swapchain::clear_color`]
+    pub fn set_window_alpha(&mut self, window_alpha: WindowAlpha) -> bool {
+        let capabilities = unsafe {
+            self.surface.get_physical_device_surface_capabilities(
+                self.device.physical_device(),
+                self.surface.handle(),
+            )
         }
         .unwrap();
+        if !capabilities
+            .supported_composite_alpha
+            .contains(window_alpha.composite_alpha())
+        {
+            return false;
+        }
+        if window_alpha != self.window_alpha {
+            self.window_alpha = window_alpha;
+            self.recreate(self.width, self.height);
+        }
+        true
+    }
+
+    /// Black, transparent unless [`Swapchain::window_alpha`] is [`WindowAlpha::Opaque`]
+    pub fn clear_color(&self) -> [f32; 4] {
+        match self.window_alpha {
+            WindowAlpha::Opaque => [0.0, 0.0, 0.0, 1.0],
+            WindowAlpha::PreMultiplied | WindowAlpha::PostMultiplied => [0.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    pub fn present_scaling(&self) -> PresentScaling {
+        self.present_scaling
+    }
+
+    /// Recreates the swapchain so images that don't match the window's size are scaled with `present_scaling`,
+    /// returning false and leaving it as it was when the surface doesn't support it or [`Swapchain::present_fences`] is false,
+    /// as this needs `VK_EXT_swapchain_maintenance1`
+    pub fn set_present_scaling(&mut self, present_scaling: PresentScaling) -> bool {
+        if present_scaling == self.present_scaling {
+            return true;
+        }
+        if present_scaling == PresentScaling::Default {
+            self.present_scaling = present_scaling;
+            self.present_gravity = vk::PresentGravityFlagsEXT::empty();
+            self.recreate(self.width, self.height);
+            return true;
+        }
+        if !self.present_fences {
+            return false;
+        }
+
+        let instance = self.device.instance();
+        let surface_capabilities2_funcs =
+            ash::khr::get_surface_capabilities2::Instance::new(instance.entry(), instance);
+        let mut present_mode = vk::SurfacePresentModeEXT::default().present_mode(PRESENT_MODE);
+        let surface_info = vk::PhysicalDeviceSurfaceInfo2KHR::default()
+            .surface(self.surface.handle())
+            .push_next(&mut present_mode);
+        let mut scaling_capabilities = vk::SurfacePresentScalingCapabilitiesEXT::default();
+        let mut capabilities =
+            vk::SurfaceCapabilities2KHR::default().push_next(&mut scaling_capabilities);
         unsafe {
-            self.device
-                .wait_for_fences(&self.finished_presenting, true, u64::MAX)
+            surface_capabilities2_funcs.get_physical_device_surface_capabilities2(
+                self.device.physical_device(),
+                &surface_info,
+                &mut capabilities,
+            )
         }
         .unwrap();
 
+        if !scaling_capabilities
+            .supported_present_scaling
+            .contains(present_scaling.scaling_behavior())
+        {
+            return false;
+        }
+        let supported_gravity = scaling_capabilities.supported_present_gravity_x
+            & scaling_capabilities.supported_present_gravity_y;
+        let Some(present_gravity) = [
+            vk::PresentGravityFlagsEXT::CENTERED,
+            vk::PresentGravityFlagsEXT::MIN,
+            vk::PresentGravityFlagsEXT::MAX,
+        ]
+        .into_iter()
+        .find(|&gravity| supported_gravity.contains(gravity)) else {
+            return false;
+        };
+
+        self.present_scaling = present_scaling;
+        self.present_gravity = present_gravity;
+        self.recreate(self.width, self.height);
+        true
+    }
+
+    /// The logical size set with [`Swapchain::set_letterbox`]
+    pub fn letterbox(&self) -> Option<vk::Extent2D> {
+        self.letterbox
+    }
+
+    /// With a logical size, frames keep its aspect ratio however the window is resized,
+    /// [`Swapchain::try_next_frame`]'s callback is given the centered [`Swapchain::viewport`] to render into and the rest of the image is cleared to black
+    ///
+    /// The logical size only sets the aspect ratio and the units of [`Swapchain::cursor_to_logical`], the viewport is as big as fits in the swapchain
+    pub fn set_letterbox(&mut self, logical_size: Option<vk::Extent2D>) {
+        self.letterbox = logical_size;
+        self.invalidate_cached_commands();
+        let viewport = self.viewport();
+        if let Some(tonemapper) = &mut self.tonemapper {
+            tonemapper.resize(viewport.extent.width, viewport.extent.height);
+        }
+    }
+
+    /// The part of the swapchain images frames are shown in, all of them unless letterboxing
+    pub fn viewport(&self) -> vk::Rect2D {
+        let extent = vk::Extent2D {
+            width: self.width,
+            height: self.height,
+        };
+        match self.letterbox {
+            Some(logical_size) => letterbox_rect(extent, logical_size),
+            None => vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+        }
+    }
+
+    /// Maps a cursor position in physical pixels of the window into `0..logical_size` of [`Swapchain::set_letterbox`],
+    /// or into physical pixels of the swapchain when not letterboxing, `None` when the cursor is over the black bars
+    pub fn cursor_to_logical(&self, position: [f64; 2]) -> Option<[f64; 2]> {
+        let viewport = self.viewport();
+        map_to_logical(
+            viewport,
+            self.letterbox.unwrap_or(viewport.extent),
+            position,
+        )
+    }
+
+    fn wait_for_presents(&self) {
+        wait_for_all(&self.device, &self.finished_presenting);
+        if !self.present_fences {
+            self.device
+                .with_graphics_queue(|graphics_queue| unsafe {
+                    self.device.queue_wait_idle(graphics_queue)
+                })
+                .unwrap();
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width == self.width && height == self.height) {
+            return;
+        }
+        self.recreate(width, height);
+    }
+
+    /// With present fences the old swapchain is retired instead of waiting for the frames in flight,
+    /// so resizing doesn't stall every frame
+    fn recreate(&mut self, mut width: u32, mut height: u32) {
+        if !self.present_fences {
+            wait_for_all(&self.device, &self.render_finished_fences);
+            self.wait_for_presents();
+        }
+
         let capabilities = unsafe {
             self.surface.get_physical_device_surface_capabilities(
                 self.device.physical_device(),
@@ -246,12 +807,22 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
             capabilities.min_image_extent.height,
             capabilities.max_image_extent.height,
         );
-        let swapchain_create_info = swapchain_create_info(
+        let mut swapchain_create_info = swapchain_create_info(
             self.surface.handle(),
             vk::Extent2D { width, height },
             &graphics_queue_family_index,
+            self.format,
+            self.window_alpha.composite_alpha(),
             self.swapchain,
         );
+        let mut present_scaling_create_info = vk::SwapchainPresentScalingCreateInfoEXT::default()
+            .scaling_behavior(self.present_scaling.scaling_behavior())
+            .present_gravity_x(self.present_gravity)
+            .present_gravity_y(self.present_gravity);
+        if self.present_scaling != PresentScaling::Default {
+            swapchain_create_info =
+                swapchain_create_info.push_next(&mut present_scaling_create_info);
+        }
 
         let old_swapchain = core::mem::replace(
             &mut self.swapchain,
@@ -261,20 +832,48 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
             }
             .unwrap(),
         );
-        unsafe { self.destroy_swapchain(old_swapchain, self.allocator()) };
+        let old_image_views = core::mem::take(&mut self.image_views);
+        self.images.clear();
+        if self.present_fences {
+            // the semaphores could still be waited on by presents to the old swapchain
+            self.retired.push(RetiredSwapchain {
+                swapchain: old_swapchain,
+                image_views: old_image_views,
+                render_finished: core::mem::take(&mut self.render_finished),
+                pending_frame_indices: (1 << FRAMES_IN_FLIGHT_COUNT) - 1,
+            });
+        } else {
+            for image_view in old_image_views {
+                self.device.unregister_image_view(image_view);
+                unsafe {
+                    self.device
+                        .destroy_image_view(image_view, self.device.allocator());
+                }
+            }
+            unsafe { self.destroy_swapchain(old_swapchain, self.allocator()) };
+        }
 
         self.width = width;
         self.height = height;
-
-        self.images.clear();
-        for image_view in self.image_views.drain(..) {
-            unsafe {
-                self.device
-                    .destroy_image_view(image_view, self.device.allocator());
-            }
+        // present ids belong to the old swapchain
+        self.last_present_id = 0;
+        let viewport = self.viewport();
+        if let Some(tonemapper) = &mut self.tonemapper {
+            tonemapper.resize(viewport.extent.width, viewport.extent.height);
         }
 
         self.images = unsafe { self.get_swapchain_images(self.swapchain) }.unwrap();
+        if let Some(cached_commands) = &mut self.cached_commands {
+            cached_commands.invalidate();
+            cached_commands.reserve(self.images.len());
+        }
+        tracing::debug!(
+            width,
+            height,
+            image_count = self.images.len(),
+            retired_swapchains = self.retired.len(),
+            "Recreated swapchain"
+        );
         for &image in &self.images {
             let image_view_create_info = vk::ImageViewCreateInfo::default()
                 .image(image)
@@ -288,177 +887,370 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
                     .create_image_view(&image_view_create_info, self.device.allocator())
             }
             .unwrap();
+            self.device.register_image_view(
+                image_view,
+                swapchain_image_view_info(&swapchain_create_info),
+            );
             self.image_views.push(image_view);
         }
+
+        while self.render_finished.len() > self.images.len() {
+            let semaphore = self.render_finished.pop().unwrap();
+            unsafe { self.device.destroy_semaphore(semaphore, self.allocator()) };
+        }
+        while self.render_finished.len() < self.images.len() {
+            let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+            let semaphore = unsafe {
+                self.device
+                    .create_semaphore(&semaphore_create_info, self.allocator())
+            }
+            .unwrap();
+            self.render_finished.push(semaphore);
+        }
     }
 
+    fn destroy_retired(&self, retired: RetiredSwapchain) {
+        for image_view in retired.image_views {
+            self.device.unregister_image_view(image_view);
+            unsafe { self.device.destroy_image_view(image_view, self.allocator()) };
+        }
+        for semaphore in retired.render_finished {
+            unsafe { self.device.destroy_semaphore(semaphore, self.allocator()) };
+        }
+        unsafe { self.destroy_swapchain(retired.swapchain, self.allocator()) };
+    }
+
+    /// The callback records rendering to the image it is given, which is in the layout it is given and has to be left in the layout written back,
+    /// only the given area of it is shown and the rest has to stay as it is
     pub fn try_next_frame<'a>(
         &mut self,
         f: impl FnOnce(
             vk::CommandBuffer,
             &mut vk::ImageLayout,
-            u32,
-            u32,
+            vk::Rect2D,
             vk::Image,
             vk::ImageView,
             usize,
         ) -> RenderSync<'a>,
+    ) -> RenderResult {
+        self.next_frame(|swapchain, frame_index, image_index| {
+            let command_buffer = swapchain.command_buffers[frame_index];
+            let render_sync = unsafe {
+                swapchain.record_frame(
+                    command_buffer,
+                    vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                    frame_index,
+                    image_index,
+                    f,
+                )
+            };
+            (command_buffer, render_sync)
+        })
+    }
+
+    /// Takes the stats of every call since the last time, up to [`MAX_FRAME_STATS`] of the latest ones
+    pub fn drain_frame_stats(&mut self) -> impl Iterator<Item = FrameStats> + '_ {
+        self.frame_stats.drain(..)
+    }
+
+    /// Whether [`FrameStats::gpu_time`] is measured
+    pub fn gpu_timestamps(&self) -> bool {
+        self.timestamp_query_pool.is_some()
+    }
+
+    /// Reads the timestamps the last frame with `frame_index` wrote, which must have finished
+    fn read_gpu_time(&mut self, frame_index: usize) -> Option<Duration> {
+        let query_pool = self.timestamp_query_pool.as_ref()?;
+        if !core::mem::take(&mut self.timestamps_written[frame_index]) {
+            return None;
+        }
+        let [start, end] = query_pool.results(frame_index)[..] else {
+            unreachable!()
+        };
+        Some(query_pool.duration(start?, end?))
+    }
+
+    pub fn cached_commands(&self) -> bool {
+        self.cached_commands.is_some()
+    }
+
+    /// With a size, [`Swapchain::try_next_cached_frame`] keeps one command buffer per swapchain image and frame index
+    /// and only records them again after [`Swapchain::invalidate_cached_commands`], with per frame data going through
+    /// a mapped buffer of `frame_data_size` bytes instead of push constants
+    pub fn set_cached_commands(&mut self, frame_data_size: Option<u64>) {
+        // the command buffers could still be in use, so the pool they came from is only destroyed once they have finished
+        self.cached_commands = None;
+        if let Some(frame_data_size) = frame_data_size {
+            self.cached_commands = Some(CachedCommands::new(
+                &self.device,
+                frame_data_size,
+                self.images.len(),
+            ));
+        }
+    }
+
+    /// Has every cached command buffer recorded again, which is needed whenever something they use changes,
+    /// recreating the swapchain, [`Swapchain::set_letterbox`], [`Swapchain::set_tonemap`] and [`Swapchain::set_srgb_encoding`] do this themselves
+    pub fn invalidate_cached_commands(&mut self) {
+        if let Some(cached_commands) = &mut self.cached_commands {
+            cached_commands.invalidate();
+        }
+    }
+
+    /// Like [`Swapchain::try_next_frame`] but with [`Swapchain::set_cached_commands`], `write_frame_data` is given this frame's data
+    /// to fill in every frame, while `record` is only called the first time each swapchain image is used with a frame index
+    /// and is given the device address of the data it has to read
+    ///
+    /// Everything `record` uses has to stay alive until [`Swapchain::invalidate_cached_commands`] and the frames using it have finished,
+    /// and frames going through the [`Tonemapper`] are always recorded, as it changes per frame state like the TAA jitter,
+    /// they aren't kept as recorded so turning the tonemapper off never replays its resources
+    pub fn try_next_cached_frame(
+        &mut self,
+        write_frame_data: impl FnOnce(&mut [u8]),
+        record: impl FnOnce(
+            vk::CommandBuffer,
+            &mut vk::ImageLayout,
+            vk::Rect2D,
+            vk::Image,
+            vk::ImageView,
+            usize,
+            vk::DeviceAddress,
+        ),
+    ) -> RenderResult {
+        assert!(
+            self.cached_commands.is_some(),
+            "Swapchain::set_cached_commands has to be called before Swapchain::try_next_cached_frame",
+        );
+        self.next_frame(|swapchain, frame_index, image_index| {
+            let cached_commands = swapchain.cached_commands.as_mut().unwrap();
+            let frame_data = &mut cached_commands.frame_data[frame_index];
+            write_frame_data(unsafe { frame_data.get_mapped_mut() }.unwrap());
+            let frame_data_address = unsafe { frame_data.device_address() };
+
+            let (command_buffer, recorded) =
+                cached_commands.command_buffers[image_index as usize][frame_index];
+            let tonemapped = swapchain.tonemapper.is_some();
+            if !recorded || tonemapped {
+                unsafe {
+                    swapchain.record_frame(
+                        command_buffer,
+                        vk::CommandBufferUsageFlags::empty(),
+                        frame_index,
+                        image_index,
+                        |command_buffer, layout, area, image, image_view, frame_index| {
+                            record(
+                                command_buffer,
+                                layout,
+                                area,
+                                image,
+                                image_view,
+                                frame_index,
+                                frame_data_address,
+                            );
+                            RenderSync::default()
+                        },
+                    );
+                }
+                swapchain.cached_commands.as_mut().unwrap().command_buffers[image_index as usize]
+                    [frame_index]
+                    .1 = !tonemapped;
+            }
+            (command_buffer, RenderSync::default())
+        })
+    }
+
+    /// Waits for the frame index to be free and acquires an image, then submits and presents whatever `record` returns
+    fn next_frame<'a>(
+        &mut self,
+        record: impl FnOnce(&mut Self, usize, u32) -> (vk::CommandBuffer, RenderSync<'a>),
+    ) -> RenderResult {
+        let mut stats = FrameStats {
+            frame_index: self.frame_counter,
+            skipped: true,
+            ..FrameStats::default()
+        };
+        let result = self.next_frame_with_stats(&mut stats, record);
+        if self.frame_stats.len() == MAX_FRAME_STATS {
+            self.frame_stats.pop_front();
+        }
+        self.frame_stats.push_back(stats);
+        result
+    }
+
+    fn next_frame_with_stats<'a>(
+        &mut self,
+        stats: &mut FrameStats,
+        record: impl FnOnce(&mut Self, usize, u32) -> (vk::CommandBuffer, RenderSync<'a>),
     ) -> RenderResult {
         let frame_index = self.frame_counter;
 
-        match unsafe {
-            self.device
-                .wait_for_fences(&[self.render_finished_fences[frame_index]], true, 0)
-        } {
-            Err(vk::Result::TIMEOUT) => return RenderResult::NotReady,
+        let fence_wait_start = Instant::now();
+        match self.device.report_device_lost(unsafe {
+            self.device.wait_for_fences(
+                &[self.render_finished_fences[frame_index].handle()],
+                true,
+                0,
+            )
+        }) {
+            Err(vk::Result::TIMEOUT) => {
+                stats.fence_wait_time = fence_wait_start.elapsed();
+                return RenderResult::NotReady;
+            }
             e => e.unwrap(),
         }
         match unsafe {
             self.device
-                .wait_for_fences(&[self.finished_presenting[frame_index]], true, 0)
+                .wait_for_fences(&[self.finished_presenting[frame_index].handle()], true, 0)
         } {
-            Err(vk::Result::TIMEOUT) => return RenderResult::NotReady,
+            Err(vk::Result::TIMEOUT) => {
+                stats.fence_wait_time = fence_wait_start.elapsed();
+                return RenderResult::NotReady;
+            }
             e => e.unwrap(),
         }
 
-        let (image_index, mut suboptimal) = match unsafe {
+        // every present to a retired swapchain has finished once each frame index has been waited on since it was retired
+        for retired in &mut self.retired {
+            retired.pending_frame_indices &= !(1 << frame_index);
+        }
+        let finished = self
+            .retired
+            .extract_if(.., |retired| retired.pending_frame_indices == 0)
+            .collect::<Vec<_>>();
+        for retired in finished {
+            self.destroy_retired(retired);
+        }
+        stats.gpu_time = self.read_gpu_time(frame_index);
+
+        if self.latency_mode == LatencyMode::LowLatency
+            && !self.wait_for_last_present(Duration::ZERO)
+        {
+            stats.fence_wait_time = fence_wait_start.elapsed();
+            return RenderResult::NotReady;
+        }
+        stats.fence_wait_time = fence_wait_start.elapsed();
+
+        // the frame that last used this frame index has finished, so some of what it dropped can be destroyed
+        if self.destroy_resources_each_frame {
+            self.device.destroy_resources();
+        }
+
+        let acquire_start = Instant::now();
+        let acquired = unsafe {
             self.acquire_next_image(
                 self.swapchain,
                 0,
-                self.aquired_image[frame_index],
+                self.aquired_image[frame_index].handle(),
                 vk::Fence::null(),
             )
-        } {
+        };
+        stats.acquire_time = acquire_start.elapsed();
+        let (image_index, mut suboptimal) = match acquired {
             Err(vk::Result::TIMEOUT | vk::Result::NOT_READY) => return RenderResult::NotReady,
             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return RenderResult::OutOfDate,
             e => e.unwrap(),
         };
+        stats.image_index = Some(image_index);
 
         self.frame_counter = (self.frame_counter + 1) % FRAMES_IN_FLIGHT_COUNT;
 
-        unsafe {
-            self.device.reset_command_buffer(
-                self.command_buffers[frame_index],
-                vk::CommandBufferResetFlags::empty(),
-            )
-        }
-        .unwrap();
-
-        let command_buffer_begin_info = vk::CommandBufferBeginInfo::default()
-            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-        unsafe {
-            self.device.begin_command_buffer(
-                self.command_buffers[frame_index],
-                &command_buffer_begin_info,
-            )
-        }
-        .unwrap();
-
-        let mut image_layout = vk::ImageLayout::UNDEFINED;
-        let RenderSync {
-            wait_sempahore_info: user_wait_semaphore_info,
-            signal_sempahore_info: user_signal_semaphore_info,
-        } = f(
-            self.command_buffers[frame_index],
-            &mut image_layout,
-            self.width,
-            self.height,
-            self.images[image_index as usize],
-            self.image_views[image_index as usize],
-            frame_index,
-        );
-
-        unsafe {
-            transition_image(
-                &self.device,
-                self.command_buffers[frame_index],
-                self.images[image_index as usize],
-                &mut image_layout,
-                vk::ImageLayout::PRESENT_SRC_KHR,
-            );
-        }
-        unsafe {
-            self.device
-                .end_command_buffer(self.command_buffers[frame_index])
-        }
-        .unwrap();
+        let (
+            command_buffer,
+            RenderSync {
+                wait_semaphore_infos: user_wait_semaphore_infos,
+                signal_semaphore_infos: user_signal_semaphore_infos,
+            },
+        ) = {
+            let record_start = Instant::now();
+            let recorded = record(self, frame_index, image_index);
+            stats.record_time = record_start.elapsed();
+            recorded
+        };
+        self.timestamps_written[frame_index] = self.timestamp_query_pool.is_some();
 
+        let submit_start = Instant::now();
         {
-            unsafe {
-                self.device
-                    .reset_fences(&[self.render_finished_fences[frame_index]])
-            }
-            .unwrap();
+            unsafe { self.render_finished_fences[frame_index].reset() };
 
-            let command_infos = [vk::CommandBufferSubmitInfo::default()
-                .command_buffer(self.command_buffers[frame_index])];
+            let command_infos =
+                [vk::CommandBufferSubmitInfo::default().command_buffer(command_buffer)];
 
-            let acquire_wait_info = vk::SemaphoreSubmitInfo::default()
-                .semaphore(self.aquired_image[frame_index])
-                .stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT);
+            let acquire_wait_info = self.aquired_image[frame_index]
+                .submit_info(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT);
             let render_finished_signal_info = vk::SemaphoreSubmitInfo::default()
-                .semaphore(self.render_finished[frame_index])
+                .semaphore(self.render_finished[image_index as usize])
                 .stage_mask(vk::PipelineStageFlags2::ALL_GRAPHICS);
             let render_finished_timeline_signal_info = self.device.signal_timeline_submit_info();
 
-            let wait_infos = match user_wait_semaphore_info {
-                Some(user_wait_info) => &[acquire_wait_info, user_wait_info] as &[_],
-                None => &[acquire_wait_info] as &[_],
-            };
-            let signal_infos = match user_signal_semaphore_info {
-                Some(user_signal_info) => &[
-                    render_finished_signal_info,
-                    render_finished_timeline_signal_info,
-                    user_signal_info,
-                ] as &[_],
-                None => &[
-                    render_finished_signal_info,
-                    render_finished_timeline_signal_info,
-                ] as &[_],
-            };
-
-            self.device
-                .with_graphics_queue(|graphics_queue| unsafe {
-                    self.device.queue_submit2(
-                        graphics_queue,
-                        &[vk::SubmitInfo2::default()
-                            .command_buffer_infos(&command_infos)
-                            .wait_semaphore_infos(wait_infos)
-                            .signal_semaphore_infos(signal_infos)],
-                        self.render_finished_fences[frame_index],
-                    )
-                })
-                .unwrap();
-        }
-
-        {
+            let wait_infos = [acquire_wait_info]
+                .into_iter()
+                .chain(user_wait_semaphore_infos)
+                .collect::<Vec<_>>();
+            let signal_infos = [
+                render_finished_signal_info,
+                render_finished_timeline_signal_info,
+            ]
+            .into_iter()
+            .chain(user_signal_semaphore_infos)
+            .collect::<Vec<_>>();
+
+            // work queued with Device::batch_submit during the frame goes out in the same call, ahead of the frame
             unsafe {
-                self.device
-                    .reset_fences(&[self.finished_presenting[frame_index]])
+                self.device.flush_submits(
+                    &[vk::SubmitInfo2::default()
+                        .command_buffer_infos(&command_infos)
+                        .wait_semaphore_infos(&wait_infos)
+                        .signal_semaphore_infos(&signal_infos)],
+                    self.render_finished_fences[frame_index].handle(),
+                )
             }
             .unwrap();
+        }
 
+        stats.submit_time = submit_start.elapsed();
+        stats.skipped = false;
+
+        let present_start = Instant::now();
+        {
             let mut result = vk::Result::SUCCESS;
-            let mut present_finished_fences = vk::SwapchainPresentFenceInfoEXT::default().fences(
-                core::slice::from_ref(&self.finished_presenting[frame_index]),
-            );
-            let present_info = vk::PresentInfoKHR::default()
-                .push_next(&mut present_finished_fences)
-                .wait_semaphores(core::slice::from_ref(&self.render_finished[frame_index]))
+            let finished_presenting = self.finished_presenting[frame_index].handle();
+            let mut present_finished_fences = vk::SwapchainPresentFenceInfoEXT::default()
+                .fences(core::slice::from_ref(&finished_presenting));
+            let mut present_info = vk::PresentInfoKHR::default()
+                .wait_semaphores(core::slice::from_ref(
+                    &self.render_finished[image_index as usize],
+                ))
                 .swapchains(core::slice::from_ref(&self.swapchain))
                 .image_indices(core::slice::from_ref(&image_index))
                 .results(core::slice::from_mut(&mut result));
+            if self.present_fences {
+                unsafe { self.finished_presenting[frame_index].reset() };
+                present_info = present_info.push_next(&mut present_finished_fences);
+            }
+            let present_id = self.last_present_id + 1;
+            let mut present_ids =
+                vk::PresentIdKHR::default().present_ids(core::slice::from_ref(&present_id));
+            if self.present_wait_funcs.is_some() {
+                present_info = present_info.push_next(&mut present_ids);
+            }
 
-            suboptimal |= match self.device.with_graphics_queue(|graphics_queue| unsafe {
-                self.queue_present(graphics_queue, &present_info)
-            }) {
+            suboptimal |= match self
+                .device
+                .report_device_lost(self.device.with_graphics_queue(|graphics_queue| unsafe {
+                    self.queue_present(graphics_queue, &present_info)
+                })) {
                 Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    stats.present_time = present_start.elapsed();
                     return RenderResult::OutOfDate;
                 }
                 result => result.unwrap(),
             };
             result.result().unwrap();
+            if self.present_wait_funcs.is_some() {
+                self.last_present_id = present_id;
+            }
         }
+        stats.present_time = present_start.elapsed();
 
         if suboptimal {
             RenderResult::Suboptimal
@@ -466,11 +1258,156 @@ impl<'allocator, 'window> Swapchain<'allocator, 'window> {
             RenderResult::Success
         }
     }
+
+    /// Records a whole frame into `command_buffer`, from clearing the letterbox bars to the transition for presenting
+    ///
+    /// # Safety
+    /// `command_buffer` must not be in use by the gpu and must come from a pool that allows resetting it
+    unsafe fn record_frame<'a>(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        usage_flags: vk::CommandBufferUsageFlags,
+        frame_index: usize,
+        image_index: u32,
+        f: impl FnOnce(
+            vk::CommandBuffer,
+            &mut vk::ImageLayout,
+            vk::Rect2D,
+            vk::Image,
+            vk::ImageView,
+            usize,
+        ) -> RenderSync<'a>,
+    ) -> RenderSync<'a> {
+        unsafe {
+            self.device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())
+        }
+        .unwrap();
+
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::default().flags(usage_flags);
+        unsafe {
+            self.device
+                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+        }
+        .unwrap();
+        if let Some(query_pool) = &mut self.timestamp_query_pool {
+            unsafe {
+                query_pool.cmd_reset(command_buffer, frame_index);
+                query_pool.cmd_write_timestamp(
+                    command_buffer,
+                    vk::PipelineStageFlags2::TOP_OF_PIPE,
+                    frame_index,
+                    0,
+                );
+            }
+        }
+
+        let mut image_layout = vk::ImageLayout::UNDEFINED;
+        let viewport = self.viewport();
+        if self.letterbox.is_some() {
+            unsafe {
+                cmd_clear_bars(
+                    &self.device,
+                    command_buffer,
+                    self.images[image_index as usize],
+                    &mut image_layout,
+                    self.clear_color(),
+                );
+            }
+        }
+        let render_sync = match &mut self.tonemapper {
+            Some(tonemapper) => {
+                let target = tonemapper.target();
+                unsafe { tonemapper.cmd_prepare_target(command_buffer) };
+                let mut target_layout = target.layout();
+                let render_sync = f(
+                    command_buffer,
+                    &mut target_layout,
+                    vk::Rect2D {
+                        offset: vk::Offset2D { x: 0, y: 0 },
+                        extent: target.extent(),
+                    },
+                    target.handle(),
+                    target.view(),
+                    frame_index,
+                );
+                unsafe {
+                    target.set_layout(target_layout);
+                    tonemapper.cmd_tonemap(
+                        command_buffer,
+                        frame_index,
+                        self.images[image_index as usize],
+                        self.image_views[image_index as usize],
+                        &mut image_layout,
+                        viewport.offset,
+                    );
+                }
+                render_sync
+            }
+            None => f(
+                command_buffer,
+                &mut image_layout,
+                viewport,
+                self.images[image_index as usize],
+                self.image_views[image_index as usize],
+                frame_index,
+            ),
+        };
+
+        {
+            let _label = unsafe {
+                self.device
+                    .cmd_label(command_buffer, c"Present Transition", [0.5, 0.5, 0.5, 1.0])
+            };
+            // an image the callback never touched still has to wait for the acquire semaphore before changing layout
+            match ImageUsage::from_layout(image_layout).map(|usage| match usage {
+                ImageUsage::Undefined => ImageUsage::Acquired,
+                usage => usage,
+            }) {
+                Some(ImageUsage::Present) => {}
+                Some(usage) => unsafe {
+                    BarrierBuilder::new()
+                        .image(
+                            self.images[image_index as usize],
+                            vk::ImageAspectFlags::COLOR,
+                            usage,
+                            ImageUsage::Present,
+                        )
+                        .record(&self.device, command_buffer);
+                },
+                None => unsafe {
+                    transition_image(
+                        &self.device,
+                        command_buffer,
+                        self.images[image_index as usize],
+                        &mut image_layout,
+                        vk::ImageLayout::PRESENT_SRC_KHR,
+                    );
+                },
+            }
+        }
+        if let Some(query_pool) = &self.timestamp_query_pool {
+            unsafe {
+                query_pool.cmd_write_timestamp(
+                    command_buffer,
+                    vk::PipelineStageFlags2::BOTTOM_OF_PIPE,
+                    frame_index,
+                    1,
+                );
+            }
+        }
+        unsafe { self.device.end_command_buffer(command_buffer) }.unwrap();
+
+        render_sync
+    }
 }
 
+/// Extra semaphores for the frame's submit, on top of the acquire wait and the render finished signals
+/// the swapchain adds itself, e.g. waiting on both an async compute pass and an upload
+#[derive(Default)]
 pub struct RenderSync<'a> {
-    pub wait_sempahore_info: Option<vk::SemaphoreSubmitInfo<'a>>,
-    pub signal_sempahore_info: Option<vk::SemaphoreSubmitInfo<'a>>,
+    pub wait_semaphore_infos: Vec<vk::SemaphoreSubmitInfo<'a>>,
+    pub signal_semaphore_infos: Vec<vk::SemaphoreSubmitInfo<'a>>,
 }
 
 pub enum RenderResult {
@@ -490,36 +1427,18 @@ impl Deref for Swapchain<'_, '_> {
 
 impl Drop for Swapchain<'_, '_> {
     fn drop(&mut self) {
-        unsafe {
-            self.device
-                .wait_for_fences(&self.render_finished_fences, true, u64::MAX)
-        }
-        .unwrap();
-        unsafe {
-            self.device
-                .wait_for_fences(&self.finished_presenting, true, u64::MAX)
-        }
-        .unwrap();
+        wait_for_all(&self.device, &self.render_finished_fences);
+        self.wait_for_presents();
 
-        for &semaphore in &self.aquired_image {
-            unsafe { self.device.destroy_semaphore(semaphore, self.allocator()) };
+        for retired in core::mem::take(&mut self.retired) {
+            self.destroy_retired(retired);
         }
         for &semaphore in &self.render_finished {
             unsafe { self.device.destroy_semaphore(semaphore, self.allocator()) };
         }
-        for &fence in &self.render_finished_fences {
-            unsafe { self.device.destroy_fence(fence, self.allocator()) };
-        }
-        for &fence in &self.finished_presenting {
-            unsafe { self.device.destroy_fence(fence, self.allocator()) };
-        }
-
-        unsafe {
-            self.device
-                .destroy_command_pool(self.command_pool, self.allocator());
-        }
 
         for &image_view in &self.image_views {
+            self.device.unregister_image_view(image_view);
             unsafe { self.device.destroy_image_view(image_view, self.allocator()) };
         }
 
@@ -527,16 +1446,25 @@ impl Drop for Swapchain<'_, '_> {
     }
 }
 
+fn wait_for_all(device: &Device<'_>, fences: &PerFrame<Fence<'_>>) {
+    let fences = fences.iter().map(Fence::handle).collect::<Vec<_>>();
+    device
+        .report_device_lost(unsafe { device.wait_for_fences(&fences, true, u64::MAX) })
+        .unwrap();
+}
+
 fn swapchain_create_info<'a>(
     surface: vk::SurfaceKHR,
     extent: vk::Extent2D,
     queue_family_index: &'a u32,
+    format: vk::Format,
+    composite_alpha: vk::CompositeAlphaFlagsKHR,
     old_swapchain: vk::SwapchainKHR,
 ) -> vk::SwapchainCreateInfoKHR<'a> {
     vk::SwapchainCreateInfoKHR::default()
         .surface(surface)
         .min_image_count(3)
-        .image_format(vk::Format::B8G8R8A8_UNORM)
+        .image_format(format)
         .image_color_space(vk::ColorSpaceKHR::SRGB_NONLINEAR)
         .image_extent(extent)
         .image_array_layers(1)
@@ -544,12 +1472,25 @@ fn swapchain_create_info<'a>(
         .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
         .queue_family_indices(core::slice::from_ref(queue_family_index))
         .pre_transform(vk::SurfaceTransformFlagsKHR::IDENTITY)
-        .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-        .present_mode(vk::PresentModeKHR::MAILBOX)
+        .composite_alpha(composite_alpha)
+        .present_mode(PRESENT_MODE)
         .clipped(true)
         .old_swapchain(old_swapchain)
 }
 
+/// Swapchain images are created without flags and with the same usage, format and size as the swapchain
+fn swapchain_image_view_info(
+    swapchain_create_info: &vk::SwapchainCreateInfoKHR<'_>,
+) -> ImageViewInfo {
+    ImageViewInfo {
+        flags: vk::ImageCreateFlags::empty(),
+        usage: swapchain_create_info.image_usage,
+        format: swapchain_create_info.image_format,
+        extent: swapchain_create_info.image_extent,
+        layer_count: swapchain_create_info.image_array_layers,
+    }
+}
+
 pub fn make_subresource_range(aspect_mask: vk::ImageAspectFlags) -> vk::ImageSubresourceRange {
     vk::ImageSubresourceRange::default()
         .aspect_mask(aspect_mask)
@@ -559,15 +1500,35 @@ pub fn make_subresource_range(aspect_mask: vk::ImageAspectFlags) -> vk::ImageSub
         .layer_count(vk::REMAINING_ARRAY_LAYERS)
 }
 
+/// Layouts that only allow reading, so moving an image that is already in one of them into the same layout needs no barrier
+pub fn is_read_only_layout(layout: vk::ImageLayout) -> bool {
+    matches!(
+        layout,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            | vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+            | vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+            | vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL
+            | vk::ImageLayout::STENCIL_READ_ONLY_OPTIMAL
+            | vk::ImageLayout::READ_ONLY_OPTIMAL
+            | vk::ImageLayout::PRESENT_SRC_KHR
+    )
+}
+
+/// Skips the barrier when the image is already in `new_layout` and it's read only, see [`is_read_only_layout`]
+///
 /// # Safety
-/// See [Device::cmd_pipeline_barrier2](ash::device::Device::cmd_pipeline_barrier2)
+/// See [`Device::cmd_pipeline_barrier2`]
 pub unsafe fn transition_image(
-    device: &ash::Device,
+    device: &Device<'_>,
     command_buffer: vk::CommandBuffer,
     image: vk::Image,
     current_layout: &mut vk::ImageLayout,
     new_layout: vk::ImageLayout,
 ) {
+    if *current_layout == new_layout && is_read_only_layout(new_layout) {
+        return;
+    }
+
     let image_barrier = vk::ImageMemoryBarrier2::default()
         .src_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
         .src_access_mask(vk::AccessFlags2::MEMORY_WRITE)
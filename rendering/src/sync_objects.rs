@@ -0,0 +1,241 @@
+use crate::{Device, Instance, ResourceToDestroy};
+use ash::vk;
+use std::{sync::Arc, time::Duration};
+
+fn timeout_nanos(timeout: Duration) -> u64 {
+    timeout.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+pub struct Fence<'allocator> {
+    device: Arc<Device<'allocator>>,
+    fence: vk::Fence,
+}
+
+impl<'allocator> Fence<'allocator> {
+    pub fn new(device: Arc<Device<'allocator>>, name: &str, signaled: bool) -> Self {
+        let fence_create_info = vk::FenceCreateInfo::default().flags(if signaled {
+            vk::FenceCreateFlags::SIGNALED
+        } else {
+            vk::FenceCreateFlags::empty()
+        });
+        let fence = unsafe { device.create_fence(&fence_create_info, device.allocator()) }.unwrap();
+        device.track_resource(fence, name);
+        Self { device, fence }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::Fence {
+        self.fence
+    }
+
+    pub fn is_signaled(&self) -> bool {
+        self.device
+            .report_device_lost(unsafe { self.device.get_fence_status(self.fence) })
+            .unwrap()
+    }
+
+    /// Returns whether the fence was signaled before `timeout` passed
+    pub fn wait(&self, timeout: Duration) -> bool {
+        match self.device.report_device_lost(unsafe {
+            self.device
+                .wait_for_fences(&[self.fence], true, timeout_nanos(timeout))
+        }) {
+            Ok(()) => true,
+            Err(vk::Result::TIMEOUT) => false,
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    /// # Safety
+    /// The fence must not be used by a submission that hasn't finished
+    pub unsafe fn reset(&self) {
+        unsafe { self.device.reset_fences(&[self.fence]) }.unwrap();
+    }
+}
+
+impl Drop for Fence<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::Fence(self.fence),
+            );
+        }
+    }
+}
+
+/// A semaphore for ordering submissions and presents on the gpu, which the cpu can't wait on, see [`TimelineSemaphore`] for that
+pub struct BinarySemaphore<'allocator> {
+    device: Arc<Device<'allocator>>,
+    semaphore: vk::Semaphore,
+}
+
+impl<'allocator> BinarySemaphore<'allocator> {
+    pub fn new(device: Arc<Device<'allocator>>, name: &str) -> Self {
+        let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+        let semaphore =
+            unsafe { device.create_semaphore(&semaphore_create_info, device.allocator()) }.unwrap();
+        device.track_resource(semaphore, name);
+        Self { device, semaphore }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::Semaphore {
+        self.semaphore
+    }
+
+    /// For [`RenderSync`](crate::RenderSync) or a [`BatchedSubmit`](crate::BatchedSubmit),
+    /// `stage_mask` is the stages that wait when waiting and the stages that finish first when signaling
+    pub fn submit_info(
+        &self,
+        stage_mask: vk::PipelineStageFlags2,
+    ) -> vk::SemaphoreSubmitInfo<'static> {
+        vk::SemaphoreSubmitInfo::default()
+            .semaphore(self.semaphore)
+            .stage_mask(stage_mask)
+    }
+}
+
+impl Drop for BinarySemaphore<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::Semaphore(self.semaphore),
+            );
+        }
+    }
+}
+
+/// A semaphore with a counter that only goes up, which both the gpu and the cpu can signal and wait on,
+/// separate from the device's own timeline, see [`Device::current_timeline_counter`]
+pub struct TimelineSemaphore<'allocator> {
+    device: Arc<Device<'allocator>>,
+    semaphore: vk::Semaphore,
+}
+
+impl<'allocator> TimelineSemaphore<'allocator> {
+    pub fn new(device: Arc<Device<'allocator>>, name: &str, initial_value: u64) -> Self {
+        Self::create(
+            device,
+            name,
+            initial_value,
+            vk::ExternalSemaphoreHandleTypeFlags::empty(),
+        )
+    }
+
+    /// `handle_types` are passed on through [`vk::ExportSemaphoreCreateInfo`] when not empty
+    pub(crate) fn create(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        initial_value: u64,
+        handle_types: vk::ExternalSemaphoreHandleTypeFlags,
+    ) -> Self {
+        let mut semaphore_type_create_info = vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(initial_value);
+        let mut export_semaphore_create_info =
+            vk::ExportSemaphoreCreateInfo::default().handle_types(handle_types);
+        let mut semaphore_create_info =
+            vk::SemaphoreCreateInfo::default().push_next(&mut semaphore_type_create_info);
+        if !handle_types.is_empty() {
+            semaphore_create_info =
+                semaphore_create_info.push_next(&mut export_semaphore_create_info);
+        }
+        let semaphore =
+            unsafe { device.create_semaphore(&semaphore_create_info, device.allocator()) }.unwrap();
+        device.track_resource(semaphore, name);
+        Self { device, semaphore }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    pub fn handle(&self) -> vk::Semaphore {
+        self.semaphore
+    }
+
+    /// The value the semaphore has reached
+    pub fn value(&self) -> u64 {
+        self.device
+            .report_device_lost(unsafe { self.device.get_semaphore_counter_value(self.semaphore) })
+            .unwrap()
+    }
+
+    /// Sets the value from the cpu, which must be higher than the current value and every pending signal
+    pub fn signal(&self, value: u64) {
+        let signal_info = vk::SemaphoreSignalInfo::default()
+            .semaphore(self.semaphore)
+            .value(value);
+        unsafe { self.device.signal_semaphore(&signal_info) }.unwrap();
+    }
+
+    /// Returns whether the semaphore reached `value` before `timeout` passed
+    pub fn wait(&self, value: u64, timeout: Duration) -> bool {
+        let wait_info = vk::SemaphoreWaitInfo::default()
+            .semaphores(core::slice::from_ref(&self.semaphore))
+            .values(core::slice::from_ref(&value));
+        match self.device.report_device_lost(unsafe {
+            self.device
+                .wait_semaphores(&wait_info, timeout_nanos(timeout))
+        }) {
+            Ok(()) => true,
+            Err(vk::Result::TIMEOUT) => false,
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    /// Like [`BinarySemaphore::submit_info`], waiting for or signaling `value`
+    pub fn submit_info(
+        &self,
+        value: u64,
+        stage_mask: vk::PipelineStageFlags2,
+    ) -> vk::SemaphoreSubmitInfo<'static> {
+        vk::SemaphoreSubmitInfo::default()
+            .semaphore(self.semaphore)
+            .value(value)
+            .stage_mask(stage_mask)
+    }
+}
+
+impl Drop for TimelineSemaphore<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::Semaphore(self.semaphore),
+            );
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{BufferUsage, Device, ImageUsage, make_subresource_range};
+use ash::vk;
+use std::collections::HashMap;
+
+/// What has happened to a resource since the last write to it
+#[derive(Clone, Copy)]
+struct Access {
+    write_stages: vk::PipelineStageFlags2,
+    write_access: vk::AccessFlags2,
+    /// Stages that read the resource after the write, later writes have to wait for them
+    read_stages: vk::PipelineStageFlags2,
+    /// Where the last write has already been made visible, reads from these don't need another barrier
+    visible_stages: vk::PipelineStageFlags2,
+    visible_access: vk::AccessFlags2,
+}
+
+impl Access {
+    fn new(
+        stages: vk::PipelineStageFlags2,
+        access: vk::AccessFlags2,
+        write_access: vk::AccessFlags2,
+    ) -> Self {
+        Self {
+            write_stages: stages,
+            write_access,
+            read_stages: vk::PipelineStageFlags2::NONE,
+            visible_stages: stages,
+            visible_access: access,
+        }
+    }
+
+    /// The source stages and accesses of the barrier needed before the use, if one is needed at all,
+    /// and updates the access to include the use
+    fn use_resource(
+        &mut self,
+        stages: vk::PipelineStageFlags2,
+        access: vk::AccessFlags2,
+        write_access: vk::AccessFlags2,
+        layout_changes: bool,
+    ) -> Option<(vk::PipelineStageFlags2, vk::AccessFlags2)> {
+        if layout_changes || !write_access.is_empty() {
+            // writes, including layout transitions, wait for the last write and every read since
+            let src = (self.write_stages | self.read_stages, self.write_access);
+            *self = Access::new(stages, access, write_access);
+            Some(src)
+        } else if self.visible_stages.contains(stages) && self.visible_access.contains(access) {
+            self.read_stages |= stages;
+            None
+        } else {
+            self.read_stages |= stages;
+            self.visible_stages |= stages;
+            self.visible_access |= access;
+            Some((self.write_stages, self.write_access))
+        }
+    }
+}
+
+struct TrackedImage {
+    aspect_mask: vk::ImageAspectFlags,
+    layout: vk::ImageLayout,
+    access: Access,
+}
+
+/// Opt in hazard tracking, remembers how each registered resource was last used
+/// so [`SyncTracker::use_image`] and [`SyncTracker::use_buffer`] record exactly the barrier a new use needs, if any
+///
+/// Uses are expected to execute in the order they were recorded, so a tracker should be used with a single queue
+#[derive(Default)]
+pub struct SyncTracker {
+    images: HashMap<vk::Image, TrackedImage>,
+    buffers: HashMap<vk::Buffer, Access>,
+}
+
+impl SyncTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking `image` as if it was last used as `usage`, [`ImageUsage::Acquired`] for swapchain images
+    pub fn register_image(
+        &mut self,
+        image: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+        usage: ImageUsage,
+    ) {
+        self.images.insert(
+            image,
+            TrackedImage {
+                aspect_mask,
+                layout: usage.layout(),
+                access: Access::new(usage.stages(), usage.access(), usage.write_access()),
+            },
+        );
+    }
+
+    /// Starts tracking `buffer` as if it was last used as `usage`
+    pub fn register_buffer(&mut self, buffer: vk::Buffer, usage: BufferUsage) {
+        self.buffers.insert(
+            buffer,
+            Access::new(usage.stages(), usage.access(), usage.write_access()),
+        );
+    }
+
+    /// Stops tracking `image`, which has to happen before the handle is destroyed since handles can be reused
+    pub fn forget_image(&mut self, image: vk::Image) {
+        self.images.remove(&image);
+    }
+
+    pub fn forget_buffer(&mut self, buffer: vk::Buffer) {
+        self.buffers.remove(&buffer);
+    }
+
+    /// The layout `image` will be in once the uses recorded so far have run
+    pub fn image_layout(&self, image: vk::Image) -> Option<vk::ImageLayout> {
+        self.images.get(&image).map(|tracked| tracked.layout)
+    }
+
+    /// Records the barrier needed before `image` can be used as `usage`, nothing is recorded when no barrier is needed
+    ///
+    /// # Safety
+    /// See [`Device::cmd_pipeline_barrier2`], `image` must have been registered
+    /// and only used through this tracker since
+    pub unsafe fn use_image(
+        &mut self,
+        device: &Device<'_>,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        usage: ImageUsage,
+    ) {
+        let tracked = self
+            .images
+            .get_mut(&image)
+            .expect("images have to be registered with the sync tracker before they are used");
+        let old_layout = tracked.layout;
+        let new_layout = usage.layout();
+        let Some((src_stages, src_access)) = tracked.access.use_resource(
+            usage.stages(),
+            usage.access(),
+            usage.write_access(),
+            // moving to UNDEFINED discards the contents without a transition
+            old_layout != new_layout && new_layout != vk::ImageLayout::UNDEFINED,
+        ) else {
+            return;
+        };
+        if new_layout != vk::ImageLayout::UNDEFINED {
+            tracked.layout = new_layout;
+        }
+
+        let image_barrier = vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(src_stages)
+            .src_access_mask(src_access)
+            .dst_stage_mask(usage.stages())
+            .dst_access_mask(usage.access())
+            .old_layout(old_layout)
+            .new_layout(tracked.layout)
+            .subresource_range(make_subresource_range(tracked.aspect_mask))
+            .image(image);
+        let dependency_info = vk::DependencyInfo::default()
+            .image_memory_barriers(core::slice::from_ref(&image_barrier));
+        unsafe { device.cmd_pipeline_barrier2(command_buffer, &dependency_info) };
+    }
+
+    /// Records the barrier needed before `buffer` can be used as `usage`, nothing is recorded when no barrier is needed
+    ///
+    /// # Safety
+    /// See [`Device::cmd_pipeline_barrier2`], `buffer` must have been registered
+    /// and only used through this tracker since
+    pub unsafe fn use_buffer(
+        &mut self,
+        device: &Device<'_>,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        usage: BufferUsage,
+    ) {
+        let access = self
+            .buffers
+            .get_mut(&buffer)
+            .expect("buffers have to be registered with the sync tracker before they are used");
+        let Some((src_stages, src_access)) =
+            access.use_resource(usage.stages(), usage.access(), usage.write_access(), false)
+        else {
+            return;
+        };
+
+        let buffer_barrier = vk::BufferMemoryBarrier2::default()
+            .src_stage_mask(src_stages)
+            .src_access_mask(src_access)
+            .dst_stage_mask(usage.stages())
+            .dst_access_mask(usage.access())
+            .buffer(buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE);
+        let dependency_info = vk::DependencyInfo::default()
+            .buffer_memory_barriers(core::slice::from_ref(&buffer_barrier));
+        unsafe { device.cmd_pipeline_barrier2(command_buffer, &dependency_info) };
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{Device, Image};
+use image::ImageError;
+use std::{path::Path, sync::Arc};
+
+impl<'allocator> Image<'allocator> {
+    /// Decodes a PNG or JPEG file, see [`Image::from_bytes`]
+    pub fn from_path(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        path: &Path,
+    ) -> Result<Self, ImageError> {
+        let pixels = image::open(path)?.into_rgba8();
+        Ok(Self::from_rgba8(
+            device,
+            name,
+            pixels.width(),
+            pixels.height(),
+            &pixels,
+        ))
+    }
+
+    /// Decodes an in memory PNG or JPEG into an `R8G8B8A8_SRGB` image that is ready to be sampled, see [`Image::from_rgba8`]
+    pub fn from_bytes(
+        device: Arc<Device<'allocator>>,
+        name: &str,
+        bytes: &[u8],
+    ) -> Result<Self, ImageError> {
+        let pixels = image::load_from_memory(bytes)?.into_rgba8();
+        Ok(Self::from_rgba8(
+            device,
+            name,
+            pixels.width(),
+            pixels.height(),
+            &pixels,
+        ))
+    }
+}
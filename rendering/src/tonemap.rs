@@ -0,0 +1,466 @@
+use crate::{
+    AntiAliasing, BarrierBuilder, Device, FRAMES_IN_FLIGHT_COUNT, GraphicsPipelineBuilder,
+    HIGH_MEMORY_PRIORITY, Image, ImageUsage, Instance, PerFrame, Pipeline, PipelineLayout,
+    ResourceToDestroy, Sampler, Shader, TemporalAntiAliasing, transition_image,
+};
+use ash::vk;
+use bytemuck::NoUninit;
+use scope_guard::scope_guard;
+use std::{ops::RangeInclusive, sync::Arc};
+
+/// The format of [`Tonemapper::target`], enough range for lighting without caring about the swapchain's format
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+const TONEMAP_SPIRV: &[u32] =
+    crate::include_spirv!(concat!(env!("OUT_DIR"), "/shaders/tonemap.spv"));
+
+/// The smallest and largest [`Tonemapper::render_scale`]
+pub const RENDER_SCALE_RANGE: RangeInclusive<f32> = 0.25..=2.0;
+
+/// How [`Tonemapper`] maps unbounded colors into the `0..=1` range of the swapchain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TonemapOperator {
+    /// Cuts off everything above 1
+    Clamp = 0,
+    /// `color / (1 + color)`, which never quite reaches white
+    Reinhard = 1,
+    /// A fit of the ACES filmic curve, with more contrast and a shoulder that does reach white
+    Aces = 2,
+}
+
+#[derive(Clone, Copy, NoUninit)]
+#[repr(C)]
+struct PushConstants {
+    operator: u32,
+    exposure: f32,
+    encode_srgb: u32,
+    fxaa: u32,
+}
+
+/// Owns an [`HDR_FORMAT`] render target and tonemaps it onto another image with a full screen pass,
+/// see [`Swapchain::set_tonemap`](crate::Swapchain::set_tonemap) for rendering every frame through one
+pub struct Tonemapper<'allocator> {
+    device: Arc<Device<'allocator>>,
+    target: Image<'allocator>,
+    sampler: Sampler<'allocator>,
+    pipeline_layout: PipelineLayout<'allocator>,
+    pipeline: Pipeline<'allocator>,
+    output_format: vk::Format,
+    output_extent: vk::Extent2D,
+    render_scale: f32,
+    descriptor_pool: vk::DescriptorPool,
+    /// One per frame in flight, so a set is only rewritten once the frame that last used it has finished
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    operator: TonemapOperator,
+    exposure: f32,
+    encode_srgb: bool,
+    anti_aliasing: AntiAliasing,
+    /// Only kept while [`AntiAliasing::Taa`] is used, as it holds two more images as big as the target
+    taa: Option<TemporalAntiAliasing<'allocator>>,
+}
+
+impl<'allocator> Tonemapper<'allocator> {
+    /// `output_format`, `width`, and `height` are those of the images [`Tonemapper::cmd_tonemap`] writes to,
+    /// usually the [`Swapchain`](crate::Swapchain)'s
+    pub fn new(
+        device: Arc<Device<'allocator>>,
+        output_format: vk::Format,
+        width: u32,
+        height: u32,
+        operator: TonemapOperator,
+    ) -> Self {
+        let shader = unsafe { Shader::new(device.clone(), "Tonemap Shader", TONEMAP_SPIRV) };
+        let pipeline_layout =
+            PipelineLayout::from_shaders(device.clone(), "Tonemap Pipeline Layout", &[&shader]);
+        let pipeline = create_pipeline(&device, &pipeline_layout, &shader, output_format);
+
+        let pool_sizes = [vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(FRAMES_IN_FLIGHT_COUNT as _)];
+        let pool_create_info = vk::DescriptorPoolCreateInfo::default()
+            .max_sets(FRAMES_IN_FLIGHT_COUNT as _)
+            .pool_sizes(&pool_sizes);
+        let descriptor_pool = scope_guard!(
+            |descriptor_pool| unsafe {
+                device.destroy_descriptor_pool(descriptor_pool, device.allocator())
+            },
+            unsafe { device.create_descriptor_pool(&pool_create_info, device.allocator()) }
+                .unwrap()
+        );
+
+        let set_layouts = PerFrame::splat(pipeline_layout.set_layouts()[0]);
+        let allocate_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(*descriptor_pool)
+            .set_layouts(&set_layouts);
+        let descriptor_sets = unsafe { device.allocate_descriptor_sets(&allocate_info) }
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        device.track_resource(*descriptor_pool, "Tonemap Descriptor Pool");
+
+        Self {
+            target: create_target(device.clone(), width, height),
+            sampler: Sampler::linear_clamp(device.clone(), "Tonemap Sampler"),
+            pipeline_layout,
+            pipeline,
+            output_format,
+            output_extent: vk::Extent2D { width, height },
+            render_scale: 1.0,
+            descriptor_pool: descriptor_pool.into_inner(),
+            descriptor_sets,
+            operator,
+            exposure: 1.0,
+            encode_srgb: true,
+            anti_aliasing: AntiAliasing::None,
+            taa: None,
+            device,
+        }
+    }
+
+    pub fn instance(&self) -> &Arc<Instance<'allocator>> {
+        self.device.instance()
+    }
+
+    pub fn allocator(&self) -> Option<&vk::AllocationCallbacks<'allocator>> {
+        self.device.allocator()
+    }
+
+    pub fn device(&self) -> &Arc<Device<'allocator>> {
+        &self.device
+    }
+
+    /// The image to render into, it is sampled by [`Tonemapper::cmd_tonemap`] so it has to be finished by then
+    pub fn target(&self) -> &Image<'allocator> {
+        &self.target
+    }
+
+    pub fn output_format(&self) -> vk::Format {
+        self.output_format
+    }
+
+    /// Rebuilds the pipeline for images of a different format, like when the swapchain switches to an `_SRGB` format
+    pub fn set_output_format(&mut self, output_format: vk::Format) {
+        if output_format == self.output_format {
+            return;
+        }
+        let shader = unsafe { Shader::new(self.device.clone(), "Tonemap Shader", TONEMAP_SPIRV) };
+        self.pipeline =
+            create_pipeline(&self.device, &self.pipeline_layout, &shader, output_format);
+        self.output_format = output_format;
+    }
+
+    pub fn operator(&self) -> TonemapOperator {
+        self.operator
+    }
+
+    pub fn set_operator(&mut self, operator: TonemapOperator) {
+        self.operator = operator;
+    }
+
+    /// What colors are multiplied by before the operator is applied, 1 by default
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure;
+    }
+
+    /// Whether the output is encoded to sRGB when its format doesn't do that itself, on by default,
+    /// turn it off when the rendered colors are already meant to be shown as they are
+    pub fn encode_srgb(&self) -> bool {
+        self.encode_srgb
+    }
+
+    pub fn set_encode_srgb(&mut self, encode_srgb: bool) {
+        self.encode_srgb = encode_srgb;
+    }
+
+    pub fn anti_aliasing(&self) -> AntiAliasing {
+        self.anti_aliasing
+    }
+
+    /// Switching to [`AntiAliasing::Taa`] starts with an empty history, frames have to be jittered from then on, see [`Tonemapper::taa`]
+    pub fn set_anti_aliasing(&mut self, anti_aliasing: AntiAliasing) {
+        self.anti_aliasing = anti_aliasing;
+        match anti_aliasing {
+            AntiAliasing::Taa => {
+                if self.taa.is_none() {
+                    let extent = self.target.extent();
+                    self.taa = Some(TemporalAntiAliasing::new(
+                        self.device.clone(),
+                        extent.width,
+                        extent.height,
+                    ));
+                }
+            }
+            AntiAliasing::None | AntiAliasing::Fxaa => self.taa = None,
+        }
+    }
+
+    /// The temporal anti aliasing state when [`AntiAliasing::Taa`] is used, for the jitter and camera movement
+    pub fn taa(&self) -> Option<&TemporalAntiAliasing<'allocator>> {
+        self.taa.as_ref()
+    }
+
+    pub fn taa_mut(&mut self) -> Option<&mut TemporalAntiAliasing<'allocator>> {
+        self.taa.as_mut()
+    }
+
+    /// The size of the images [`Tonemapper::cmd_tonemap`] writes to
+    pub fn output_extent(&self) -> vk::Extent2D {
+        self.output_extent
+    }
+
+    /// The size of the target relative to the output, 1 by default
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Renders at a different resolution than the output, which the tonemap pass then upscales or downscales with bilinear filtering,
+    /// the traversal cost grows with the number of pixels so this is the easiest way to trade quality for speed
+    ///
+    /// `render_scale` is clamped to [`RENDER_SCALE_RANGE`]
+    pub fn set_render_scale(&mut self, render_scale: f32) {
+        self.render_scale =
+            render_scale.clamp(*RENDER_SCALE_RANGE.start(), *RENDER_SCALE_RANGE.end());
+        self.resize(self.output_extent.width, self.output_extent.height);
+    }
+
+    /// Changes the size of the output, replacing the target with one scaled to it by [`Tonemapper::render_scale`],
+    /// the old one is destroyed once the frames using it have finished
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.output_extent = vk::Extent2D { width, height };
+        let scaled = vk::Extent2D {
+            width: ((width as f32 * self.render_scale).round() as u32).max(1),
+            height: ((height as f32 * self.render_scale).round() as u32).max(1),
+        };
+        if self.target.extent() != scaled {
+            self.target = create_target(self.device.clone(), scaled.width, scaled.height);
+        }
+        if let Some(taa) = &mut self.taa {
+            taa.resize(scaled.width, scaled.height);
+        }
+    }
+
+    /// Records a barrier that waits for the last [`Tonemapper::cmd_tonemap`] to stop reading the target
+    /// and discards its contents, the target is left in [`vk::ImageLayout::UNDEFINED`] just like a freshly acquired swapchain image,
+    /// so a barrier from [`ImageUsage::Acquired`] is all that is needed before rendering to it again
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state
+    pub unsafe fn cmd_prepare_target(&self, command_buffer: vk::CommandBuffer) {
+        // an execution dependency is enough to stop a read being overwritten,
+        // and it chains with the COLOR_ATTACHMENT_OUTPUT source stage of a barrier from `ImageUsage::Acquired`
+        let memory_barrier = vk::MemoryBarrier2::default()
+            .src_stage_mask(ImageUsage::FragmentShaderRead.stages())
+            .dst_stage_mask(ImageUsage::Acquired.stages());
+        let dependency_info =
+            vk::DependencyInfo::default().memory_barriers(core::slice::from_ref(&memory_barrier));
+        unsafe {
+            self.device
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info);
+            self.target.set_layout(vk::ImageLayout::UNDEFINED);
+        }
+    }
+
+    /// Tonemaps the whole target onto the [`Tonemapper::output_extent`] big area of `dst_image` at `dst_offset`,
+    /// applying the anti aliasing on the way, `dst_image` is left in [`vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL`]
+    /// and the rest of it is left untouched
+    ///
+    /// A `dst_layout` of [`vk::ImageLayout::UNDEFINED`] is treated as a freshly acquired swapchain image
+    ///
+    /// # Safety
+    /// `command_buffer` must be in the recording state, the frame that last used `frame_index` must have finished,
+    /// `dst_image` must be in `dst_layout` and be created with [`vk::ImageUsageFlags::COLOR_ATTACHMENT`] and [`Tonemapper::output_format`],
+    /// and the target's tracked layout must be up to date, see [`Image::set_layout`]
+    pub unsafe fn cmd_tonemap(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        dst_image: vk::Image,
+        dst_image_view: vk::ImageView,
+        dst_layout: &mut vk::ImageLayout,
+        dst_offset: vk::Offset2D,
+    ) {
+        let _label = unsafe {
+            self.device
+                .cmd_label(command_buffer, c"Tonemap", [0.9, 0.7, 0.3, 1.0])
+        };
+
+        let source = match &mut self.taa {
+            Some(taa) => unsafe { taa.cmd_resolve(command_buffer, frame_index, &self.target) },
+            None => &self.target,
+        };
+
+        let descriptor_set = self.descriptor_sets[frame_index];
+        let image_info = vk::DescriptorImageInfo::default()
+            .image_view(source.view())
+            .sampler(self.sampler.handle())
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(core::slice::from_ref(&image_info));
+        unsafe { self.device.update_descriptor_sets(&[write], &[]) };
+
+        unsafe { source.cmd_transition(command_buffer, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL) };
+        match ImageUsage::from_layout(*dst_layout).map(|usage| match usage {
+            ImageUsage::Undefined => ImageUsage::Acquired,
+            usage => usage,
+        }) {
+            Some(usage) => unsafe {
+                BarrierBuilder::new()
+                    .image(
+                        dst_image,
+                        vk::ImageAspectFlags::COLOR,
+                        usage,
+                        ImageUsage::ColorAttachment,
+                    )
+                    .record(&self.device, command_buffer);
+            },
+            None => unsafe {
+                transition_image(
+                    &self.device,
+                    command_buffer,
+                    dst_image,
+                    dst_layout,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                );
+            },
+        }
+        *dst_layout = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+
+        unsafe {
+            cmd_begin_full_screen_pass(
+                &self.device,
+                command_buffer,
+                dst_image_view,
+                vk::Rect2D {
+                    offset: dst_offset,
+                    extent: self.output_extent,
+                },
+                &self.pipeline,
+                &self.pipeline_layout,
+                descriptor_set,
+            );
+            self.pipeline_layout.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout.push_constant_stages(),
+                0,
+                &PushConstants {
+                    operator: self.operator as u32,
+                    exposure: self.exposure,
+                    encode_srgb: (self.encode_srgb && !is_srgb_format(self.output_format)) as u32,
+                    fxaa: (self.anti_aliasing == AntiAliasing::Fxaa) as u32,
+                },
+            );
+            self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            self.device.cmd_end_rendering(command_buffer);
+        }
+    }
+}
+
+impl Drop for Tonemapper<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.schedule_destroy_resource(
+                self.device.current_timeline_counter(),
+                ResourceToDestroy::DescriptorPool(self.descriptor_pool),
+            );
+        }
+    }
+}
+
+/// Begins rendering to `render_area` of `image_view` and binds everything a full screen triangle pass needs,
+/// which is then drawn with 3 vertices and ended with [`Device::cmd_end_rendering`]
+///
+/// # Safety
+/// `command_buffer` must be in the recording state and `image_view` must be in [`vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL`]
+pub(crate) unsafe fn cmd_begin_full_screen_pass(
+    device: &Device<'_>,
+    command_buffer: vk::CommandBuffer,
+    image_view: vk::ImageView,
+    render_area: vk::Rect2D,
+    pipeline: &Pipeline<'_>,
+    pipeline_layout: &PipelineLayout<'_>,
+    descriptor_set: vk::DescriptorSet,
+) {
+    let color_attachment_info = vk::RenderingAttachmentInfo::default()
+        .image_view(image_view)
+        .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        .load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .store_op(vk::AttachmentStoreOp::STORE);
+    let rendering_info = vk::RenderingInfo::default()
+        .render_area(render_area)
+        .layer_count(1)
+        .color_attachments(core::slice::from_ref(&color_attachment_info));
+
+    let viewport = vk::Viewport::default()
+        .x(render_area.offset.x as _)
+        .y(render_area.offset.y as _)
+        .width(render_area.extent.width as _)
+        .height(render_area.extent.height as _)
+        .max_depth(1.0);
+    unsafe {
+        device.cmd_begin_rendering(command_buffer, &rendering_info);
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+        device.cmd_bind_pipeline(command_buffer, pipeline.bind_point(), pipeline.handle());
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            pipeline.bind_point(),
+            pipeline_layout.handle(),
+            0,
+            &[descriptor_set],
+            &[],
+        );
+    }
+}
+
+fn create_target<'allocator>(
+    device: Arc<Device<'allocator>>,
+    width: u32,
+    height: u32,
+) -> Image<'allocator> {
+    Image::new(
+        device,
+        "HDR Render Target",
+        HDR_FORMAT,
+        width,
+        height,
+        1,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+        true,
+        Some(HIGH_MEMORY_PRIORITY),
+    )
+}
+
+fn create_pipeline<'allocator>(
+    device: &Arc<Device<'allocator>>,
+    pipeline_layout: &PipelineLayout<'allocator>,
+    shader: &Shader<'allocator>,
+    output_format: vk::Format,
+) -> Pipeline<'allocator> {
+    GraphicsPipelineBuilder::new(pipeline_layout, output_format)
+        .vertex(shader, c"vertex")
+        .fragment(shader, c"fragment")
+        .build(device.clone(), "Tonemap Pipeline")
+}
+
+/// Whether writes to `format` are encoded to sRGB by the hardware
+pub(crate) fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8_SRGB
+            | vk::Format::R8G8_SRGB
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_SRGB
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
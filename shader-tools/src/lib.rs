@@ -0,0 +1,75 @@
+use std::{path::Path, process::Command};
+
+pub const SPIRV_MAGIC_NUMBER: u32 = 0x07230203;
+const SPIRV_HEADER_SIZE: usize = 5;
+const OP_ENTRY_POINT: u32 = 15;
+
+/// A `slangc` invocation that compiles `source` to SPIR-V at `output`,
+/// shared by the build scripts and shader hot reloading so shaders are always compiled the same way
+///
+/// Each of `defines` is passed as `-D<define>`, `debug_info` keeps source lines around
+/// so validation errors and shader printf can point at them
+pub fn slangc(source: &Path, output: &Path, defines: &[&str], debug_info: bool) -> Command {
+    let mut command = Command::new("slangc");
+    command
+        .arg(source)
+        .arg("-o")
+        .arg(output)
+        .args([
+            "-warnings-as-errors",
+            "all",
+            "-fvk-use-scalar-layout",
+            "-fvk-use-entrypoint-name",
+        ])
+        .args(defines.iter().map(|define| format!("-D{define}")))
+        .args(debug_info.then_some("-g"));
+    command
+}
+
+/// The opcode and operands of every instruction in a SPIR-V module, up to the first malformed one
+///
+/// Yields nothing when `spirv_code` doesn't start with a SPIR-V header
+pub fn spirv_instructions(spirv_code: &[u32]) -> impl Iterator<Item = (u32, &[u32])> {
+    let mut words = match spirv_code {
+        [SPIRV_MAGIC_NUMBER, ..] if spirv_code.len() >= SPIRV_HEADER_SIZE => {
+            &spirv_code[SPIRV_HEADER_SIZE..]
+        }
+        _ => &[],
+    };
+    std::iter::from_fn(move || {
+        let &first = words.first()?;
+        let word_count = (first >> 16) as usize;
+        if word_count == 0 || word_count > words.len() {
+            return None;
+        }
+        let operands = &words[1..word_count];
+        words = &words[word_count..];
+        Some((first & 0xFFFF, operands))
+    })
+}
+
+/// Splits a nul terminated SPIR-V literal string from the operands that follow it
+pub fn parse_spirv_string(words: &[u32]) -> (Vec<u8>, &[u32]) {
+    let mut bytes = vec![];
+    for (index, word) in words.iter().enumerate() {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                return (bytes, &words[index + 1..]);
+            }
+            bytes.push(byte);
+        }
+    }
+    (bytes, &[])
+}
+
+/// The names of the entry points of a SPIR-V module, in the order they are declared
+pub fn spirv_entry_points(spirv_code: &[u32]) -> Vec<String> {
+    spirv_instructions(spirv_code)
+        .filter_map(|(opcode, operands)| match (opcode, operands) {
+            (OP_ENTRY_POINT, [_, _, name @ ..]) => {
+                Some(String::from_utf8_lossy(&parse_spirv_string(name).0).into_owned())
+            }
+            _ => None,
+        })
+        .collect()
+}